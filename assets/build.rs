@@ -32,6 +32,7 @@ fn main() {
             {
                 "vert" => shaderc::ShaderKind::Vertex,
                 "frag" => shaderc::ShaderKind::Fragment,
+                "comp" => shaderc::ShaderKind::Compute,
                 kind => panic!("Unknown shader kind: {kind}"),
             };
             let source = std::fs::read_to_string(Path::new("shaders/").join(&path)).unwrap();
@@ -0,0 +1,51 @@
+#version 450
+
+layout(local_size_x = 64) in;
+
+// laid out like VkDrawIndexedIndirectCommand
+struct Draw {
+    uint indexCount;
+    uint instanceCount;
+    uint firstIndex;
+    int vertexOffset;
+    uint firstInstance;
+};
+
+layout(set = 0, binding = 0) uniform Frustum {
+    // xyz is the normal pointing into the frustum, w the distance along it to the origin
+    vec4 planes[6];
+} frustum;
+
+layout(set = 0, binding = 1) readonly buffer Candidates {
+    Draw draws[];
+} candidates;
+
+// xyz is the centre of the bounding sphere of each candidate, w its radius
+layout(set = 0, binding = 2) readonly buffer Bounds {
+    vec4 spheres[];
+} bounds;
+
+layout(set = 0, binding = 3) writeonly buffer Visible {
+    Draw draws[];
+} visible;
+
+// has to be cleared to 0 before the dispatch
+layout(set = 0, binding = 4) buffer Count {
+    uint count;
+} count;
+
+void main() {
+    uint i = gl_GlobalInvocationID.x;
+    if (i >= candidates.draws.length()) {
+        return;
+    }
+
+    vec4 sphere = bounds.spheres[i];
+    for (int plane = 0; plane < 6; plane++) {
+        if (dot(frustum.planes[plane].xyz, sphere.xyz) + frustum.planes[plane].w < -sphere.w) {
+            return;
+        }
+    }
+
+    visible.draws[atomicAdd(count.count, 1)] = candidates.draws[i];
+}
@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) out vec2 outTexcoord;
+
+void main() {
+    // a single triangle from (-1, -1) to (3, 3), which covers the whole screen
+    vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    outTexcoord = uv;
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout(location = 0) in vec2 texcoord;
+
+layout(location = 0) out vec4 outColour;
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+
+void main() {
+    // the sampler takes coordinates in pixels rather than from 0 to 1
+    vec4 colour = textureLod(scene, texcoord * textureSize(scene, 0), 0);
+    // weighted by how bright each channel looks
+    float luminance = dot(colour.rgb, vec3(0.2126, 0.7152, 0.0722));
+    outColour = vec4(vec3(luminance), colour.a);
+}
@@ -0,0 +1,22 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 colour;
+
+layout(location = 0) out vec3 fragColor;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 viewProj;
+} camera;
+
+// line colours are given in srgb, like material colours
+vec3 srgb_to_linear(vec3 srgb) {
+    vec3 low = srgb / 12.92;
+    vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
+void main() {
+    gl_Position = camera.viewProj * vec4(position, 1.0);
+    fragColor = srgb_to_linear(colour);
+}
@@ -7,6 +7,12 @@ layout(location = 0) out vec3 fragColor;
 
 layout(set = 0, binding = 0) uniform Camera {
     mat4 viewProj;
+    // xyz is the direction sunlight travels in, w how much light reaches faces turned away
+    vec4 sunDirection;
+    // rgb is the sun's srgb colour, a its intensity
+    vec4 sunColour;
+    // only used by the sky, drawn behind everything
+    vec4 sky;
 } camera;
 
 struct Transform {
@@ -25,11 +31,22 @@ layout(set = 1, binding = 1) readonly buffer Materials {
     Material materials[];
 } materials;
 
+// material colours are given in srgb, so they are converted before lighting them
+vec3 srgb_to_linear(vec3 srgb) {
+    vec3 low = srgb / 12.92;
+    vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
 void main() {
     uint index = gl_DrawID;
     Transform transform = transforms.transforms[index];
     Material material = materials.materials[index];
     
+    vec3 sun = srgb_to_linear(camera.sunColour.rgb) * camera.sunColour.a;
+    float diffuse = max(dot(normal, -camera.sunDirection.xyz), 0.0);
+    vec3 light = vec3(camera.sunDirection.w) + sun * diffuse;
+
     gl_Position = camera.viewProj * transform.transform * vec4(position, 1.0);
-    fragColor = material.colour.rgb * (0.5 + 0.5 * max(dot(normal, vec3(1.0)), 0.0));
+    fragColor = srgb_to_linear(material.colour.rgb) * light;
 }
@@ -0,0 +1,18 @@
+#version 450
+
+layout(location = 0) in vec2 texcoord;
+
+layout(location = 0) out vec4 outColour;
+
+layout(set = 0, binding = 0) uniform Camera {
+    mat4 viewProj;
+    vec4 sunDirection;
+    vec4 sunColour;
+    // rgb is the linear colour at the top of the sky
+    vec4 sky;
+} camera;
+
+void main() {
+    // fading out towards the horizon at the bottom of the screen
+    outColour = vec4(mix(camera.sky.rgb, vec3(1.0), texcoord.y * 0.25), 1.0);
+}
@@ -5,6 +5,7 @@ layout(location = 1) in vec4 colour;
 layout(location = 2) in vec4 area;
 layout(location = 3) in float radius;
 layout(location = 4) in vec2 texcoord;
+layout(location = 5) in float picture;
 
 layout(location = 0) out vec4 outColour;
 
@@ -39,6 +40,14 @@ float rect_sdf(
     return distance;
 }
 
+// colours are given in srgb, like a colour picker shows them, but blending and the srgb
+// framebuffer expect linear values
+vec3 srgb_to_linear(vec3 srgb) {
+    vec3 low = srgb / 12.92;
+    vec3 high = pow((srgb + 0.055) / 1.055, vec3(2.4));
+    return mix(high, low, lessThanEqual(srgb, vec3(0.04045)));
+}
+
 void main() {
     float distance = rect_sdf(position.xy, area.xy, area.zw, radius);
 
@@ -46,15 +55,16 @@ void main() {
 
     if (distance > 0.0) {
         col = vec4(0.0);
+    } else if (picture > 0.5) {
+        // pictures are sampled from srgb images, so they are already linear
+        outColour = colour * textureLod(atlas, texcoord, 0);
+        return;
     } else if (texcoord == vec2(0.0)) {
         col = colour;
     } else {
         col = colour * textureLod(atlas, texcoord, 0).x;
     }
 
-    outColour.x = pow(col.x, 2.2);
-    outColour.y = pow(col.y, 2.2);
-    outColour.z = pow(col.z, 2.2);
-    outColour.w = col.w;
+    outColour = vec4(srgb_to_linear(col.rgb), col.a);
 }
 
@@ -7,12 +7,13 @@ layout(location = 1) out vec4 outColour;
 layout(location = 2) out vec4 outArea;
 layout(location = 3) out float outRadius;
 layout(location = 4) out vec2 outTexcoord;
+layout(location = 5) out float outPicture;
 
 struct Rectangle {
     vec4 colour;
     vec4 area;
     vec4 sample_area;
-    float radius;
+    vec4 radius;
 };
 
 layout(set = 0, binding = 0) readonly buffer RectangleBuffer {
@@ -32,7 +33,8 @@ void main() {
     
     outColour = rectangle.colour;
     outArea = rectangle.area;
-    outRadius = rectangle.radius;
+    outRadius = rectangle.radius.x;
+    outPicture = rectangle.radius.y;
 
     uint n = gl_VertexIndex % 4;
     vec2 sample_offset = rectangle.sample_area.xy;
@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec2 texcoord;
+
+layout(location = 0) out vec4 outColour;
+
+layout(set = 0, binding = 0) uniform sampler2D scene;
+
+void main() {
+    // how many texels of the scene there are to each pixel of the frame, as texcoord goes from 0
+    // to 1 across the frame
+    vec2 scale = textureSize(scene, 0) * vec2(dFdx(texcoord.x), dFdy(texcoord.y));
+    // the sampler takes coordinates in pixels rather than from 0 to 1. Mapping from the pixel's
+    // exact centre, rather than the interpolated texcoord, keeps each pixel centred on the texels
+    // it covers instead of drifting by up to half a texel
+    outColour = textureLod(scene, gl_FragCoord.xy * scale, 0);
+}
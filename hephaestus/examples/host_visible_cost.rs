@@ -0,0 +1,25 @@
+//! Compares making a small buffer through a staging copy with writing it straight into host
+//! visible memory, like the camera's uniforms are every frame
+use std::time::Instant;
+
+use hephaestus::{buffer::Static, BufferUsageFlags, Context};
+
+fn main() {
+    let ctx = Context::headless("host_visible_cost").unwrap();
+    // the size of the camera's uniforms
+    let data = [0; 128];
+
+    let start = Instant::now();
+    for _ in 0..1000 {
+        Static::new(&ctx, &data, BufferUsageFlags::UNIFORM_BUFFER).unwrap();
+    }
+    let staged = start.elapsed() / 1000;
+
+    let start = Instant::now();
+    for _ in 0..1000 {
+        Static::new_host_visible(&ctx, &data, BufferUsageFlags::UNIFORM_BUFFER).unwrap();
+    }
+    let host_visible = start.elapsed() / 1000;
+
+    println!("staged: {staged:?} per buffer, host visible: {host_visible:?} per buffer");
+}
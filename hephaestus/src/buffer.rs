@@ -8,9 +8,11 @@ use ash::{
         MemoryPropertyFlags, MemoryRequirements, SharingMode,
     },
 };
+use log::error;
 
 use crate::{
     command::Region,
+    debug::{self, Kind},
     task::{Fence, SubmitInfo, Task},
     Context, Device,
 };
@@ -35,6 +37,51 @@ impl<T: Buffer> Buffer for Rc<T> {
     }
 }
 
+/// Rounds `size` up to the next multiple of `alignment`, which has to be a power of two like all
+/// of vulkan's alignments are
+pub fn align_up(size: usize, alignment: usize) -> usize {
+    assert!(
+        alignment.is_power_of_two(),
+        "{alignment} isn't a power of two"
+    );
+    (size + alignment - 1) & !(alignment - 1)
+}
+
+/// Packs elements one after another for a single buffer, each starting at a multiple of the
+/// alignment so it can be bound on its own by its offset. Use
+/// [`PhysicalDevice::uniform_alignment`](crate::PhysicalDevice::uniform_alignment) or
+/// [`PhysicalDevice::storage_alignment`](crate::PhysicalDevice::storage_alignment) for the
+/// buffer it is going into.
+pub struct Packed {
+    alignment: usize,
+    data: Vec<u8>,
+}
+
+impl Packed {
+    pub fn new(alignment: usize) -> Self {
+        Self {
+            alignment,
+            data: Vec::new(),
+        }
+    }
+
+    /// Adds an element after the padding it needs, returning the offset it starts at
+    pub fn push<T: bytemuck::Pod>(&mut self, element: &T) -> usize {
+        let offset = align_up(self.data.len(), self.alignment);
+        self.data.resize(offset, 0);
+        self.data.extend_from_slice(bytemuck::bytes_of(element));
+        offset
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn build(self, ctx: &Context, usage: BufferUsageFlags) -> VkResult<Rc<Static>> {
+        Static::new(ctx, &self.data, usage)
+    }
+}
+
 pub(crate) fn find_memory_type(
     ctx: &Context,
     requirements: MemoryRequirements,
@@ -83,6 +130,7 @@ impl Dynamic {
             .memory_type_index(type_index as u32);
         let memory = unsafe { ctx.device.allocate_memory(&alloc_info, None)? };
         unsafe { ctx.device.bind_buffer_memory(handle, memory, 0)? };
+        debug::created(Kind::Buffer);
 
         Ok(Rc::new(Self {
             device: ctx.device.clone(),
@@ -92,6 +140,11 @@ impl Dynamic {
         }))
     }
 
+    /// Only does anything with the `debug` feature
+    pub fn set_name(&self, name: &str) {
+        debug::set_name(&self.device, self.handle, name)
+    }
+
     pub fn write(&self, data: &[u8]) -> VkResult<()> {
         let memory: *mut c_void = unsafe {
             self.device.map_memory(self.memory, 0, data.len() as u64, MemoryMapFlags::default())?
@@ -102,12 +155,24 @@ impl Dynamic {
 
         Ok(())
     }
+
+    pub fn read(&self) -> VkResult<Vec<u8>> {
+        let memory: *mut c_void = unsafe {
+            self.device.map_memory(self.memory, 0, self.size as u64, MemoryMapFlags::default())?
+        };
+        let memory: *const u8 = memory.cast();
+        let data = unsafe { slice::from_raw_parts(memory, self.size).to_vec() };
+        unsafe { self.device.unmap_memory(self.memory) };
+
+        Ok(data)
+    }
 }
 
 impl Drop for Dynamic {
     fn drop(&mut self) {
         unsafe { self.device.destroy_buffer(self.handle, None) }
         unsafe { self.device.free_memory(self.memory, None) }
+        debug::destroyed(Kind::Buffer);
     }
 }
 
@@ -125,6 +190,8 @@ impl Buffer for Dynamic {
     }
 }
 
+/// A buffer written once when it is created. Usually in device local memory, which is fastest for
+/// the gpu to read but has to be copied into through a staging buffer.
 pub struct Static {
     device: Rc<Device>,
     pub handle: vk::Buffer,
@@ -133,33 +200,49 @@ pub struct Static {
 }
 
 impl Static {
-    pub fn new(ctx: &Context, data: &[u8], usage: BufferUsageFlags) -> VkResult<Rc<Self>> {
-        let size = data.len();
+    fn alloc(
+        ctx: &Context,
+        size: usize,
+        usage: BufferUsageFlags,
+        wanted: MemoryPropertyFlags,
+    ) -> VkResult<Self> {
         let create_info = BufferCreateInfo::builder()
             .size(size as u64)
-            .usage(usage | BufferUsageFlags::TRANSFER_DST)
+            .usage(usage)
             .sharing_mode(SharingMode::EXCLUSIVE);
         let handle = unsafe { ctx.device.create_buffer(&create_info, None)? };
 
         let requirements = unsafe { ctx.device.get_buffer_memory_requirements(handle) };
-        let type_index = find_memory_type(ctx, requirements, MemoryPropertyFlags::DEVICE_LOCAL)
-            .expect("No suitable memory types");
+        let type_index =
+            find_memory_type(ctx, requirements, wanted).expect("No suitable memory types");
 
         let alloc_info = MemoryAllocateInfo::builder()
             .allocation_size(requirements.size)
             .memory_type_index(type_index as u32);
         let memory = unsafe { ctx.device.allocate_memory(&alloc_info, None)? };
         unsafe { ctx.device.bind_buffer_memory(handle, memory, 0)? };
+        debug::created(Kind::Buffer);
 
-        let staging = Dynamic::new(ctx, size, BufferUsageFlags::TRANSFER_SRC)?;
-        staging.write(data)?;
-
-        let buffer = Self {
+        Ok(Self {
             device: ctx.device.clone(),
             handle,
             memory,
             size,
-        };
+        })
+    }
+
+    /// Copies `data` into device local memory, waiting for the copy to finish
+    pub fn new(ctx: &Context, data: &[u8], usage: BufferUsageFlags) -> VkResult<Rc<Self>> {
+        let size = data.len();
+        let buffer = Self::alloc(
+            ctx,
+            size,
+            usage | BufferUsageFlags::TRANSFER_DST,
+            MemoryPropertyFlags::DEVICE_LOCAL,
+        )?;
+
+        let staging = Dynamic::new(ctx, size, BufferUsageFlags::TRANSFER_SRC)?;
+        staging.write(data)?;
 
         let cmd = ctx
             .command_pool
@@ -190,12 +273,53 @@ impl Static {
 
         Ok(Rc::new(buffer))
     }
+
+    /// Writes `data` straight into host visible memory, without a staging copy or waiting on the
+    /// gpu. The gpu reads it more slowly, which is worth it for small buffers remade every frame,
+    /// like uniforms, but not for big or long lived ones like meshes. Fails with
+    /// `ERROR_INITIALIZATION_FAILED` if `data` is empty, as a buffer can't be empty.
+    pub fn new_host_visible(
+        ctx: &Context,
+        data: &[u8],
+        usage: BufferUsageFlags,
+    ) -> VkResult<Rc<Self>> {
+        if data.is_empty() {
+            error!("Host visible buffers can't be empty");
+            return Err(vk::Result::ERROR_INITIALIZATION_FAILED);
+        }
+        let buffer = Self::alloc(
+            ctx,
+            data.len(),
+            usage,
+            MemoryPropertyFlags::HOST_VISIBLE | MemoryPropertyFlags::HOST_COHERENT,
+        )?;
+
+        let memory: *mut c_void = unsafe {
+            ctx.device.map_memory(
+                buffer.memory,
+                0,
+                data.len() as u64,
+                MemoryMapFlags::default(),
+            )?
+        };
+        let memory: *mut u8 = memory.cast();
+        unsafe { slice::from_raw_parts_mut(memory, data.len()).copy_from_slice(data) };
+        unsafe { ctx.device.unmap_memory(buffer.memory) };
+
+        Ok(Rc::new(buffer))
+    }
+
+    /// Only does anything with the `debug` feature
+    pub fn set_name(&self, name: &str) {
+        debug::set_name(&self.device, self.handle, name)
+    }
 }
 
 impl Drop for Static {
     fn drop(&mut self) {
         unsafe { self.device.destroy_buffer(self.handle, None) }
         unsafe { self.device.free_memory(self.memory, None) }
+        debug::destroyed(Kind::Buffer);
     }
 }
 
@@ -212,3 +336,64 @@ impl Buffer for Static {
         self.size
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_align_up() {
+        // the alignments vulkan devices usually have
+        for alignment in [1, 4, 16, 64, 256] {
+            assert_eq!(align_up(0, alignment), 0);
+            assert_eq!(align_up(alignment, alignment), alignment);
+            assert_eq!(align_up(alignment + 1, alignment), alignment * 2);
+        }
+        // a vec4, a mat4 and the camera's uniforms
+        assert_eq!(align_up(16, 64), 64);
+        assert_eq!(align_up(64, 256), 256);
+        assert_eq!(align_up(128, 256), 256);
+        assert_eq!(align_up(272, 256), 512);
+        assert_eq!(align_up(20, 16), 32);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_align_up_power_of_two() {
+        align_up(16, 12);
+    }
+
+    #[test]
+    pub fn test_packed() {
+        let mut packed = Packed::new(256);
+        let offsets = (0..3)
+            .map(|i| packed.push(&[i as f32; 16]))
+            .collect::<Vec<_>>();
+        assert_eq!(offsets, vec![0, 256, 512]);
+        // the last element isn't padded
+        assert_eq!(packed.data().len(), 512 + 64);
+        assert_eq!(
+            bytemuck::pod_read_unaligned::<[f32; 16]>(&packed.data()[256..320]),
+            [1.0; 16]
+        );
+        assert!(packed.data()[64..256].iter().all(|byte| *byte == 0));
+
+        let mut packed = Packed::new(4);
+        assert_eq!(packed.push(&1u8), 0);
+        assert_eq!(packed.push(&2u32), 4);
+        assert_eq!(packed.data(), &[1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    pub fn test_host_visible_empty() {
+        let ctx = Context::headless("test").unwrap();
+        assert_eq!(
+            Static::new_host_visible(&ctx, &[], BufferUsageFlags::UNIFORM_BUFFER).err(),
+            Some(vk::Result::ERROR_INITIALIZATION_FAILED)
+        );
+        assert!(
+            Static::new_host_visible(&ctx, &[0; 128], BufferUsageFlags::UNIFORM_BUFFER).is_ok()
+        );
+    }
+}
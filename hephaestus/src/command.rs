@@ -3,19 +3,19 @@ use std::{any::Any, rc::Rc};
 use ash::{
     prelude::VkResult,
     vk::{
-        self, AccessFlags, BufferCopy, BufferImageCopy, ClearValue, CommandBufferAllocateInfo,
-        CommandBufferBeginInfo, CommandBufferLevel, CommandPoolCreateInfo, DependencyFlags,
-        Extent2D, Extent3D, ImageAspectFlags, ImageLayout, ImageMemoryBarrier,
-        ImageSubresourceLayers, ImageSubresourceRange, IndexType, Offset2D, Offset3D,
-        PipelineBindPoint, PipelineLayout, PipelineStageFlags, Rect2D, RenderPassBeginInfo,
-        SubpassContents, Viewport,
+        self, AccessFlags, BufferCopy, BufferImageCopy, BufferMemoryBarrier, ClearValue,
+        CommandBufferAllocateInfo, CommandBufferBeginInfo, CommandBufferLevel,
+        CommandPoolCreateInfo, DependencyFlags, Extent2D, Extent3D, ImageAspectFlags, ImageLayout,
+        ImageMemoryBarrier, ImageSubresourceLayers, ImageSubresourceRange, IndexType, Offset2D,
+        Offset3D, PipelineBindPoint, PipelineLayout, PipelineStageFlags, Rect2D,
+        RenderPassBeginInfo, SubpassContents, Viewport,
     },
 };
 
 use crate::{
     buffer, descriptor,
-    image::Image,
-    pipeline::{Framebuffer, Graphics, RenderPass},
+    image::ImageHandle,
+    pipeline::{Compute, Framebuffer, Graphics, RenderPass},
     Device, Queue,
 };
 
@@ -31,6 +31,12 @@ pub struct BufferToImageRegion {
     pub to_extent: Extent3D,
 }
 
+pub struct ImageToBufferRegion {
+    pub from_offset: Offset3D,
+    pub from_extent: Extent3D,
+    pub to_offset: usize,
+}
+
 pub struct TransitionLayout {
     pub from: ImageLayout,
     pub to: ImageLayout,
@@ -67,18 +73,21 @@ impl Drop for Buffer {
 
 pub enum Pipeline<'a> {
     Graphics(&'a Graphics),
+    Compute(&'a Compute),
 }
 
 impl Pipeline<'_> {
     pub fn bind_point(&self) -> PipelineBindPoint {
         match self {
             Self::Graphics(_) => PipelineBindPoint::GRAPHICS,
+            Self::Compute(_) => PipelineBindPoint::COMPUTE,
         }
     }
 
     pub fn layout(&self) -> PipelineLayout {
         match self {
             Self::Graphics(pipeline) => pipeline.layout,
+            Self::Compute(pipeline) => pipeline.layout,
         }
     }
 }
@@ -135,6 +144,29 @@ impl<'a> Recorder<'a> {
         self
     }
 
+    pub fn bind_compute_pipeline(mut self, pipeline: &'a Compute) -> Self {
+        self.pipeline = Some(Pipeline::Compute(pipeline));
+        unsafe {
+            self.buffer.device.cmd_bind_pipeline(
+                self.buffer.handle,
+                PipelineBindPoint::COMPUTE,
+                pipeline.handle,
+            )
+        };
+        self
+    }
+
+    pub fn dispatch(self, x: u32, y: u32, z: u32) -> Self {
+        unsafe { self.buffer.device.cmd_dispatch(self.buffer.handle, x, y, z) };
+        self
+    }
+
+    /// Draws the triangle of a [`Graphics::fullscreen`](crate::pipeline::Graphics::fullscreen)
+    /// pipeline
+    pub fn draw_fullscreen(self) -> Self {
+        self.draw(3, 1, 0, 0)
+    }
+
     pub fn draw(
         self,
         vertices: u32,
@@ -195,6 +227,37 @@ impl<'a> Recorder<'a> {
         self
     }
 
+    /// Like [`Recorder::draw_indexed_indirect`], but reads how many draws there are from the
+    /// first `u32` of `count_buffer`, up to `max_draws`, so a compute shader can decide what is
+    /// drawn without reading anything back. Only for devices with
+    /// [`Device::draw_indirect_count`].
+    pub fn draw_indexed_indirect_count<T: buffer::Buffer + 'static, C: buffer::Buffer + 'static>(
+        mut self,
+        buffer: &Rc<T>,
+        count_buffer: &Rc<C>,
+        max_draws: u32,
+        stride: u32,
+    ) -> Self {
+        assert!(
+            self.buffer.device.draw_indirect_count,
+            "Draws with a count written on the gpu aren't supported"
+        );
+        unsafe {
+            self.buffer.device.cmd_draw_indexed_indirect_count(
+                self.buffer.handle,
+                buffer.buffer(),
+                0,
+                count_buffer.buffer(),
+                0,
+                max_draws,
+                stride,
+            )
+        }
+        self.buffer.resources.push(buffer.clone());
+        self.buffer.resources.push(count_buffer.clone());
+        self
+    }
+
     pub fn set_viewport(self, width: u32, height: u32) -> Self {
         let viewport = Viewport::builder()
             .x(0.0)
@@ -294,10 +357,26 @@ impl<'a> Recorder<'a> {
         self
     }
 
-    pub fn copy_buffer_to_image<A: buffer::Buffer>(
+    /// Fills the whole buffer with `value`, which has to have been created with `TRANSFER_DST`,
+    /// like resetting a count before a compute shader adds to it
+    pub fn fill_buffer<T: buffer::Buffer + 'static>(mut self, buffer: &Rc<T>, value: u32) -> Self {
+        unsafe {
+            self.buffer.device.cmd_fill_buffer(
+                self.buffer.handle,
+                buffer.buffer(),
+                0,
+                vk::WHOLE_SIZE,
+                value,
+            )
+        }
+        self.buffer.resources.push(buffer.clone());
+        self
+    }
+
+    pub fn copy_buffer_to_image<A: buffer::Buffer, I: ImageHandle>(
         self,
         from: &A,
-        to: &Image,
+        to: &I,
         layout: ImageLayout,
         region: BufferToImageRegion,
     ) -> Self {
@@ -319,7 +398,7 @@ impl<'a> Recorder<'a> {
             self.buffer.device.cmd_copy_buffer_to_image(
                 self.buffer.handle,
                 from.buffer(),
-                to.handle,
+                to.image(),
                 layout,
                 &[region],
             )
@@ -327,11 +406,44 @@ impl<'a> Recorder<'a> {
         self
     }
 
-    pub fn transition_layout(self, image: &Image, info: TransitionLayout) -> Self {
+    pub fn copy_image_to_buffer<I: ImageHandle, A: buffer::Buffer>(
+        self,
+        from: &I,
+        layout: ImageLayout,
+        to: &A,
+        region: ImageToBufferRegion,
+    ) -> Self {
+        let region = BufferImageCopy::builder()
+            .buffer_offset(region.to_offset as u64)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(ImageSubresourceLayers {
+                aspect_mask: ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(region.from_offset)
+            .image_extent(region.from_extent)
+            .build();
+
+        unsafe {
+            self.buffer.device.cmd_copy_image_to_buffer(
+                self.buffer.handle,
+                from.image(),
+                layout,
+                to.buffer(),
+                &[region],
+            )
+        }
+        self
+    }
+
+    pub fn transition_layout<I: ImageHandle>(self, image: &I, info: TransitionLayout) -> Self {
         let barrier = ImageMemoryBarrier::builder()
             .old_layout(info.from)
             .new_layout(info.to)
-            .image(image.handle)
+            .image(image.image())
             .subresource_range(ImageSubresourceRange {
                 aspect_mask: ImageAspectFlags::COLOR,
                 base_mip_level: 0,
@@ -358,6 +470,36 @@ impl<'a> Recorder<'a> {
         self
     }
 
+    /// Waits for the writes to `buffer` in `before` to finish before it is used by `after`
+    pub fn buffer_barrier<T: buffer::Buffer>(
+        self,
+        buffer: &T,
+        before: (AccessFlags, PipelineStageFlags),
+        after: (AccessFlags, PipelineStageFlags),
+    ) -> Self {
+        let barrier = BufferMemoryBarrier::builder()
+            .buffer(buffer.buffer())
+            .offset(0)
+            .size(vk::WHOLE_SIZE)
+            .src_access_mask(before.0)
+            .dst_access_mask(after.0)
+            .build();
+
+        unsafe {
+            self.buffer.device.cmd_pipeline_barrier(
+                self.buffer.handle,
+                before.1,
+                after.1,
+                DependencyFlags::empty(),
+                &[],
+                &[barrier],
+                &[],
+            )
+        }
+
+        self
+    }
+
     pub fn next_subpass(self) -> Self {
         unsafe {
             self.buffer
@@ -404,3 +546,300 @@ impl Drop for Pool {
         unsafe { self.device.destroy_command_pool(self.handle, None) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        buffer::Dynamic,
+        image::{Image, ImageInfo, ImageView},
+        pipeline::{self, clear_colour, AttachmentInfo, ShaderModule, Subpass},
+        task::{Fence, SubmitInfo, Task},
+        vertex::{self, AttributeType},
+        AttachmentLoadOp, AttachmentStoreOp, BufferUsageFlags, Context, DescriptorType, Format,
+        ImageUsageFlags, SampleCountFlags,
+    };
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    pub fn test_culled_draws() {
+        // shaders are compiled into the assets at the workspace root
+        std::env::set_current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/..")).unwrap();
+
+        let ctx = Context::headless("test").unwrap();
+        let shader = ShaderModule::new(
+            &ctx.device,
+            &std::fs::read("assets/shaders/cull.comp.spv").unwrap(),
+        )
+        .unwrap();
+        let layout = descriptor::Layout::new(
+            &ctx,
+            &[
+                DescriptorType::UNIFORM_BUFFER,
+                DescriptorType::STORAGE_BUFFER,
+                DescriptorType::STORAGE_BUFFER,
+                DescriptorType::STORAGE_BUFFER,
+                DescriptorType::STORAGE_BUFFER,
+            ],
+            1,
+        )
+        .unwrap();
+        let pipeline = Compute::new(&ctx.device, &shader, vec![&layout]).unwrap();
+
+        // a box from -1 to 1 on every axis
+        let planes: [[f32; 4]; 6] = [
+            [1.0, 0.0, 0.0, 1.0],
+            [-1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 1.0],
+            [0.0, -1.0, 0.0, 1.0],
+            [0.0, 0.0, 1.0, 1.0],
+            [0.0, 0.0, -1.0, 1.0],
+        ];
+        let frustum = Dynamic::new(&ctx, 96, BufferUsageFlags::UNIFORM_BUFFER).unwrap();
+        frustum.write(bytemuck::cast_slice(&planes)).unwrap();
+
+        // inside, far outside and poking into the box, each covering its own pixel of the image
+        // below
+        let draws: [[u32; 5]; 3] = [[6, 1, 0, 0, 0], [6, 1, 6, 0, 1], [6, 1, 12, 0, 2]];
+        let spheres: [[f32; 4]; 3] = [
+            [0.0, 0.0, 0.0, 0.5],
+            [5.0, 0.0, 0.0, 0.5],
+            [1.2, 0.0, 0.0, 0.5],
+        ];
+        let candidates = Dynamic::new(&ctx, 60, BufferUsageFlags::STORAGE_BUFFER).unwrap();
+        candidates.write(bytemuck::cast_slice(&draws)).unwrap();
+        let bounds = Dynamic::new(&ctx, 48, BufferUsageFlags::STORAGE_BUFFER).unwrap();
+        bounds.write(bytemuck::cast_slice(&spheres)).unwrap();
+
+        let visible = Dynamic::new(
+            &ctx,
+            60,
+            BufferUsageFlags::STORAGE_BUFFER | BufferUsageFlags::INDIRECT_BUFFER,
+        )
+        .unwrap();
+        // past the visible draws the culled one is left over, so it is drawn if the count is
+        // ignored
+        visible
+            .write(bytemuck::cast_slice(&[draws[0], draws[2], draws[1]]))
+            .unwrap();
+        let count = Dynamic::new(
+            &ctx,
+            4,
+            BufferUsageFlags::STORAGE_BUFFER
+                | BufferUsageFlags::INDIRECT_BUFFER
+                | BufferUsageFlags::TRANSFER_DST,
+        )
+        .unwrap();
+
+        let set = layout
+            .alloc()
+            .unwrap()
+            .write_buffer(0, &frustum)
+            .write_buffer(1, &candidates)
+            .write_buffer(2, &bounds)
+            .write_buffer(3, &visible)
+            .write_buffer(4, &count)
+            .finish();
+
+        // a 3x1 image, drawn into with the line shaders and an identity camera
+        let extent = Extent2D {
+            width: 3,
+            height: 1,
+        };
+        let render_pass = {
+            let mut builder = RenderPass::builder();
+            let colour = builder.attachment(
+                Format::R8G8B8A8_UNORM,
+                AttachmentInfo {
+                    initial_layout: ImageLayout::UNDEFINED,
+                    final_layout: ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    load_op: AttachmentLoadOp::CLEAR,
+                    store_op: AttachmentStoreOp::STORE,
+                    samples: SampleCountFlags::TYPE_1,
+                },
+            );
+            builder.subpass(
+                Subpass::new(PipelineBindPoint::GRAPHICS)
+                    .colour(colour, ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+            );
+            builder.build(&ctx.device).unwrap()
+        };
+        let image = Image::new(
+            &ctx,
+            ImageInfo {
+                format: Format::R8G8B8A8_UNORM,
+                extent,
+                usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC,
+                samples: SampleCountFlags::TYPE_1,
+            },
+        )
+        .unwrap();
+        let view = ImageView::new(
+            &ctx.device,
+            &image,
+            Format::R8G8B8A8_UNORM,
+            ImageAspectFlags::COLOR,
+            extent,
+        )
+        .unwrap();
+        let framebuffer = render_pass.get_framebuffer(&ctx.device, &[&view]).unwrap();
+
+        let shader = |path| ShaderModule::new(&ctx.device, &std::fs::read(path).unwrap()).unwrap();
+        let (vertex, fragment) = (
+            shader("assets/shaders/line.vert.spv"),
+            shader("assets/shaders/line.frag.spv"),
+        );
+        let camera_layout =
+            descriptor::Layout::new(&ctx, &[DescriptorType::UNIFORM_BUFFER], 1).unwrap();
+        let draw_pipeline = Graphics::builder()
+            .vertex(&vertex)
+            .vertex_info(
+                vertex::Info::new(24)
+                    .attribute(AttributeType::Vec3, 0)
+                    .attribute(AttributeType::Vec3, 12),
+            )
+            .fragment(&fragment)
+            .render_pass(&render_pass)
+            .subpass(0)
+            .viewport(pipeline::Viewport::Dynamic)
+            .layouts(vec![&camera_layout])
+            .build(&ctx.device)
+            .unwrap();
+        let camera = Dynamic::new(&ctx, 64, BufferUsageFlags::UNIFORM_BUFFER).unwrap();
+        let identity: [f32; 16] = std::array::from_fn(|i| if i % 5 == 0 { 1.0 } else { 0.0 });
+        camera.write(bytemuck::cast_slice(&identity)).unwrap();
+        let camera_set = camera_layout
+            .alloc()
+            .unwrap()
+            .write_buffer(0, &camera)
+            .finish();
+
+        // a thin white triangle over the centre of each pixel, wound both ways so it isn't culled
+        let vertices = (0..3)
+            .flat_map(|i| {
+                let x = -1.0 + (2 * i + 1) as f32 / 3.0;
+                [[x - 0.3, -1.0], [x + 0.3, -1.0], [x, 1.0]]
+            })
+            .flat_map(|[x, y]| [x, y, 0.0, 1.0, 1.0, 1.0])
+            .collect::<Vec<f32>>();
+        let vertex_buffer = Dynamic::new(&ctx, 216, BufferUsageFlags::VERTEX_BUFFER).unwrap();
+        vertex_buffer
+            .write(bytemuck::cast_slice(&vertices))
+            .unwrap();
+        let indices = (0..3)
+            .flat_map(|i| [0, 1, 2, 0, 2, 1].map(|index| 3 * i + index))
+            .collect::<Vec<u32>>();
+        let index_buffer = Dynamic::new(&ctx, 72, BufferUsageFlags::INDEX_BUFFER).unwrap();
+        index_buffer.write(bytemuck::cast_slice(&indices)).unwrap();
+        let pixels = Dynamic::new(&ctx, 12, BufferUsageFlags::TRANSFER_DST).unwrap();
+
+        let drawn = (
+            AccessFlags::INDIRECT_COMMAND_READ | AccessFlags::HOST_READ,
+            PipelineStageFlags::DRAW_INDIRECT | PipelineStageFlags::HOST,
+        );
+        let cmd = ctx
+            .command_pool
+            .alloc()
+            .unwrap()
+            .begin()
+            .unwrap()
+            .fill_buffer(&count, 0)
+            .buffer_barrier(
+                &count,
+                (AccessFlags::TRANSFER_WRITE, PipelineStageFlags::TRANSFER),
+                (
+                    AccessFlags::SHADER_READ | AccessFlags::SHADER_WRITE,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                ),
+            )
+            .bind_compute_pipeline(&pipeline)
+            .bind_descriptor_set(&set, 0)
+            .dispatch(1, 1, 1)
+            .buffer_barrier(
+                &visible,
+                (
+                    AccessFlags::SHADER_WRITE,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                ),
+                drawn,
+            )
+            .buffer_barrier(
+                &count,
+                (
+                    AccessFlags::SHADER_WRITE,
+                    PipelineStageFlags::COMPUTE_SHADER,
+                ),
+                drawn,
+            );
+        let cmd = if ctx.device.draw_indirect_count {
+            cmd.begin_render_pass(&render_pass, &framebuffer, &[clear_colour([0.0; 4])])
+                .set_viewport(extent.width, extent.height)
+                .set_scissor(extent.width, extent.height)
+                .bind_graphics_pipeline(&draw_pipeline)
+                .bind_descriptor_set(&camera_set, 0)
+                .bind_vertex_buffer(&vertex_buffer, 0)
+                .bind_index_buffer(&index_buffer)
+                .draw_indexed_indirect_count(&visible, &count, 3, 20)
+                .end_render_pass()
+                .transition_layout(
+                    &image,
+                    TransitionLayout {
+                        from: ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        to: ImageLayout::TRANSFER_SRC_OPTIMAL,
+                        before: (
+                            AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                        ),
+                        after: (AccessFlags::TRANSFER_READ, PipelineStageFlags::TRANSFER),
+                    },
+                )
+                .copy_image_to_buffer(
+                    &image,
+                    ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    &*pixels,
+                    ImageToBufferRegion {
+                        from_offset: Offset3D::default(),
+                        from_extent: Extent3D {
+                            width: extent.width,
+                            height: extent.height,
+                            depth: 1,
+                        },
+                        to_offset: 0,
+                    },
+                )
+        } else {
+            cmd
+        };
+        let cmd = cmd.end().unwrap();
+
+        let mut task = Task::new();
+        let fence = Fence::new(&ctx.device).unwrap();
+        task.submit(SubmitInfo {
+            cmd: &cmd,
+            fence: fence.clone(),
+            device: &ctx.device,
+            queue: &ctx.device.queues.graphics,
+            wait: &[],
+            signal: &[],
+        })
+        .unwrap();
+        fence.wait().unwrap();
+
+        let count = bytemuck::pod_read_unaligned::<u32>(&count.read().unwrap());
+        assert_eq!(count, 2);
+
+        // the order they are added in isn't fixed
+        let visible = visible.read().unwrap();
+        let mut visible = bytemuck::cast_slice::<u8, [u32; 5]>(&visible)[..2].to_vec();
+        visible.sort();
+        assert_eq!(visible, vec![draws[0], draws[2]]);
+
+        if !ctx.device.draw_indirect_count {
+            return;
+        }
+        // only the pixels of the draws in the count are drawn
+        let pixels = pixels.read().unwrap();
+        let drawn = pixels.chunks(4).map(|pixel| pixel[0]).collect::<Vec<_>>();
+        assert_eq!(drawn, vec![255, 0, 255]);
+    }
+}
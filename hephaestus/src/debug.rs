@@ -0,0 +1,162 @@
+//! Names for vulkan objects, which debuggers and the validation layers show instead of handles,
+//! and counts of how many are alive for finding leaks. Both only do anything with the `debug`
+//! feature.
+
+#[cfg(feature = "debug")]
+use std::{cell::Cell, ffi::CString};
+
+#[cfg(feature = "debug")]
+use ash::vk::DebugUtilsObjectNameInfoEXT;
+use ash::vk::Handle;
+#[cfg(feature = "debug")]
+use log::warn;
+
+use crate::Device;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Buffer,
+    Image,
+    DescriptorSet,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 3] = [Kind::Buffer, Kind::Image, Kind::DescriptorSet];
+}
+
+#[cfg(feature = "debug")]
+thread_local! {
+    // objects are kept in `Rc`s, so they are always dropped on the thread that made them
+    static LIVE: Cell<[usize; 3]> = const { Cell::new([0; 3]) };
+}
+
+#[cfg_attr(not(feature = "debug"), allow(unused_variables))]
+pub(crate) fn created(kind: Kind) {
+    #[cfg(feature = "debug")]
+    LIVE.with(|live| {
+        let mut counts = live.get();
+        counts[kind as usize] += 1;
+        live.set(counts);
+    });
+}
+
+#[cfg_attr(not(feature = "debug"), allow(unused_variables))]
+pub(crate) fn destroyed(kind: Kind) {
+    #[cfg(feature = "debug")]
+    LIVE.with(|live| {
+        let mut counts = live.get();
+        counts[kind as usize] -= 1;
+        live.set(counts);
+    });
+}
+
+/// How many objects of `kind` made on this thread haven't been dropped yet
+#[cfg(feature = "debug")]
+pub fn live(kind: Kind) -> usize {
+    LIVE.with(|live| live.get()[kind as usize])
+}
+
+#[cfg_attr(not(feature = "debug"), allow(unused_variables))]
+pub(crate) fn set_name<H: Handle>(device: &Device, handle: H, name: &str) {
+    #[cfg(feature = "debug")]
+    {
+        let name = CString::new(name).unwrap();
+        let info = DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(H::TYPE)
+            .object_handle(handle.as_raw())
+            .object_name(&name);
+        let result = unsafe {
+            device
+                .extensions
+                .debug_utils
+                .set_debug_utils_object_name(device.handle(), &info)
+        };
+        if let Err(err) = result {
+            warn!("Failed to name {name:?}: {err}")
+        }
+    }
+}
+
+/// Warns when the number of live objects of a kind keeps going up from one frame to the next,
+/// which is usually something holding on to an `Rc` it should have dropped
+#[derive(Default)]
+pub struct Tracker {
+    #[cfg(feature = "debug")]
+    frame: usize,
+    // the counts at the last check
+    #[cfg(feature = "debug")]
+    counts: [usize; 3],
+    // how many checks in a row each count has gone up at
+    #[cfg(feature = "debug")]
+    growing: [usize; 3],
+}
+
+impl Tracker {
+    /// Frames between each check, so counts that go up and down within a few frames, like while
+    /// frames are in flight, aren't mistaken for leaks
+    pub const INTERVAL: usize = 60;
+    /// Checks in a row a count can go up at before it is warned about
+    pub const CHECKS: usize = 5;
+
+    /// Called once a frame
+    pub fn frame(&mut self) {
+        #[cfg(feature = "debug")]
+        {
+            self.frame += 1;
+            if !self.frame.is_multiple_of(Self::INTERVAL) {
+                return;
+            }
+
+            for kind in Kind::ALL {
+                let i = kind as usize;
+                let count = live(kind);
+                if count > self.counts[i] {
+                    self.growing[i] += 1;
+                } else {
+                    self.growing[i] = 0;
+                }
+                self.counts[i] = count;
+
+                if self.growing[i] == Self::CHECKS {
+                    warn!(
+                        "{count} of {kind:?} are alive, having gone up every {} frames for the last {} frames",
+                        Self::INTERVAL,
+                        Self::INTERVAL * Self::CHECKS
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "debug"))]
+mod tests {
+    use super::*;
+    use crate::{
+        buffer::{Dynamic, Static},
+        BufferUsageFlags, Context,
+    };
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    pub fn test_live_buffers() {
+        let ctx = Context::headless("test").unwrap();
+
+        let staged = (0..50)
+            .map(|i| {
+                let buffer = Static::new(&ctx, &[0; 16], BufferUsageFlags::UNIFORM_BUFFER).unwrap();
+                buffer.set_name(&format!("staged {i}"));
+                buffer
+            })
+            .collect::<Vec<_>>();
+        let dynamic = (0..50)
+            .map(|_| Dynamic::new(&ctx, 16, BufferUsageFlags::UNIFORM_BUFFER).unwrap())
+            .collect::<Vec<_>>();
+        // the staging buffers are already gone
+        assert_eq!(live(Kind::Buffer), 100);
+
+        drop(staged);
+        drop(dynamic);
+        assert_eq!(live(Kind::Buffer), 0);
+    }
+}
@@ -1,13 +1,17 @@
-use std::{any::Any, rc::Rc};
+use std::{
+    any::Any,
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use ash::{
     prelude::VkResult,
     vk::{
-        self, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolSize, DescriptorSetAllocateInfo, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, ImageLayout, ShaderStageFlags, WriteDescriptorSet
+        self, DescriptorBufferInfo, DescriptorImageInfo, DescriptorPoolCreateFlags, DescriptorPoolCreateInfo, DescriptorPoolResetFlags, DescriptorPoolSize, DescriptorSetAllocateInfo, DescriptorSetLayoutBinding, DescriptorSetLayoutCreateInfo, DescriptorType, ImageLayout, ShaderStageFlags, WriteDescriptorSet
     },
 };
 
-use crate::{buffer, image::{ImageView, Sampler}, Context, Device};
+use crate::{buffer, debug::{self, Kind}, image::{ImageView, Sampler}, Context, Device};
 
 #[derive(Clone)]
 pub struct Layout {
@@ -19,10 +23,83 @@ pub struct Layout {
 
 pub struct Set {
     layout: Rc<Layout>,
+    // the frame pool it came from, which frees it when it is reset, or the layout's own pool if
+    // `None`
+    pool: Option<Rc<PoolHandle>>,
     pub handle: vk::DescriptorSet,
     resources: Vec<Rc<dyn Any>>,
 }
 
+struct PoolHandle {
+    device: Rc<Device>,
+    handle: vk::DescriptorPool,
+}
+
+impl Drop for PoolHandle {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_descriptor_pool(self.handle, None) }
+    }
+}
+
+/// Sets for a single frame, which are freed all at once by resetting the pool when the frame is
+/// done with rather than one at a time. Once it is full another pool is added, and on the next
+/// reset they are swapped for a single pool big enough for them all, so it grows to fit the
+/// busiest frame.
+pub struct Pool {
+    layout: Rc<Layout>,
+    // sets are allocated from the last, with the rest kept until the reset as their sets are
+    // still in use
+    handles: RefCell<Vec<Rc<PoolHandle>>>,
+    // how many sets all of the handles can hold together
+    capacity: Cell<usize>,
+}
+
+impl Pool {
+    pub fn alloc(&self) -> VkResult<Set> {
+        let current = self.handles.borrow().last().unwrap().clone();
+        let (handle, pool) = match self.layout.alloc_from(current.handle) {
+            Ok(handle) => (handle, current),
+            Err(vk::Result::ERROR_OUT_OF_POOL_MEMORY | vk::Result::ERROR_FRAGMENTED_POOL) => {
+                // doubling what there is in total
+                let pool = self.layout.frame_pool(self.capacity.get())?;
+                self.capacity.set(self.capacity.get() * 2);
+                self.handles.borrow_mut().push(pool.clone());
+                (self.layout.alloc_from(pool.handle)?, pool)
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(Set {
+            handle,
+            layout: self.layout.clone(),
+            pool: Some(pool),
+            resources: Vec::new(),
+        })
+    }
+
+    /// How many sets the pool holds before it has to grow
+    pub fn capacity(&self) -> usize {
+        self.capacity.get()
+    }
+
+    /// Frees every set allocated since the last reset, which all have to have been dropped
+    pub fn reset(&mut self) -> VkResult<()> {
+        let handles = self.handles.get_mut();
+        assert!(
+            handles.iter().all(|handle| Rc::strong_count(handle) == 1),
+            "Sets from the pool are still alive"
+        );
+        if handles.len() > 1 {
+            *handles = vec![self.layout.frame_pool(self.capacity.get())?];
+            return Ok(());
+        }
+        unsafe {
+            self.layout
+                .device
+                .reset_descriptor_pool(handles[0].handle, DescriptorPoolResetFlags::empty())
+        }
+    }
+}
+
 impl Layout {
     pub fn new(ctx: &Context, bindings: &[DescriptorType], capacity: usize) -> VkResult<Rc<Self>> {
         let binding_infos = bindings
@@ -43,6 +120,27 @@ impl Layout {
                 .create_descriptor_set_layout(&create_info, None)?
         };
 
+        let pool = Self::create_pool(
+            &ctx.device,
+            bindings,
+            capacity,
+            DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET,
+        )?;
+
+        Ok(Rc::new(Self {
+            device: ctx.device.clone(),
+            layout,
+            pool,
+            bindings: bindings.to_vec(),
+        }))
+    }
+
+    fn create_pool(
+        device: &Device,
+        bindings: &[DescriptorType],
+        capacity: usize,
+        flags: DescriptorPoolCreateFlags,
+    ) -> VkResult<vk::DescriptorPool> {
         let pool_sizes = bindings
             .iter()
             .map(|ty| {
@@ -54,31 +152,54 @@ impl Layout {
             .collect::<Vec<_>>();
 
         let create_info = DescriptorPoolCreateInfo::builder()
-            .flags(DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET)
+            .flags(flags)
             .pool_sizes(&pool_sizes)
             .max_sets(capacity as u32);
-        let pool = unsafe { ctx.device.create_descriptor_pool(&create_info, None)? };
-
-        Ok(Rc::new(Self {
-            device: ctx.device.clone(),
-            layout,
-            pool,
-            bindings: bindings.to_vec(),
-        }))
+        unsafe { device.create_descriptor_pool(&create_info, None) }
     }
 
-    pub fn alloc(self: &Rc<Self>) -> VkResult<Set> {
+    fn alloc_from(&self, pool: vk::DescriptorPool) -> VkResult<vk::DescriptorSet> {
         let set_layouts = [self.layout];
         let alloc_info = DescriptorSetAllocateInfo::builder()
-            .descriptor_pool(self.pool)
+            .descriptor_pool(pool)
             .set_layouts(&set_layouts);
         let handle = unsafe { self.device.allocate_descriptor_sets(&alloc_info)?[0] };
+        debug::created(Kind::DescriptorSet);
+        Ok(handle)
+    }
+
+    /// Allocates a set that lives until it is dropped
+    pub fn alloc(self: &Rc<Self>) -> VkResult<Set> {
+        let handle = self.alloc_from(self.pool)?;
         Ok(Set {
             handle,
             layout: self.clone(),
+            pool: None,
             resources: Vec::new(),
         })
     }
+
+    fn frame_pool(&self, capacity: usize) -> VkResult<Rc<PoolHandle>> {
+        let handle = Self::create_pool(
+            &self.device,
+            &self.bindings,
+            capacity,
+            DescriptorPoolCreateFlags::empty(),
+        )?;
+        Ok(Rc::new(PoolHandle {
+            device: self.device.clone(),
+            handle,
+        }))
+    }
+
+    /// A separate pool for a single frame, starting with room for `capacity` sets, see [`Pool`]
+    pub fn pool(self: &Rc<Self>, capacity: usize) -> VkResult<Pool> {
+        Ok(Pool {
+            layout: self.clone(),
+            handles: RefCell::new(vec![self.frame_pool(capacity)?]),
+            capacity: Cell::new(capacity),
+        })
+    }
 }
 
 impl Drop for Layout {
@@ -139,6 +260,11 @@ impl Set {
         self
     }
 
+    /// Only does anything with the `debug` feature
+    pub fn set_name(&self, name: &str) {
+        debug::set_name(&self.layout.device, self.handle, name)
+    }
+
     pub fn finish(self) -> Rc<Self> {
         Rc::new(self)
     }
@@ -146,11 +272,60 @@ impl Set {
 
 impl Drop for Set {
     fn drop(&mut self) {
-        unsafe {
-            self.layout
-                .device
-                .free_descriptor_sets(self.layout.pool, &[self.handle])
-                .unwrap()
+        if self.pool.is_none() {
+            unsafe {
+                self.layout
+                    .device
+                    .free_descriptor_sets(self.layout.pool, &[self.handle])
+                    .unwrap()
+            }
+        }
+        debug::destroyed(Kind::DescriptorSet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    pub fn test_pool_grows() {
+        let ctx = Context::headless("test").unwrap();
+        let layout = Layout::new(&ctx, &[DescriptorType::UNIFORM_BUFFER], 1).unwrap();
+        let mut pool = layout.pool(2).unwrap();
+
+        // a frame with more sets than the pool started with
+        let sets = (0..5).map(|_| pool.alloc().unwrap()).collect::<Vec<_>>();
+        assert_eq!(pool.capacity(), 8);
+        drop(sets);
+        pool.reset().unwrap();
+
+        // which all fit in the one pool from then on
+        let sets = (0..8).map(|_| pool.alloc().unwrap()).collect::<Vec<_>>();
+        assert_eq!(pool.capacity(), 8);
+        drop(sets);
+        pool.reset().unwrap();
+    }
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    #[cfg(feature = "debug")]
+    pub fn test_pool_allocs() {
+        use crate::debug::{live, Kind};
+
+        let ctx = Context::headless("test").unwrap();
+        let layout = Layout::new(&ctx, &[DescriptorType::UNIFORM_BUFFER], 1).unwrap();
+        let mut pool = layout.pool(100).unwrap();
+
+        // every frame allocates only the sets it asks for, and they are all gone once it is reset
+        let before = live(Kind::DescriptorSet);
+        for _ in 0..10 {
+            let sets = (0..100).map(|_| pool.alloc().unwrap()).collect::<Vec<_>>();
+            assert_eq!(live(Kind::DescriptorSet) - before, 100);
+            drop(sets);
+            pool.reset().unwrap();
+            assert_eq!(live(Kind::DescriptorSet), before);
         }
     }
 }
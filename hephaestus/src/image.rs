@@ -11,7 +11,29 @@ use ash::{
     },
 };
 
-use crate::{buffer::find_memory_type, Context, Device};
+use crate::{
+    buffer::find_memory_type,
+    debug::{self, Kind},
+    Context, Device,
+};
+
+/// Anything commands can use as an image, including the swapchain's images which aren't owned
+/// by an [`Image`]
+pub trait ImageHandle {
+    fn image(&self) -> vk::Image;
+}
+
+impl<T: ImageHandle> ImageHandle for Rc<T> {
+    fn image(&self) -> vk::Image {
+        T::image(self)
+    }
+}
+
+impl ImageHandle for vk::Image {
+    fn image(&self) -> vk::Image {
+        *self
+    }
+}
 
 pub struct Image {
     device: Rc<Device>,
@@ -19,6 +41,12 @@ pub struct Image {
     pub memory: DeviceMemory,
 }
 
+impl ImageHandle for Image {
+    fn image(&self) -> vk::Image {
+        self.handle
+    }
+}
+
 pub struct ImageInfo {
     pub format: Format,
     pub extent: Extent2D,
@@ -56,6 +84,7 @@ impl Image {
             .memory_type_index(type_index as u32);
         let memory = unsafe { ctx.device.allocate_memory(&alloc_info, None)? };
         unsafe { ctx.device.bind_image_memory(handle, memory, 0)? };
+        debug::created(Kind::Image);
 
         Ok(Rc::new(Self {
             device: ctx.device.clone(),
@@ -63,12 +92,18 @@ impl Image {
             memory,
         }))
     }
+
+    /// Only does anything with the `debug` feature
+    pub fn set_name(&self, name: &str) {
+        debug::set_name(&self.device, self.handle, name)
+    }
 }
 
 impl Drop for Image {
     fn drop(&mut self) {
         unsafe { self.device.destroy_image(self.handle, None) }
         unsafe { self.device.free_memory(self.memory, None) }
+        debug::destroyed(Kind::Image);
     }
 }
 
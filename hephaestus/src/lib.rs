@@ -1,8 +1,10 @@
 pub mod buffer;
 pub mod command;
+pub mod debug;
 pub mod descriptor;
 pub mod image;
 pub mod pipeline;
+pub mod post;
 pub mod task;
 pub mod vertex;
 
@@ -21,7 +23,7 @@ pub use ash::vk::{
 };
 use ash::{
     vk::{
-        self, ApplicationInfo, ColorSpaceKHR, CompositeAlphaFlagsKHR, DeviceCreateInfo, DeviceQueueCreateInfo, Image, InstanceCreateInfo, PhysicalDeviceFeatures, PhysicalDeviceProperties, PhysicalDeviceVulkan11Features, PresentModeKHR, QueueFamilyProperties, QueueFlags, SharingMode, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SwapchainCreateInfoKHR, SwapchainKHR
+        self, ApplicationInfo, ColorSpaceKHR, CompositeAlphaFlagsKHR, DeviceCreateInfo, DeviceQueueCreateInfo, Image, InstanceCreateInfo, PhysicalDeviceFeatures, PhysicalDeviceFeatures2, PhysicalDeviceProperties, PhysicalDeviceVulkan11Features, PhysicalDeviceVulkan12Features, PresentModeKHR, QueueFamilyProperties, QueueFlags, SharingMode, SurfaceCapabilitiesKHR, SurfaceFormatKHR, SwapchainCreateInfoKHR, SwapchainKHR
     },
     Entry,
 };
@@ -32,13 +34,19 @@ use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
 
 pub struct InstanceExtensions {
     pub surface: ash::extensions::khr::Surface,
+    #[cfg(feature = "debug")]
+    pub debug_utils: ash::extensions::ext::DebugUtils,
 }
 
 impl InstanceExtensions {
     pub fn new(entry: &Entry, instance: &ash::Instance) -> Self {
         let surface = ash::extensions::khr::Surface::new(entry, instance);
 
-        Self { surface }
+        Self {
+            surface,
+            #[cfg(feature = "debug")]
+            debug_utils: ash::extensions::ext::DebugUtils::new(entry, instance),
+        }
     }
 }
 
@@ -63,11 +71,22 @@ pub struct PhysicalDevice {
 }
 
 impl PhysicalDevice {
-    pub fn get_samples(&self) -> SampleCountFlags {
+    /// The most samples per pixel that both colour and depth attachments support
+    pub fn max_samples(&self) -> SampleCountFlags {
         let samples = self.properties.limits.framebuffer_color_sample_counts
             & self.properties.limits.framebuffer_depth_sample_counts;
         SampleCountFlags::from_raw(1 << (31 - samples.as_raw().leading_zeros()))
     }
+
+    /// What offsets into a uniform buffer have to be a multiple of, to bind from them
+    pub fn uniform_alignment(&self) -> usize {
+        self.properties.limits.min_uniform_buffer_offset_alignment as usize
+    }
+
+    /// What offsets into a storage buffer have to be a multiple of, to bind from them
+    pub fn storage_alignment(&self) -> usize {
+        self.properties.limits.min_storage_buffer_offset_alignment as usize
+    }
 }
 
 pub struct Surface {
@@ -77,6 +96,8 @@ pub struct Surface {
     pub capabilities: SurfaceCapabilitiesKHR,
     pub formats: Vec<SurfaceFormatKHR>,
     pub present_modes: Vec<PresentModeKHR>,
+    /// Waits for the display to refresh before presenting, which stops tearing but adds latency
+    pub vsync: bool,
 }
 
 impl Surface {
@@ -119,6 +140,7 @@ impl Surface {
                     width: extent.0,
                     height: extent.1,
                 },
+                vsync: false,
             })
         }
     }
@@ -136,11 +158,32 @@ impl Drop for Surface {
 }
 
 impl Instance {
+    #[cfg(not(feature = "debug"))]
     const EXTENSIONS: &'static [&'static CStr] = &[ash::extensions::khr::Surface::name()];
+    #[cfg(feature = "debug")]
+    const EXTENSIONS: &'static [&'static CStr] = &[
+        ash::extensions::khr::Surface::name(),
+        ash::extensions::ext::DebugUtils::name(),
+    ];
 
     const LAYERS: &'static [&'static CStr] = &[c"VK_LAYER_KHRONOS_validation"];
 
     pub fn new<T: HasRawDisplayHandle>(entry: &Entry, name: &CStr, window: T) -> VkResult<Self> {
+        let presentation_extensions =
+            ash_window::enumerate_required_extensions(window.raw_display_handle())?;
+        Self::with_extensions(entry, name, presentation_extensions)
+    }
+
+    /// Creates an instance without any of the extensions needed to present to a window
+    pub fn headless(entry: &Entry, name: &CStr) -> VkResult<Self> {
+        Self::with_extensions(entry, name, &[])
+    }
+
+    fn with_extensions(
+        entry: &Entry,
+        name: &CStr,
+        presentation_extensions: &[*const c_char],
+    ) -> VkResult<Self> {
         let app_info = ApplicationInfo::builder()
             .engine_name(name)
             .engine_version(vk::make_api_version(0, 1, 0, 0))
@@ -164,8 +207,6 @@ impl Instance {
             .collect::<Vec<_>>();
 
         let available = entry.enumerate_instance_extension_properties(None)?;
-        let presentation_extensions =
-            ash_window::enumerate_required_extensions(window.raw_display_handle())?;
         println!(
             "{:?}",
             available
@@ -237,7 +278,20 @@ pub struct Queues {
 }
 
 pub struct DeviceExtensions {
-    pub swapchain: ash::extensions::khr::Swapchain,
+    /// `None` for a device created without a surface, which has nothing to present to
+    pub swapchain: Option<ash::extensions::khr::Swapchain>,
+    /// The instance's, kept here for naming objects with just the device
+    #[cfg(feature = "debug")]
+    pub debug_utils: ash::extensions::ext::DebugUtils,
+}
+
+impl DeviceExtensions {
+    /// Panics for a device created without a surface, which never has a swapchain to use it on
+    pub fn swapchain(&self) -> &ash::extensions::khr::Swapchain {
+        self.swapchain
+            .as_ref()
+            .expect("The device was created without a surface")
+    }
 }
 
 pub struct Device {
@@ -245,6 +299,9 @@ pub struct Device {
     pub extensions: DeviceExtensions,
     pub physical: PhysicalDevice,
     pub queues: Queues,
+    /// Whether [`command::Recorder::draw_indexed_indirect_count`] can be used, which not every
+    /// device supports
+    pub draw_indirect_count: bool,
 }
 
 impl Deref for Device {
@@ -262,6 +319,7 @@ pub struct Swapchain {
     pub views: Vec<Rc<ImageView>>,
     pub format: Format,
     pub extent: Extent2D,
+    pub usage: ImageUsageFlags,
 }
 
 impl Swapchain {
@@ -275,12 +333,17 @@ impl Swapchain {
             })
             .unwrap_or_else(|| surface.formats.first().unwrap());
 
-        let present_mode = surface
-            .present_modes
-            .iter()
-            .copied()
-            .find(|mode| *mode == PresentModeKHR::MAILBOX)
-            .unwrap_or(PresentModeKHR::FIFO);
+        // fifo is always supported, and is the only mode that is guaranteed to wait for vsync
+        let present_mode = if surface.vsync {
+            PresentModeKHR::FIFO
+        } else {
+            surface
+                .present_modes
+                .iter()
+                .copied()
+                .find(|mode| *mode == PresentModeKHR::MAILBOX)
+                .unwrap_or(PresentModeKHR::FIFO)
+        };
 
         let extent = if surface.capabilities.current_extent.width == u32::MAX {
             Extent2D {
@@ -303,6 +366,10 @@ impl Swapchain {
             (surface.capabilities.min_image_count + 1).min(surface.capabilities.max_image_count)
         };
 
+        // lets frames be copied out of the swapchain, for screenshots, wherever the surface allows
+        let usage = ImageUsageFlags::COLOR_ATTACHMENT
+            | (surface.capabilities.supported_usage_flags & ImageUsageFlags::TRANSFER_SRC);
+
         let create_info = SwapchainCreateInfoKHR::builder()
             .surface(surface.handle)
             .min_image_count(image_count)
@@ -310,7 +377,7 @@ impl Swapchain {
             .image_color_space(format.color_space)
             .image_extent(extent)
             .image_array_layers(1)
-            .image_usage(ImageUsageFlags::COLOR_ATTACHMENT)
+            .image_usage(usage)
             .pre_transform(surface.capabilities.current_transform)
             .composite_alpha(CompositeAlphaFlagsKHR::OPAQUE)
             .present_mode(present_mode)
@@ -328,11 +395,11 @@ impl Swapchain {
         let handle = unsafe {
             device
                 .extensions
-                .swapchain
+                .swapchain()
                 .create_swapchain(&create_info, None)?
         };
 
-        let images = unsafe { device.extensions.swapchain.get_swapchain_images(handle)? };
+        let images = unsafe { device.extensions.swapchain().get_swapchain_images(handle)? };
         let views = images
             .iter()
             .map(|image| {
@@ -353,6 +420,7 @@ impl Swapchain {
             views,
             format: format.format,
             extent,
+            usage,
         })
     }
 }
@@ -362,7 +430,7 @@ impl Drop for Swapchain {
         unsafe {
             self.device
                 .extensions
-                .swapchain
+                .swapchain()
                 .destroy_swapchain(self.handle, None)
         };
     }
@@ -371,7 +439,13 @@ impl Drop for Swapchain {
 impl Device {
     const EXTENSIONS: &'static [&'static CStr] = &[ash::extensions::khr::Swapchain::name()];
 
-    pub fn new(instance: &Instance, physical: PhysicalDevice, surface: &Surface) -> VkResult<Self> {
+    /// Without a surface the graphics queue is also used as the present queue, which is never
+    /// presented to, and the swapchain extension isn't requested
+    pub fn new(
+        instance: &Instance,
+        physical: PhysicalDevice,
+        surface: Option<&Surface>,
+    ) -> VkResult<Self> {
         let priorities = &[1.0];
 
         let graphics_index = physical
@@ -379,18 +453,25 @@ impl Device {
             .iter()
             .position(|family| family.queue_flags.contains(QueueFlags::GRAPHICS))
             .expect("No graphics capable queue families") as u32;
-        let present_index = physical
-            .queue_families
-            .iter()
-            .enumerate()
-            .position(|(i, _)| unsafe {
-                instance
-                    .extensions
-                    .surface
-                    .get_physical_device_surface_support(physical.handle, i as u32, surface.handle)
-                    .unwrap()
-            })
-            .expect("No presentation capable queue families") as u32;
+        let present_index = match surface {
+            Some(surface) => physical
+                .queue_families
+                .iter()
+                .enumerate()
+                .position(|(i, _)| unsafe {
+                    instance
+                        .extensions
+                        .surface
+                        .get_physical_device_surface_support(
+                            physical.handle,
+                            i as u32,
+                            surface.handle,
+                        )
+                        .unwrap()
+                })
+                .expect("No presentation capable queue families") as u32,
+            None => graphics_index,
+        };
 
         let indices = HashSet::from([graphics_index, present_index]);
         let queue_create_infos = indices
@@ -404,7 +485,11 @@ impl Device {
             .collect::<Vec<_>>();
 
         let available = unsafe { instance.enumerate_device_extension_properties(physical.handle)? };
-        let extensions = Self::EXTENSIONS
+        let wanted: &[&CStr] = match surface {
+            Some(_) => Self::EXTENSIONS,
+            None => &[],
+        };
+        let extensions = wanted
             .iter()
             .filter(|wanted| {
                 let found = available
@@ -420,11 +505,24 @@ impl Device {
 
         let features = PhysicalDeviceFeatures::builder().multi_draw_indirect(true);
         let mut features11 = PhysicalDeviceVulkan11Features::builder().shader_draw_parameters(true);
+        // for draws whose count is written on the gpu, by culling in a compute shader, so only
+        // turned on where it is supported
+        let draw_indirect_count = {
+            let mut supported = PhysicalDeviceVulkan12Features::default();
+            let mut features = PhysicalDeviceFeatures2::builder().push_next(&mut supported);
+            unsafe { instance.get_physical_device_features2(physical.handle, &mut features) };
+            supported.draw_indirect_count == vk::TRUE
+        };
+        if !draw_indirect_count {
+            warn!("Draws with a count written on the gpu aren't supported");
+        }
+        let mut features12 =
+            PhysicalDeviceVulkan12Features::builder().draw_indirect_count(draw_indirect_count);
 
         let create_info = DeviceCreateInfo::builder()
             .enabled_extension_names(&extensions)
             .queue_create_infos(&queue_create_infos)
-            .enabled_features(&features).push_next(&mut features11);
+            .enabled_features(&features).push_next(&mut features11).push_next(&mut features12);
 
         let inner = unsafe { instance.create_device(physical.handle, &create_info, None)? };
 
@@ -433,14 +531,19 @@ impl Device {
             present: Queue::new(&inner, present_index),
         };
 
-        let swapchain = ash::extensions::khr::Swapchain::new(instance, &inner);
-        let extensions = DeviceExtensions { swapchain };
+        let swapchain = surface.map(|_| ash::extensions::khr::Swapchain::new(instance, &inner));
+        let extensions = DeviceExtensions {
+            swapchain,
+            #[cfg(feature = "debug")]
+            debug_utils: instance.extensions.debug_utils.clone(),
+        };
 
         Ok(Self {
             inner,
             extensions,
             physical,
             queues,
+            draw_indirect_count,
         })
     }
 }
@@ -455,7 +558,8 @@ pub struct Context {
     pub swapchain: Option<Swapchain>,
     pub command_pool: Rc<command::Pool>,
     pub device: Rc<Device>,
-    pub surface: Surface,
+    /// `None` for a headless context, which can only render to images it owns
+    pub surface: Option<Surface>,
     pub instance: Rc<Instance>,
     pub entry: Entry,
 }
@@ -466,62 +570,77 @@ impl Context {
         window: T,
         extent: (u32, u32),
     ) -> VkResult<Self> {
-        let entry = unsafe { Entry::load() }.unwrap_or_else(|_| {
-            println!("Failed to load vulkan dll, using linked vulkan");
-            Entry::linked()
-        });
+        let entry = Self::load_entry();
         let name = CString::new(name).unwrap();
         let instance = Rc::new(Instance::new(&entry, &name, &window)?);
         let physical = unsafe { instance.get_physical_device()? };
         let surface = Surface::new(&entry, &instance, &physical, window, extent)?;
-        let device = Rc::new(Device::new(&instance, physical, &surface)?);
+        let device = Rc::new(Device::new(&instance, physical, Some(&surface))?);
         let swapchain = Some(Swapchain::new(&device, &surface)?);
         let command_pool = command::Pool::new(&device, &device.queues.graphics)?;
 
         Ok(Self {
             entry,
             instance,
-            surface,
+            surface: Some(surface),
             device,
             swapchain,
             command_pool,
         })
     }
 
-    fn refresh_surface(&mut self) -> VkResult<()> {
+    /// Creates a context without a window, surface or swapchain for offscreen rendering
+    pub fn headless(name: &str) -> VkResult<Self> {
+        let entry = Self::load_entry();
+        let name = CString::new(name).unwrap();
+        let instance = Rc::new(Instance::headless(&entry, &name)?);
+        let physical = unsafe { instance.get_physical_device()? };
+        let device = Rc::new(Device::new(&instance, physical, None)?);
+        let command_pool = command::Pool::new(&device, &device.queues.graphics)?;
+
+        Ok(Self {
+            entry,
+            instance,
+            surface: None,
+            device,
+            swapchain: None,
+            command_pool,
+        })
+    }
+
+    fn load_entry() -> Entry {
+        unsafe { Entry::load() }.unwrap_or_else(|_| {
+            println!("Failed to load vulkan dll, using linked vulkan");
+            Entry::linked()
+        })
+    }
+
+    fn refresh_surface(instance: &Instance, device: &Device, surface: &mut Surface) -> VkResult<()> {
         unsafe {
-            self.surface.capabilities = self
-                .instance
+            surface.capabilities = instance
                 .extensions
                 .surface
-                .get_physical_device_surface_capabilities(
-                    self.device.physical.handle,
-                    self.surface.handle,
-                )?;
-            self.surface.formats = self
-                .instance
+                .get_physical_device_surface_capabilities(device.physical.handle, surface.handle)?;
+            surface.formats = instance
                 .extensions
                 .surface
-                .get_physical_device_surface_formats(
-                    self.device.physical.handle,
-                    self.surface.handle,
-                )?;
-            self.surface.present_modes = self
-                .instance
+                .get_physical_device_surface_formats(device.physical.handle, surface.handle)?;
+            surface.present_modes = instance
                 .extensions
                 .surface
-                .get_physical_device_surface_present_modes(
-                    self.device.physical.handle,
-                    self.surface.handle,
-                )?;
+                .get_physical_device_surface_present_modes(device.physical.handle, surface.handle)?;
             Ok(())
         }
     }
 
+    /// Does nothing for a headless context, as there is no swapchain to recreate
     pub fn recreate_swapchain(&mut self) -> VkResult<()> {
-        self.refresh_surface()?;
+        let Some(surface) = self.surface.as_mut() else {
+            return Ok(());
+        };
+        Self::refresh_surface(&self.instance, &self.device, surface)?;
         drop(self.swapchain.take());
-        self.swapchain = Some(Swapchain::new(&self.device, &self.surface)?);
+        self.swapchain = Some(Swapchain::new(&self.device, surface)?);
         Ok(())
     }
 }
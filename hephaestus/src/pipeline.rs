@@ -5,22 +5,23 @@ use ash::{
     vk::{
         self, AccessFlags, AttachmentDescription, AttachmentLoadOp, AttachmentReference,
         AttachmentStoreOp, BlendFactor, BlendOp, ClearColorValue, ClearDepthStencilValue,
-        ClearValue, ColorComponentFlags, CompareOp, CullModeFlags, DependencyFlags, DynamicState,
-        Extent2D, Format, FramebufferCreateInfo, FrontFace, GraphicsPipelineCreateInfo, Offset2D,
-        Pipeline, PipelineCache, PipelineColorBlendAttachmentState,
-        PipelineColorBlendStateCreateInfo, PipelineDepthStencilStateCreateInfo,
-        PipelineDynamicStateCreateInfo, PipelineInputAssemblyStateCreateInfo, PipelineLayout,
-        PipelineLayoutCreateInfo, PipelineMultisampleStateCreateInfo,
-        PipelineRasterizationStateCreateInfo, PipelineShaderStageCreateInfo, PipelineStageFlags,
-        PipelineVertexInputStateCreateInfo, PipelineViewportStateCreateInfo, PolygonMode,
-        PrimitiveTopology, Rect2D, RenderPassCreateInfo, Result, SampleCountFlags,
-        ShaderModuleCreateInfo, ShaderStageFlags, SubpassDependency, SubpassDescription,
-        VertexInputAttributeDescription, VertexInputBindingDescription, VertexInputRate,
+        ClearValue, ColorComponentFlags, CompareOp, ComputePipelineCreateInfo, CullModeFlags,
+        DependencyFlags, DynamicState, Extent2D, Format, FramebufferCreateInfo, FrontFace,
+        GraphicsPipelineCreateInfo, Offset2D, Pipeline, PipelineCache,
+        PipelineColorBlendAttachmentState, PipelineColorBlendStateCreateInfo,
+        PipelineDepthStencilStateCreateInfo, PipelineDynamicStateCreateInfo,
+        PipelineInputAssemblyStateCreateInfo, PipelineLayout, PipelineLayoutCreateInfo,
+        PipelineMultisampleStateCreateInfo, PipelineRasterizationStateCreateInfo,
+        PipelineShaderStageCreateInfo, PipelineStageFlags, PipelineVertexInputStateCreateInfo,
+        PipelineViewportStateCreateInfo, PolygonMode, Rect2D, RenderPassCreateInfo, Result,
+        SampleCountFlags, ShaderModuleCreateInfo, ShaderStageFlags, SubpassDependency,
+        SubpassDescription, VertexInputAttributeDescription, VertexInputBindingDescription,
+        VertexInputRate,
     },
 };
 use log::error;
 
-pub use ash::vk::{ImageLayout, PipelineBindPoint};
+pub use ash::vk::{ImageLayout, PipelineBindPoint, PrimitiveTopology};
 
 use crate::{descriptor, vertex, Device, ImageView};
 
@@ -258,6 +259,21 @@ impl Graphics {
     pub fn builder<'a>() -> GraphicsBuilder<'a> {
         GraphicsBuilder::default()
     }
+
+    /// Starts a pipeline that covers the whole viewport with a single triangle, without a vertex
+    /// buffer. `vertex` makes the triangle out of `gl_VertexIndex`, like
+    /// `assets/shaders/fullscreen.vert.glsl` does, and it is drawn with
+    /// [`Recorder::draw_fullscreen`](crate::command::Recorder::draw_fullscreen).
+    pub fn fullscreen<'a>(
+        vertex: &'a ShaderModule,
+        fragment: &'a ShaderModule,
+    ) -> GraphicsBuilder<'a> {
+        Self::builder()
+            .vertex(vertex)
+            .fragment(fragment)
+            .viewport(Viewport::Dynamic)
+            .topology(PrimitiveTopology::TRIANGLE_LIST)
+    }
 }
 
 impl Drop for Graphics {
@@ -267,6 +283,53 @@ impl Drop for Graphics {
     }
 }
 
+pub struct Compute {
+    device: Rc<Device>,
+    pub layout: PipelineLayout,
+    pub handle: Pipeline,
+}
+
+impl Compute {
+    pub fn new(
+        device: &Rc<Device>,
+        shader: &ShaderModule,
+        layouts: Vec<&descriptor::Layout>,
+    ) -> VkResult<Compute> {
+        let stage = PipelineShaderStageCreateInfo::builder()
+            .stage(ShaderStageFlags::COMPUTE)
+            .module(shader.handle)
+            .name(c"main")
+            .build();
+
+        let set_layouts = layouts.iter().map(|x| x.layout).collect::<Vec<_>>();
+        let create_info = PipelineLayoutCreateInfo::builder().set_layouts(&set_layouts);
+        let layout = unsafe { device.create_pipeline_layout(&create_info, None)? };
+
+        let create_info = ComputePipelineCreateInfo::builder()
+            .stage(stage)
+            .layout(layout)
+            .build();
+
+        let result =
+            unsafe { device.create_compute_pipelines(PipelineCache::null(), &[create_info], None) };
+        match result {
+            Ok(handles) => Ok(Compute {
+                device: device.clone(),
+                handle: *handles.first().unwrap(),
+                layout,
+            }),
+            Err((_, result)) => Err(result),
+        }
+    }
+}
+
+impl Drop for Compute {
+    fn drop(&mut self) {
+        unsafe { self.device.destroy_pipeline(self.handle, None) };
+        unsafe { self.device.destroy_pipeline_layout(self.layout, None) };
+    }
+}
+
 pub enum Viewport {
     Dynamic,
     Fixed(u32, u32),
@@ -282,7 +345,9 @@ pub struct GraphicsBuilder<'a> {
     vertex_info: Option<vertex::Info>,
     layouts: Vec<&'a descriptor::Layout>,
     depth: bool,
-    multisampled: Option<SampleCountFlags>
+    depth_read_only: bool,
+    multisampled: Option<SampleCountFlags>,
+    topology: Option<PrimitiveTopology>,
 }
 
 impl<'a> GraphicsBuilder<'a> {
@@ -326,11 +391,25 @@ impl<'a> GraphicsBuilder<'a> {
         self
     }
 
+    /// Tests against the depth buffer without writing to it, for things drawn on top of
+    /// surfaces (decals, overlays)
+    pub fn depth_read_only(mut self) -> Self {
+        self.depth = true;
+        self.depth_read_only = true;
+        self
+    }
+
     pub fn multisampled(mut self, samples: SampleCountFlags) -> Self {
         self.multisampled = Some(samples);
         self
     }
 
+    /// How the vertices are put together, a list of triangles if not given
+    pub fn topology(mut self, topology: PrimitiveTopology) -> Self {
+        self.topology = Some(topology);
+        self
+    }
+
     pub fn build(self, device: &Rc<Device>) -> VkResult<Graphics> {
         let vertex_stage = PipelineShaderStageCreateInfo::builder()
             .stage(ShaderStageFlags::VERTEX)
@@ -353,15 +432,22 @@ impl<'a> GraphicsBuilder<'a> {
         let dynamic_state =
             PipelineDynamicStateCreateInfo::builder().dynamic_states(&dynamic_states);
 
-        let vertex_info = self.vertex_info.expect("Missing vertex info");
-        let vertex_bindings = [VertexInputBindingDescription::builder()
-            .binding(0)
-            .stride(vertex_info.stride as u32)
-            .input_rate(VertexInputRate::VERTEX)
-            .build()];
-        let attributes = vertex_info
-            .attributes
+        // without any vertex info the vertex shader makes up its own vertices
+        let vertex_bindings = self
+            .vertex_info
+            .iter()
+            .map(|vertex_info| {
+                VertexInputBindingDescription::builder()
+                    .binding(0)
+                    .stride(vertex_info.stride as u32)
+                    .input_rate(VertexInputRate::VERTEX)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        let attributes = self
+            .vertex_info
             .into_iter()
+            .flat_map(|vertex_info| vertex_info.attributes)
             .enumerate()
             .map(|(i, (ty, offset))| {
                 VertexInputAttributeDescription::builder()
@@ -378,7 +464,7 @@ impl<'a> GraphicsBuilder<'a> {
             .vertex_attribute_descriptions(&attributes);
 
         let input_assembly = PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(PrimitiveTopology::TRIANGLE_LIST)
+            .topology(self.topology.unwrap_or(PrimitiveTopology::TRIANGLE_LIST))
             .primitive_restart_enable(false);
 
         let (viewports, scissors) = match viewport {
@@ -419,7 +505,7 @@ impl<'a> GraphicsBuilder<'a> {
         let depth_stencil = if self.depth {
             PipelineDepthStencilStateCreateInfo::builder()
                 .depth_test_enable(true)
-                .depth_write_enable(true)
+                .depth_write_enable(!self.depth_read_only)
                 .depth_compare_op(CompareOp::LESS)
                 .depth_bounds_test_enable(false)
                 .stencil_test_enable(false)
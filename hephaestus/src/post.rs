@@ -0,0 +1,84 @@
+use std::rc::Rc;
+
+use ash::{
+    prelude::VkResult,
+    vk::{
+        AttachmentLoadOp, AttachmentStoreOp, Extent2D, Format, ImageAspectFlags, ImageUsageFlags,
+        SampleCountFlags,
+    },
+};
+
+use crate::{
+    descriptor,
+    image::{Image, ImageInfo, ImageView, Sampler},
+    pipeline::{AttachmentInfo, Framebuffer, ImageLayout, PipelineBindPoint, RenderPass, Subpass},
+    Context, Device,
+};
+
+/// A render pass for a post-processing effect, drawing into a single colour attachment which is
+/// left ready for the next effect, or whatever comes after them, to sample
+pub fn render_pass(device: &Rc<Device>, format: Format) -> VkResult<RenderPass> {
+    let mut builder = RenderPass::builder();
+    let colour = builder.attachment(
+        format,
+        AttachmentInfo {
+            initial_layout: ImageLayout::UNDEFINED,
+            final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            load_op: AttachmentLoadOp::DONT_CARE,
+            store_op: AttachmentStoreOp::STORE,
+            samples: SampleCountFlags::TYPE_1,
+        },
+    );
+    builder.subpass(
+        Subpass::new(PipelineBindPoint::GRAPHICS)
+            .colour(colour, ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+    );
+    builder.build(device)
+}
+
+/// A colour image that one pass draws into and a later one samples, which is how effects are
+/// chained one after another
+pub struct Target {
+    pub image: Rc<Image>,
+    pub view: Rc<ImageView>,
+    pub framebuffer: Framebuffer,
+    /// Samples the image at binding 0
+    pub set: Rc<descriptor::Set>,
+}
+
+impl Target {
+    /// Creates a target for `render_pass`, which has to have a single colour attachment of
+    /// `format` like the ones from [`render_pass`]. `layout` is for the set the next pass
+    /// samples it with.
+    pub fn new(
+        ctx: &Context,
+        render_pass: &RenderPass,
+        format: Format,
+        extent: Extent2D,
+        layout: &Rc<descriptor::Layout>,
+        sampler: &Rc<Sampler>,
+    ) -> VkResult<Self> {
+        let image = Image::new(
+            ctx,
+            ImageInfo {
+                format,
+                extent,
+                usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED,
+                samples: SampleCountFlags::TYPE_1,
+            },
+        )?;
+        let view = ImageView::new(&ctx.device, &image, format, ImageAspectFlags::COLOR, extent)?;
+        let framebuffer = render_pass.get_framebuffer(&ctx.device, &[&view])?;
+        let set = layout
+            .alloc()?
+            .write_image(0, &view, sampler, ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .finish();
+
+        Ok(Self {
+            image,
+            view,
+            framebuffer,
+            set,
+        })
+    }
+}
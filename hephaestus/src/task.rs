@@ -100,7 +100,7 @@ impl Task {
         signal: Rc<Semaphore>,
     ) -> VkResult<(u32, bool)> {
         let result = unsafe {
-            device.extensions.swapchain.acquire_next_image(
+            device.extensions.swapchain().acquire_next_image(
                 swapchain.handle,
                 u64::MAX,
                 signal.handle,
@@ -176,7 +176,7 @@ impl Task {
         let result = unsafe {
             device
                 .extensions
-                .swapchain
+                .swapchain()
                 .queue_present(device.queues.present.handle, &present_info)
         };
 
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use glam::{IVec2, Vec2, Vec3};
+
+// how close another client has to be before a client is told about it
+pub const RADIUS: f32 = 64.0;
+
+/// Buckets positions on the ground plane into cells as wide as the interest radius, so finding
+/// everything in range only needs to look at the cells around a position
+pub struct Grid<T> {
+    cells: HashMap<IVec2, Vec<(Vec3, T)>>,
+}
+
+impl<T: Copy> Grid<T> {
+    pub fn new(entries: impl IntoIterator<Item = (Vec3, T)>) -> Self {
+        let mut cells: HashMap<IVec2, Vec<(Vec3, T)>> = HashMap::new();
+        entries.into_iter().for_each(|(position, value)| {
+            cells
+                .entry(Self::cell(position))
+                .or_default()
+                .push((position, value))
+        });
+        Self { cells }
+    }
+
+    fn cell(position: Vec3) -> IVec2 {
+        (Vec2::new(position.x, position.z) / RADIUS)
+            .floor()
+            .as_ivec2()
+    }
+
+    /// Everything within the interest radius of a position
+    pub fn nearby(&self, position: Vec3) -> impl Iterator<Item = T> + '_ {
+        let centre = Self::cell(position);
+        (-1..=1)
+            .flat_map(move |x| (-1..=1).map(move |y| centre + IVec2::new(x, y)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .filter(move |(other, _)| other.distance(position) <= RADIUS)
+            .map(|(_, value)| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_nearby() {
+        let grid = Grid::new([
+            (Vec3::ZERO, 0),
+            // in the next cell over, but still in range
+            (Vec3::new(-10.0, 0.0, 0.0), 1),
+            (Vec3::new(RADIUS * 1.5, 0.0, 0.0), 2),
+            (Vec3::new(RADIUS * 10.0, 0.0, 0.0), 3),
+        ]);
+        let mut nearby = grid.nearby(Vec3::new(1.0, 0.0, 1.0)).collect::<Vec<_>>();
+        nearby.sort();
+        assert_eq!(nearby, vec![0, 1]);
+    }
+}
@@ -0,0 +1,1294 @@
+mod interest;
+mod metrics;
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet, VecDeque},
+    io::ErrorKind,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+use glam::{Quat, Vec3};
+use interest::Grid;
+use metrics::Metrics;
+use nyx::{
+    combat::{AttackKind, CombatDefensive, CombatOffensive, Damage, TargetId, ATTACK_RANGE},
+    data::{self, skills::SkillDamage, Content, NodeId},
+    equipment::{
+        combat_bonus, Equipment, EquipmentId, EquipmentInventory, Equipped, Passive, PassiveStats,
+        Slot,
+    },
+    experience::Experience,
+    item::{
+        Inventory, Item, ItemStack, LootTable, Rarity, Recipe, RecipeId, RecipeOutput, RARITIES,
+    },
+    protocol::{
+        self, ClientId, Clientbound, ClientboundBundle, FailureReason, Sequence, Serverbound, Tick,
+        MAX_PACKET_SIZE, TPS,
+    },
+    task::{Proficiencies, CRAFT_XP},
+    transport::ServerTransport,
+};
+
+const FORCED_LATENCY: Duration = Duration::from_millis(0);
+// how often the metrics are logged
+const REPORT_INTERVAL: Duration = Duration::from_secs(30);
+// where the state of each client is written when the server shuts down
+const SAVE_DIR: &str = "saves";
+// overrides how many times a second the server ticks, which is `TPS` otherwise
+const TPS_VAR: &str = "HYPNOS_TPS";
+// how far a client has to move or turn before the other clients are told about it
+const MOVE_THRESHOLD: f32 = 0.05;
+const TURN_THRESHOLD: f32 = 0.02;
+// how much further than an attack's reach a target can be, as the client may have moved since
+// its last move arrived
+const RANGE_LENIENCY: f32 = 1.0;
+
+pub struct Client {
+    id: ClientId,
+    position: Cell<Vec3>,
+    rotation: Cell<Quat>,
+    // where the other clients were last told this client is
+    broadcast: Cell<(Vec3, Quat)>,
+    // the sequence of the latest move applied
+    sequence: Cell<Option<Sequence>>,
+    // whether it has moved since the last broadcast
+    moved: Cell<bool>,
+    // the other clients close enough that this client has been told about them
+    visible: RefCell<HashSet<ClientId>>,
+    inventory: RefCell<Inventory>,
+    equipment: RefCell<EquipmentInventory>,
+    equipped: RefCell<Equipped>,
+    proficiencies: RefCell<Proficiencies>,
+    experience: Cell<Experience>,
+}
+
+impl Client {
+    pub fn new(id: ClientId) -> Self {
+        Self {
+            id,
+            position: Cell::new(Vec3::ZERO),
+            rotation: Cell::new(Quat::IDENTITY),
+            broadcast: Cell::new((Vec3::ZERO, Quat::IDENTITY)),
+            sequence: Cell::new(None),
+            moved: Cell::new(false),
+            visible: RefCell::new(HashSet::new()),
+            inventory: RefCell::new(Inventory::default()),
+            equipment: RefCell::new(EquipmentInventory(Vec::new())),
+            equipped: RefCell::new(Equipped::default()),
+            proficiencies: RefCell::new(Proficiencies::default()),
+            experience: Cell::new(Experience::default()),
+        }
+    }
+
+    /// Moves the client, unless a later move has already arrived. Returns whether it was applied
+    fn apply_move(&self, position: Vec3, rotation: Quat, sequence: Sequence) -> bool {
+        if self.sequence.get().is_some_and(|last| sequence <= last) {
+            return false;
+        }
+        self.position.set(position);
+        self.rotation.set(rotation);
+        self.sequence.set(Some(sequence));
+        self.moved.set(true);
+        true
+    }
+
+    /// Whether the client has moved far enough since the last broadcast for the others to notice
+    fn noticeable(&self) -> bool {
+        let (position, rotation) = self.broadcast.get();
+        self.position.get().distance(position) > MOVE_THRESHOLD
+            || self.rotation.get().angle_between(rotation) > TURN_THRESHOLD
+    }
+}
+
+fn handle_networking(
+    socket: impl ServerTransport,
+    clientbound_rx: Receiver<(SocketAddr, Clientbound)>,
+    flush_rx: Receiver<Tick>,
+    serverbound_tx: Sender<(SocketAddr, Serverbound)>,
+    metrics: Arc<Metrics>,
+) {
+    let mut buf = [0; MAX_PACKET_SIZE];
+    println!("Listening");
+    let mut messages: HashMap<SocketAddr, Vec<Clientbound>> = HashMap::new();
+    let mut to_receive = VecDeque::new();
+    let mut last_seen: HashMap<SocketAddr, Instant> = HashMap::new();
+
+    loop {
+        // checked before taking the messages, so everything sent before the flush goes out with it
+        let flush = flush_rx.try_recv();
+        while let Ok((addr, message)) = clientbound_rx.try_recv() {
+            match messages.get_mut(&addr) {
+                Some(messages) => messages.push(message),
+                None => {
+                    messages.insert(addr, vec![message]);
+                }
+            }
+        }
+
+        if let Err(TryRecvError::Disconnected) = flush {
+            // the server is shutting down, and has already flushed for the last time
+            return;
+        }
+        if let Ok(tick) = flush {
+            messages.iter_mut().for_each(|(addr, messages)| {
+                let packets = match ClientboundBundle::pack(tick, std::mem::take(messages)) {
+                    Ok(packets) => packets,
+                    Err(e) => {
+                        println!("Dropping the messages to {addr:?} for {tick:?}: {e}");
+                        return;
+                    }
+                };
+                packets.into_iter().for_each(|packet| {
+                    socket.send_to(&packet, *addr).unwrap();
+                    metrics.sent(packet.len());
+                })
+            })
+        }
+
+        let (n, addr) = match socket.recv_from(&mut buf) {
+            Ok((n, addr)) => {
+                metrics.received(n);
+                (n, addr)
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+            Err(e) => panic!("{e:?}"),
+        };
+        let Ok(message) = protocol::deserialize::<Serverbound>(&buf[0..n]) else {
+            println!("Dropping a malformed packet of {n} bytes from {addr:?}");
+            continue;
+        };
+        println!("{n} from {addr:?}");
+        last_seen.insert(addr, Instant::now());
+
+        last_seen.clone().iter().for_each(|(addr, seen)| {
+            if seen.elapsed() > Duration::from_secs(10) {
+                serverbound_tx
+                    .send((*addr, Serverbound::Disconnect))
+                    .unwrap();
+                last_seen.remove(addr);
+            }
+        });
+
+        to_receive.push_back((Instant::now(), (addr, message)));
+        while let Some((time, _)) = to_receive.get(0) {
+            if *time + FORCED_LATENCY < Instant::now() {
+                serverbound_tx
+                    .send(to_receive.pop_front().unwrap().1)
+                    .unwrap()
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+fn add_client(
+    clients: &mut HashMap<SocketAddr, Client>,
+    targets: &[CombatDefensive],
+    tx: &Sender<(SocketAddr, Clientbound)>,
+    metrics: &Metrics,
+    id: ClientId,
+    addr: SocketAddr,
+) -> Result<()> {
+    tx.send((addr, Clientbound::AuthSuccess(id)))?;
+    targets
+        .iter()
+        .enumerate()
+        .map(|(index, target)| {
+            tx.send((addr, Clientbound::SetHealth(TargetId(index), target.health)))?;
+            Ok(())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // the other clients are spawned for them once they are in range, by `update_interest`
+    if clients.insert(addr, Client::new(id)).is_none() {
+        metrics.connect();
+    }
+
+    Ok(())
+}
+
+/// Removes a client, despawning it for everyone who could see it
+fn remove_client(
+    clients: &mut HashMap<SocketAddr, Client>,
+    tx: &Sender<(SocketAddr, Clientbound)>,
+    metrics: &Metrics,
+    addr: SocketAddr,
+) -> Result<()> {
+    let Some(client) = clients.remove(&addr) else {
+        return Ok(());
+    };
+    clients
+        .iter()
+        .filter(|(_, other)| other.visible.borrow_mut().remove(&client.id))
+        .try_for_each(|(other_addr, _)| tx.send((*other_addr, Clientbound::Despawn(client.id))))?;
+    metrics.disconnect();
+    Ok(())
+}
+
+/// Learns any recipes the client has met the conditions for, letting them know about each one
+fn discover(
+    client: &Client,
+    recipes: &[Recipe],
+    tx: &Sender<(SocketAddr, Clientbound)>,
+    addr: SocketAddr,
+) -> Result<()> {
+    client
+        .proficiencies
+        .borrow_mut()
+        .discover(recipes)
+        .into_iter()
+        .try_for_each(|recipe| tx.send((addr, Clientbound::UnlockRecipe(recipe))))?;
+    Ok(())
+}
+
+/// Learns a recipe the client asked for, if they have met its unlock condition
+fn learn(
+    client: &Client,
+    content: &Content,
+    id: RecipeId,
+) -> Result<Vec<Clientbound>, FailureReason> {
+    let recipe = content.recipe(&id).ok_or(FailureReason::UnknownRecipe)?;
+    let mut proficiencies = client.proficiencies.borrow_mut();
+    if !proficiencies.met(recipe.unlock) {
+        return Err(FailureReason::UnlockNotMet);
+    }
+    // learning a recipe that is already known isn't a mistake, there is just nothing to tell them
+    Ok(proficiencies
+        .learn(&content.recipes, &id)
+        .then_some(Clientbound::UnlockRecipe(id))
+        .into_iter()
+        .collect())
+}
+
+/// Gathers from a node, returning the stacks that changed
+fn gather(
+    client: &Client,
+    content: &Content,
+    id: &NodeId,
+) -> Result<Vec<Clientbound>, FailureReason> {
+    let node = content.node(id).ok_or(FailureReason::UnknownNode)?;
+    let mut inventory = client.inventory.borrow_mut();
+    Ok(node
+        .loot
+        .pick()
+        .iter()
+        .map(|stack| {
+            inventory.add(*stack);
+            client.proficiencies.borrow_mut().gather(stack.item.kind);
+            Clientbound::SetStack(ItemStack {
+                item: stack.item,
+                quantity: inventory.get(stack.item).unwrap_or_default(),
+            })
+        })
+        .collect())
+}
+
+/// Crafts a recipe `count` times, rolling the rarity of each output separately. Returns the
+/// messages telling the client what changed.
+fn craft(
+    client: &Client,
+    content: &Content,
+    id: &RecipeId,
+    rarities: &[Rarity],
+    count: usize,
+    next_equipment: &mut u64,
+) -> Result<Vec<Clientbound>, FailureReason> {
+    let mut inventory = client.inventory.borrow_mut();
+    let mut equipment = client.equipment.borrow_mut();
+    let mut proficiencies = client.proficiencies.borrow_mut();
+    let recipe = content.recipe(id).ok_or(FailureReason::UnknownRecipe)?;
+    if !proficiencies.is_unlocked(id) {
+        return Err(FailureReason::RecipeLocked);
+    }
+    if !recipe.craftable(&inventory.items().collect::<Vec<_>>(), rarities, count) {
+        return Err(FailureReason::InsufficientItems);
+    }
+
+    // the stacks that changed, only sent once however many times they changed
+    let mut changed: Vec<Item> = Vec::new();
+    let mut messages = Vec::new();
+
+    recipe
+        .inputs
+        .iter()
+        .cloned()
+        .zip(rarities.iter().copied())
+        .for_each(|((kind, quantity), rarity)| {
+            let item = Item { kind, rarity };
+            inventory.remove(ItemStack {
+                item,
+                quantity: quantity * count,
+            });
+            changed.push(item);
+        });
+
+    let tags = recipe.output.tags();
+    for _ in 0..count {
+        let chances = recipe.rarity_chances(rarities, proficiencies.rank_up_for(&tags));
+        let ranked_up = proficiencies.gain(&tags, CRAFT_XP);
+        if !ranked_up.is_empty() {
+            println!("{:?} ranked up in {ranked_up:?}", client.id);
+        }
+        let rarity = *RARITIES
+            .into_iter()
+            .zip(chances)
+            .fold(LootTable::default(), |picker, (rarity, chance)| {
+                picker.add(chance, rarity)
+            })
+            .pick();
+
+        match recipe.output {
+            RecipeOutput::Item(kind) => {
+                let item = Item { kind, rarity };
+                inventory.add(ItemStack { item, quantity: 1 });
+                if !changed.contains(&item) {
+                    changed.push(item);
+                }
+            }
+            RecipeOutput::Equipment(kind) => {
+                let piece = Equipment {
+                    id: EquipmentId(*next_equipment),
+                    kind,
+                    rarity,
+                    durability: 10,
+                    passives: vec![Passive::Empty; rarity.index() + 1],
+                };
+                *next_equipment += 1;
+                equipment.0.push(piece.clone());
+                messages.push(Clientbound::AddEquipment(piece));
+            }
+        }
+    }
+
+    Ok(changed
+        .into_iter()
+        .map(|item| {
+            Clientbound::SetStack(ItemStack {
+                item,
+                quantity: inventory.get(item).unwrap_or_default(),
+            })
+        })
+        .chain(messages)
+        .chain(
+            tags.into_iter()
+                .map(|tag| Clientbound::SetRank(tag, proficiencies.rank(tag))),
+        )
+        .collect())
+}
+
+/// Puts the passive of a reagent into the first empty passive of a piece of equipment, using up
+/// the reagent
+fn refine(
+    client: &Client,
+    id: EquipmentId,
+    reagent: Item,
+) -> Result<Vec<Clientbound>, FailureReason> {
+    let quantity = client
+        .inventory
+        .borrow()
+        .get(reagent)
+        .filter(|quantity| *quantity > 0)
+        .ok_or(FailureReason::InsufficientItems)?;
+    let replacement = reagent.passive().ok_or(FailureReason::NotAReagent)?;
+
+    let mut equipment = client.equipment.borrow_mut();
+    let equipment = equipment
+        .0
+        .iter_mut()
+        .find(|equipment| equipment.id == id)
+        .ok_or(FailureReason::NoSuchEquipment)?;
+    let passive = equipment
+        .passives
+        .iter_mut()
+        .find(|passive| **passive == Passive::Empty)
+        .ok_or(FailureReason::NoEmptyPassive)?;
+    *passive = replacement;
+
+    let stack = ItemStack {
+        item: reagent,
+        quantity: quantity - 1,
+    };
+    client.inventory.borrow_mut().set(stack);
+    Ok(vec![
+        Clientbound::SetStack(stack),
+        Clientbound::SetPassives(id, equipment.passives.clone()),
+    ])
+}
+
+/// Puts a piece the client owns in a slot, or empties the slot
+fn equip(
+    client: &Client,
+    slot: Slot,
+    id: Option<EquipmentId>,
+) -> Result<Vec<Clientbound>, FailureReason> {
+    let mut equipped = client.equipped.borrow_mut();
+    let Some(id) = id else {
+        equipped.unequip(slot);
+        return Ok(Vec::new());
+    };
+    let equipment = client.equipment.borrow();
+    let piece = equipment
+        .0
+        .iter()
+        .find(|piece| piece.id == id)
+        .ok_or(FailureReason::NoSuchEquipment)?;
+    if !equipped.equip(slot, piece) {
+        return Err(FailureReason::WrongSlot);
+    }
+    Ok(Vec::new())
+}
+
+/// What a client's attack does and how far it reaches, from the server's copy of their level,
+/// equipment and skills
+fn damage(
+    client: &Client,
+    skills: &[SkillDamage],
+    attack: &AttackKind,
+) -> Result<(Damage, f32), FailureReason> {
+    match attack {
+        AttackKind::Basic => {
+            let equipped = client.equipped.borrow();
+            let equipment = client.equipment.borrow();
+            let mut stats = CombatOffensive::player(client.experience.get().level);
+            stats += &combat_bonus(equipped.worn(&equipment));
+            let passives =
+                PassiveStats::new(equipped.worn(&equipment).flat_map(|piece| &piece.passives));
+            Ok((Damage::Attack(passives.roll_crit(&stats)), ATTACK_RANGE))
+        }
+        AttackKind::Skill(name) => {
+            let skill = skills
+                .iter()
+                .find(|skill| skill.name == name)
+                .ok_or(FailureReason::UnknownSkill)?;
+            Ok((Damage::Flat(skill.damage), skill.reach))
+        }
+    }
+}
+
+/// Resolves a client's attack on a target, returning its health afterwards
+fn attack(
+    client: &Client,
+    targets: &mut [CombatDefensive],
+    skills: &[SkillDamage],
+    target: TargetId,
+    attack: &AttackKind,
+) -> Result<u32, FailureReason> {
+    let defensive = targets
+        .get_mut(target.0)
+        .ok_or(FailureReason::UnknownTarget)?;
+    if defensive.health == 0 {
+        return Err(FailureReason::TargetDead);
+    }
+    let (damage, reach) = damage(client, skills, attack)?;
+    // the client settles the targets onto the ground, so only the distance across it counts
+    let offset = client.position.get() - data::targets::POSITIONS[target.0];
+    if Vec3::new(offset.x, 0.0, offset.z).length() > reach + RANGE_LENIENCY {
+        return Err(FailureReason::OutOfRange);
+    }
+    Ok(damage.apply(defensive))
+}
+
+/// Gives a client the experience for killing something with `max_health`
+fn reward(client: &Client, max_health: u32) -> Clientbound {
+    let mut experience = client.experience.get();
+    if experience.kill(max_health) > 0 {
+        println!("{:?} levelled up to {}", client.id, experience.level);
+    }
+    client.experience.set(experience);
+    Clientbound::SetExperience(experience)
+}
+
+/// Sends the messages for an action, or why it couldn't be done
+fn respond(
+    tx: &Sender<(SocketAddr, Clientbound)>,
+    addr: SocketAddr,
+    result: Result<Vec<Clientbound>, FailureReason>,
+) -> Result<()> {
+    match result {
+        Ok(messages) => messages
+            .into_iter()
+            .try_for_each(|message| tx.send((addr, message)))?,
+        Err(reason) => tx.send((addr, Clientbound::ActionFailed { reason }))?,
+    }
+    Ok(())
+}
+
+/// Writes everything about a client that should outlive the server to the save directory
+fn save(client: &Client) -> Result<()> {
+    let state = serde_json::json!({
+        "experience": client.experience.get(),
+        "items": client.inventory.borrow().items().collect::<Vec<_>>(),
+        "equipment": client.equipment.borrow().0,
+        "proficiencies": *client.proficiencies.borrow(),
+    });
+    std::fs::create_dir_all(SAVE_DIR)?;
+    let path = format!("{SAVE_DIR}/{}.json", client.id.0);
+    std::fs::write(path, serde_json::to_vec_pretty(&state)?)?;
+    Ok(())
+}
+
+/// Tells every client the server is closing and saves them, carrying on with the rest if one of
+/// them can't be saved
+fn shutdown(
+    clients: &HashMap<SocketAddr, Client>,
+    tx: &Sender<(SocketAddr, Clientbound)>,
+    mut save: impl FnMut(&Client) -> Result<()>,
+) -> Result<()> {
+    clients.iter().try_for_each(|(addr, client)| {
+        if let Err(e) = save(client) {
+            println!("Failed to save {:?}: {e}", client.id);
+        }
+        tx.send((*addr, Clientbound::ServerShutdown))
+    })?;
+    Ok(())
+}
+
+/// How long each tick has to do its work, and how many times it has taken longer than that
+struct TickRate {
+    budget: Duration,
+    overruns: u64,
+}
+
+impl TickRate {
+    fn new(tps: f32) -> Self {
+        Self {
+            budget: Duration::from_secs_f32(1.0 / tps),
+            overruns: 0,
+        }
+    }
+
+    fn from_env() -> Self {
+        let tps = std::env::var(TPS_VAR)
+            .ok()
+            .and_then(|tps| tps.parse::<f32>().ok())
+            .filter(|tps| tps.is_finite() && *tps > 0.0)
+            .unwrap_or(TPS);
+        Self::new(tps)
+    }
+
+    /// How long to sleep after a tick that took `elapsed`, which is nothing if it went over
+    fn remaining(&mut self, tick: Tick, elapsed: Duration) -> Duration {
+        self.budget.checked_sub(elapsed).unwrap_or_else(|| {
+            self.overruns += 1;
+            println!(
+                "{tick:?} took {elapsed:?}, {:?} over budget ({} overruns so far)",
+                elapsed - self.budget,
+                self.overruns
+            );
+            Duration::ZERO
+        })
+    }
+}
+
+/// Sends the latest move of every client that moved this tick back to them, so they can check it
+/// against where they thought they were, and to the other clients if it is noticeable
+fn broadcast_moves(
+    clients: &HashMap<SocketAddr, Client>,
+    tx: &Sender<(SocketAddr, Clientbound)>,
+) -> Result<()> {
+    clients.iter().try_for_each(|(addr, client)| {
+        let (true, Some(sequence)) = (client.moved.take(), client.sequence.get()) else {
+            return Ok(());
+        };
+        let (position, rotation) = (client.position.get(), client.rotation.get());
+        let message = Clientbound::Move(client.id, position.into(), rotation, sequence);
+        let noticeable = client.noticeable();
+        if noticeable {
+            client.broadcast.set((position, rotation));
+        }
+        clients
+            .iter()
+            .filter(|(other_addr, other)| {
+                *other_addr == addr || noticeable && other.visible.borrow().contains(&client.id)
+            })
+            .try_for_each(|(other_addr, _)| tx.send((*other_addr, message.clone())))?;
+        Ok(())
+    })
+}
+
+/// Every other client in range of a client, which it is then known to be able to see
+fn snapshot(clients: &HashMap<SocketAddr, Client>, client: &Client, tick: Tick) -> Clientbound {
+    let entities = clients
+        .values()
+        .filter(|other| other.id != client.id)
+        .filter(|other| other.position.get().distance(client.position.get()) <= interest::RADIUS)
+        .map(|other| (other.id, other.position.get(), other.rotation.get()))
+        .collect::<Vec<_>>();
+    *client.visible.borrow_mut() = entities.iter().map(|(id, ..)| *id).collect();
+    Clientbound::Snapshot { tick, entities }
+}
+
+/// Spawns the clients that have come into range of each client, and despawns the ones that have
+/// left it
+fn update_interest(
+    clients: &HashMap<SocketAddr, Client>,
+    tx: &Sender<(SocketAddr, Clientbound)>,
+) -> Result<()> {
+    let grid = Grid::new(
+        clients
+            .values()
+            .map(|client| (client.position.get(), client)),
+    );
+    clients.iter().try_for_each(|(addr, client)| {
+        let nearby = grid
+            .nearby(client.position.get())
+            .filter(|other| other.id != client.id)
+            .collect::<Vec<_>>();
+        let mut visible = client.visible.borrow_mut();
+        nearby
+            .iter()
+            .filter(|other| !visible.contains(&other.id))
+            .try_for_each(|other| {
+                let spawn =
+                    Clientbound::Spawn(other.id, other.position.get(), other.rotation.get());
+                tx.send((*addr, spawn))
+            })?;
+        visible
+            .iter()
+            .filter(|id| !nearby.iter().any(|other| other.id == **id))
+            .try_for_each(|id| tx.send((*addr, Clientbound::Despawn(*id))))?;
+        *visible = nearby.iter().map(|other| other.id).collect();
+        Ok(())
+    })
+}
+
+/// Runs the server over a transport until `running` is cleared, then shuts it down
+pub fn run(
+    transport: impl ServerTransport,
+    content: Content,
+    running: Arc<AtomicBool>,
+) -> Result<()> {
+    let mut clients: HashMap<SocketAddr, Client> = HashMap::new();
+    let (serverbound_tx, serverbound_rx) = unbounded();
+    let (clientbound_tx, clientbound_rx) = unbounded();
+    let (flush_tx, flush_rx) = unbounded();
+    let metrics = Arc::new(Metrics::default());
+
+    let networking_metrics = metrics.clone();
+    let networking = std::thread::spawn(|| {
+        handle_networking(
+            transport,
+            clientbound_rx,
+            flush_rx,
+            serverbound_tx,
+            networking_metrics,
+        )
+    });
+
+    let mut next = 0;
+    let mut next_equipment = 0;
+    let mut tick = Tick(0);
+    let rx = serverbound_rx;
+    let tx = clientbound_tx;
+
+    let checksum = content.checksum();
+    let mut targets = data::targets::get();
+    let skills = data::skills::get();
+    let mut rate = TickRate::from_env();
+    println!("Ticking every {:?}", rate.budget);
+    let mut last_report = Instant::now();
+
+    while running.load(Ordering::Relaxed) {
+        let start = Instant::now();
+
+        while let Ok((addr, message)) = rx.try_recv() {
+            if let Serverbound::AuthRequest(theirs) = message {
+                if theirs != checksum {
+                    println!("{addr:?} has different content, turning them away");
+                    let reason = String::from("the client and server data files differ");
+                    tx.send((addr, Clientbound::AuthFailure(reason))).unwrap();
+                    continue;
+                }
+                let id = ClientId(next);
+                add_client(&mut clients, &targets, &tx, &metrics, id, addr).unwrap();
+                next += 1;
+            }
+
+            let Some(client) = clients.get(&addr) else {
+                continue;
+            };
+            if let Serverbound::AuthRequest(_) = message {
+                discover(client, &content.recipes, &tx, addr).unwrap();
+            }
+            match message {
+                Serverbound::Move(position, rotation, _, sequence) => {
+                    if !client.apply_move(position, rotation, sequence) {
+                        println!(
+                            "{:?} sent {sequence:?} out of order, dropping it",
+                            client.id
+                        );
+                    }
+                }
+                Serverbound::Gather(id) => {
+                    respond(&tx, addr, gather(client, &content, &id)).unwrap();
+                    discover(client, &content.recipes, &tx, addr).unwrap();
+                }
+                Serverbound::Craft(id, rarities, count) => {
+                    let result =
+                        craft(client, &content, &id, &rarities, count, &mut next_equipment);
+                    respond(&tx, addr, result).unwrap();
+                    discover(client, &content.recipes, &tx, addr).unwrap();
+                }
+                Serverbound::Refine(id, reagent) => {
+                    respond(&tx, addr, refine(client, id, reagent)).unwrap();
+                }
+                Serverbound::LearnRecipe(id) => {
+                    respond(&tx, addr, learn(client, &content, id)).unwrap();
+                }
+                Serverbound::Equip(slot, id) => {
+                    respond(&tx, addr, equip(client, slot, id)).unwrap();
+                }
+                Serverbound::Attack {
+                    target,
+                    attack: kind,
+                } => {
+                    let health = match attack(client, &mut targets, &skills, target, &kind) {
+                        Ok(health) => health,
+                        Err(reason) => {
+                            respond(&tx, addr, Err(reason)).unwrap();
+                            continue;
+                        }
+                    };
+                    println!(
+                        "{:?} attacked {target:?}, leaving it on {health} health",
+                        client.id
+                    );
+                    clients.keys().for_each(|other_addr| {
+                        tx.send((*other_addr, Clientbound::SetHealth(target, health)))
+                            .unwrap();
+                    });
+                    // only whoever landed the killing blow gets the experience
+                    if health == 0 {
+                        let reward = reward(client, targets[target.0].max_health);
+                        tx.send((addr, reward)).unwrap();
+                    }
+                }
+                Serverbound::RequestSnapshot => {
+                    tx.send((addr, snapshot(&clients, client, tick))).unwrap();
+                }
+                Serverbound::Disconnect => {
+                    remove_client(&mut clients, &tx, &metrics, addr).unwrap();
+                }
+
+                Serverbound::AuthRequest(_) => (),
+            }
+        }
+
+        broadcast_moves(&clients, &tx).unwrap();
+        update_interest(&clients, &tx).unwrap();
+        tick.0 += 1;
+        flush_tx.send(tick).unwrap();
+        metrics.tick(start.elapsed(), rate.budget);
+        if last_report.elapsed() >= REPORT_INTERVAL {
+            println!("{}", metrics.report(last_report.elapsed()));
+            last_report = Instant::now();
+        }
+        std::thread::sleep(rate.remaining(tick, start.elapsed()))
+    }
+
+    println!("Shutting down");
+    shutdown(&clients, &tx, save)?;
+    tick.0 += 1;
+    flush_tx.send(tick)?;
+    // hanging up lets the networking thread finish once the last messages are out
+    drop(flush_tx);
+    networking.join().unwrap();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nyx::{
+        equipment::EquipmentKind,
+        item::ItemKind,
+        transport::{self, ClientTransport, Loopback},
+    };
+
+    #[test]
+    pub fn test_attack() {
+        let client = Client::new(ClientId(0));
+        let skills = data::skills::get();
+        let mut targets = data::targets::get();
+        let target = TargetId(data::targets::DUMMY_1);
+        let fireball = AttackKind::Skill(String::from(data::skills::FIREBALL));
+
+        let mut attack = |kind: &AttackKind| attack(&client, &mut targets, &skills, target, kind);
+        assert_eq!(attack(&fireball), Ok(80));
+        assert_eq!(attack(&AttackKind::Basic), Ok(48));
+        let unknown = attack(&AttackKind::Skill(String::from("smite")));
+        assert_eq!(unknown, Err(FailureReason::UnknownSkill));
+        assert_eq!(attack(&AttackKind::Basic), Ok(16));
+        assert_eq!(attack(&AttackKind::Basic), Ok(0));
+        // dead targets can't be attacked again
+        assert_eq!(attack(&AttackKind::Basic), Err(FailureReason::TargetDead));
+
+        let missing = TargetId(targets.len());
+        let missing = super::attack(&client, &mut targets, &skills, missing, &fireball);
+        assert_eq!(missing, Err(FailureReason::UnknownTarget));
+    }
+
+    #[test]
+    pub fn test_attack_range() {
+        let client = Client::new(ClientId(0));
+        let skills = data::skills::get();
+        let mut targets = data::targets::get();
+        let target = TargetId(data::targets::DUMMY_2);
+        let fireball = AttackKind::Skill(String::from(data::skills::FIREBALL));
+
+        client.position.set(Vec3::new(6.0, 0.0, 0.0));
+        let basic = attack(&client, &mut targets, &skills, target, &AttackKind::Basic);
+        assert_eq!(basic, Err(FailureReason::OutOfRange));
+        // skills reach further than a basic attack
+        let fireball = attack(&client, &mut targets, &skills, target, &fireball);
+        assert_eq!(fireball, Ok(180));
+        // only how far apart they are across the ground counts
+        client.position.set(Vec3::new(-5.0, 20.0, 0.0));
+        let basic = attack(&client, &mut targets, &skills, target, &AttackKind::Basic);
+        assert_eq!(basic, Ok(148));
+    }
+
+    #[test]
+    pub fn test_kill_experience() {
+        let client = Client::new(ClientId(0));
+        let targets = data::targets::get();
+        reward(&client, targets[data::targets::DUMMY_1].max_health);
+        assert_eq!(client.experience.get().level, 1);
+        let Clientbound::SetExperience(experience) =
+            reward(&client, targets[data::targets::DUMMY_2].max_health)
+        else {
+            panic!("a kill should send the new experience");
+        };
+        assert_eq!(experience, client.experience.get());
+        assert_eq!(experience.level, 2);
+
+        // the level the server worked out decides the damage, not what the client says
+        let mut targets = data::targets::get();
+        let target = TargetId(data::targets::DUMMY_1);
+        let health = attack(&client, &mut targets, &[], target, &AttackKind::Basic);
+        assert_eq!(health, Ok(100 - CombatOffensive::player(2).total_damage()));
+    }
+
+    #[test]
+    pub fn test_equipped_damage() {
+        let client = Client::new(ClientId(0));
+        let sword = Equipment {
+            id: EquipmentId(0),
+            kind: EquipmentKind::CopperSword,
+            rarity: Rarity::Common,
+            durability: 10,
+            passives: Vec::new(),
+        };
+        client.equipment.borrow_mut().0.push(sword);
+
+        let wrong = equip(&client, Slot::Head, Some(EquipmentId(0)));
+        assert_eq!(wrong.unwrap_err(), FailureReason::WrongSlot);
+        let missing = equip(&client, Slot::Weapon, Some(EquipmentId(1)));
+        assert_eq!(missing.unwrap_err(), FailureReason::NoSuchEquipment);
+        assert!(equip(&client, Slot::Weapon, Some(EquipmentId(0))).is_ok());
+
+        let mut targets = data::targets::get();
+        let target = TargetId(data::targets::DUMMY_1);
+        // the sword's damage is added to the player's own
+        let health = attack(&client, &mut targets, &[], target, &AttackKind::Basic);
+        assert_eq!(health, Ok(58));
+
+        assert!(equip(&client, Slot::Weapon, None).is_ok());
+        let health = attack(&client, &mut targets, &[], target, &AttackKind::Basic);
+        assert_eq!(health, Ok(26));
+    }
+
+    const ORE: Item = Item {
+        kind: ItemKind::CopperOre,
+        rarity: Rarity::Common,
+    };
+
+    fn content() -> Content {
+        Content::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../data")).unwrap()
+    }
+
+    /// A client that has gathered `quantity` copper ore, and learned what that unlocks
+    fn miner(recipes: &[Recipe], quantity: usize) -> Client {
+        let client = Client::new(ClientId(0));
+        client.inventory.borrow_mut().add(ItemStack {
+            item: ORE,
+            quantity,
+        });
+        client
+            .proficiencies
+            .borrow_mut()
+            .gather(ItemKind::CopperOre);
+        client.proficiencies.borrow_mut().discover(recipes);
+        client
+    }
+
+    fn ingots(client: &Client) -> usize {
+        client
+            .inventory
+            .borrow()
+            .items()
+            .filter(|stack| stack.item.kind == ItemKind::CopperIngot)
+            .map(|stack| stack.quantity)
+            .sum()
+    }
+
+    #[test]
+    pub fn test_craft_batch() {
+        let content = content();
+        let client = miner(&content.recipes, 11);
+        let ingot = RecipeId(String::from("copper_ingot"));
+        let mut next_equipment = 0;
+
+        // 6 ingots need 12 ore
+        let result = craft(
+            &client,
+            &content,
+            &ingot,
+            &[Rarity::Common],
+            6,
+            &mut next_equipment,
+        );
+        assert_eq!(result.unwrap_err(), FailureReason::InsufficientItems);
+        assert_eq!(client.inventory.borrow().get(ORE), Some(11));
+
+        let messages = craft(
+            &client,
+            &content,
+            &ingot,
+            &[Rarity::Common],
+            5,
+            &mut next_equipment,
+        )
+        .unwrap();
+        assert_eq!(client.inventory.borrow().get(ORE).unwrap_or_default(), 1);
+        assert_eq!(ingots(&client), 5);
+        // the client is sent the ranks it gained rather than working them out itself
+        let proficiencies = client.proficiencies.borrow();
+        let ranks = messages
+            .iter()
+            .filter_map(|message| match message {
+                Clientbound::SetRank(tag, rank) => Some((*tag, *rank)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert!(!ranks.is_empty());
+        assert!(ranks
+            .iter()
+            .all(|(tag, rank)| proficiencies.rank(*tag) == *rank && rank.level + rank.xp > 0));
+    }
+
+    #[test]
+    pub fn test_craft_reordered() {
+        // the server's recipes are in a different order to the client's
+        let mut content = content();
+        content.recipes.reverse();
+        let client = miner(&content.recipes, 2);
+
+        craft(
+            &client,
+            &content,
+            &RecipeId(String::from("copper_ingot")),
+            &[Rarity::Common],
+            1,
+            &mut 0,
+        )
+        .unwrap();
+        assert_eq!(ingots(&client), 1);
+        assert!(client.equipment.borrow().0.is_empty());
+    }
+
+    #[test]
+    pub fn test_rejections() {
+        let content = content();
+        let client = miner(&content.recipes, 2);
+        let sword = RecipeId(String::from("copper_sword"));
+        let missing = RecipeId(String::from("tin_ingot"));
+
+        let craft = |id: &RecipeId| craft(&client, &content, id, &[Rarity::Common], 1, &mut 0);
+        assert_eq!(craft(&missing).unwrap_err(), FailureReason::UnknownRecipe);
+        assert_eq!(craft(&sword).unwrap_err(), FailureReason::RecipeLocked);
+        let learnt = learn(&client, &content, sword);
+        assert_eq!(learnt.unwrap_err(), FailureReason::UnlockNotMet);
+
+        let refined = refine(&client, EquipmentId(0), ORE);
+        assert_eq!(refined.unwrap_err(), FailureReason::NotAReagent);
+        let gathered = gather(&client, &content, &NodeId(String::from("tin_ore")));
+        assert_eq!(gathered.unwrap_err(), FailureReason::UnknownNode);
+    }
+
+    #[test]
+    pub fn test_move_threshold() {
+        let mover: SocketAddr = "127.0.0.1:1".parse().unwrap();
+        let watcher: SocketAddr = "127.0.0.1:2".parse().unwrap();
+        let clients = HashMap::from([
+            (mover, Client::new(ClientId(0))),
+            (watcher, Client::new(ClientId(1))),
+        ]);
+        let (tx, rx) = unbounded();
+        update_interest(&clients, &tx).unwrap();
+        rx.try_iter().for_each(drop);
+        let step = |position: Vec3, sequence: u64| {
+            let client = &clients[&mover];
+            assert!(client.apply_move(position, Quat::IDENTITY, Sequence(sequence)));
+            broadcast_moves(&clients, &tx).unwrap();
+            rx.try_iter().map(|(addr, _)| addr).collect::<Vec<_>>()
+        };
+
+        // jitter is only echoed back to the client that moved
+        assert_eq!(step(Vec3::splat(0.01), 0), vec![mover]);
+        // but it adds up until the others are told
+        let mut told = step(Vec3::splat(0.1), 1);
+        told.sort();
+        assert_eq!(told, vec![mover, watcher]);
+        // and nothing is sent for clients that didn't move
+        broadcast_moves(&clients, &tx).unwrap();
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    pub fn test_interest() {
+        let [a, b, far]: [SocketAddr; 3] =
+            ["127.0.0.1:1", "127.0.0.1:2", "127.0.0.1:3"].map(|addr| addr.parse().unwrap());
+        let clients = HashMap::from([
+            (a, Client::new(ClientId(0))),
+            (b, Client::new(ClientId(1))),
+            (far, Client::new(ClientId(2))),
+        ]);
+        clients[&b].position.set(Vec3::new(1.0, 0.0, 0.0));
+        clients[&far]
+            .position
+            .set(Vec3::new(interest::RADIUS * 10.0, 0.0, 0.0));
+        let (tx, rx) = unbounded();
+
+        update_interest(&clients, &tx).unwrap();
+        let mut spawned = rx.try_iter().map(|(addr, _)| addr).collect::<Vec<_>>();
+        spawned.sort();
+        assert_eq!(spawned, vec![a, b]);
+
+        clients[&a].apply_move(Vec3::new(2.0, 0.0, 0.0), Quat::IDENTITY, Sequence(0));
+        broadcast_moves(&clients, &tx).unwrap();
+        assert!(rx.try_iter().all(|(addr, _)| addr != far));
+
+        // walking over to the distant client swaps who they can see
+        clients[&a]
+            .position
+            .set(Vec3::new(interest::RADIUS * 10.0, 0.0, 1.0));
+        update_interest(&clients, &tx).unwrap();
+        let messages = rx.try_iter().collect::<Vec<_>>();
+        assert_eq!(messages.len(), 4);
+        assert!(messages.iter().any(|message| matches!(
+            message,
+            (addr, Clientbound::Despawn(ClientId(1))) if *addr == a
+        )));
+        assert!(messages.iter().any(|message| matches!(
+            message,
+            (addr, Clientbound::Spawn(ClientId(0), ..)) if *addr == far
+        )));
+    }
+
+    #[test]
+    pub fn test_snapshot() {
+        let [a, b, far]: [SocketAddr; 3] =
+            ["127.0.0.1:1", "127.0.0.1:2", "127.0.0.1:3"].map(|addr| addr.parse().unwrap());
+        let clients = HashMap::from([
+            (a, Client::new(ClientId(0))),
+            (b, Client::new(ClientId(1))),
+            (far, Client::new(ClientId(2))),
+        ]);
+        clients[&b].position.set(Vec3::ONE);
+        clients[&far]
+            .position
+            .set(Vec3::new(interest::RADIUS * 10.0, 0.0, 0.0));
+        // a client that thinks it can see someone it can't
+        clients[&a].visible.borrow_mut().insert(ClientId(2));
+
+        let Clientbound::Snapshot { tick, entities } = snapshot(&clients, &clients[&a], Tick(3))
+        else {
+            panic!("expected a snapshot");
+        };
+        assert_eq!(tick, Tick(3));
+        assert_eq!(entities, vec![(ClientId(1), Vec3::ONE, Quat::IDENTITY)]);
+        assert_eq!(*clients[&a].visible.borrow(), HashSet::from([ClientId(1)]));
+    }
+
+    #[test]
+    pub fn test_out_of_order_move() {
+        let client = Client::new(ClientId(0));
+        assert!(client.apply_move(Vec3::X, Quat::IDENTITY, Sequence(2)));
+        // an older move arriving late doesn't undo the newer one
+        assert!(!client.apply_move(Vec3::Y, Quat::IDENTITY, Sequence(1)));
+        assert!(!client.apply_move(Vec3::Y, Quat::IDENTITY, Sequence(2)));
+        assert_eq!(client.position.get(), Vec3::X);
+        assert!(client.apply_move(Vec3::Z, Quat::IDENTITY, Sequence(3)));
+        assert_eq!(client.position.get(), Vec3::Z);
+    }
+
+    #[test]
+    pub fn test_tick_overrun() {
+        let mut rate = TickRate {
+            budget: Duration::from_millis(50),
+            overruns: 0,
+        };
+        let remaining = rate.remaining(Tick(0), Duration::from_millis(20));
+        assert_eq!(remaining, Duration::from_millis(30));
+        assert_eq!(rate.overruns, 0);
+        // going over budget doesn't underflow, it just doesn't sleep
+        let remaining = rate.remaining(Tick(1), Duration::from_millis(80));
+        assert_eq!(remaining, Duration::ZERO);
+        assert_eq!(rate.overruns, 1);
+    }
+
+    #[test]
+    pub fn test_connection_metrics() {
+        let [a, b]: [SocketAddr; 2] =
+            ["127.0.0.1:1", "127.0.0.1:2"].map(|addr| addr.parse().unwrap());
+        let mut clients = HashMap::new();
+        let metrics = Metrics::default();
+        let (tx, _rx) = unbounded();
+
+        add_client(&mut clients, &[], &tx, &metrics, ClientId(0), a).unwrap();
+        add_client(&mut clients, &[], &tx, &metrics, ClientId(1), b).unwrap();
+        // authenticating again doesn't count as another client
+        add_client(&mut clients, &[], &tx, &metrics, ClientId(2), b).unwrap();
+        assert_eq!(metrics.connected(), 2);
+
+        remove_client(&mut clients, &tx, &metrics, a).unwrap();
+        // timing out after already disconnecting doesn't count twice
+        remove_client(&mut clients, &tx, &metrics, a).unwrap();
+        assert_eq!(metrics.connected(), 1);
+        assert_eq!(metrics.report(REPORT_INTERVAL).connected, 1);
+    }
+
+    #[test]
+    pub fn test_shutdown() {
+        let [a, b]: [SocketAddr; 2] =
+            ["127.0.0.1:1", "127.0.0.1:2"].map(|addr| addr.parse().unwrap());
+        let clients = HashMap::from([(a, Client::new(ClientId(0))), (b, Client::new(ClientId(1)))]);
+        let (tx, rx) = unbounded();
+
+        let mut saved = Vec::new();
+        shutdown(&clients, &tx, |client| {
+            saved.push(client.id);
+            // one failing doesn't stop the others being saved
+            anyhow::ensure!(client.id != ClientId(0), "disk full");
+            Ok(())
+        })
+        .unwrap();
+        saved.sort_by_key(|id| id.0);
+        assert_eq!(saved, vec![ClientId(0), ClientId(1)]);
+        let notified = rx
+            .try_iter()
+            .filter(|(_, message)| matches!(message, Clientbound::ServerShutdown))
+            .count();
+        assert_eq!(notified, 2);
+    }
+
+    /// A client talking to a server running on another thread, keeping track of what it has
+    struct LoopbackClient {
+        transport: Loopback,
+        stacks: HashMap<Item, usize>,
+        equipment: Vec<Equipment>,
+    }
+
+    impl LoopbackClient {
+        fn send(&self, message: Serverbound) {
+            ClientTransport::send(&self.transport, &protocol::serialize(&message).unwrap())
+                .unwrap();
+        }
+
+        /// Applies everything the server sends until `done` is happy with it
+        fn wait_until(&mut self, done: impl Fn(&Self) -> bool) {
+            let start = Instant::now();
+            let mut buffer = [0; MAX_PACKET_SIZE];
+            while !done(self) {
+                assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+                let Ok(n) = ClientTransport::recv(&self.transport, &mut buffer) else {
+                    std::thread::sleep(Duration::from_millis(1));
+                    continue;
+                };
+                let bundle = protocol::deserialize::<ClientboundBundle>(&buffer[..n]).unwrap();
+                bundle
+                    .messages
+                    .into_iter()
+                    .for_each(|message| match message {
+                        Clientbound::SetStack(stack) => {
+                            self.stacks.insert(stack.item, stack.quantity);
+                        }
+                        Clientbound::AddEquipment(piece) => self.equipment.push(piece),
+                        Clientbound::SetPassives(id, passives) => {
+                            let piece = self.equipment.iter_mut().find(|piece| piece.id == id);
+                            piece.unwrap().passives = passives;
+                        }
+                        Clientbound::ActionFailed { reason } => panic!("{reason}"),
+                        _ => (),
+                    });
+            }
+        }
+
+        fn count(&self, kind: ItemKind) -> usize {
+            self.stacks
+                .iter()
+                .filter(|(item, _)| item.kind == kind)
+                .map(|(_, quantity)| quantity)
+                .sum()
+        }
+
+        /// The rarity there is the most of an item in
+        fn most(&self, kind: ItemKind) -> (Item, usize) {
+            self.stacks
+                .iter()
+                .filter(|(item, _)| item.kind == kind)
+                .max_by_key(|(_, quantity)| **quantity)
+                .map(|(item, quantity)| (*item, *quantity))
+                .unwrap()
+        }
+    }
+
+    #[test]
+    pub fn test_loopback_gather_craft_refine() {
+        let content = Content::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../data")).unwrap();
+        let checksum = content.checksum();
+        let (server, transport) = transport::loopback();
+        // left running, there is nothing to save once the test is over
+        std::thread::spawn(move || run(server, content, Arc::new(AtomicBool::new(true))));
+        let mut client = LoopbackClient {
+            transport,
+            stacks: HashMap::new(),
+            equipment: Vec::new(),
+        };
+
+        client.send(Serverbound::AuthRequest(checksum));
+        let node = NodeId(String::from("copper_ore"));
+        (0..20).for_each(|_| client.send(Serverbound::Gather(node.clone())));
+        client.wait_until(|client| client.count(ItemKind::CopperOre) == 40);
+
+        // enough to rank up smelting twice, unlocking the sword and the reagent
+        let ingot = RecipeId(String::from("copper_ingot"));
+        client.send(Serverbound::Craft(ingot, vec![Rarity::Common], 20));
+        client.wait_until(|client| client.count(ItemKind::CopperIngot) == 20);
+
+        let (ingots, _) = client.most(ItemKind::CopperIngot);
+        let sword = RecipeId(String::from("copper_sword"));
+        client.send(Serverbound::Craft(sword, vec![ingots.rarity], 1));
+        client.wait_until(|client| client.equipment.len() == 1);
+
+        let (ingots, _) = client.most(ItemKind::CopperIngot);
+        let reagent = RecipeId(String::from("fire_damage_reagent"));
+        client.send(Serverbound::Craft(reagent, vec![ingots.rarity], 1));
+        client.wait_until(|client| client.count(ItemKind::FireDamageReagent) == 1);
+
+        let (reagent, _) = client.most(ItemKind::FireDamageReagent);
+        client.send(Serverbound::Refine(client.equipment[0].id, reagent));
+        client.wait_until(|client| client.equipment[0].passives[0] != Passive::Empty);
+        assert_eq!(client.equipment[0].passives[0], reagent.passive().unwrap());
+        assert_eq!(client.count(ItemKind::FireDamageReagent), 0);
+    }
+}
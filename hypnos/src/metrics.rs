@@ -0,0 +1,132 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Counters describing how the server is doing, shared between the simulation and networking
+/// threads. Everything but the client counts is reset each time a report is taken.
+#[derive(Default)]
+pub struct Metrics {
+    connects: AtomicU64,
+    disconnects: AtomicU64,
+    packets_in: AtomicU64,
+    packets_out: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    ticks: AtomicU64,
+    // in microseconds
+    tick_time: AtomicU64,
+    max_tick_time: AtomicU64,
+    overruns: AtomicU64,
+}
+
+impl Metrics {
+    pub fn connect(&self) {
+        self.connects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn disconnect(&self) {
+        self.disconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connected(&self) -> u64 {
+        self.connects.load(Ordering::Relaxed) - self.disconnects.load(Ordering::Relaxed)
+    }
+
+    pub fn received(&self, bytes: usize) {
+        self.packets_in.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn sent(&self, bytes: usize) {
+        self.packets_out.fetch_add(1, Ordering::Relaxed);
+        self.bytes_out.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Records how long a tick took to do its work, and whether that was more than it had
+    pub fn tick(&self, elapsed: Duration, budget: Duration) {
+        let micros = elapsed.as_micros() as u64;
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_time.fetch_add(micros, Ordering::Relaxed);
+        self.max_tick_time.fetch_max(micros, Ordering::Relaxed);
+        if elapsed > budget {
+            self.overruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Summarises the last `interval`, starting the counts again from zero
+    pub fn report(&self, interval: Duration) -> Report {
+        let take = |counter: &AtomicU64| counter.swap(0, Ordering::Relaxed);
+        let ticks = take(&self.ticks);
+        Report {
+            interval,
+            connected: self.connected(),
+            packets_in: take(&self.packets_in),
+            packets_out: take(&self.packets_out),
+            bytes_in: take(&self.bytes_in),
+            bytes_out: take(&self.bytes_out),
+            ticks,
+            average_tick: Duration::from_micros(take(&self.tick_time) / ticks.max(1)),
+            max_tick: Duration::from_micros(take(&self.max_tick_time)),
+            overruns: take(&self.overruns),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Report {
+    pub interval: Duration,
+    pub connected: u64,
+    pub packets_in: u64,
+    pub packets_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub ticks: u64,
+    pub average_tick: Duration,
+    pub max_tick: Duration,
+    pub overruns: u64,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let per_second = |count: u64| count as f32 / self.interval.as_secs_f32();
+        write!(
+            f,
+            "clients={} in={:.1}pkt/s,{:.0}B/s out={:.1}pkt/s,{:.0}B/s ticks={} avg_tick={:?} max_tick={:?} overruns={}",
+            self.connected,
+            per_second(self.packets_in),
+            per_second(self.bytes_in),
+            per_second(self.packets_out),
+            per_second(self.bytes_out),
+            self.ticks,
+            self.average_tick,
+            self.max_tick,
+            self.overruns
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_report() {
+        let metrics = Metrics::default();
+        metrics.received(100);
+        metrics.received(50);
+        let budget = Duration::from_millis(50);
+        metrics.tick(Duration::from_millis(10), budget);
+        metrics.tick(Duration::from_millis(70), budget);
+
+        let report = metrics.report(Duration::from_secs(1));
+        assert_eq!(report.packets_in, 2);
+        assert_eq!(report.bytes_in, 150);
+        assert_eq!(report.average_tick, Duration::from_millis(40));
+        assert_eq!(report.max_tick, Duration::from_millis(70));
+        assert_eq!(report.overruns, 1);
+        // the next report only covers what happened since
+        assert_eq!(metrics.report(Duration::from_secs(1)).packets_in, 0);
+    }
+}
@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+
+// the true damage a player does at level 1, before their equipment
+const PLAYER_DAMAGE: u32 = 32;
+// how much each of a player's damage stats increases by per level
+pub const DAMAGE_PER_LEVEL: u32 = 2;
+// how far away a basic attack can hit
+pub const ATTACK_RANGE: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttackType {
+    pub damage: u32,
+    pub penetration: u32,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CombatOffensive {
+    pub fire: AttackType,
+    pub earth: AttackType,
+    pub lightning: AttackType,
+    pub air: AttackType,
+    pub nature: AttackType,
+
+    pub true_damage: u32,
+}
+
+impl std::ops::AddAssign for AttackType {
+    fn add_assign(&mut self, other: Self) {
+        self.damage += other.damage;
+        self.penetration += other.penetration;
+    }
+}
+
+impl std::ops::SubAssign for AttackType {
+    fn sub_assign(&mut self, other: Self) {
+        self.damage = self.damage.saturating_sub(other.damage);
+        self.penetration = self.penetration.saturating_sub(other.penetration);
+    }
+}
+
+impl std::ops::AddAssign<&CombatOffensive> for CombatOffensive {
+    fn add_assign(&mut self, other: &CombatOffensive) {
+        self.fire += other.fire;
+        self.earth += other.earth;
+        self.lightning += other.lightning;
+        self.air += other.air;
+        self.nature += other.nature;
+        self.true_damage += other.true_damage;
+    }
+}
+
+impl std::ops::SubAssign<&CombatOffensive> for CombatOffensive {
+    fn sub_assign(&mut self, other: &CombatOffensive) {
+        self.fire -= other.fire;
+        self.earth -= other.earth;
+        self.lightning -= other.lightning;
+        self.air -= other.air;
+        self.nature -= other.nature;
+        self.true_damage = self.true_damage.saturating_sub(other.true_damage);
+    }
+}
+
+impl CombatOffensive {
+    /// The stats of a player at a level, before their equipment
+    pub fn player(level: u32) -> Self {
+        let mut stats = Self {
+            true_damage: PLAYER_DAMAGE,
+            ..Default::default()
+        };
+        (1..level).for_each(|_| stats.level_up());
+        stats
+    }
+
+    /// Increases every damage stat by [`DAMAGE_PER_LEVEL`]
+    pub fn level_up(&mut self) {
+        [
+            &mut self.fire,
+            &mut self.earth,
+            &mut self.lightning,
+            &mut self.air,
+            &mut self.nature,
+        ]
+        .into_iter()
+        .for_each(|attack| attack.damage += DAMAGE_PER_LEVEL);
+        self.true_damage += DAMAGE_PER_LEVEL;
+    }
+
+    /// The damage of every type added together, before any resistances
+    pub fn total_damage(&self) -> u32 {
+        [self.fire, self.earth, self.lightning, self.air, self.nature]
+            .into_iter()
+            .fold(self.true_damage, |total, attack| {
+                total.saturating_add(attack.damage)
+            })
+    }
+}
+
+impl std::fmt::Display for CombatOffensive {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "CombatOffensive: \nfire: {:?}\nearth: {:?}\nlightning: {:?}\nair: {:?}\nnature: {:?}\ntrue_damage: {:?}",
+            self.fire, self.earth, self.lightning, self.air, self.nature, self.true_damage
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CombatDefensive {
+    pub health: u32,
+    pub max_health: u32,
+    pub fire_resistance: i32,
+    pub earth_resistance: i32,
+    pub lightning_resistance: i32,
+    pub air_resistance: i32,
+    pub nature_resistance: i32,
+}
+
+impl std::fmt::Display for CombatDefensive {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "CombatDefensive: \nhealth: {}\nfire_resistance: {}\nearth_resistance: {}\nlightning_resistance: {}\nair_resistance: {}\nnature_resistance: {}\n",
+            self.health, self.fire_resistance, self.earth_resistance, self.lightning_resistance, self.air_resistance, self.nature_resistance
+        )
+    }
+}
+
+impl CombatDefensive {
+    /// Takes damage that isn't split into damage types (skill effects), returning the new health.
+    /// Like an attack, health won't go below 0.
+    pub fn receive_damage(&mut self, damage: u32) -> u32 {
+        self.health = self.health.saturating_sub(damage);
+        self.health
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct AttackOutcome {
+    pub fire_damage: u32,
+    pub earth_damage: u32,
+    pub lightning_damage: u32,
+    pub air_damage: u32,
+    pub nature_damage: u32,
+
+    pub true_damage: u32,
+
+    pub post_attack_health: u32,
+}
+
+impl AttackOutcome {
+    pub fn sum_damage(&self) -> u32 {
+        self.fire_damage
+            + self.earth_damage
+            + self.lightning_damage
+            + self.air_damage
+            + self.nature_damage
+            + self.true_damage
+    }
+}
+
+pub trait Attackable {
+    /// returns the new health of the attacked entity along with how much damge was done.
+    /// If the damage would put the player bellow 0, the health will be set to 0.
+    fn receive_attack(&mut self, damage_source: &CombatOffensive) -> AttackOutcome;
+}
+
+impl AttackType {
+    /// The fraction of damage that gets through a resistance, after penetration. Positive
+    /// resistances reduce damage (100 resistance halves it), negative resistances increase it
+    /// (-100 resistance gives 1.5x damage). Penetration only removes resistance down to 0, so it
+    /// never turns a resistance into a weakness.
+    pub fn multiplier(&self, resistance: i32) -> f32 {
+        let resistance = if resistance > 0 {
+            resistance.saturating_sub_unsigned(self.penetration).max(0)
+        } else {
+            resistance
+        };
+
+        if resistance >= 0 {
+            100.0 / (100.0 + resistance as f32)
+        } else {
+            2.0 - 100.0 / (100.0 - resistance as f32)
+        }
+    }
+
+    /// How much damage this does against a resistance, rounded to the nearest whole number
+    pub fn against(&self, resistance: i32) -> u32 {
+        (self.damage as f32 * self.multiplier(resistance)).round() as u32
+    }
+}
+
+impl Attackable for CombatDefensive {
+    fn receive_attack(&mut self, source: &CombatOffensive) -> AttackOutcome {
+        let fire_damage = source.fire.against(self.fire_resistance);
+        let earth_damage = source.earth.against(self.earth_resistance);
+        let lightning_damage = source.lightning.against(self.lightning_resistance);
+        let air_damage = source.air.against(self.air_resistance);
+        let nature_damage = source.nature.against(self.nature_resistance);
+
+        let total_damage = fire_damage
+            .saturating_add(earth_damage)
+            .saturating_add(lightning_damage)
+            .saturating_add(air_damage)
+            .saturating_add(nature_damage)
+            .saturating_add(source.true_damage);
+
+        // saturate to prevent underflows when the damage is greater than the remaining health
+        self.health = self.health.saturating_sub(total_damage);
+
+        AttackOutcome {
+            fire_damage,
+            earth_damage,
+            lightning_damage,
+            air_damage,
+            nature_damage,
+
+            true_damage: source.true_damage,
+            post_attack_health: self.health,
+        }
+    }
+}
+
+/// Identifies an attackable entity the server knows about, so every client agrees on which
+/// entity is being attacked
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetId(pub usize);
+
+/// Which attack a client is making. The server works out the damage from its own copy of the
+/// attacker's stats, so clients can't choose how much damage they do.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttackKind {
+    /// A regular attack with the attacker's stats and equipment
+    Basic,
+    /// A skill hitting the target, by the skill's name
+    Skill(String),
+}
+
+/// What an attack on a target does
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Damage {
+    /// A regular attack using the attacker's stats, reduced by the target's resistances
+    Attack(CombatOffensive),
+    /// Damage that isn't split into damage types, such as from a skill
+    Flat(u32),
+}
+
+impl Damage {
+    /// Applies the damage to a target, returning its health afterwards
+    pub fn apply(&self, defensive: &mut CombatDefensive) -> u32 {
+        match self {
+            Damage::Attack(source) => defensive.receive_attack(source).post_attack_health,
+            Damage::Flat(damage) => defensive.receive_damage(*damage),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONE: AttackType = AttackType {
+        damage: 0,
+        penetration: 0,
+    };
+
+    fn fire(damage: u32, penetration: u32) -> CombatOffensive {
+        CombatOffensive {
+            fire: AttackType {
+                damage,
+                penetration,
+            },
+            earth: NONE,
+            lightning: NONE,
+            air: NONE,
+            nature: NONE,
+            true_damage: 0,
+        }
+    }
+
+    fn target(health: u32, fire_resistance: i32) -> CombatDefensive {
+        CombatDefensive {
+            health,
+            max_health: health,
+            fire_resistance,
+            earth_resistance: 0,
+            lightning_resistance: 0,
+            air_resistance: 0,
+            nature_resistance: 0,
+        }
+    }
+
+    #[test]
+    pub fn test_resistance() {
+        // 100 resistance halves the damage
+        let outcome = target(100, 100).receive_attack(&fire(50, 0));
+        assert_eq!(outcome.fire_damage, 25);
+        assert_eq!(outcome.post_attack_health, 75);
+
+        // penetration reduces the effective resistance
+        let outcome = target(100, 100).receive_attack(&fire(50, 50));
+        assert_eq!(outcome.fire_damage, 33);
+    }
+
+    #[test]
+    pub fn test_penetration_exceeding_resistance() {
+        let outcome = target(100, 20).receive_attack(&fire(50, 80));
+        assert_eq!(outcome.fire_damage, 50);
+        assert_eq!(outcome.post_attack_health, 50);
+
+        // exactly matching the resistance ignores it as well
+        let outcome = target(100, 20).receive_attack(&fire(50, 20));
+        assert_eq!(outcome.fire_damage, 50);
+    }
+
+    #[test]
+    pub fn test_zero_damage() {
+        let mut defensive = target(100, 50);
+        let outcome = defensive.receive_attack(&fire(0, 0));
+        assert_eq!(outcome.sum_damage(), 0);
+        assert_eq!(outcome.post_attack_health, 100);
+        assert_eq!(defensive.receive_damage(0), 100);
+    }
+
+    #[test]
+    pub fn test_health_clamped() {
+        let mut defensive = target(10, 0);
+        let mut source = fire(50, 0);
+        source.true_damage = 5;
+        let outcome = defensive.receive_attack(&source);
+        assert_eq!(outcome.sum_damage(), 55);
+        assert_eq!(outcome.post_attack_health, 0);
+        assert_eq!(defensive.health, 0);
+
+        // already dead targets stay at 0
+        assert_eq!(defensive.receive_damage(u32::MAX), 0);
+    }
+
+    #[test]
+    pub fn test_exact_damage() {
+        let cases = [
+            // (damage, penetration, resistance, expected)
+            (100, 0, 0, 100),
+            (100, 0, 100, 50),
+            (100, 0, 300, 25),
+            (100, 50, 150, 50),
+            (100, 0, -100, 150),
+            (100, 0, -300, 175),
+            // penetration doesn't affect resistances that are already negative
+            (100, 50, -100, 150),
+            // 66.67 rounds up, where integer division used to truncate to 66
+            (100, 0, 50, 67),
+            // 2.5 is the rounding boundary
+            (5, 0, 100, 3),
+            (1, 0, 200, 0),
+        ];
+
+        for (damage, penetration, resistance, expected) in cases {
+            let attack = AttackType {
+                damage,
+                penetration,
+            };
+            assert_eq!(
+                attack.against(resistance),
+                expected,
+                "{damage} damage with {penetration} penetration against {resistance} resistance"
+            );
+        }
+    }
+
+    #[test]
+    pub fn test_player_levels() {
+        let stats = CombatOffensive::player(3);
+        assert_eq!(stats.true_damage, PLAYER_DAMAGE + 2 * DAMAGE_PER_LEVEL);
+        assert_eq!(stats.fire.damage, 2 * DAMAGE_PER_LEVEL);
+        assert_eq!(CombatOffensive::player(1).total_damage(), PLAYER_DAMAGE);
+    }
+
+    #[test]
+    pub fn test_penetration_only_removes_exceeded_resistance() {
+        let attack = AttackType {
+            damage: 100,
+            penetration: 30,
+        };
+        // 30 of the 130 resistance is ignored
+        assert_eq!(attack.against(130), 50);
+        // penetrating past 0 doesn't add any damage
+        assert_eq!(attack.against(10), 100);
+        assert_eq!(attack.multiplier(10), 1.0);
+    }
+}
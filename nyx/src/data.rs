@@ -1,41 +1,191 @@
-use crate::{
-    equipment::EquipmentKind,
-    item::{ItemKind, Recipe, RecipeOutput},
-};
-
-pub fn recipes() -> Vec<Recipe> {
-    vec![
-        Recipe {
-            inputs: vec![(ItemKind::CopperOre, 2)],
-            output: RecipeOutput::Item(ItemKind::CopperIngot),
-        },
-        Recipe {
-            inputs: vec![(ItemKind::CopperIngot, 3)],
-            output: RecipeOutput::Equipment(EquipmentKind::CopperSword),
-        },
-        Recipe {
-            inputs: vec![(ItemKind::CopperIngot, 2)],
-            output: RecipeOutput::Item(ItemKind::FireDamageReagent),
-        },
-    ]
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::item::{ItemStack, LootTable, Recipe, RecipeId, Unlock};
+
+/// Where the data files are loaded from, relative to where the client and server are run
+pub const CONTENT_DIR: &str = "data";
+
+/// Names a gathering node
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeId(pub String);
+
+/// Something in the world that can be gathered from, dropping items from its loot table
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: NodeId,
+    pub loot: LootTable<Vec<ItemStack>>,
+}
+
+/// The recipes and gathering nodes loaded from the data files, which the client and server have
+/// to agree on
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Content {
+    pub recipes: Vec<Recipe>,
+    pub nodes: Vec<Node>,
+}
+
+impl Content {
+    pub fn load() -> Result<Self> {
+        Self::load_from(CONTENT_DIR)
+    }
+
+    pub fn load_from<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let read = |name: &str| {
+            let path = dir.as_ref().join(name);
+            std::fs::read_to_string(&path).with_context(|| format!("failed to read {path:?}"))
+        };
+        Self::parse(&read("recipes.json")?, &read("nodes.json")?)
+    }
+
+    /// Parses and validates the contents of the data files. Unknown item and equipment kinds are
+    /// rejected while parsing.
+    pub fn parse(recipes: &str, nodes: &str) -> Result<Self> {
+        let content = Self {
+            recipes: serde_json::from_str(recipes).context("invalid recipes")?,
+            nodes: serde_json::from_str(nodes).context("invalid nodes")?,
+        };
+        content.validate()?;
+        Ok(content)
+    }
+
+    pub fn recipe(&self, id: &RecipeId) -> Option<&Recipe> {
+        self.recipes.iter().find(|recipe| recipe.id == *id)
+    }
+
+    pub fn node(&self, id: &NodeId) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == *id)
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (i, node) in self.nodes.iter().enumerate() {
+            let NodeId(id) = &node.id;
+            if self.nodes[..i].iter().any(|other| other.id == node.id) {
+                bail!("there is more than one node called {id}");
+            }
+            if node
+                .loot
+                .entries()
+                .any(|(probability, _)| *probability <= 0.0)
+            {
+                bail!("node {id} has an entry that can never drop");
+            }
+            // picking from the table relies on the probabilities adding up to 1
+            let total: f32 = node
+                .loot
+                .entries()
+                .map(|(probability, _)| probability)
+                .sum();
+            if (total - 1.0).abs() > 1e-3 {
+                bail!("the probabilities of node {id} add up to {total}, not 1");
+            }
+            if node
+                .loot
+                .entries()
+                .flat_map(|(_, stacks)| stacks)
+                .any(|stack| stack.quantity == 0)
+            {
+                bail!("node {id} drops an empty stack");
+            }
+        }
+
+        for (i, recipe) in self.recipes.iter().enumerate() {
+            let RecipeId(id) = &recipe.id;
+            if self.recipes[..i].iter().any(|other| other.id == recipe.id) {
+                bail!("there is more than one recipe called {id}");
+            }
+            if recipe.inputs.is_empty() || recipe.inputs.iter().any(|(_, count)| *count == 0) {
+                bail!("recipe {id} needs at least one of every input");
+            }
+            if let Unlock::Gathered(kind) = recipe.unlock {
+                let gatherable = self
+                    .nodes
+                    .iter()
+                    .flat_map(|node| node.loot.entries())
+                    .flat_map(|(_, stacks)| stacks)
+                    .any(|stack| stack.item.kind == kind);
+                if !gatherable {
+                    bail!("recipe {id} is unlocked by gathering {kind}, which no node drops");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A hash of the content that the client sends when connecting, so that the server can turn it
+    /// away if their data files differ
+    pub fn checksum(&self) -> u64 {
+        // FNV-1a, as the standard library's hasher isn't guaranteed to be the same between builds
+        serde_json::to_vec(self)
+            .unwrap()
+            .into_iter()
+            .fold(0xcbf29ce484222325, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
 }
 
 pub mod nodes {
-    use crate::item::{Item, ItemKind, ItemStack, LootTable, Rarity};
+    pub const COPPER_ORE: &str = "copper_ore";
+}
 
-    pub const COPPER_ORE: usize = 0;
+pub mod targets {
+    use glam::Vec3;
 
-    pub fn get() -> Vec<LootTable<Vec<ItemStack>>> {
-        vec![LootTable::default().add(
-            1.0,
-            vec![ItemStack {
-                item: Item {
-                    kind: ItemKind::CopperOre,
-                    rarity: Rarity::Common,
-                },
-                quantity: 2,
-            }],
-        )]
+    use crate::combat::CombatDefensive;
+
+    pub const DUMMY_1: usize = 0;
+    pub const DUMMY_2: usize = 1;
+
+    // where each target stands, before being settled onto the ground
+    pub const POSITIONS: [Vec3; 2] = [Vec3::new(5.0, 0.0, 0.0), Vec3::new(-5.0, 0.0, 0.0)];
+
+    fn dummy(health: u32) -> CombatDefensive {
+        CombatDefensive {
+            health,
+            max_health: health,
+            fire_resistance: 0,
+            earth_resistance: 0,
+            lightning_resistance: 0,
+            air_resistance: 0,
+            nature_resistance: 0,
+        }
+    }
+
+    pub fn get() -> Vec<CombatDefensive> {
+        vec![dummy(100), dummy(200)]
+    }
+}
+
+pub mod skills {
+    pub const FIREBALL: &str = "fireball";
+    pub const ICE_DART: &str = "IceDart";
+
+    /// What the server needs to know to resolve a hit from a skill
+    #[derive(Clone, Copy, Debug)]
+    pub struct SkillDamage {
+        pub name: &'static str,
+        // done to every target the skill hits
+        pub damage: u32,
+        // how far from the caster a target can be hit, including the skill's area of effect
+        pub reach: f32,
+    }
+
+    pub fn get() -> Vec<SkillDamage> {
+        vec![
+            SkillDamage {
+                name: FIREBALL,
+                damage: 20,
+                reach: 12.0,
+            },
+            SkillDamage {
+                name: ICE_DART,
+                damage: 20,
+                reach: 12.0,
+            },
+        ]
     }
 }
 
@@ -93,3 +243,54 @@ pub mod tasks {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::{ItemKind, RecipeOutput};
+
+    fn content() -> Content {
+        Content::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../", "data")).unwrap()
+    }
+
+    #[test]
+    pub fn test_client_server_agree() {
+        // the client and server each load the data files for themselves
+        let client = content();
+        let server = content();
+        assert_eq!(client, server);
+        assert_eq!(client.checksum(), server.checksum());
+        let ingot = client
+            .recipe(&RecipeId(String::from("copper_ingot")))
+            .unwrap();
+        assert_eq!(ingot.output, RecipeOutput::Item(ItemKind::CopperIngot));
+
+        let mut changed = server.clone();
+        changed.recipes.swap(0, 1);
+        assert_ne!(client.checksum(), changed.checksum());
+    }
+
+    #[test]
+    pub fn test_invalid_content() {
+        let nodes = r#"[{ "id": "ore", "loot": { "entries": [[1.0, [{ "item": { "kind": "CopperOre", "rarity": "Common" }, "quantity": 2 }]]] } }]"#;
+        let recipe = |input: &str, unlock: &str| {
+            format!(
+                r#"{{ "id": "ingot", "inputs": [["{input}", 2]], "output": {{ "Item": "CopperIngot" }}, "unlock": {unlock} }}"#
+            )
+        };
+        let known = recipe("CopperOre", r#""Known""#);
+
+        assert!(Content::parse(&format!("[{known}]"), nodes).is_ok());
+        // there is no such item
+        let unknown = recipe("TinOre", r#""Known""#);
+        assert!(Content::parse(&format!("[{unknown}]"), nodes).is_err());
+        // no node drops ingots, so the recipe could never be learnt
+        let unlearnable = recipe("CopperOre", r#"{ "Gathered": "CopperIngot" }"#);
+        assert!(Content::parse(&format!("[{unlearnable}]"), nodes).is_err());
+        // recipes are looked up by id, so they can't share one
+        assert!(Content::parse(&format!("[{known}, {known}]"), nodes).is_err());
+        // the drop chances have to add up to 1
+        let nodes = nodes.replace("1.0", "0.5");
+        assert!(Content::parse(&format!("[{known}]"), &nodes).is_err());
+    }
+}
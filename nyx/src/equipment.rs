@@ -1,16 +1,32 @@
 use std::fmt::Display;
 
-use crate::item::{Rarity, Tag};
+use rand::Rng;
+
+use crate::{
+    combat::{CombatDefensive, CombatOffensive},
+    item::{tag_list, Rarity, Tag},
+};
+
+// how much more damage each rarity above common does
+const DAMAGE_PER_RARITY: f32 = 0.25;
+// how many times the damage a critical hit does
+const CRIT_MULTIPLIER: u32 = 2;
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub enum EquipmentKind {
     CopperSword,
+    CopperHelmet,
+    CopperChestplate,
+    CopperRing,
 }
 
 impl Display for EquipmentKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
-            Self::CopperSword => "Copper Sword"
+            Self::CopperSword => "Copper Sword",
+            Self::CopperHelmet => "Copper Helmet",
+            Self::CopperChestplate => "Copper Chestplate",
+            Self::CopperRing => "Copper Ring",
         })
     }
 }
@@ -18,24 +34,166 @@ impl Display for EquipmentKind {
 impl EquipmentKind {
     pub fn tags(&self) -> Vec<Tag> {
         match self {
-            Self::CopperSword => vec![Tag::Weaponsmithing, Tag::Copper]
+            Self::CopperSword => vec![Tag::Weaponsmithing, Tag::Copper],
+            Self::CopperHelmet | Self::CopperChestplate | Self::CopperRing => vec![Tag::Copper],
+        }
+    }
+
+    /// The damage a common piece of this kind does
+    pub fn base_damage(&self) -> u32 {
+        match self {
+            Self::CopperSword => 10,
+            Self::CopperHelmet | Self::CopperChestplate | Self::CopperRing => 0,
         }
     }
+
+    /// The slot this kind of equipment is worn in
+    pub fn slot(&self) -> Slot {
+        match self {
+            Self::CopperSword => Slot::Weapon,
+            Self::CopperHelmet => Slot::Head,
+            Self::CopperChestplate => Slot::Chest,
+            Self::CopperRing => Slot::Trinket,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Slot {
+    Weapon,
+    Head,
+    Chest,
+    Trinket,
+}
+
+impl Slot {
+    pub const ALL: [Slot; 4] = [Slot::Weapon, Slot::Head, Slot::Chest, Slot::Trinket];
+}
+
+impl Display for Slot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::Weapon => "Weapon",
+            Self::Head => "Head",
+            Self::Chest => "Chest",
+            Self::Trinket => "Trinket",
+        })
+    }
 }
 
 #[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
 pub enum Passive {
     Empty,
+    // fire damage as a fraction of the equipment's damage
     FireDamage(f32),
+    // the chance for an attack to do double damage
+    CritChance(f32),
+    // the fraction of damage dealt that is healed
+    Lifesteal(f32),
+    // added to every resistance
+    Resistance(f32),
+    // the fraction taken off skill cooldowns
+    Haste(f32),
 }
 
 impl Display for Passive {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Empty => write!(f, "Empty"),
-            Self::FireDamage(bonus) => write!(f, "+{}% Fire Damage", (bonus * 100.0) as u32) 
+            Self::FireDamage(bonus) => write!(f, "+{}% Fire Damage", (bonus * 100.0) as u32),
+            Self::CritChance(chance) => write!(f, "+{}% Crit Chance", (chance * 100.0) as u32),
+            Self::Lifesteal(fraction) => write!(f, "+{}% Lifesteal", (fraction * 100.0) as u32),
+            Self::Resistance(resistance) => write!(f, "+{} Resistance", *resistance as i32),
+            Self::Haste(fraction) => write!(f, "+{}% Haste", (fraction * 100.0) as u32),
+        }
+    }
+}
+
+/// The combined effect of a set of passives. Damage and resistance bonuses add together, while
+/// chances and fractions combine with diminishing returns so they never reach 100%.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PassiveStats {
+    pub fire_damage: f32,
+    pub crit_chance: f32,
+    pub lifesteal: f32,
+    pub resistance: f32,
+    pub haste: f32,
+}
+
+impl PassiveStats {
+    pub fn new<'a>(passives: impl IntoIterator<Item = &'a Passive>) -> Self {
+        passives
+            .into_iter()
+            .fold(Self::default(), |mut stats, passive| {
+                stats.add(*passive);
+                stats
+            })
+    }
+
+    pub fn add(&mut self, passive: Passive) {
+        // two 50% chances combine to 75%, not 100%
+        let diminish =
+            |total: &mut f32, amount: f32| *total = 1.0 - (1.0 - *total) * (1.0 - amount);
+        match passive {
+            Passive::Empty => (),
+            Passive::FireDamage(bonus) => self.fire_damage += bonus,
+            Passive::Resistance(resistance) => self.resistance += resistance,
+            Passive::CritChance(chance) => diminish(&mut self.crit_chance, chance),
+            Passive::Lifesteal(fraction) => diminish(&mut self.lifesteal, fraction),
+            Passive::Haste(fraction) => diminish(&mut self.haste, fraction),
         }
     }
+
+    /// The stats with a critical hit applied if `roll`, between 0 and 1, is within the crit chance
+    pub fn crit(&self, stats: &CombatOffensive, roll: f32) -> CombatOffensive {
+        let mut stats = stats.clone();
+        if roll >= self.crit_chance {
+            return stats;
+        }
+
+        [
+            &mut stats.fire,
+            &mut stats.earth,
+            &mut stats.lightning,
+            &mut stats.air,
+            &mut stats.nature,
+        ]
+        .into_iter()
+        .for_each(|attack| attack.damage = attack.damage.saturating_mul(CRIT_MULTIPLIER));
+        stats.true_damage = stats.true_damage.saturating_mul(CRIT_MULTIPLIER);
+        stats
+    }
+
+    pub fn roll_crit(&self, stats: &CombatOffensive) -> CombatOffensive {
+        self.crit(stats, rand::thread_rng().gen())
+    }
+
+    /// The health healed by dealing `damage`
+    pub fn lifesteal(&self, damage: u32) -> u32 {
+        (damage as f32 * self.lifesteal).round() as u32
+    }
+
+    /// The defensive stats with the resistance added to every resistance
+    pub fn defensive(&self, base: &CombatDefensive) -> CombatDefensive {
+        let resistance = self.resistance.round() as i32;
+        let mut stats = *base;
+        [
+            &mut stats.fire_resistance,
+            &mut stats.earth_resistance,
+            &mut stats.lightning_resistance,
+            &mut stats.air_resistance,
+            &mut stats.nature_resistance,
+        ]
+        .into_iter()
+        .for_each(|stat| *stat = stat.saturating_add(resistance));
+        stats
+    }
+
+    /// A cooldown, in seconds, shortened by the haste. However much haste there is, it is never
+    /// shortened below a tenth, so it can't go to zero or below.
+    pub fn cooldown(&self, cooldown: f32) -> f32 {
+        cooldown * (1.0 - self.haste).max(0.1)
+    }
 }
 
 #[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
@@ -50,15 +208,230 @@ pub struct Equipment {
     pub passives: Vec<Passive>,
 }
 
+impl Equipment {
+    /// The damage the piece does, which rarity increases
+    fn damage(&self) -> f32 {
+        self.kind.base_damage() as f32 * (1.0 + self.rarity.index() as f32 * DAMAGE_PER_RARITY)
+    }
+
+    /// The stats the equipment gives when it is equipped on its own
+    pub fn combat_bonus(&self) -> CombatOffensive {
+        combat_bonus([self])
+    }
+
+    /// The lines of detail shown about the piece, below its name
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("{} {}", self.rarity, self.kind.slot()),
+            format!("Durability: {}", self.durability),
+            format!("Tags: {}", tag_list(&self.kind.tags())),
+        ];
+        lines.extend(self.passives.iter().map(Passive::to_string));
+
+        let stats = self.combat_bonus();
+        [
+            ("Damage", stats.true_damage),
+            ("Fire", stats.fire.damage),
+            ("Earth", stats.earth.damage),
+            ("Lightning", stats.lightning.damage),
+            ("Air", stats.air.damage),
+            ("Nature", stats.nature.damage),
+        ]
+        .into_iter()
+        .filter(|(_, damage)| *damage > 0)
+        .for_each(|(name, damage)| lines.push(format!("{name}: {damage}")));
+        lines
+    }
+}
+
+/// The stats a set of equipped pieces give together. Their damage adds up, and the passives of
+/// every piece are combined before adding extra damage types as a fraction of it.
+pub fn combat_bonus<'a>(pieces: impl IntoIterator<Item = &'a Equipment>) -> CombatOffensive {
+    let pieces = pieces.into_iter().collect::<Vec<_>>();
+    let damage: f32 = pieces.iter().map(|piece| piece.damage()).sum();
+    let passives = PassiveStats::new(pieces.iter().flat_map(|piece| piece.passives.iter()));
+
+    let mut stats = CombatOffensive {
+        true_damage: damage.round() as u32,
+        ..Default::default()
+    };
+    stats.fire.damage = (damage * passives.fire_damage).round() as u32;
+    stats
+}
+
 pub struct EquipmentInventory(pub Vec<Equipment>);
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct Equipped {
     pub weapon: Option<EquipmentId>,
+    pub head: Option<EquipmentId>,
+    pub chest: Option<EquipmentId>,
+    pub trinket: Option<EquipmentId>,
 }
 
 impl Equipped {
     pub fn equipment(&self) -> impl Iterator<Item = EquipmentId> + '_ {
-        [self.weapon.as_ref()].into_iter().filter_map(|x| x).copied()
+        [self.weapon, self.head, self.chest, self.trinket].into_iter().flatten()
+    }
+
+    /// The equipped pieces, looked up in the inventory they are in
+    pub fn worn<'a>(
+        &'a self,
+        equipment: &'a EquipmentInventory,
+    ) -> impl Iterator<Item = &'a Equipment> + 'a {
+        self.equipment()
+            .filter_map(|id| equipment.0.iter().find(|piece| piece.id == id))
+    }
+
+    pub fn get(&self, slot: Slot) -> Option<EquipmentId> {
+        match slot {
+            Slot::Weapon => self.weapon,
+            Slot::Head => self.head,
+            Slot::Chest => self.chest,
+            Slot::Trinket => self.trinket,
+        }
+    }
+
+    fn slot_mut(&mut self, slot: Slot) -> &mut Option<EquipmentId> {
+        match slot {
+            Slot::Weapon => &mut self.weapon,
+            Slot::Head => &mut self.head,
+            Slot::Chest => &mut self.chest,
+            Slot::Trinket => &mut self.trinket,
+        }
+    }
+
+    /// Puts the piece in the slot, replacing whatever was there. Returns false without equipping
+    /// it if the piece can't be worn in that slot.
+    pub fn equip(&mut self, slot: Slot, piece: &Equipment) -> bool {
+        if piece.kind.slot() != slot {
+            return false;
+        }
+        *self.slot_mut(slot) = Some(piece.id);
+        true
+    }
+
+    pub fn unequip(&mut self, slot: Slot) -> Option<EquipmentId> {
+        self.slot_mut(slot).take()
+    }
+
+    /// Equips the piece in its own slot, or takes it off if it is already equipped
+    pub fn toggle(&mut self, piece: &Equipment) {
+        let slot = piece.kind.slot();
+        if self.get(slot) == Some(piece.id) {
+            self.unequip(slot);
+        } else {
+            self.equip(slot, piece);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_stats() {
+        let mut sword = Equipment {
+            id: EquipmentId(0),
+            kind: EquipmentKind::CopperSword,
+            rarity: Rarity::Common,
+            durability: 10,
+            passives: vec![Passive::Empty],
+        };
+        assert_eq!(sword.combat_bonus().true_damage, 10);
+        assert_eq!(sword.combat_bonus().fire.damage, 0);
+
+        sword.rarity = Rarity::Rare;
+        sword.passives = vec![Passive::FireDamage(0.5), Passive::Empty, Passive::Empty];
+        assert_eq!(sword.combat_bonus().true_damage, 15);
+        assert_eq!(sword.combat_bonus().fire.damage, 8);
+    }
+
+    #[test]
+    pub fn test_passive_aggregation() {
+        let piece = |kind, passives| Equipment {
+            id: EquipmentId(0),
+            kind,
+            rarity: Rarity::Common,
+            durability: 10,
+            passives,
+        };
+        let sword = piece(EquipmentKind::CopperSword, vec![Passive::FireDamage(0.2)]);
+        let ring = piece(
+            EquipmentKind::CopperRing,
+            vec![Passive::FireDamage(0.3), Passive::CritChance(0.5)],
+        );
+
+        // fire damage on the ring still scales off the sword's damage
+        let stats = combat_bonus([&sword, &ring]);
+        assert_eq!(stats.true_damage, 10);
+        assert_eq!(stats.fire.damage, 5);
+
+        let passives = PassiveStats::new(&[Passive::FireDamage(0.2), Passive::FireDamage(0.3)]);
+        assert!((passives.fire_damage - 0.5).abs() < 1e-6);
+
+        let passives = PassiveStats::new(&[Passive::CritChance(0.5), Passive::CritChance(0.5)]);
+        assert!((passives.crit_chance - 0.75).abs() < 1e-6);
+        assert_eq!(passives.crit(&stats, 0.7).true_damage, 20);
+        assert_eq!(passives.crit(&stats, 0.8).true_damage, 10);
+    }
+
+    #[test]
+    pub fn test_cooldown() {
+        let passives = PassiveStats::new(&[Passive::Haste(0.5)]);
+        assert!((passives.cooldown(2.0) - 1.0).abs() < 1e-6);
+
+        // an item with more than all the haste still leaves some of the cooldown
+        let passives = PassiveStats::new(&[Passive::Haste(1.5)]);
+        assert!((passives.cooldown(2.0) - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    pub fn test_describe() {
+        let sword = Equipment {
+            id: EquipmentId(0),
+            kind: EquipmentKind::CopperSword,
+            rarity: Rarity::Rare,
+            durability: 10,
+            passives: vec![Passive::FireDamage(0.5), Passive::Empty],
+        };
+        assert_eq!(
+            sword.describe(),
+            vec![
+                "Rare Weapon",
+                "Durability: 10",
+                "Tags: Weaponsmithing, Copper",
+                "+50% Fire Damage",
+                "Empty",
+                "Damage: 15",
+                "Fire: 8",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn test_equip_slots() {
+        let piece = |id, kind| Equipment {
+            id: EquipmentId(id),
+            kind,
+            rarity: Rarity::Common,
+            durability: 10,
+            passives: Vec::new(),
+        };
+        let sword = piece(0, EquipmentKind::CopperSword);
+        let helmet = piece(1, EquipmentKind::CopperHelmet);
+
+        let mut equipped = Equipped::default();
+        assert!(!equipped.equip(Slot::Weapon, &helmet));
+        assert_eq!(equipped.weapon, None);
+
+        assert!(equipped.equip(Slot::Weapon, &sword));
+        assert!(equipped.equip(Slot::Head, &helmet));
+        assert_eq!(equipped.equipment().collect::<Vec<_>>(), vec![sword.id, helmet.id]);
+
+        equipped.toggle(&helmet);
+        assert_eq!(equipped.head, None);
+        assert_eq!(equipped.weapon, Some(sword.id));
     }
 }
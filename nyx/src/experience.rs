@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+// how much experience a kill is worth per point of the victim's max health
+const XP_PER_HEALTH: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Experience {
+    pub level: u32,
+    pub xp: u32,
+}
+
+impl Default for Experience {
+    fn default() -> Self {
+        Self { level: 1, xp: 0 }
+    }
+}
+
+impl Experience {
+    /// The experience needed to reach the next level
+    pub fn required(&self) -> u32 {
+        self.level * 100
+    }
+
+    /// Adds experience, returning how many levels were gained
+    pub fn gain(&mut self, xp: u32) -> u32 {
+        self.xp += xp;
+        let mut levels = 0;
+        while self.xp >= self.required() {
+            self.xp -= self.required();
+            self.level += 1;
+            levels += 1;
+        }
+        levels
+    }
+
+    /// Adds the experience for killing something with `max_health`, returning how many levels
+    /// were gained
+    pub fn kill(&mut self, max_health: u32) -> u32 {
+        self.gain((max_health as f32 * XP_PER_HEALTH) as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_level_up() {
+        let mut experience = Experience::default();
+        assert_eq!(experience.gain(50), 0);
+        assert_eq!(experience.gain(60), 1);
+        assert_eq!(experience.level, 2);
+        assert_eq!(experience.xp, 10);
+    }
+
+    #[test]
+    pub fn test_kill() {
+        let mut experience = Experience::default();
+        assert_eq!(experience.kill(100), 0);
+        assert_eq!(experience.xp, 50);
+        assert_eq!(experience.kill(200), 1);
+        assert_eq!(experience.level, 2);
+        assert_eq!(experience.xp, 50);
+    }
+}
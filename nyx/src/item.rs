@@ -42,6 +42,15 @@ impl ItemKind {
             Self::FireDamageReagent => vec![Tag::Alchemy],
         }
     }
+
+    /// A line of flavour text shown when looking at the item
+    pub fn description(&self) -> &'static str {
+        match self {
+            Self::CopperOre => "Soft, reddish ore dug from the ground. Smelts into ingots.",
+            Self::CopperIngot => "A bar of refined copper, ready to be worked.",
+            Self::FireDamageReagent => "Warm to the touch. Refines fire into equipment.",
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
@@ -83,6 +92,30 @@ impl Rarity {
     }
 }
 
+impl Display for Rarity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Self::Common => "Common",
+                Self::Uncommon => "Uncommon",
+                Self::Rare => "Rare",
+                Self::Epic => "Epic",
+                Self::Legendary => "Legendary",
+            }
+        )
+    }
+}
+
+/// Lists tags as a comma separated line
+pub fn tag_list(tags: &[Tag]) -> String {
+    tags.iter()
+        .map(|tag| format!("{tag:?}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub struct Item {
     pub kind: ItemKind,
@@ -98,6 +131,19 @@ impl Item {
             _ => None,
         }
     }
+
+    /// The lines of detail shown about the item, below its name
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            self.rarity.to_string(),
+            format!("Tags: {}", tag_list(&self.kind.tags())),
+        ];
+        if let Some(passive) = self.passive() {
+            lines.push(format!("Refines: {passive}"));
+        }
+        lines.push(self.kind.description().to_string());
+        lines
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
@@ -119,37 +165,85 @@ impl RecipeOutput {
             RecipeOutput::Equipment(equipment) => equipment.tags(),
         }
     }
+
+    /// Whether more than one can be crafted at once. Each piece of equipment is unique so it is
+    /// crafted one at a time.
+    pub fn batchable(&self) -> bool {
+        matches!(self, RecipeOutput::Item(_))
+    }
 }
 
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+/// What the player needs to have done before they learn a recipe
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Unlock {
+    // known from the start
+    Known,
+    // learned the first time the item is gathered
+    Gathered(ItemKind),
+    // learned on reaching a rank in a tag
+    Rank(Tag, u32),
+}
+
+/// Names a recipe. Recipes are referred to by id rather than where they are in the data files,
+/// so reordering them doesn't change what the client crafts.
+#[derive(Clone, Debug, Hash, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct RecipeId(pub String);
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct Recipe {
+    pub id: RecipeId,
     pub inputs: Vec<(ItemKind, usize)>,
     pub output: RecipeOutput,
+    pub unlock: Unlock,
 }
 
 impl Recipe {
-    pub fn craftable(&self, inventory: &[ItemStack], rarities: &[Rarity]) -> bool {
+    /// Whether there are enough of the chosen inputs to craft the recipe `count` times
+    pub fn craftable(&self, inventory: &[ItemStack], rarities: &[Rarity], count: usize) -> bool {
+        count > 0 && count <= self.max_craftable(inventory, rarities)
+    }
+
+    /// How many times the recipe can be crafted with the chosen inputs, which is at most once
+    /// for outputs that can't be batched
+    pub fn max_craftable(&self, inventory: &[ItemStack], rarities: &[Rarity]) -> usize {
+        // the same item can be used for more than one input, so add them up first
+        let mut required: Vec<ItemStack> = Vec::new();
         self.inputs
             .iter()
             .zip(rarities)
-            .all(|((kind, quantity), rarity)| {
-                *quantity
-                    <= inventory
-                        .iter()
-                        .find_map(|s| {
-                            if s.item
-                                == (Item {
-                                    kind: *kind,
-                                    rarity: *rarity,
-                                })
-                            {
-                                Some(s.quantity)
-                            } else {
-                                None
-                            }
-                        })
-                        .unwrap_or_default()
+            .for_each(|((kind, quantity), rarity)| {
+                let item = Item {
+                    kind: *kind,
+                    rarity: *rarity,
+                };
+                match required.iter_mut().find(|stack| stack.item == item) {
+                    Some(stack) => stack.quantity += quantity,
+                    None => required.push(ItemStack {
+                        item,
+                        quantity: *quantity,
+                    }),
+                }
+            });
+
+        let max = required
+            .iter()
+            .filter(|stack| stack.quantity > 0)
+            .map(|stack| {
+                let available = inventory
+                    .iter()
+                    .filter(|s| s.item == stack.item)
+                    .map(|s| s.quantity)
+                    .sum::<usize>();
+                available / stack.quantity
             })
+            .min()
+            .unwrap_or(usize::MAX);
+
+        if self.output.batchable() {
+            max
+        } else {
+            max.min(1)
+        }
     }
 
     pub fn rarity_chances(&self, rarities: &[Rarity], rank_up: f32) -> Vec<f32> {
@@ -238,7 +332,7 @@ impl Inventory {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct LootTable<T> {
     entries: Vec<(f32, T)>,
 }
@@ -257,6 +351,10 @@ impl<T> LootTable<T> {
         self
     }
 
+    pub fn entries(&self) -> impl Iterator<Item = &(f32, T)> {
+        self.entries.iter()
+    }
+
     pub fn pick(&self) -> &T {
         let mut rng = rand::thread_rng();
         let mut p: f32 = rng.gen();
@@ -273,3 +371,71 @@ impl<T> LootTable<T> {
             .unwrap()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(kind: ItemKind, quantity: usize) -> ItemStack {
+        ItemStack {
+            item: Item {
+                kind,
+                rarity: Rarity::Common,
+            },
+            quantity,
+        }
+    }
+
+    fn recipe(inputs: Vec<(ItemKind, usize)>) -> Recipe {
+        Recipe {
+            id: RecipeId(String::from("reagent")),
+            inputs,
+            output: RecipeOutput::Item(ItemKind::FireDamageReagent),
+            unlock: Unlock::Known,
+        }
+    }
+
+    #[test]
+    pub fn test_max_craftable_limiting_ingredient() {
+        let recipe = recipe(vec![(ItemKind::CopperOre, 2), (ItemKind::CopperIngot, 3)]);
+        let rarities = [Rarity::Common, Rarity::Common];
+
+        let inventory = [
+            stack(ItemKind::CopperOre, 20),
+            stack(ItemKind::CopperIngot, 7),
+        ];
+        assert_eq!(recipe.max_craftable(&inventory, &rarities), 2);
+        assert!(recipe.craftable(&inventory, &rarities, 2));
+        assert!(!recipe.craftable(&inventory, &rarities, 3));
+
+        let inventory = [stack(ItemKind::CopperOre, 20)];
+        assert_eq!(recipe.max_craftable(&inventory, &rarities), 0);
+        assert!(!recipe.craftable(&inventory, &rarities, 1));
+        assert!(!recipe.craftable(&inventory, &rarities, 0));
+    }
+
+    #[test]
+    pub fn test_max_craftable_repeated_inputs() {
+        // both inputs come out of the same stack, so each craft needs 5 ore
+        let recipe = recipe(vec![(ItemKind::CopperOre, 2), (ItemKind::CopperOre, 3)]);
+        let rarities = [Rarity::Common, Rarity::Common];
+        let inventory = [stack(ItemKind::CopperOre, 14)];
+        assert_eq!(recipe.max_craftable(&inventory, &rarities), 2);
+
+        // different rarities are different items
+        let rarities = [Rarity::Common, Rarity::Rare];
+        assert_eq!(recipe.max_craftable(&inventory, &rarities), 0);
+    }
+
+    #[test]
+    pub fn test_equipment_not_batched() {
+        let recipe = Recipe {
+            id: RecipeId(String::from("sword")),
+            inputs: vec![(ItemKind::CopperIngot, 3)],
+            output: RecipeOutput::Equipment(EquipmentKind::CopperSword),
+            unlock: Unlock::Known,
+        };
+        let inventory = [stack(ItemKind::CopperIngot, 30)];
+        assert_eq!(recipe.max_craftable(&inventory, &[Rarity::Common]), 1);
+    }
+}
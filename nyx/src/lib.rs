@@ -1,6 +1,9 @@
+pub mod combat;
 pub mod data;
 pub mod equipment;
+pub mod experience;
 pub mod item;
 pub mod protocol;
 pub mod task;
+pub mod transport;
 
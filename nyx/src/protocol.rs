@@ -1,8 +1,29 @@
-use glam::Vec3;
+use bincode::Options;
+use glam::{Quat, Vec3};
+use serde::{de::DeserializeOwned, Serialize};
 
-use crate::{equipment::{Equipment, EquipmentId, Passive}, item::{Item, ItemStack, Rarity}};
+use crate::{combat::{AttackKind, TargetId}, data::NodeId, equipment::{Equipment, EquipmentId, Passive, Slot}, experience::Experience, item::{Item, ItemStack, Rarity, RecipeId, Tag}, task::Rank};
 
 pub const TPS: f32 = 20.0;
+// the largest packet either side sends, and so the size of the buffers they are received into
+pub const MAX_PACKET_SIZE: usize = 4096;
+
+/// The same encoding as `bincode::serialize`
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_fixint_encoding()
+}
+
+pub fn serialize<T: Serialize>(message: &T) -> bincode::Result<Vec<u8>> {
+    options().serialize(message)
+}
+
+/// Decodes a packet, failing rather than panicking on anything malformed or truncated. Nothing
+/// bigger than a packet is read, so a malformed length can't make it allocate huge amounts of memory
+pub fn deserialize<T: DeserializeOwned>(packet: &[u8]) -> bincode::Result<T> {
+    options()
+        .with_limit(MAX_PACKET_SIZE as u64)
+        .deserialize(packet)
+}
 
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub struct ClientId(pub u64);
@@ -15,25 +36,109 @@ impl Tick {
     }
 }
 
+/// Counts up with every move a client sends, so moves that arrive out of order can be spotted
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, PartialOrd, Ord, serde::Deserialize, serde::Serialize)]
+pub struct Sequence(pub u64);
+
+// positions are sent in steps of this size, giving a range of about 33 million units either way
+pub const QUANTUM: f32 = 1.0 / 64.0;
+
+/// A position rounded to a grid of [`QUANTUM`] sized steps, so the client and server can compare
+/// positions exactly
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Quantized([i32; 3]);
+
+impl From<Vec3> for Quantized {
+    fn from(position: Vec3) -> Self {
+        let steps = (position / QUANTUM)
+            .round()
+            .clamp(Vec3::splat(i32::MIN as f32), Vec3::splat(i32::MAX as f32));
+        Self([steps.x as i32, steps.y as i32, steps.z as i32])
+    }
+}
+
+impl From<Quantized> for Vec3 {
+    fn from(Quantized([x, y, z]): Quantized) -> Self {
+        Vec3::new(x as f32, y as f32, z as f32) * QUANTUM
+    }
+}
+
+/// Why the server refused to do something the client asked for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FailureReason {
+    UnknownRecipe,
+    RecipeLocked,
+    UnlockNotMet,
+    InsufficientItems,
+    UnknownNode,
+    NoSuchEquipment,
+    NotAReagent,
+    NoEmptyPassive,
+    UnknownTarget,
+    TargetDead,
+    OutOfRange,
+    UnknownSkill,
+    WrongSlot,
+}
+
+impl std::fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", match self {
+            Self::UnknownRecipe => "That recipe doesn't exist",
+            Self::RecipeLocked => "You haven't learned that recipe",
+            Self::UnlockNotMet => "You can't learn that recipe yet",
+            Self::InsufficientItems => "You don't have enough items",
+            Self::UnknownNode => "There is nothing to gather there",
+            Self::NoSuchEquipment => "You don't have that equipment",
+            Self::NotAReagent => "That item can't be used to refine",
+            Self::NoEmptyPassive => "That equipment has no empty passive slots",
+            Self::UnknownTarget => "That target doesn't exist",
+            Self::TargetDead => "That target is already dead",
+            Self::OutOfRange => "That target is too far away",
+            Self::UnknownSkill => "That skill doesn't exist",
+            Self::WrongSlot => "That equipment can't be worn there",
+        })
+    }
+}
+
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum Clientbound {
     AuthSuccess(ClientId),
-    Spawn(ClientId, Vec3),
+    // the client's data files don't match the server's
+    AuthFailure(String),
+    Spawn(ClientId, Vec3, Quat),
     Despawn(ClientId),
-    Move(ClientId, Vec3, Tick),
+    // the sequence of the latest move applied, for the client that moved to check against
+    Move(ClientId, Quantized, Quat, Sequence),
     SetStack(ItemStack),
     AddEquipment(Equipment),
-    SetPassives(EquipmentId, Vec<Passive>)
+    SetPassives(EquipmentId, Vec<Passive>),
+    SetHealth(TargetId, u32),
+    // the experience of the client, after a kill
+    SetExperience(Experience),
+    UnlockRecipe(RecipeId),
+    // the rank of one of the client's tags, after crafting something with it
+    SetRank(Tag, Rank),
+    ActionFailed { reason: FailureReason },
+    // every other client the client should know about, replacing whatever it thought there was
+    Snapshot { tick: Tick, entities: Vec<(ClientId, Vec3, Quat)> },
+    ServerShutdown
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub enum Serverbound {
-    AuthRequest,
-    Move(Vec3, Tick),
+    // the checksum of the client's content
+    AuthRequest(u64),
+    Move(Vec3, Quat, Tick, Sequence),
     Disconnect,
-    Craft(usize, Vec<Rarity>),
-    Gather(usize),
-    Refine(EquipmentId, Item)
+    Craft(RecipeId, Vec<Rarity>, usize),
+    Gather(NodeId),
+    Refine(EquipmentId, Item),
+    Attack { target: TargetId, attack: AttackKind },
+    // puts a piece of equipment in a slot, or empties it
+    Equip(Slot, Option<EquipmentId>),
+    LearnRecipe(RecipeId),
+    RequestSnapshot
 }
 
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
@@ -41,3 +146,83 @@ pub struct ClientboundBundle {
     pub tick: Tick,
     pub messages: Vec<Clientbound>
 }
+
+impl ClientboundBundle {
+    /// Splits the messages for a tick into as many packets as it takes to keep each one under
+    /// [`MAX_PACKET_SIZE`]. Fails if a message couldn't fit in a packet on its own.
+    pub fn pack(tick: Tick, messages: Vec<Clientbound>) -> bincode::Result<Vec<Vec<u8>>> {
+        let empty = options().serialized_size(&Self { tick, messages: Vec::new() })?;
+        let mut bundles = vec![(empty, Vec::new())];
+        for message in messages {
+            let size = options().serialized_size(&message)?;
+            if empty + size > MAX_PACKET_SIZE as u64 {
+                return Err(Box::new(bincode::ErrorKind::Custom(format!(
+                    "a message of {size} bytes can't fit in a packet"
+                ))));
+            }
+            let (used, current) = bundles.last_mut().unwrap();
+            if *used + size > MAX_PACKET_SIZE as u64 {
+                bundles.push((empty + size, vec![message]));
+            } else {
+                *used += size;
+                current.push(message);
+            }
+        }
+        bundles
+            .into_iter()
+            .map(|(_, messages)| serialize(&Self { tick, messages }))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_quantize() {
+        let position = Vec3::new(1.0, -2.5, 100.3);
+        let quantized = Vec3::from(Quantized::from(position));
+        assert!(position.abs_diff_eq(quantized, QUANTUM / 2.0));
+        // well past where the map ends
+        let distant = Vec3::new(1000.0, -5000.25, 20000.5);
+        assert_eq!(Vec3::from(Quantized::from(distant)), distant);
+        // anything too far away is kept at the edge rather than wrapping around
+        let far = Vec3::from(Quantized::from(Vec3::splat(1e10)));
+        assert_eq!(far, Vec3::splat(i32::MAX as f32 * QUANTUM));
+    }
+
+    fn passives(count: usize) -> Clientbound {
+        Clientbound::SetPassives(EquipmentId(0), vec![Passive::Empty; count])
+    }
+
+    #[test]
+    pub fn test_pack() {
+        let messages = (0..100).map(|_| passives(20)).collect::<Vec<_>>();
+        let packets = ClientboundBundle::pack(Tick(1), messages).unwrap();
+        assert!(packets.len() > 1);
+        assert!(packets.iter().all(|packet| packet.len() <= MAX_PACKET_SIZE));
+        let received = packets
+            .iter()
+            .map(|packet| deserialize::<ClientboundBundle>(packet).unwrap().messages.len())
+            .sum::<usize>();
+        assert_eq!(received, 100);
+
+        // a message too big to ever send is an error rather than sent truncated
+        assert!(ClientboundBundle::pack(Tick(1), vec![passives(MAX_PACKET_SIZE)]).is_err());
+    }
+
+    #[test]
+    pub fn test_malformed() {
+        let packet = serialize(&ClientboundBundle {
+            tick: Tick(1),
+            messages: vec![Clientbound::SetHealth(TargetId(0), 10)],
+        })
+        .unwrap();
+        assert!(deserialize::<ClientboundBundle>(&packet[..packet.len() - 1]).is_err());
+        // a length far bigger than the packet could hold
+        let mut huge = Tick(1).0.to_le_bytes().to_vec();
+        huge.extend(u64::MAX.to_le_bytes());
+        assert!(deserialize::<ClientboundBundle>(&huge).is_err());
+    }
+}
@@ -1,10 +1,19 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::item::{ItemKind, RecipeOutput, Tag};
+use serde::{Deserialize, Serialize};
+
+use crate::item::{ItemKind, ItemStack, Rarity, Recipe, RecipeId, RecipeOutput, Tag, Unlock};
 
 pub type Quantity = u32;
 
-#[derive(Debug, Default, Clone)]
+// proficiency xp needed to reach rank 1, each rank after needs this much more than the last
+const XP_PER_RANK: u32 = 100;
+// how much each rank in a tag improves the rarity of things made with that tag
+const RANK_UP_PER_RANK: f32 = 0.05;
+/// Proficiency xp given to each tag of a crafted recipe's output
+pub const CRAFT_XP: u32 = 20;
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Query {
     pub tags: Vec<Tag>,
 }
@@ -56,7 +65,7 @@ impl Task {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Proficiency(pub Vec<(Query, f32)>);
 impl Proficiency {
     pub fn get(&self, tags: &[Tag]) -> f32 {
@@ -68,7 +77,194 @@ impl Proficiency {
     }
 }
 
-#[derive(Debug, Default)]
+/// How far the player has progressed in a single tag
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rank {
+    pub level: u32,
+    pub xp: u32,
+}
+
+impl Rank {
+    /// The xp needed to reach the next rank
+    pub fn required(&self) -> u32 {
+        (self.level + 1) * XP_PER_RANK
+    }
+
+    /// Adds xp, returning how many ranks were gained
+    pub fn gain(&mut self, xp: u32) -> u32 {
+        self.xp += xp;
+        let mut levels = 0;
+        while self.xp >= self.required() {
+            self.xp -= self.required();
+            self.level += 1;
+            levels += 1;
+        }
+        levels
+    }
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Proficiencies {
-    pub rank_up: Proficiency
+    // bonuses from completing tasks
+    pub rank_up: Proficiency,
+    #[serde(default)]
+    pub ranks: HashMap<Tag, Rank>,
+    // every kind of item that has ever been gathered
+    #[serde(default)]
+    pub gathered: HashSet<ItemKind>,
+    #[serde(default)]
+    pub unlocked: HashSet<RecipeId>,
+}
+
+impl Proficiencies {
+    pub fn rank(&self, tag: Tag) -> Rank {
+        self.ranks.get(&tag).copied().unwrap_or_default()
+    }
+
+    /// The rank up chance for something with all of `tags`, adding up the bonuses from tasks
+    /// and the ranks of each tag
+    pub fn rank_up_for(&self, tags: &[Tag]) -> f32 {
+        let ranks = tags
+            .iter()
+            .map(|tag| self.rank(*tag).level as f32 * RANK_UP_PER_RANK)
+            .sum::<f32>();
+        self.rank_up.get(tags) + ranks
+    }
+
+    /// Adds xp to every one of `tags`, returning the tags that ranked up
+    pub fn gain(&mut self, tags: &[Tag], amount: u32) -> Vec<Tag> {
+        tags.iter()
+            .copied()
+            .filter(|tag| self.ranks.entry(*tag).or_default().gain(amount) > 0)
+            .collect()
+    }
+
+    pub fn gather(&mut self, kind: ItemKind) {
+        self.gathered.insert(kind);
+    }
+
+    /// Whether the player has done what is needed to learn a recipe
+    pub fn met(&self, unlock: Unlock) -> bool {
+        match unlock {
+            Unlock::Known => true,
+            Unlock::Gathered(kind) => self.gathered.contains(&kind),
+            Unlock::Rank(tag, level) => self.rank(tag).level >= level,
+        }
+    }
+
+    pub fn is_unlocked(&self, recipe: &RecipeId) -> bool {
+        self.unlocked.contains(recipe)
+    }
+
+    /// Learns a recipe if its unlock condition has been met, returning whether it was newly
+    /// learned
+    pub fn learn(&mut self, recipes: &[Recipe], recipe: &RecipeId) -> bool {
+        let Some(unlock) = recipes
+            .iter()
+            .find(|other| other.id == *recipe)
+            .map(|recipe| recipe.unlock)
+        else {
+            return false;
+        };
+        self.met(unlock) && self.unlocked.insert(recipe.clone())
+    }
+
+    /// Learns every recipe whose unlock condition has been met, returning the newly learned ones
+    pub fn discover(&mut self, recipes: &[Recipe]) -> Vec<RecipeId> {
+        recipes
+            .iter()
+            .filter(|recipe| self.learn(recipes, &recipe.id))
+            .map(|recipe| recipe.id.clone())
+            .collect()
+    }
+
+    /// Whether a recipe has been learned and there are enough of the chosen inputs to craft it
+    /// `count` times
+    pub fn can_craft(
+        &self,
+        recipes: &[Recipe],
+        recipe: &RecipeId,
+        inventory: &[ItemStack],
+        rarities: &[Rarity],
+        count: usize,
+    ) -> bool {
+        self.is_unlocked(recipe)
+            && recipes
+                .iter()
+                .find(|other| other.id == *recipe)
+                .is_some_and(|recipe| recipe.craftable(inventory, rarities, count))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::item::Item;
+
+    #[test]
+    pub fn test_rank_up_aggregation() {
+        let mut proficiencies = Proficiencies::default();
+        proficiencies.rank_up.0.push((
+            Query {
+                tags: vec![Tag::Smelting],
+            },
+            0.1,
+        ));
+        assert_eq!(proficiencies.rank_up_for(&[Tag::Copper]), 0.0);
+        assert_eq!(
+            proficiencies.rank_up_for(&[Tag::Smelting, Tag::Copper]),
+            0.1
+        );
+
+        // rank 1 in copper and rank 2 in smelting
+        assert_eq!(
+            proficiencies.gain(&[Tag::Smelting, Tag::Copper], 100),
+            vec![Tag::Smelting, Tag::Copper]
+        );
+        assert_eq!(
+            proficiencies.gain(&[Tag::Smelting], 200),
+            vec![Tag::Smelting]
+        );
+        assert_eq!(proficiencies.rank(Tag::Smelting), Rank { level: 2, xp: 0 });
+        assert_eq!(proficiencies.rank(Tag::Copper).level, 1);
+        assert_eq!(proficiencies.rank(Tag::Mining).level, 0);
+
+        let combined = proficiencies.rank_up_for(&[Tag::Smelting, Tag::Copper]);
+        assert!((combined - (0.1 + 3.0 * RANK_UP_PER_RANK)).abs() < f32::EPSILON);
+        let copper = proficiencies.rank_up_for(&[Tag::Copper]);
+        assert!((copper - RANK_UP_PER_RANK).abs() < f32::EPSILON);
+    }
+    #[test]
+    pub fn test_locked_recipe_uncraftable() {
+        let recipes =
+            crate::data::Content::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../", "data"))
+                .unwrap()
+                .recipes;
+        let mut proficiencies = Proficiencies::default();
+        let ore = ItemStack {
+            item: Item {
+                kind: ItemKind::CopperOre,
+                rarity: Rarity::Common,
+            },
+            quantity: 10,
+        };
+
+        let id = |name: &str| RecipeId(String::from(name));
+        let ingot = id("copper_ingot");
+
+        // copper ingots are learned by gathering copper ore
+        assert!(!proficiencies.can_craft(&recipes, &ingot, &[ore], &[Rarity::Common], 1));
+        assert!(!proficiencies.learn(&recipes, &ingot));
+        assert!(proficiencies.discover(&recipes).is_empty());
+
+        proficiencies.gather(ItemKind::CopperOre);
+        assert_eq!(proficiencies.discover(&recipes), vec![ingot.clone()]);
+        assert!(proficiencies.can_craft(&recipes, &ingot, &[ore], &[Rarity::Common], 5));
+        assert!(!proficiencies.can_craft(&recipes, &ingot, &[ore], &[Rarity::Common], 6));
+
+        // the others need ranks in smelting
+        proficiencies.gain(&[Tag::Smelting], XP_PER_RANK);
+        assert_eq!(proficiencies.discover(&recipes), vec![id("copper_sword")]);
+        assert!(!proficiencies.is_unlocked(&id("fire_damage_reagent")));
+    }
 }
@@ -0,0 +1,134 @@
+use std::{
+    io::{self, ErrorKind},
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket},
+};
+
+use crossbeam_channel::{unbounded, Receiver, Sender, TryRecvError};
+
+/// The address the server knows a loopback client by
+pub const LOOPBACK_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0));
+
+/// How the server sends and receives packets, so it can run over UDP or in the same process as
+/// its only client
+pub trait ServerTransport: Send + 'static {
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<()>;
+    /// Receives the next packet into `buffer`, or fails with [`ErrorKind::WouldBlock`] if there
+    /// isn't one yet
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)>;
+}
+
+/// How a client sends packets to and receives packets from the server
+pub trait ClientTransport {
+    fn send(&self, packet: &[u8]) -> io::Result<()>;
+    /// Receives the next packet into `buffer`, or fails with [`ErrorKind::WouldBlock`] if there
+    /// isn't one yet
+    fn recv(&self, buffer: &mut [u8]) -> io::Result<usize>;
+}
+
+impl ServerTransport for UdpSocket {
+    fn send_to(&self, packet: &[u8], addr: SocketAddr) -> io::Result<()> {
+        UdpSocket::send_to(self, packet, addr).map(|_| ())
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        UdpSocket::recv_from(self, buffer)
+    }
+}
+
+impl ClientTransport for UdpSocket {
+    fn send(&self, packet: &[u8]) -> io::Result<()> {
+        UdpSocket::send(self, packet).map(|_| ())
+    }
+
+    fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        UdpSocket::recv(self, buffer)
+    }
+}
+
+/// One end of an in-process connection between a client and a server
+pub struct Loopback {
+    tx: Sender<Vec<u8>>,
+    rx: Receiver<Vec<u8>>,
+}
+
+/// Connects a server and a client in the same process, without any sockets
+pub fn loopback() -> (Loopback, Loopback) {
+    let (server_tx, client_rx) = unbounded();
+    let (client_tx, server_rx) = unbounded();
+    (
+        Loopback {
+            tx: server_tx,
+            rx: server_rx,
+        },
+        Loopback {
+            tx: client_tx,
+            rx: client_rx,
+        },
+    )
+}
+
+impl Loopback {
+    fn send(&self, packet: &[u8]) -> io::Result<()> {
+        // like UDP, sending to the other end after it has gone isn't an error, it just goes nowhere
+        let _ = self.tx.send(packet.to_vec());
+        Ok(())
+    }
+
+    fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        match self.rx.try_recv() {
+            Ok(packet) => {
+                // like UDP, anything that doesn't fit in the buffer is lost
+                let n = packet.len().min(buffer.len());
+                buffer[..n].copy_from_slice(&packet[..n]);
+                Ok(n)
+            }
+            Err(TryRecvError::Empty | TryRecvError::Disconnected) => {
+                Err(ErrorKind::WouldBlock.into())
+            }
+        }
+    }
+}
+
+impl ServerTransport for Loopback {
+    fn send_to(&self, packet: &[u8], _: SocketAddr) -> io::Result<()> {
+        self.send(packet)
+    }
+
+    fn recv_from(&self, buffer: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        self.recv(buffer).map(|n| (n, LOOPBACK_ADDR))
+    }
+}
+
+impl ClientTransport for Loopback {
+    fn send(&self, packet: &[u8]) -> io::Result<()> {
+        Loopback::send(self, packet)
+    }
+
+    fn recv(&self, buffer: &mut [u8]) -> io::Result<usize> {
+        Loopback::recv(self, buffer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_loopback() {
+        let (server, client) = loopback();
+        let mut buffer = [0; 4];
+        assert_eq!(
+            ClientTransport::recv(&client, &mut buffer).unwrap_err().kind(),
+            ErrorKind::WouldBlock
+        );
+
+        ClientTransport::send(&client, &[1, 2, 3]).unwrap();
+        assert_eq!(server.recv_from(&mut buffer).unwrap(), (3, LOOPBACK_ADDR));
+        assert_eq!(buffer[..3], [1, 2, 3]);
+
+        server.send_to(&[1, 2, 3, 4, 5], LOOPBACK_ADDR).unwrap();
+        assert_eq!(ClientTransport::recv(&client, &mut buffer).unwrap(), 4);
+        drop(client);
+        assert!(server.send_to(&[1], LOOPBACK_ADDR).is_ok());
+    }
+}
@@ -1,10 +1,14 @@
 use fontdue::layout::TextStyle;
 use glam::{Vec2, Vec4};
-use std::rc::Rc;
+use hephaestus::image::ImageView;
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+};
 
 use crate::{
-    clicked, right_clicked, Area, Constraint, Element, Event, Font, Rectangle, Scene, Signal,
-    Signals,
+    clicked, drag_started, hovered, released, right_clicked, Area, Constraint, Element, Event,
+    Font, Key, Picture, Rectangle, Scene, Signal, Signals,
 };
 
 pub struct Container<T: Element> {
@@ -35,7 +39,7 @@ impl<T: Element> Element for Container<T> {
         });
         area.origin += self.padding;
         area.size -= self.padding * 2.0;
-        self.child.paint(area, scene, events, signals);
+        self.child.draw(area, scene, events, signals);
     }
 }
 
@@ -161,7 +165,7 @@ impl Element for VGroup {
                     origin: Vec2::new(x, y),
                     size,
                 };
-                child.paint(area, scene, events, signals);
+                child.draw(area, scene, events, signals);
                 x += size.x + self.spacing;
             });
     }
@@ -235,7 +239,7 @@ impl Element for HGroup {
                     origin: Vec2::new(x, y),
                     size,
                 };
-                child.paint(area, scene, events, signals);
+                child.draw(area, scene, events, signals);
                 y += size.y + self.spacing;
             });
     }
@@ -261,7 +265,7 @@ impl<T: Element> Element for Offset<T> {
     ) {
         area.origin += self.offset;
         area.size -= self.offset;
-        self.child.paint(area, scene, events, signals);
+        self.child.draw(area, scene, events, signals);
     }
 }
 
@@ -275,11 +279,14 @@ impl<T: Element> Element for Clicked<T> {
         self.child.layout(constraint)
     }
 
-    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
         if clicked(events, area) {
             signals.set(self.signal)
         }
-        self.child.paint(area, scene, events, signals)
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        self.child.draw(area, scene, events, signals)
     }
 }
 
@@ -293,11 +300,81 @@ impl<T: Element> Element for RightClicked<T> {
         self.child.layout(constraint)
     }
 
-    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
         if right_clicked(events, area) {
             signals.set(self.signal)
         }
-        self.child.paint(area, scene, events, signals)
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        self.child.draw(area, scene, events, signals)
+    }
+}
+
+/// Sets its signal while the cursor is over its child
+pub struct Hovered<T: Element> {
+    pub signal: Signal,
+    pub child: T,
+}
+
+impl<T: Element> Element for Hovered<T> {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.child.layout(constraint)
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
+        if hovered(events, area) {
+            signals.set(self.signal)
+        }
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        self.child.draw(area, scene, events, signals)
+    }
+}
+
+/// Sets its signal when a drag starts over its child, so it can be picked up
+pub struct Dragged<T: Element> {
+    pub signal: Signal,
+    pub child: T,
+}
+
+impl<T: Element> Element for Dragged<T> {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.child.layout(constraint)
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
+        if drag_started(events, area) {
+            signals.set(self.signal)
+        }
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        self.child.draw(area, scene, events, signals)
+    }
+}
+
+/// Sets its signal when the mouse is released over its child, so something dragged can be dropped
+/// on it
+pub struct Dropped<T: Element> {
+    pub signal: Signal,
+    pub child: T,
+}
+
+impl<T: Element> Element for Dropped<T> {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.child.layout(constraint)
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
+        if released(events, area) {
+            signals.set(self.signal)
+        }
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        self.child.draw(area, scene, events, signals)
     }
 }
 
@@ -336,7 +413,7 @@ impl<A: Element, B: Element> Element for VPair<A, B> {
     }
 
     fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
-        self.left.paint(
+        self.left.draw(
             Area {
                 origin: area.origin,
                 size: self.sizes.0,
@@ -345,7 +422,7 @@ impl<A: Element, B: Element> Element for VPair<A, B> {
             events,
             signals,
         );
-        self.right.paint(
+        self.right.draw(
             Area {
                 origin: area
                     .origin
@@ -370,6 +447,779 @@ impl<T: Element> Element for Constrain<T> {
     }
 
     fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
-        self.child.paint(area, scene, events, signals)
+        self.child.draw(area, scene, events, signals)
+    }
+}
+
+pub struct ProgressBar {
+    pub progress: f32,
+    pub size: Vec2,
+    pub colour: Vec4,
+    pub background: Vec4,
+    pub radius: f32,
+}
+
+impl Element for ProgressBar {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.size.min(constraint.max)
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, _: &[Event], _: &mut Signals) {
+        scene.rectangle(Rectangle {
+            area,
+            colour: self.background,
+            radius: self.radius,
+        });
+        scene.rectangle(Rectangle {
+            area: Area {
+                origin: area.origin,
+                size: area.size.with_x(area.size.x * self.progress.clamp(0.0, 1.0)),
+            },
+            colour: self.colour,
+            radius: self.radius,
+        });
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct SliderState {
+    value: f32,
+    grabbed: bool,
+}
+
+/// Where a [`Slider`] is set to. Elements are rebuilt every frame, so the parent keeps this and
+/// hands a clone to each new slider, reading the value back out of it.
+#[derive(Clone, Default)]
+pub struct SliderHandle(Rc<Cell<SliderState>>);
+
+impl SliderHandle {
+    pub fn new(value: f32) -> Self {
+        Self(Rc::new(Cell::new(SliderState {
+            value,
+            grabbed: false,
+        })))
+    }
+
+    pub fn value(&self) -> f32 {
+        self.0.get().value
+    }
+
+    /// Moves the slider, unless it is being dragged
+    pub fn set(&self, value: f32) {
+        let mut state = self.0.get();
+        if !state.grabbed {
+            state.value = value;
+            self.0.set(state);
+        }
+    }
+
+    pub fn grabbed(&self) -> bool {
+        self.0.get().grabbed
+    }
+}
+
+/// A track with a handle that is dragged along it to pick a value between `min` and `max`,
+/// snapping to multiples of `step` above `min` if there is one
+pub struct Slider {
+    pub handle: SliderHandle,
+    pub min: f32,
+    pub max: f32,
+    pub step: Option<f32>,
+    pub size: Vec2,
+    pub colour: Vec4,
+    pub background: Vec4,
+}
+
+impl Slider {
+    fn value_at(&self, area: Area, x: f32) -> f32 {
+        let along = ((x - area.origin.x) / area.size.x).clamp(0.0, 1.0);
+        let value = self.min + along * (self.max - self.min);
+        match self.step {
+            Some(step) => (self.min + ((value - self.min) / step).round() * step).min(self.max),
+            None => value,
+        }
+    }
+}
+
+impl Element for Slider {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.size.min(constraint.max)
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], _: &mut Signals) {
+        let mut state = self.handle.0.get();
+        events.iter().for_each(|event| match *event {
+            Event::Click(position) if area.contains(position) => {
+                state.grabbed = true;
+                state.value = self.value_at(area, position.x);
+            }
+            Event::Hover(position) if state.grabbed => {
+                state.value = self.value_at(area, position.x)
+            }
+            Event::Release(position) if state.grabbed => {
+                state.value = self.value_at(area, position.x);
+                state.grabbed = false;
+            }
+            _ => (),
+        });
+        self.handle.0.set(state);
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, _: &[Event], _: &mut Signals) {
+        let radius = area.size.y / 2.0;
+        let track = Area {
+            origin: area.origin + Vec2::new(0.0, radius / 2.0),
+            size: area.size.with_y(radius),
+        };
+        scene.rectangle(Rectangle {
+            area: track,
+            colour: self.background,
+            radius: radius / 2.0,
+        });
+
+        let along = ((self.handle.value() - self.min) / (self.max - self.min)).clamp(0.0, 1.0);
+        let x = area.origin.x + along * area.size.x;
+        scene.rectangle(Rectangle {
+            area: Area {
+                origin: track.origin,
+                size: track.size.with_x(x - area.origin.x),
+            },
+            colour: self.colour,
+            radius: radius / 2.0,
+        });
+        scene.rectangle(Rectangle {
+            area: Area {
+                origin: Vec2::new(x - radius, area.origin.y),
+                size: Vec2::splat(area.size.y),
+            },
+            colour: Vec4::ONE,
+            radius,
+        });
+    }
+}
+
+/// Whether a [`Checkbox`] is ticked, kept by the parent between frames like a [`SliderHandle`]
+#[derive(Clone, Default)]
+pub struct CheckboxHandle(Rc<Cell<bool>>);
+
+impl CheckboxHandle {
+    pub fn new(checked: bool) -> Self {
+        Self(Rc::new(Cell::new(checked)))
+    }
+
+    pub fn checked(&self) -> bool {
+        self.0.get()
+    }
+
+    pub fn set(&self, checked: bool) {
+        self.0.set(checked)
+    }
+}
+
+/// A box next to a label which is ticked and unticked by clicking either
+pub struct Checkbox {
+    pub handle: CheckboxHandle,
+    pub label: Text,
+    pub colour: Vec4,
+    pub background: Vec4,
+    label_size: Vec2,
+}
+
+impl Checkbox {
+    const SPACING: f32 = 8.0;
+
+    pub fn new(handle: CheckboxHandle, label: Text) -> Self {
+        Self {
+            handle,
+            label,
+            colour: Vec4::ONE,
+            background: Vec4::new(0.3, 0.3, 0.3, 1.0),
+            label_size: Vec2::ZERO,
+        }
+    }
+
+    fn box_size(&self) -> f32 {
+        self.label.font_size
+    }
+}
+
+impl Element for Checkbox {
+    fn layout(&mut self, mut constraint: Constraint<Vec2>) -> Vec2 {
+        let size = self.box_size();
+        constraint.max.x -= size + Self::SPACING;
+        self.label_size = self.label.layout(constraint);
+        Vec2::new(
+            size + Self::SPACING + self.label_size.x,
+            size.max(self.label_size.y),
+        )
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], _: &mut Signals) {
+        if clicked(events, area) {
+            self.handle.set(!self.handle.checked())
+        }
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        let size = self.box_size();
+        let origin = area.origin + Vec2::new(0.0, (area.size.y - size) / 2.0);
+        scene.rectangle(Rectangle {
+            area: Area {
+                origin,
+                size: Vec2::splat(size),
+            },
+            colour: self.background,
+            radius: size / 4.0,
+        });
+        if self.handle.checked() {
+            let inset = size / 4.0;
+            scene.rectangle(Rectangle {
+                area: Area {
+                    origin: origin + inset,
+                    size: Vec2::splat(size - inset * 2.0),
+                },
+                colour: self.colour,
+                radius: inset / 2.0,
+            });
+        }
+
+        let label = Area {
+            origin: area.origin
+                + Vec2::new(
+                    size + Self::SPACING,
+                    (area.size.y - self.label_size.y) / 2.0,
+                ),
+            size: self.label_size,
+        };
+        self.label.draw(label, scene, events, signals);
+    }
+}
+
+#[derive(Clone, Copy)]
+struct DropdownState<T: Copy> {
+    selected: T,
+    open: bool,
+}
+
+/// Which option a [`Dropdown`] has picked and whether its list is open, kept by the parent between
+/// frames like a [`SliderHandle`]
+#[derive(Clone)]
+pub struct DropdownHandle<T: Copy>(Rc<Cell<DropdownState<T>>>);
+
+impl<T: Copy> DropdownHandle<T> {
+    pub fn new(selected: T) -> Self {
+        Self(Rc::new(Cell::new(DropdownState {
+            selected,
+            open: false,
+        })))
+    }
+
+    pub fn selected(&self) -> T {
+        self.0.get().selected
+    }
+
+    pub fn open(&self) -> bool {
+        self.0.get().open
+    }
+}
+
+/// Shows the selected option, and when clicked lists every option over the rest of the ui to
+/// pick from. Picking one sets its signal.
+pub struct Dropdown<T: Copy + PartialEq> {
+    pub handle: DropdownHandle<T>,
+    pub signal: Signal,
+    pub options: Vec<(T, Text)>,
+    pub colour: Vec4,
+    pub highlight: Vec4,
+    row: Vec2,
+    sizes: Vec<Vec2>,
+}
+
+impl<T: Copy + PartialEq> Dropdown<T> {
+    const PADDING: f32 = 8.0;
+
+    pub fn new(handle: DropdownHandle<T>, signal: Signal, options: Vec<(T, Text)>) -> Self {
+        Self {
+            handle,
+            signal,
+            options,
+            colour: Vec4::new(0.2, 0.2, 0.2, 1.0),
+            highlight: Vec4::new(0.3, 0.3, 0.3, 1.0),
+            row: Vec2::ZERO,
+            sizes: Vec::new(),
+        }
+    }
+
+    /// Where an option is listed while open, below the selected one
+    fn option_area(&self, area: Area, index: usize) -> Area {
+        Area {
+            origin: area.origin + Vec2::new(0.0, self.row.y * (index + 1) as f32),
+            size: self.row,
+        }
+    }
+}
+
+impl<T: Copy + PartialEq> Element for Dropdown<T> {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.sizes = self
+            .options
+            .iter_mut()
+            .map(|(_, label)| label.layout(constraint))
+            .collect();
+        self.row = self
+            .sizes
+            .iter()
+            .fold(Vec2::ZERO, |row, size| row.max(*size))
+            + Self::PADDING * 2.0;
+        self.row.min(constraint.max)
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
+        let mut state = self.handle.0.get();
+        if clicked(events, area) {
+            state.open = !state.open;
+        } else if state.open {
+            let picked = (0..self.options.len())
+                .find(|index| clicked(events, self.option_area(area, *index)));
+            if let Some(index) = picked {
+                state.selected = self.options[index].0;
+                signals.set(self.signal);
+            }
+            // clicking anywhere else closes it too
+            if events.iter().any(|event| matches!(event, Event::Click(_))) {
+                state.open = false;
+            }
+        }
+        self.handle.0.set(state);
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        let state = self.handle.0.get();
+        scene.rectangle(Rectangle {
+            area,
+            colour: self.colour,
+            radius: 4.0,
+        });
+        if let Some(index) = self
+            .options
+            .iter()
+            .position(|(value, _)| *value == state.selected)
+        {
+            let label = Area {
+                origin: area.origin + Self::PADDING,
+                size: self.sizes[index],
+            };
+            self.options[index].1.draw(label, scene, events, signals);
+        }
+
+        if !state.open {
+            return;
+        }
+        let areas = (0..self.options.len())
+            .map(|index| self.option_area(area, index))
+            .collect::<Vec<Area>>();
+        scene.overlay(|scene| {
+            self.options
+                .iter_mut()
+                .zip(&areas)
+                .zip(&self.sizes)
+                .for_each(|(((_, label), area), size)| {
+                    scene.rectangle(Rectangle {
+                        area: *area,
+                        colour: if hovered(events, *area) {
+                            self.highlight
+                        } else {
+                            self.colour
+                        },
+                        radius: 0.0,
+                    });
+                    let label_area = Area {
+                        origin: area.origin + Self::PADDING,
+                        size: *size,
+                    };
+                    label.draw(label_area, scene, events, signals);
+                })
+        });
+    }
+}
+
+/// The text typed into a [`TextInput`], kept by the parent between frames like a [`SliderHandle`]
+#[derive(Clone, Default)]
+pub struct TextHandle(Rc<RefCell<String>>);
+
+impl TextHandle {
+    pub fn new<T: ToString>(text: T) -> Self {
+        Self(Rc::new(RefCell::new(text.to_string())))
+    }
+
+    pub fn text(&self) -> String {
+        self.0.borrow().clone()
+    }
+
+    pub fn set<T: ToString>(&self, text: T) {
+        *self.0.borrow_mut() = text.to_string()
+    }
+}
+
+/// A field that is typed into while it has focus, which it takes when clicked. Its signal
+/// identifies it to [`Signals::focus`], and is also set when enter is pressed in it.
+pub struct TextInput {
+    pub handle: TextHandle,
+    pub signal: Signal,
+    pub text: Text,
+    pub width: f32,
+    pub colour: Vec4,
+    pub focused_colour: Vec4,
+    text_size: Vec2,
+}
+
+impl TextInput {
+    const PADDING: f32 = 4.0;
+
+    pub fn new(handle: TextHandle, signal: Signal, width: f32, text: Text) -> Self {
+        Self {
+            handle,
+            signal,
+            text,
+            width,
+            colour: Vec4::new(0.1, 0.1, 0.1, 1.0),
+            focused_colour: Vec4::new(0.2, 0.2, 0.2, 1.0),
+            text_size: Vec2::ZERO,
+        }
+    }
+}
+
+impl Element for TextInput {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.text.text = self.handle.text();
+        self.text_size = self.text.layout(constraint);
+        Vec2::new(self.width, self.text.font_size + Self::PADDING * 2.0).min(constraint.max)
+    }
+
+    fn event(&mut self, area: Area, events: &[Event], signals: &mut Signals) {
+        signals.focusable(self.signal);
+        if clicked(events, area) {
+            signals.focus(self.signal);
+        } else if signals.focused(self.signal)
+            && events.iter().any(|event| matches!(event, Event::Click(_)))
+        {
+            // clicked somewhere else
+            signals.unfocus();
+        }
+        if !signals.focused(self.signal) {
+            return;
+        }
+
+        let mut text = self.handle.0.borrow_mut();
+        events.iter().for_each(|event| match event {
+            Event::Character(c) if !c.is_control() => text.push(*c),
+            Event::Key(Key::Backspace) => {
+                text.pop();
+            }
+            Event::Key(Key::Enter) => signals.set(self.signal),
+            _ => (),
+        });
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        let focused = signals.focused(self.signal);
+        scene.rectangle(Rectangle {
+            area,
+            colour: if focused {
+                self.focused_colour
+            } else {
+                self.colour
+            },
+            radius: 4.0,
+        });
+
+        // typed this frame, after it was laid out
+        self.text.text = self.handle.text();
+        self.text_size = self.text.layout(Constraint {
+            min: Vec2::ZERO,
+            max: area.size,
+        });
+        let origin = area.origin + Self::PADDING;
+        let label = Area {
+            origin,
+            size: self.text_size,
+        };
+        self.text.draw(label, scene, events, signals);
+
+        if focused {
+            scene.rectangle(Rectangle {
+                area: Area {
+                    origin: origin + Vec2::new(self.text_size.x + 2.0, 0.0),
+                    size: Vec2::new(2.0, self.text.font_size),
+                },
+                colour: self.text.colour,
+                radius: 0.0,
+            });
+        }
+    }
+}
+
+pub struct Marker {
+    /// Where the marker is across the area, from -1 at the left and top edges to 1 at the right
+    /// and bottom
+    pub position: Vec2,
+    pub size: f32,
+    pub colour: Vec4,
+}
+
+/// Round markers scattered over a background, for showing where things are on a map
+pub struct Markers {
+    pub size: Vec2,
+    pub background: Vec4,
+    pub radius: f32,
+    pub markers: Vec<Marker>,
+}
+
+impl Element for Markers {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.size.min(constraint.max)
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, _: &[Event], _: &mut Signals) {
+        scene.rectangle(Rectangle {
+            area,
+            colour: self.background,
+            radius: self.radius,
+        });
+        self.markers.iter().for_each(|marker| {
+            let centre = area.origin + (marker.position + 1.0) / 2.0 * area.size;
+            scene.rectangle(Rectangle {
+                area: Area {
+                    origin: centre - marker.size / 2.0,
+                    size: Vec2::splat(marker.size),
+                },
+                colour: marker.colour,
+                radius: marker.size / 2.0,
+            });
+        });
+    }
+}
+
+/// Shows an image, which has to be in `SHADER_READ_ONLY_OPTIMAL` by the time the ui is drawn
+pub struct Image {
+    pub view: Rc<ImageView>,
+    pub size: Vec2,
+}
+
+impl Element for Image {
+    fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2 {
+        self.size.min(constraint.max)
+    }
+
+    fn paint(&mut self, area: Area, scene: &mut Scene, _: &[Event], _: &mut Signals) {
+        scene.picture(Picture {
+            area,
+            view: self.view.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn font() -> Rc<Font> {
+        Font::from_bytes(
+            include_bytes!("../../assets/fonts/JetBrainsMono-Medium.ttf").as_slice(),
+            Default::default(),
+        )
+        .map(Rc::new)
+        .unwrap()
+    }
+
+    #[test]
+    pub fn test_slider() {
+        let handle = SliderHandle::new(0.0);
+        let mut slider = Slider {
+            handle: handle.clone(),
+            min: 10.0,
+            max: 20.0,
+            step: None,
+            size: Vec2::new(200.0, 20.0),
+            colour: Vec4::ONE,
+            background: Vec4::ZERO,
+        };
+        let area = Area {
+            origin: Vec2::new(100.0, 100.0),
+            size: slider.size,
+        };
+        let mut signals = Signals::default();
+
+        // grabbed near the start of the track, then dragged to the middle
+        slider.event(area, &[Event::Click(Vec2::new(120.0, 110.0))], &mut signals);
+        assert!(handle.grabbed());
+        assert_eq!(handle.value(), 11.0);
+        slider.event(area, &[Event::Hover(Vec2::new(200.0, 300.0))], &mut signals);
+        assert_eq!(handle.value(), 15.0);
+
+        // let go past the end of the track
+        slider.event(
+            area,
+            &[Event::Release(Vec2::new(500.0, 110.0))],
+            &mut signals,
+        );
+        assert!(!handle.grabbed());
+        assert_eq!(handle.value(), 20.0);
+
+        // hovering without dragging leaves it alone, and steps snap the value
+        slider.event(area, &[Event::Hover(Vec2::new(100.0, 110.0))], &mut signals);
+        assert_eq!(handle.value(), 20.0);
+        slider.step = Some(3.0);
+        slider.event(area, &[Event::Click(Vec2::new(180.0, 110.0))], &mut signals);
+        assert_eq!(handle.value(), 13.0);
+    }
+
+    #[test]
+    pub fn test_checkbox() {
+        let handle = CheckboxHandle::new(false);
+        let area = Area {
+            origin: Vec2::ZERO,
+            size: Vec2::new(100.0, 20.0),
+        };
+        let font = font();
+        let mut signals = Signals::default();
+        // rebuilt for every frame's events, like the ui does
+        let mut toggle = |events: &[Event]| {
+            let mut checkbox = Checkbox::new(handle.clone(), text("Vsync", 20.0, font.clone()));
+            checkbox.event(area, events, &mut signals);
+        };
+
+        toggle(&[Event::Click(Vec2::new(50.0, 10.0))]);
+        assert!(handle.checked());
+        // only clicks inside it count
+        toggle(&[Event::Hover(Vec2::new(50.0, 10.0))]);
+        toggle(&[Event::Click(Vec2::new(150.0, 10.0))]);
+        assert!(handle.checked());
+        toggle(&[Event::Click(Vec2::new(50.0, 10.0))]);
+        assert!(!handle.checked());
+    }
+
+    #[test]
+    pub fn test_dropdown() {
+        let mut signals = Signals::default();
+        let signal = signals.signal();
+        let handle = DropdownHandle::new(1);
+        let font = font();
+        let mut dropdown = Dropdown::new(
+            handle.clone(),
+            signal,
+            [1, 4, 8]
+                .into_iter()
+                .map(|samples| (samples, text(samples, 20.0, font.clone())))
+                .collect(),
+        );
+        let row = dropdown.layout(Constraint {
+            min: Vec2::ZERO,
+            max: Vec2::splat(1000.0),
+        });
+        let area = Area {
+            origin: Vec2::ZERO,
+            size: row,
+        };
+        let click = |row: f32| [Event::Click(area.size * Vec2::new(0.5, row + 0.5))];
+
+        dropdown.event(area, &click(0.0), &mut signals);
+        assert!(handle.open());
+        assert!(!signals.get(signal));
+
+        // the third option is picked from the list below
+        dropdown.event(area, &click(3.0), &mut signals);
+        assert_eq!(handle.selected(), 8);
+        assert!(!handle.open());
+        assert!(signals.get(signal));
+
+        // once closed, clicking where the list was does nothing
+        signals.clear();
+        dropdown.event(area, &click(2.0), &mut signals);
+        assert_eq!(handle.selected(), 8);
+        assert!(!signals.get(signal));
+    }
+
+    #[test]
+    pub fn test_focus() {
+        let mut signals = Signals::default();
+        let font = font();
+        let handles = [TextHandle::default(), TextHandle::default()];
+        let ids = [signals.signal(), signals.signal()];
+        let areas = [0.0, 50.0].map(|y| Area {
+            origin: Vec2::new(0.0, y),
+            size: Vec2::new(200.0, 30.0),
+        });
+        // a frame of the ui, with both fields rebuilt and given the same events
+        let mut frame = |events: &[Event]| {
+            signals.clear();
+            handles
+                .iter()
+                .zip(ids)
+                .zip(areas)
+                .for_each(|((handle, id), area)| {
+                    let mut input =
+                        TextInput::new(handle.clone(), id, 200.0, text("", 20.0, font.clone()));
+                    input.event(area, events, &mut signals);
+                });
+            signals.navigate(events);
+        };
+        let typed = |handle: &TextHandle| handle.text();
+
+        // nothing has focus to begin with
+        frame(&[Event::Character('a')]);
+        assert_eq!(typed(&handles[0]), "");
+
+        frame(&[Event::Click(Vec2::new(10.0, 10.0))]);
+        frame(&[Event::Character('h'), Event::Character('i')]);
+        assert_eq!(typed(&handles[0]), "hi");
+        assert_eq!(typed(&handles[1]), "");
+
+        // tab moves on to the second field
+        frame(&[Event::Key(Key::Tab)]);
+        frame(&[
+            Event::Character('x'),
+            Event::Key(Key::Backspace),
+            Event::Character('y'),
+        ]);
+        assert_eq!(typed(&handles[0]), "hi");
+        assert_eq!(typed(&handles[1]), "y");
+
+        // and escape leaves neither focused
+        frame(&[Event::Key(Key::Escape)]);
+        frame(&[Event::Character('z')]);
+        assert_eq!(typed(&handles[0]), "hi");
+        assert_eq!(typed(&handles[1]), "y");
+    }
+
+    #[test]
+    pub fn test_dragged() {
+        let mut signals = Signals::default();
+        let signal = signals.signal();
+        let area = Area {
+            origin: Vec2::ZERO,
+            size: Vec2::new(100.0, 20.0),
+        };
+        let mut dragged = Dragged {
+            signal,
+            child: text("Sword", 20.0, font()),
+        };
+
+        // pressing on it isn't enough to pick it up
+        dragged.event(area, &[Event::Click(Vec2::new(10.0, 10.0))], &mut signals);
+        assert!(!signals.get(signal));
+
+        // nor is a drag that started somewhere else
+        let elsewhere = Event::DragStart(Vec2::new(200.0, 10.0));
+        dragged.event(area, &[elsewhere], &mut signals);
+        assert!(!signals.get(signal));
+
+        // the drag is picked up from where it started, wherever the cursor has moved to since
+        let events = [
+            Event::DragStart(Vec2::new(10.0, 10.0)),
+            Event::Drag(Vec2::new(200.0, 10.0)),
+        ];
+        dragged.event(area, &events, &mut signals);
+        assert!(signals.get(signal));
     }
 }
@@ -23,31 +23,116 @@ use hephaestus::{
 #[derive(Debug, Clone)]
 pub enum Event {
     Click(Vec2),
-    RightClick(Vec2)
+    RightClick(Vec2),
+    // where the cursor is this frame
+    Hover(Vec2),
+    // the left button was let go, ending a drag
+    Release(Vec2),
+    // the cursor has moved far enough with the left button held to start dragging, from where it
+    // was pressed
+    DragStart(Vec2),
+    // where the cursor is while dragging
+    Drag(Vec2),
+    // typed text, which only the focused element takes
+    Character(char),
+    Key(Key),
+}
+
+/// Keys for editing text and moving focus between elements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Key {
+    Backspace,
+    Enter,
+    Tab,
+    Escape,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct Signal(usize);
 
 #[derive(Default, Clone, Debug)]
-pub struct Signals(Vec<bool>);
+pub struct Signals {
+    states: Vec<bool>,
+    // released signals, which are handed out again before any new ones
+    free: Vec<usize>,
+    // the element keys go to, which is identified by one of its signals
+    focused: Option<usize>,
+    // the elements that could take focus this frame, in the order they handled events
+    focusable: Vec<usize>,
+}
 
 impl Signals {
     pub fn signal(&mut self) -> Signal {
-        self.0.push(false);
-        Signal(self.0.len() - 1)
+        if let Some(index) = self.free.pop() {
+            self.states[index] = false;
+            return Signal(index);
+        }
+        self.states.push(false);
+        Signal(self.states.len() - 1)
+    }
+
+    /// Gives a signal back once whatever it belonged to is gone, so it can be reused. It mustn't
+    /// be used again afterwards, as it may be handed out to something else.
+    pub fn release(&mut self, signal: Signal) {
+        if signal.0 < self.states.len() && !self.free.contains(&signal.0) {
+            self.states[signal.0] = false;
+            self.free.push(signal.0);
+            if self.focused == Some(signal.0) {
+                self.focused = None;
+            }
+        }
     }
 
     pub fn get(&self, signal: Signal) -> bool {
-        self.0.get(signal.0).copied().unwrap_or_default()
+        self.states.get(signal.0).copied().unwrap_or_default()
     }
 
     pub fn set(&mut self, signal: Signal) {
-        self.0.get_mut(signal.0).map(|x| *x = true);
+        self.states.get_mut(signal.0).map(|x| *x = true);
     }
 
     pub fn clear(&mut self) {
-        self.0.iter_mut().for_each(|x| *x = false);
+        self.states.iter_mut().for_each(|x| *x = false);
+        self.focusable.clear();
+    }
+
+    /// Marks the element with `signal` as one that can take focus, for tab to move between. It
+    /// has to be called every frame, as elements are.
+    pub fn focusable(&mut self, signal: Signal) {
+        self.focusable.push(signal.0);
+    }
+
+    pub fn focus(&mut self, signal: Signal) {
+        self.focused = Some(signal.0);
+    }
+
+    pub fn focused(&self, signal: Signal) -> bool {
+        self.focused == Some(signal.0)
+    }
+
+    pub fn unfocus(&mut self) {
+        self.focused = None;
+    }
+
+    /// Whether anything has focus, in which case keys should go to it rather than the game
+    pub fn has_focus(&self) -> bool {
+        self.focused.is_some()
+    }
+
+    /// Moves focus to the next focusable element on tab, or drops it on escape. Called once every
+    /// element has handled the frame's events.
+    pub fn navigate(&mut self, events: &[Event]) {
+        events.iter().for_each(|event| match event {
+            Event::Key(Key::Tab) if !self.focusable.is_empty() => {
+                let next = self
+                    .focused
+                    .and_then(|focused| self.focusable.iter().position(|x| *x == focused))
+                    .map_or(0, |index| (index + 1) % self.focusable.len());
+                self.focused = Some(self.focusable[next]);
+            }
+            Event::Key(Key::Escape) => self.focused = None,
+            _ => (),
+        })
     }
 }
 
@@ -77,6 +162,45 @@ pub fn right_clicked(events: &[Event], area: Area) -> bool {
         .any(|position| area.contains(position))
 }
 
+pub fn released(events: &[Event], area: Area) -> bool {
+    events
+        .iter()
+        .filter_map(|event| {
+            if let Event::Release(position) = event {
+                Some(*position)
+            } else {
+                None
+            }
+        })
+        .any(|position| area.contains(position))
+}
+
+pub fn drag_started(events: &[Event], area: Area) -> bool {
+    events
+        .iter()
+        .filter_map(|event| {
+            if let Event::DragStart(position) = event {
+                Some(*position)
+            } else {
+                None
+            }
+        })
+        .any(|position| area.contains(position))
+}
+
+pub fn hovered(events: &[Event], area: Area) -> bool {
+    events
+        .iter()
+        .filter_map(|event| {
+            if let Event::Hover(position) = event {
+                Some(*position)
+            } else {
+                None
+            }
+        })
+        .any(|position| area.contains(position))
+}
+
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Constraint<T> {
@@ -136,6 +260,8 @@ impl Area {
     }
 }
 
+/// Colours throughout the ui are in sRGB, like a colour picker shows them, and are converted to
+/// linear when drawn
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Rectangle {
     area: Area,
@@ -157,20 +283,29 @@ impl Text {
     }
 }
 
+/// An image drawn in full colour, such as one rendered offscreen
+pub struct Picture {
+    pub area: Area,
+    pub view: Rc<ImageView>,
+}
+
 #[derive(Default)]
 pub struct Layer {
     rectangles: Vec<Rectangle>,
     text: Vec<Text>,
+    pictures: Vec<Picture>,
 }
 
 impl Layer {
     pub fn is_empty(&self) -> bool {
-        self.rectangles.is_empty() && self.text.is_empty()
+        self.rectangles.is_empty() && self.text.is_empty() && self.pictures.is_empty()
     }
 }
 
 pub struct Scene {
     layers: Vec<Layer>,
+    // drawn over every layer, for things like open dropdowns that cover whatever comes after them
+    overlays: Vec<Layer>,
 }
 
 pub struct RenderedScene {
@@ -178,12 +313,15 @@ pub struct RenderedScene {
     indices: Vec<u32>,
     rectangles: Vec<RectangleData>,
     image: (Size, Vec<u8>),
+    // the first index of each picture, which are drawn one at a time after everything else
+    pictures: Vec<(u32, Rc<ImageView>)>,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Self {
             layers: vec![Layer::default()],
+            overlays: Vec::new(),
         }
     }
 
@@ -195,43 +333,96 @@ impl Scene {
         self.layers.last_mut().unwrap().text.push(text)
     }
 
+    pub fn picture(&mut self, picture: Picture) {
+        self.layers.last_mut().unwrap().pictures.push(picture)
+    }
+
     pub fn layer(&mut self) {
         self.layers.push(Layer::default())
     }
 
+    /// Paints over everything else in the scene, including anything added after this
+    pub fn overlay<F: FnOnce(&mut Scene)>(&mut self, paint: F) {
+        let mut overlay = Scene::new();
+        paint(&mut overlay);
+        self.overlays.extend(overlay.layers);
+        self.overlays.extend(overlay.overlays);
+    }
+
+    fn all_layers(&self) -> impl Iterator<Item = &Layer> {
+        self.layers.iter().chain(&self.overlays)
+    }
+
     pub fn render(&self) -> Result<RenderedScene> {
-        let (mut vertices, mut indices, mut rectangles) = self.render_rectangles();
-        let (mut text_vertices, text_indices, mut text_rectangles, image) = self.render_text()?;
-        indices.append(
-            &mut text_indices
-                .into_iter()
-                .map(|index| index + vertices.len() as u32)
-                .collect(),
-        );
+        let (mut vertices, rectangle_indices, mut rectangles) = self.render_rectangles();
+        let (mut text_vertices, text_indices, mut text_rectangles, glyphs, image) =
+            self.render_text()?;
+
+        // each layer's rectangles then its text, so a layer covers the text of those under it
+        let offset = vertices.len() as u32;
+        let mut rectangle_indices = rectangle_indices.chunks(6);
+        let mut text_indices = text_indices.chunks(6);
+        let mut indices = Vec::new();
+        self.all_layers().zip(glyphs).for_each(|(layer, glyphs)| {
+            rectangle_indices
+                .by_ref()
+                .take(layer.rectangles.len())
+                .for_each(|quad| indices.extend_from_slice(quad));
+            text_indices
+                .by_ref()
+                .take(glyphs)
+                .for_each(|quad| indices.extend(quad.iter().map(|index| index + offset)));
+        });
         vertices.append(&mut text_vertices);
         rectangles.append(&mut text_rectangles);
 
+        let pictures = self
+            .all_layers()
+            .flat_map(|layer| &layer.pictures)
+            .map(|picture| {
+                let (mut picture_vertices, picture_indices) = Area::vertices(&[picture.area]);
+                let first = indices.len() as u32;
+                indices.extend(
+                    picture_indices
+                        .into_iter()
+                        .map(|index| index + vertices.len() as u32),
+                );
+                vertices.append(&mut picture_vertices);
+                rectangles.push(RectangleData {
+                    colour: Vec4::ONE,
+                    area: picture.area.as_vec4(),
+                    sample_area: Vec4::new(
+                        0.0,
+                        0.0,
+                        picture.view.extent.width as f32,
+                        picture.view.extent.height as f32,
+                    ),
+                    radius: Vec4::new(0.0, 1.0, 0.0, 0.0),
+                });
+                (first, picture.view.clone())
+            })
+            .collect();
+
         Ok(RenderedScene {
             vertices,
             indices,
             rectangles,
             image,
+            pictures,
         })
     }
 
     fn render_rectangles(&self) -> (Vec<Vec2>, Vec<u32>, Vec<RectangleData>) {
         let (vertices, indices) = Area::vertices(
             &self
-                .layers
-                .iter()
+                .all_layers()
                 .flat_map(|layer| &layer.rectangles)
                 .map(|rectangle| rectangle.area)
                 .collect::<Vec<Area>>(),
         );
 
         let rectangles = self
-            .layers
-            .iter()
+            .all_layers()
             .flat_map(|layer| &layer.rectangles)
             .map(|rectangle| RectangleData {
                 colour: rectangle.colour,
@@ -249,10 +440,18 @@ impl Scene {
         (vertices, indices, rectangles)
     }
 
-    fn render_text(&self) -> Result<(Vec<Vec2>, Vec<u32>, Vec<RectangleData>, (Size, Vec<u8>))> {
+    /// Also returns how many glyphs are in each layer
+    fn render_text(
+        &self,
+    ) -> Result<(
+        Vec<Vec2>,
+        Vec<u32>,
+        Vec<RectangleData>,
+        Vec<usize>,
+        (Size, Vec<u8>),
+    )> {
         let text = self
-            .layers
-            .iter()
+            .all_layers()
             .flat_map(|layer| &layer.text)
             .collect::<Vec<_>>();
 
@@ -270,6 +469,12 @@ impl Scene {
             })
             .collect::<Vec<_>>();
 
+        let mut counts = layouts.iter().map(|(_, layout)| layout.len());
+        let layer_glyphs = self
+            .all_layers()
+            .map(|layer| counts.by_ref().take(layer.text.len()).sum())
+            .collect::<Vec<usize>>();
+
         let areas = layouts
             .iter()
             .zip(&text)
@@ -371,11 +576,17 @@ impl Scene {
             })
             .collect();
 
-        Ok((vertices, indices, rectangles, (image_size, image_data)))
+        Ok((
+            vertices,
+            indices,
+            rectangles,
+            layer_glyphs,
+            (image_size, image_data),
+        ))
     }
 
     pub fn is_empty(&self) -> bool {
-        self.layers.iter().all(|layer| layer.is_empty())
+        self.all_layers().all(|layer| layer.is_empty())
     }
 }
 
@@ -397,6 +608,7 @@ struct RectangleData {
     pub colour: Vec4,
     pub area: Vec4,
     pub sample_area: Vec4,
+    /// The corner radius, then 1 if this is a picture rather than a glyph or plain rectangle
     pub radius: Vec4,
 }
 
@@ -412,15 +624,39 @@ pub struct Frame {
     set: Rc<descriptor::Set>,
     view: Rc<ImageView>,
     sampler: Rc<Sampler>,
+    pictures: Vec<(u32, Rc<descriptor::Set>)>,
 }
 
 impl Renderer {
-    pub fn new(ctx: &Context, render_pass: &RenderPass, subpass: usize) -> Result<Self> {
-        let ui_vertex =
-            ShaderModule::new(&ctx.device, &std::fs::read("assets/shaders/ui.vert.spv")?)?;
+    /// Draws in `subpass` of `render_pass`, whose attachments have `samples` samples per pixel
+    pub fn new(
+        ctx: &Context,
+        render_pass: &RenderPass,
+        subpass: usize,
+        samples: SampleCountFlags,
+    ) -> Result<Self> {
+        Self::with_shaders(
+            ctx,
+            render_pass,
+            subpass,
+            samples,
+            &std::fs::read("assets/shaders/ui.vert.spv")?,
+            &std::fs::read("assets/shaders/ui.frag.spv")?,
+        )
+    }
+
+    /// Like [`Renderer::new`], with the SPIR-V of the ui shaders already read
+    pub fn with_shaders(
+        ctx: &Context,
+        render_pass: &RenderPass,
+        subpass: usize,
+        samples: SampleCountFlags,
+        vertex: &[u8],
+        fragment: &[u8],
+    ) -> Result<Self> {
+        let ui_vertex = ShaderModule::new(&ctx.device, vertex)?;
 
-        let ui_fragment =
-            ShaderModule::new(&ctx.device, &std::fs::read("assets/shaders/ui.frag.spv")?)?;
+        let ui_fragment = ShaderModule::new(&ctx.device, fragment)?;
 
         let layout = descriptor::Layout::new(
             ctx,
@@ -440,15 +676,32 @@ impl Renderer {
             .subpass(subpass as u32)
             .viewport(Viewport::Dynamic)
             .layouts(vec![&layout])
-            .multisampled(ctx.device.physical.get_samples())
+            .multisampled(samples)
             .build(&ctx.device)?;
 
         Ok(Self { pipeline, layout })
     }
 
-    pub fn prepare(&self, ctx: &Context, scene: &Scene, viewport: Vec2) -> Result<Frame> {
+    /// A pool for the sets a frame allocates, which are one for the scene and one for each
+    /// picture in it
+    pub fn pool(&self, capacity: usize) -> Result<descriptor::Pool> {
+        Ok(self.layout.pool(capacity)?)
+    }
+
+    /// Allocates the frame's sets from `pool`, which has to come from [`Renderer::pool`]
+    pub fn prepare(
+        &self,
+        ctx: &Context,
+        pool: &descriptor::Pool,
+        scene: &Scene,
+        viewport: Vec2,
+    ) -> Result<Frame> {
         let rendered = scene.render()?;
-        let num_indices = rendered.indices.len() as u32;
+        let num_indices = rendered
+            .pictures
+            .first()
+            .map(|(first, _)| *first)
+            .unwrap_or(rendered.indices.len() as u32);
         let vertex_buffer = Static::new(
             ctx,
             bytemuck::cast_slice::<Vec2, u8>(&rendered.vertices),
@@ -465,7 +718,7 @@ impl Renderer {
             BufferUsageFlags::STORAGE_BUFFER,
         )?;
 
-        let viewport_buffer = Static::new(
+        let viewport_buffer = Static::new_host_visible(
             ctx,
             bytemuck::cast_slice::<Vec2, u8>(&[viewport]),
             BufferUsageFlags::UNIFORM_BUFFER,
@@ -542,14 +795,28 @@ impl Renderer {
 
         let sampler = Sampler::new(&ctx.device)?;
 
-        let set = self
-            .layout
+        let set = pool
             .alloc()?
             .write_buffer(0, &rectangle_buffer)
             .write_buffer(1, &viewport_buffer)
             .write_image(2, &view, &sampler, ImageLayout::SHADER_READ_ONLY_OPTIMAL)
             .finish();
 
+        // each picture samples its own image in place of the glyph atlas
+        let pictures = rendered
+            .pictures
+            .iter()
+            .map(|(first, picture)| {
+                let set = pool
+                    .alloc()?
+                    .write_buffer(0, &rectangle_buffer)
+                    .write_buffer(1, &viewport_buffer)
+                    .write_image(2, picture, &sampler, ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .finish();
+                Ok((*first, set))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
         Ok(Frame {
             vertex_buffer,
             index_buffer,
@@ -557,6 +824,7 @@ impl Renderer {
             set,
             view,
             sampler,
+            pictures,
         })
     }
 
@@ -571,16 +839,34 @@ impl Renderer {
             )
             .finish();
 
-        cmd.next_subpass()
+        let cmd = cmd
+            .next_subpass()
             .bind_graphics_pipeline(&self.pipeline)
             .bind_vertex_buffer(&frame.vertex_buffer, 0)
             .bind_index_buffer(&frame.index_buffer)
             .bind_descriptor_set(&set, 0)
-            .draw_indexed(frame.num_indices, 1, 0, 0, 0)
+            .draw_indexed(frame.num_indices, 1, 0, 0, 0);
+
+        frame.pictures.iter().fold(cmd, |cmd, (first, set)| {
+            cmd.bind_descriptor_set(set, 0)
+                .draw_indexed(6, 1, *first, 0, 0)
+        })
     }
 }
 
 pub trait Element {
     fn layout(&mut self, constraint: Constraint<Vec2>) -> Vec2;
+
+    /// Reacts to this frame's events before anything is painted, which is where stateful elements
+    /// should update themselves. Does nothing by default.
+    fn event(&mut self, _area: Area, _events: &[Event], _signals: &mut Signals) {}
+
     fn paint(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals);
+
+    /// Handles events and then paints, which is how children should be drawn so their events are
+    /// seen
+    fn draw(&mut self, area: Area, scene: &mut Scene, events: &[Event], signals: &mut Signals) {
+        self.event(area, events, signals);
+        self.paint(area, scene, events, signals);
+    }
 }
@@ -6,10 +6,16 @@ pub mod scene;
 pub mod utils;
 mod vecany;
 
+// lets the derive macros refer to `tecs` from within the crate's own tests
+#[cfg(test)]
+extern crate self as tecs;
+
 use serde::{
     de::DeserializeSeed,
     Deserialize, Serialize,
 };
+use log::warn;
+use utils::Name;
 use vecany::VecAny;
 
 use std::{
@@ -24,14 +30,17 @@ use std::{
 pub trait System<E> {
     fn event(&self, _world: &World<E>, _event: &E) {}
     fn tick(&self, _world: &World<E>) {}
+    fn fixed_tick(&self, _world: &World<E>) {}
 }
 
 pub trait SystemMut<E> {
     fn tick(&mut self, _world: &World<E>) {}
+    fn fixed_tick(&mut self, _world: &World<E>) {}
 }
 
 struct Handler<T>(T);
 struct Ticker<T>(T);
+struct FixedTicker<T>(T);
 
 impl<E, T: Fn(&World<E>, &E)> System<E> for Handler<T> {
     fn event(&self, world: &World<E>, event: &E) {
@@ -45,25 +54,36 @@ impl<E, T: Fn(&World<E>)> System<E> for Ticker<T> {
     }
 }
 
+impl<E, T: Fn(&World<E>)> System<E> for FixedTicker<T> {
+    fn fixed_tick(&self, world: &World<E>) {
+        self.0(world)
+    }
+}
+
 impl<E, T: SystemMut<E>> System<E> for RefCell<T> {
     fn tick(&self, world: &World<E>) {
         self.borrow_mut().tick(world)
     }
+
+    fn fixed_tick(&self, world: &World<E>) {
+        self.borrow_mut().fixed_tick(world)
+    }
 }
 
 pub trait Archetype: Any {
     fn columns() -> Vec<TypeId>;
     fn add(self, table: &Table) -> RowIndex;
     fn remove(table: &Table, row: RowIndex);
-    fn get(table: &Table, row: RowIndex) -> Self
+    /// A copy of the entity in `row`, or `None` if there isn't one, such as for a stale row
+    fn get(table: &Table, row: RowIndex) -> Option<Self>
     where
         Self: Clone;
 
-    fn serialize(table: &Table, row: RowIndex) -> Box<dyn erased_serde::Serialize>
+    fn serialize(table: &Table, row: RowIndex) -> Option<Box<dyn erased_serde::Serialize>>
     where
         Self: Serialize + Clone,
     {
-        Box::new(Self::get(table, row)) as Box<dyn erased_serde::Serialize>
+        Self::get(table, row).map(|entity| Box::new(entity) as Box<dyn erased_serde::Serialize>)
     }
 }
 
@@ -101,27 +121,31 @@ impl Column {
     }
 
     pub fn get<T: 'static>(&self, index: RowIndex) -> Option<&T> {
-        self.data.downcast_ref()?.get(index.0 as usize)
+        self.data.get(index.0 as usize)
     }
 
     pub fn get_mut<T: 'static>(&mut self, index: RowIndex) -> Option<&mut T> {
-        self.data.downcast_mut()?.get_mut(index.0 as usize)
+        self.data.get_mut(index.0 as usize)
     }
 }
 
 pub struct Table {
     pub length: Cell<usize>,
+    // the archetype's type name, for debugging
+    name: &'static str,
     columns: Vec<(TypeId, RefCell<Column>)>,
-    pub(crate) serialize: Option<fn(&Self, RowIndex) -> Box<dyn erased_serde::Serialize>>,
+    pub(crate) serialize: Option<fn(&Self, RowIndex) -> Option<Box<dyn erased_serde::Serialize>>>,
     pub(crate) deserialize: Option<
         fn(&Self, &mut dyn erased_serde::Deserializer<'_>) -> Result<RowIndex, erased_serde::Error>,
     >,
+    clear: fn(&Self),
 }
 
 impl Table {
     pub fn new_unsaved<T: Archetype>() -> Self {
         Self {
             length: Cell::new(0),
+            name: std::any::type_name::<T>(),
             columns: T::columns()
                 .iter()
                 .cloned()
@@ -129,12 +153,14 @@ impl Table {
                 .collect(),
             serialize: None,
             deserialize: None,
+            clear: Self::clear_rows::<T>,
         }
     }
 
     pub fn new<T: Archetype + Serialize + for<'a> Deserialize<'a> + Clone>() -> Self {
         Self {
             length: Cell::new(0),
+            name: std::any::type_name::<T>(),
             columns: T::columns()
                 .iter()
                 .cloned()
@@ -146,9 +172,22 @@ impl Table {
                     <T as Deserialize>::deserialize(deserializer).map(|entity| entity.add(table))
                 },
             ),
+            clear: Self::clear_rows::<T>,
         }
     }
 
+    fn clear_rows<T: Archetype>(&self) {
+        // removing from the end never has to move another row into the gap
+        (0..self.len())
+            .rev()
+            .for_each(|row| T::remove(self, RowIndex(row as u32)));
+    }
+
+    /// Removes every row, leaving the table empty
+    pub fn clear(&self) {
+        (self.clear)(self)
+    }
+
     pub fn columns_mut(&self) -> impl Iterator<Item = RefMut<'_, Column>> {
         self.columns.iter().map(|(_, column)| column.borrow_mut())
     }
@@ -161,16 +200,24 @@ impl Table {
         self.columns.iter().any(|(ty, _)| *ty == TypeId::of::<T>())
     }
 
+    /// Whether the table has a column of `T` that really holds `T`s. A column that is already
+    /// mutably borrowed is taken to, so that querying it panics on the borrow like any other
+    /// borrow conflict rather than leaving it out.
+    pub fn holds<T: 'static>(&self) -> bool {
+        self.columns.iter().any(|(ty, column)| {
+            *ty == TypeId::of::<T>()
+                && column
+                    .try_borrow()
+                    .map_or(true, |column| column.data.ty() == *ty)
+        })
+    }
+
     pub fn column<T: 'static>(&self) -> Option<Ref<'_, [T]>> {
         self.columns
             .iter()
             .find(|(ty, _)| *ty == TypeId::of::<T>())
-            .map(|(_, column)| {
-                Ref::map(column.borrow(), |column| {
-                    column.data.downcast_ref::<T>().unwrap_or_else(|| {
-                        panic!("Failed to downcast {}", std::any::type_name::<T>())
-                    })
-                })
+            .and_then(|(_, column)| {
+                Ref::filter_map(column.borrow(), |column| column.data.downcast_ref::<T>()).ok()
             })
     }
 
@@ -211,6 +258,23 @@ impl<'a, T> Columns<'a, T> {
     pub fn iter(&self) -> impl Iterator<Item = &T> {
         self.columns.iter().flat_map(|column| column.deref())
     }
+
+    /// Pairs these items with another component from the same query, a table at a time so each
+    /// pair always belongs to the same entity
+    pub fn zip<'b, U>(&'b self, other: &'b Columns<'a, U>) -> impl Iterator<Item = (&'b T, &'b U)> {
+        assert_eq!(
+            self.columns.len(),
+            other.columns.len(),
+            "Zipping columns from different queries"
+        );
+        self.columns
+            .iter()
+            .zip(other.columns.iter())
+            .flat_map(|(a, b)| {
+                assert_eq!(a.len(), b.len(), "Zipping columns from different queries");
+                a.iter().zip(b.iter())
+            })
+    }
 }
 
 pub struct ColumnsMut<'a, T> {
@@ -230,6 +294,26 @@ impl<'a, T> ColumnsMut<'a, T> {
         self.columns.iter().flat_map(|column| column.deref())
     }
 
+    /// Pairs these items with another component from the same query, a table at a time so each
+    /// pair always belongs to the same entity
+    pub fn zip<'b, U>(
+        &'b mut self,
+        other: &'b mut ColumnsMut<'a, U>,
+    ) -> impl Iterator<Item = (&'b mut T, &'b mut U)> + use<'a, 'b, T, U> {
+        assert_eq!(
+            self.columns.len(),
+            other.columns.len(),
+            "Zipping columns from different queries"
+        );
+        self.columns
+            .iter_mut()
+            .zip(other.columns.iter_mut())
+            .flat_map(|(a, b)| {
+                assert_eq!(a.len(), b.len(), "Zipping columns from different queries");
+                a.iter_mut().zip(b.iter_mut())
+            })
+    }
+
     pub fn for_each<F: FnMut(&mut T)>(&mut self, f: F) {
         self.columns
             .iter_mut()
@@ -266,18 +350,24 @@ impl<'a, T> ColumnsMut<'a, T> {
             .and_then(|column| column.first_mut())
     }
 
+    /// Finds an item by its position in iteration order, skipping over whole columns rather
+    /// than walking every item before it
     pub fn get_mut(&mut self, mut index: usize) -> Option<&mut T> {
-        self.columns
-            .iter_mut()
-            .flat_map(|column| column.deref_mut())
-            .find(|_| {
-                if index == 0 {
-                    true
-                } else {
-                    index -= 1;
-                    false
-                }
-            })
+        for column in self.columns.iter_mut() {
+            if index < column.len() {
+                return column.get_mut(index);
+            }
+            index -= column.len();
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.iter().map(|column| column.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.iter().all(|column| column.is_empty())
     }
 }
 
@@ -347,6 +437,9 @@ impl_query_one!(A B C D E F);
 impl_query_one!(A B C D E F G);
 impl_query_one!(A B C D E F G H);
 
+/// What can be asked for from [`World::query`]. Every part of a tuple query is collected from the
+/// same tables in the same order, and the filter makes sure each of those tables has all of the
+/// parts, so the nth item of each part always belongs to the same entity.
 pub trait Query<E> {
     type Output<'a>;
 
@@ -360,8 +453,10 @@ pub trait Query<E> {
 impl<T: 'static, E> Query<E> for &'_ T {
     type Output<'a> = Columns<'a, T>;
 
+    // columns that don't hold `T` are left out here rather than in `data`, so every part of a
+    // tuple query leaves out the same tables
     fn filter(table: &(TypeId, &Table)) -> bool {
-        table.1.has_column::<T>()
+        table.1.holds::<T>()
     }
 
     fn data<'a>(
@@ -370,7 +465,11 @@ impl<T: 'static, E> Query<E> for &'_ T {
     ) -> Self::Output<'a> {
         tables
             .iter()
-            .map(|(_, table)| table.column().unwrap())
+            .map(|(_, table)| {
+                table
+                    .column()
+                    .expect("the filter checked the column holds T")
+            })
             .collect()
     }
 }
@@ -379,7 +478,7 @@ impl<T: 'static, E> Query<E> for &'_ mut T {
     type Output<'a> = ColumnsMut<'a, T>;
 
     fn filter(table: &(TypeId, &Table)) -> bool {
-        table.1.has_column::<T>()
+        table.1.holds::<T>()
     }
 
     fn data<'a>(
@@ -388,7 +487,11 @@ impl<T: 'static, E> Query<E> for &'_ mut T {
     ) -> Self::Output<'a> {
         tables
             .iter()
-            .map(|(_, table)| table.column_mut().unwrap())
+            .map(|(_, table)| {
+                table
+                    .column_mut()
+                    .expect("the filter checked the column holds T")
+            })
             .collect()
     }
 }
@@ -554,25 +657,35 @@ impl<E> Query<E> for EntityId {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct EntityId(u64);
 
 pub struct World<E> {
     next_id: Cell<u64>,
     entities: RefCell<HashMap<EntityId, (TypeId, RowIndex)>>,
+    // the entities with a `Name` component, in the order they were spawned
+    names: RefCell<HashMap<String, Vec<EntityId>>>,
     archetypes: HashMap<TypeId, Table>,
     systems: Vec<Rc<dyn System<E>>>,
     resources: HashMap<TypeId, Rc<RefCell<dyn Any>>>,
+    deferred: RefCell<Vec<Deferred<E>>>,
+    remove_hooks: HashMap<TypeId, Vec<RemoveHook<E>>>,
 }
 
+type Deferred<E> = Box<dyn FnOnce(&World<E>)>;
+type RemoveHook<E> = Box<dyn Fn(&World<E>, EntityId, &Table, RowIndex)>;
+
 impl<E> Default for World<E> {
     fn default() -> Self {
         Self {
             next_id: Cell::new(0),
             entities: RefCell::new(HashMap::new()),
+            names: RefCell::new(HashMap::new()),
             archetypes: HashMap::new(),
             systems: Vec::new(),
             resources: HashMap::new(),
+            deferred: RefCell::new(Vec::new()),
+            remove_hooks: HashMap::new(),
         }
     }
 }
@@ -606,12 +719,70 @@ impl<E> World<E> {
         self
     }
 
+    /// Adds a ticker that runs at a fixed rate through [`World::fixed_tick`] rather than every
+    /// frame
+    pub fn with_fixed_ticker<T: Fn(&World<E>) + 'static>(mut self, ticker: T) -> Self {
+        self.systems.push(Rc::new(FixedTicker(ticker)));
+        self
+    }
+
     pub fn with_resource<T: Any>(mut self, resource: T) -> Self {
         self.resources
             .insert(TypeId::of::<T>(), Rc::new(RefCell::new(resource)));
         self
     }
 
+    /// Runs `hook` with an entity's `T` just before it is despawned, including when it is
+    /// cleared away, for cleaning up anything the component holds onto elsewhere. The entity can
+    /// still be looked at, but the hook shouldn't spawn or despawn anything itself, so changes
+    /// like that need to be deferred.
+    pub fn with_remove_hook<T: 'static, F: Fn(&World<E>, EntityId, &T) + 'static>(
+        mut self,
+        hook: F,
+    ) -> Self {
+        self.remove_hooks
+            .entry(TypeId::of::<T>())
+            .or_default()
+            .push(Box::new(move |world, id, table, row| {
+                let Some(column) = table.column::<T>() else {
+                    return;
+                };
+                if let Some(component) = column.get(row.0 as usize) {
+                    hook(world, id, component);
+                }
+            }));
+        self
+    }
+
+    /// Runs the remove hooks for every component of an entity that is about to be despawned
+    fn run_remove_hooks(&self, id: EntityId, table: &Table, row: RowIndex) {
+        table
+            .columns
+            .iter()
+            .filter_map(|(ty, _)| self.remove_hooks.get(ty))
+            .flatten()
+            .for_each(|hook| hook(self, id, table, row));
+    }
+
+    /// Runs the remove hooks of every entity matching `remove`, before they are cleared away
+    fn run_clear_hooks(&self, remove: impl Fn(TypeId) -> bool) {
+        if self.remove_hooks.is_empty() {
+            return;
+        }
+        let removing = self
+            .entities
+            .borrow()
+            .iter()
+            .filter(|(_, (ty, _))| remove(*ty))
+            .map(|(id, (ty, row))| (*id, *ty, *row))
+            .collect::<Vec<_>>();
+        removing.into_iter().for_each(|(id, ty, row)| {
+            if let Some(table) = self.archetypes.get(&ty) {
+                self.run_remove_hooks(id, table, row);
+            }
+        });
+    }
+
     pub fn register<T: Archetype + Serialize + for<'a> Deserialize<'a> + Clone>(mut self) -> Self {
         self.archetypes.insert(TypeId::of::<T>(), Table::new::<T>());
         self
@@ -630,15 +801,44 @@ impl<E> World<E> {
 
         let store = self.archetypes.get(&TypeId::of::<T>()).unwrap();
         entity.add(store);
-        self.entities.borrow_mut().insert(
-            EntityId(self.next_id.get()),
-            (TypeId::of::<T>(), RowIndex(store.len() as u32 - 1)),
-        );
+        let id = EntityId(self.next_id.get());
+        let row = RowIndex(store.len() as u32 - 1);
+        self.entities
+            .borrow_mut()
+            .insert(id, (TypeId::of::<T>(), row));
+        self.index_name(id, store, row);
         self.next_id.set(self.next_id.get() + 1);
-        EntityId(self.next_id.get() - 1)
+        id
+    }
+
+    /// Adds an entity to the name index if its archetype has a [`Name`]
+    fn index_name(&self, id: EntityId, table: &Table, row: RowIndex) {
+        let Some(names) = table.column::<Name>() else {
+            return;
+        };
+        if let Some(name) = names.get(row.0 as usize) {
+            self.names
+                .borrow_mut()
+                .entry(name.0.clone())
+                .or_default()
+                .push(id);
+        }
+    }
+
+    fn unindex_names(&self, ids: &[EntityId]) {
+        let mut names = self.names.borrow_mut();
+        names
+            .values_mut()
+            .for_each(|named| named.retain(|id| !ids.contains(id)));
+        names.retain(|_, named| !named.is_empty());
     }
 
     pub fn despawn<T: Archetype + 'static>(&self, entity: EntityId) {
+        // the hooks run while the entity can still be looked up
+        if let Some((table, row)) = self.table_of(entity) {
+            self.run_remove_hooks(entity, table, row);
+        }
+
         let mut entities = self.entities.borrow_mut();
         let Some((table_id, row)) = entities.remove(&entity) else {
             return;
@@ -647,6 +847,7 @@ impl<E> World<E> {
         if table_id != TypeId::of::<T>() {
             panic!("Despawn archetype mismatch")
         }
+        self.unindex_names(&[entity]);
 
         let Some(table) = self.archetypes.get(&table_id) else {
             return;
@@ -658,6 +859,85 @@ impl<E> World<E> {
             .map(|(_, r)| *r = row);
     }
 
+    /// Whether an entity is still alive, as ids can be held onto after it has been despawned
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.entities.borrow().contains_key(&id)
+    }
+
+    /// The archetype an entity was spawned as, if it is still alive
+    pub fn archetype_of(&self, id: EntityId) -> Option<TypeId> {
+        self.entities.borrow().get(&id).map(|(ty, _)| *ty)
+    }
+
+    /// The first entity spawned with a [`Name`] of `name` that is still alive
+    pub fn find_by_name(&self, name: &str) -> Option<EntityId> {
+        self.find_all_by_name(name).first().copied()
+    }
+
+    /// Every living entity with a [`Name`] of `name`, oldest first. Names changed in place after
+    /// spawning aren't indexed, though an entity that has been renamed away is left out.
+    pub fn find_all_by_name(&self, name: &str) -> Vec<EntityId> {
+        let Some(named) = self.names.borrow().get(name).cloned() else {
+            return Vec::new();
+        };
+        named
+            .into_iter()
+            .filter(|id| {
+                self.get_component::<Name>(*id)
+                    .is_some_and(|current| current.0 == name)
+            })
+            .collect()
+    }
+
+    /// Every living entity, oldest first
+    pub fn entity_ids(&self) -> Vec<EntityId> {
+        let mut ids = self.entities.borrow().keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| id.0);
+        ids
+    }
+
+    /// The type name of an entity's archetype, for debugging
+    pub fn archetype_name(&self, id: EntityId) -> Option<&'static str> {
+        self.table_of(id).map(|(table, _)| table.name)
+    }
+
+    /// A copy of an entity for serde to serialize, which is only possible for archetypes that
+    /// were registered to be saved
+    pub fn serialize_entity(&self, id: EntityId) -> Option<Box<dyn erased_serde::Serialize>> {
+        let (table, row) = self.table_of(id)?;
+        table.serialize.and_then(|serialize| serialize(table, row))
+    }
+
+    /// Despawns every entity of one archetype, leaving the others alone
+    pub fn clear_archetype<T: Archetype>(&self) {
+        let Some(table) = self.archetypes.get(&TypeId::of::<T>()) else {
+            return;
+        };
+        self.run_clear_hooks(|ty| ty == TypeId::of::<T>());
+        table.clear();
+        let mut removed = Vec::new();
+        self.entities.borrow_mut().retain(|id, (ty, _)| {
+            let keep = *ty != TypeId::of::<T>();
+            if !keep {
+                removed.push(*id);
+            }
+            keep
+        });
+        self.unindex_names(&removed);
+    }
+
+    /// Despawns every entity, so ids start again from the beginning. Resources, systems and
+    /// registered archetypes are kept.
+    pub fn clear(&self) {
+        self.run_clear_hooks(|_| true);
+        self.archetypes.values().for_each(Table::clear);
+        self.entities.borrow_mut().clear();
+        self.names.borrow_mut().clear();
+        self.next_id.set(0);
+    }
+
+    /// Gets the components of every entity with all of the parts of `Q`, lined up so that the
+    /// parts of a tuple can be zipped together entity by entity
     pub fn query<Q: Query<E>>(&self) -> Q::Output<'_> {
         Q::data(
             &self.entities.borrow(),
@@ -681,21 +961,24 @@ impl<E> World<E> {
         )
     }
 
+    /// The table an entity's components are in, which is missing if its archetype was never
+    /// registered
+    fn table_of(&self, id: EntityId) -> Option<(&Table, RowIndex)> {
+        let (ty, row) = self.entities.borrow().get(&id).copied()?;
+        let Some(table) = self.archetypes.get(&ty) else {
+            warn!("{id:?} is of an unregistered archetype");
+            return None;
+        };
+        Some((table, row))
+    }
+
     pub fn get_component<T: 'static>(&self, id: EntityId) -> Option<Ref<'_, T>> {
-        let (table, row) = self.entities.borrow().get(&id).copied()?;
-        let table = self
-            .archetypes
-            .get(&table)
-            .expect("Using unregistered archetype");
+        let (table, row) = self.table_of(id)?;
         Ref::filter_map(table.column::<T>()?, |column| column.get(row.0 as usize)).ok()
     }
 
     pub fn get_component_mut<T: 'static>(&self, id: EntityId) -> Option<RefMut<'_, T>> {
-        let (table, row) = self.entities.borrow().get(&id).copied()?;
-        let table = self
-            .archetypes
-            .get(&table)
-            .expect("Using unregistered archetype");
+        let (table, row) = self.table_of(id)?;
         RefMut::filter_map(table.column_mut::<T>()?, |column| {
             column.get_mut(row.0 as usize)
         })
@@ -726,7 +1009,33 @@ impl<E> World<E> {
         self.systems
             .clone()
             .into_iter()
-            .for_each(|system| system.tick(self))
+            .for_each(|system| system.tick(self));
+        self.flush();
+    }
+
+    pub fn fixed_tick(&self) {
+        self.systems
+            .clone()
+            .into_iter()
+            .for_each(|system| system.fixed_tick(self));
+        self.flush();
+    }
+
+    /// Queues a change to run once every system has finished the current tick, for changes such
+    /// as despawning that shouldn't happen while other systems may still be using the entity
+    pub fn defer<F: FnOnce(&World<E>) + 'static>(&self, f: F) {
+        self.deferred.borrow_mut().push(Box::new(f))
+    }
+
+    /// Runs every deferred change, including any queued by the changes themselves
+    pub fn flush(&self) {
+        loop {
+            let deferred = std::mem::take(&mut *self.deferred.borrow_mut());
+            if deferred.is_empty() {
+                break;
+            }
+            deferred.into_iter().for_each(|f| f(self));
+        }
     }
 
     pub fn submit(&self, event: E) {
@@ -736,3 +1045,408 @@ impl<E> World<E> {
             .for_each(|system| system.event(self, &event))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    pub fn test_query_mismatched_column() {
+        // a column claiming to hold one type while holding another
+        let table = Table {
+            length: Cell::new(0),
+            name: "Mismatched",
+            columns: vec![(
+                TypeId::of::<u32>(),
+                RefCell::new(Column::new(TypeId::of::<u64>())),
+            )],
+            serialize: None,
+            deserialize: None,
+            clear: |_| (),
+        };
+        assert!(table.column::<u32>().is_none());
+        assert!(table.column_mut::<u32>().is_none());
+        assert!(table.column::<i8>().is_none());
+
+        let mut world = World::<()>::new();
+        world.archetypes.insert(TypeId::of::<()>(), table);
+        assert_eq!(world.query::<&u32>().iter().count(), 0);
+        assert_eq!(world.query::<&mut u32>().iter().count(), 0);
+        assert_eq!(world.query::<&i8>().iter().count(), 0);
+    }
+
+    #[test]
+    pub fn test_tuple_query_mismatched_column() {
+        #[derive(Archetype)]
+        struct Pair {
+            id: u32,
+            tens: u64,
+        }
+
+        let world = World::<()>::new().register_unsaved::<Pair>();
+        world.spawn(Pair { id: 1, tens: 10 });
+        // a table whose `u32` column holds `u64`s, but with a `u64` that could be queried
+        let mut tens = Column::new(TypeId::of::<u64>());
+        tens.data.push(20_u64);
+        let mut mismatched = Column::new(TypeId::of::<u64>());
+        mismatched.data.push(2_u64);
+        let table = Table {
+            length: Cell::new(1),
+            name: "Mismatched",
+            columns: vec![
+                (TypeId::of::<u32>(), RefCell::new(mismatched)),
+                (TypeId::of::<u64>(), RefCell::new(tens)),
+            ],
+            serialize: None,
+            deserialize: None,
+            clear: |_| (),
+        };
+        let mut world = world;
+        world.archetypes.insert(TypeId::of::<()>(), table);
+
+        // the whole table is left out, not just the part that doesn't match
+        let (ids, tens) = world.query::<(&u32, &u64)>();
+        assert_eq!(ids.iter().count(), tens.iter().count());
+        assert!(ids.zip(&tens).all(|(id, tens)| *id as u64 * 10 == *tens));
+        assert_eq!(world.query::<&u64>().iter().count(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    pub fn test_query_borrow_conflict() {
+        #[derive(Archetype)]
+        struct Single {
+            id: u32,
+        }
+
+        let world = World::<()>::new().register_unsaved::<Single>();
+        world.spawn(Single { id: 0 });
+        let _borrowed = world.query::<&mut u32>();
+        world.query::<&u32>();
+    }
+
+    #[test]
+    pub fn test_columns_mut_get() {
+        let a = RefCell::new(VecAny::from_vec(vec![0_u32, 1, 2]));
+        let b = RefCell::new(VecAny::new::<u32>());
+        let c = RefCell::new(VecAny::from_vec(vec![3_u32, 4]));
+        let mut columns = [&a, &b, &c]
+            .into_iter()
+            .map(|data| RefMut::map(data.borrow_mut(), |data| data.downcast_mut().unwrap()))
+            .collect::<ColumnsMut<u32>>();
+
+        assert_eq!(columns.len(), 5);
+        let flattened = columns.iter().copied().collect::<Vec<u32>>();
+        (0..columns.len())
+            .for_each(|i| assert_eq!(columns.get_mut(i).copied(), Some(flattened[i])));
+        assert!(columns.get_mut(5).is_none());
+
+        *columns.get_mut(3).unwrap() = 10;
+        drop(columns);
+        assert_eq!(c.borrow().downcast_ref::<u32>().unwrap(), [10, 4]);
+    }
+
+    #[test]
+    pub fn test_columns_zip() {
+        // two tables, each with an id and a count per entity
+        let ids = [vec![0_u32, 1], vec![2]].map(|ids| RefCell::new(VecAny::from_vec(ids)));
+        let counts =
+            [vec![0_u8, 10], vec![20]].map(|counts| RefCell::new(VecAny::from_vec(counts)));
+        fn borrow<T: 'static>(data: &RefCell<VecAny>) -> RefMut<'_, [T]> {
+            RefMut::map(data.borrow_mut(), |data| data.downcast_mut().unwrap())
+        }
+        let mut ids = ids.iter().map(borrow).collect::<ColumnsMut<u32>>();
+        let mut counts = counts.iter().map(borrow).collect::<ColumnsMut<u8>>();
+
+        ids.zip(&mut counts)
+            .for_each(|(id, count)| *count += *id as u8);
+        assert_eq!(counts.iter().copied().collect::<Vec<u8>>(), [0, 11, 22]);
+    }
+
+    #[test]
+    pub fn test_query_alignment() {
+        #[derive(Archetype)]
+        struct Pair {
+            id: u32,
+            tens: u64,
+        }
+
+        // the same components in a different order, alongside another
+        #[derive(Archetype)]
+        struct Triple {
+            flag: bool,
+            tens: u64,
+            id: u32,
+        }
+
+        let world = World::<()>::new()
+            .register_unsaved::<Pair>()
+            .register_unsaved::<Triple>();
+        let spawned = (0..20_u32)
+            .map(|id| {
+                let tens = id as u64 * 10;
+                let entity = if id % 3 == 0 {
+                    world.spawn(Triple {
+                        flag: true,
+                        tens,
+                        id,
+                    })
+                } else {
+                    world.spawn(Pair { id, tens })
+                };
+                (entity, id)
+            })
+            .collect::<HashMap<EntityId, u32>>();
+        // removing an entity moves another into its row
+        let removed = spawned.iter().find(|(_, id)| **id == 4).unwrap().0;
+        world.despawn::<Pair>(*removed);
+
+        let (entities, ids, tens) = world.query::<(EntityId, &u32, &u64)>();
+        assert_eq!(entities.len(), 19);
+        entities
+            .iter()
+            .zip(ids.iter())
+            .zip(tens.iter())
+            .for_each(|((entity, id), tens)| {
+                assert_eq!(spawned[entity], *id);
+                assert_eq!(*id as u64 * 10, *tens);
+            });
+        assert!(ids.zip(&tens).all(|(id, tens)| *id as u64 * 10 == *tens));
+    }
+
+    #[test]
+    pub fn test_clear() {
+        #[derive(Archetype)]
+        struct Pair {
+            id: u32,
+            tens: u64,
+        }
+
+        #[derive(Archetype)]
+        struct Single {
+            id: u32,
+        }
+
+        let world = World::<()>::new()
+            .register_unsaved::<Pair>()
+            .register_unsaved::<Single>();
+        (0..3).for_each(|id| {
+            world.spawn(Pair { id, tens: 0 });
+        });
+        let singles = (3..5)
+            .map(|id| world.spawn(Single { id }))
+            .collect::<Vec<EntityId>>();
+
+        world.clear_archetype::<Pair>();
+        assert_eq!(world.query::<&u64>().iter().count(), 0);
+        assert_eq!(world.query::<EntityId>(), singles);
+        assert_eq!(
+            world.query::<&u32>().iter().copied().collect::<Vec<_>>(),
+            [3, 4]
+        );
+        assert_eq!(*world.get_component::<u32>(singles[1]).unwrap(), 4);
+
+        world.clear();
+        assert!(world.query::<EntityId>().is_empty());
+        assert!(world.get_component::<u32>(singles[0]).is_none());
+        assert_eq!(world.spawn(Single { id: 0 }), EntityId(0));
+    }
+
+    #[test]
+    pub fn test_remove_hook() {
+        // counts how many times it has been dropped
+        #[derive(Clone)]
+        struct Dropped(Rc<Cell<u32>>);
+
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+
+        #[derive(Archetype)]
+        struct Handle {
+            id: u32,
+            dropped: Dropped,
+        }
+
+        let removed = Rc::new(RefCell::new(Vec::new()));
+        let world = World::<()>::new().register_unsaved::<Handle>();
+        let world = {
+            let removed = removed.clone();
+            world.with_remove_hook::<u32, _>(move |world, id, component| {
+                // the entity is still there to be looked at
+                assert!(world.contains(id));
+                removed.borrow_mut().push(*component);
+            })
+        };
+        let drops = Rc::new(Cell::new(0));
+        let handles = (0..3)
+            .map(|id| {
+                world.spawn(Handle {
+                    id,
+                    dropped: Dropped(drops.clone()),
+                })
+            })
+            .collect::<Vec<EntityId>>();
+
+        world.despawn::<Handle>(handles[0]);
+        assert_eq!(*removed.borrow(), [0]);
+        assert_eq!(drops.get(), 1);
+        // despawning it again does nothing
+        world.despawn::<Handle>(handles[0]);
+        assert_eq!(*removed.borrow(), [0]);
+
+        world.clear();
+        removed.borrow_mut().sort();
+        assert_eq!(*removed.borrow(), [0, 1, 2]);
+        assert_eq!(drops.get(), 3);
+    }
+
+    #[test]
+    pub fn test_drop_components() {
+        #[derive(Archetype)]
+        struct Shared {
+            rc: Rc<()>,
+            name: Name,
+        }
+
+        let shared = Rc::new(());
+        let world = World::<()>::new().register_unsaved::<Shared>();
+        let ids = (0..3)
+            .map(|i| {
+                world.spawn(Shared {
+                    rc: shared.clone(),
+                    name: Name(format!("shared {i}")),
+                })
+            })
+            .collect::<Vec<EntityId>>();
+        assert_eq!(Rc::strong_count(&shared), 4);
+
+        world.despawn::<Shared>(ids[1]);
+        assert_eq!(Rc::strong_count(&shared), 3);
+
+        drop(world);
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    pub fn test_serialize_stale_row() {
+        #[derive(Archetype, Clone, Serialize, Deserialize)]
+        struct Saved {
+            id: u32,
+        }
+
+        let world = World::<()>::new().register::<Saved>();
+        let ids = (0..2)
+            .map(|id| world.spawn(Saved { id }))
+            .collect::<Vec<EntityId>>();
+        let mut scene = crate::scene::Scene::default();
+        scene.from_world(&world);
+        assert!(scene
+            .save(&world, &mut serde_json::Serializer::new(Vec::new()))
+            .is_ok());
+
+        // a row past the end of the table, as if the entity had been moved without updating it
+        world.entities.borrow_mut().get_mut(&ids[1]).unwrap().1 = RowIndex(5);
+        assert!(world.serialize_entity(ids[0]).is_some());
+        assert!(world.serialize_entity(ids[1]).is_none());
+        assert!(scene
+            .save(&world, &mut serde_json::Serializer::new(Vec::new()))
+            .is_err());
+    }
+
+    #[test]
+    pub fn test_contains() {
+        #[derive(Archetype)]
+        struct Single {
+            id: u32,
+        }
+
+        let world = World::<()>::new().register_unsaved::<Single>();
+        let first = world.spawn(Single { id: 0 });
+        let second = world.spawn(Single { id: 1 });
+        assert!(world.contains(first));
+        assert_eq!(world.archetype_of(first), Some(TypeId::of::<Single>()));
+
+        world.despawn::<Single>(first);
+        assert!(!world.contains(first));
+        assert_eq!(world.archetype_of(first), None);
+        assert!(world.contains(second));
+    }
+
+    #[test]
+    pub fn test_find_by_name() {
+        #[derive(Archetype)]
+        struct Named {
+            name: Name,
+        }
+
+        let world = World::<()>::new().register_unsaved::<Named>();
+        let ore = world.spawn(Named {
+            name: Name(String::from("Copper Ore")),
+        });
+        let other = world.spawn(Named {
+            name: Name(String::from("Copper Ore")),
+        });
+        world.spawn(Named {
+            name: Name(String::from("Target Dummy")),
+        });
+        assert_eq!(world.find_by_name("Copper Ore"), Some(ore));
+        assert_eq!(world.find_all_by_name("Copper Ore"), vec![ore, other]);
+        assert_eq!(world.find_by_name("Tree"), None);
+
+        world.despawn::<Named>(ore);
+        assert_eq!(world.find_by_name("Copper Ore"), Some(other));
+        world.despawn::<Named>(other);
+        assert_eq!(world.find_by_name("Copper Ore"), None);
+
+        // renaming an entity takes it out of its old name's results
+        let dummy = world.find_by_name("Target Dummy").unwrap();
+        world.get_component_mut::<Name>(dummy).unwrap().0 = String::from("Training Dummy");
+        assert_eq!(world.find_by_name("Target Dummy"), None);
+    }
+
+    #[test]
+    pub fn test_inspect() {
+        #[derive(Archetype, Clone, Serialize, Deserialize)]
+        struct Saved {
+            id: u32,
+        }
+
+        #[derive(Archetype)]
+        struct Unsaved {
+            id: u32,
+        }
+
+        let world = World::<()>::new()
+            .register::<Saved>()
+            .register_unsaved::<Unsaved>();
+        let unsaved = world.spawn(Unsaved { id: 0 });
+        let saved = world.spawn(Saved { id: 1 });
+        assert_eq!(world.entity_ids(), vec![unsaved, saved]);
+
+        assert!(world.archetype_name(saved).unwrap().ends_with("Saved"));
+        assert!(world.serialize_entity(saved).is_some());
+        assert!(world.serialize_entity(unsaved).is_none());
+
+        world.despawn::<Saved>(saved);
+        assert_eq!(world.entity_ids(), vec![unsaved]);
+        assert_eq!(world.archetype_name(saved), None);
+    }
+
+    #[test]
+    pub fn test_get_unregistered() {
+        struct Unregistered;
+
+        let world = World::<()>::new();
+        let id = EntityId(0);
+        world
+            .entities
+            .borrow_mut()
+            .insert(id, (TypeId::of::<Unregistered>(), RowIndex(0)));
+        assert!(world.get_component::<u32>(id).is_none());
+        assert!(world.get_component_mut::<u32>(id).is_none());
+    }
+}
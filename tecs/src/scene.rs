@@ -1,31 +1,60 @@
 use std::{
     any::TypeId,
-    collections::HashMap,
-    hash::{DefaultHasher, Hash, Hasher},
+    collections::{HashMap, HashSet},
+    io::{Read, Write},
 };
 
 use erased_serde::Serialize;
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use serde::{de::{DeserializeSeed, Visitor}, Deserializer};
 
 use crate::{DeserializeArchetype, EntityId, RowIndex, Table, World};
 
+/// How a scene is written out by [`Scene::to_bytes`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Readable and editable by hand, for debugging
+    Json,
+    /// Compact, but only readable by a build with the same archetypes
+    Binary,
+    /// [`Format::Binary`] gzipped, for shipping large scenes
+    Compressed,
+}
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A set of entities to save together, which can be the whole world through
+/// [`Scene::from_world`] or just a selection of it
 #[derive(Clone, Default)]
 pub struct Scene {
     entities: Vec<EntityId>,
 }
 
 impl Scene {
+    /// Adds an entity to be saved, which is only saved once however many times it is added
     pub fn add(&mut self, entity: EntityId) {
-        self.entities.push(entity)
+        if !self.entities.contains(&entity) {
+            self.entities.push(entity)
+        }
     }
 
+    /// The entities in the scene, which for a loaded scene are the ones it spawned
+    pub fn entities(&self) -> &[EntityId] {
+        &self.entities
+    }
+
+    /// Adds every entity in the world
     pub fn from_world<E>(&mut self, world: &World<E>) {
-        world
-            .entities
-            .borrow()
-            .keys()
-            .copied()
-            .for_each(|id| self.add(id));
+        // the world's entities are unique already, so only the ones added before are checked
+        let added = self.entities.iter().copied().collect::<HashSet<_>>();
+        self.entities.extend(
+            world
+                .entities
+                .borrow()
+                .keys()
+                .copied()
+                .filter(|id| !added.contains(id)),
+        );
     }
 
     pub fn save<E, S: serde::Serializer>(
@@ -47,32 +76,79 @@ impl Scene {
                 }
             });
 
-        let scene: HashMap<u64, Vec<Box<dyn Serialize>>> = entity_map
+        let scene = entity_map
             .into_iter()
             .map(|(id, rows)| {
                 let table = world.archetypes.get(&id).unwrap();
                 let rows = rows
                     .into_iter()
-                    .map(|row| (table.serialize.unwrap())(table, row))
-                    .collect::<Vec<_>>();
+                    .map(|row| {
+                        let Some(serialize) = table.serialize else {
+                            return Err(serde::ser::Error::custom(format!(
+                                "{} isn't registered to be saved",
+                                table.name
+                            )));
+                        };
+                        serialize(table, row).ok_or_else(|| {
+                            serde::ser::Error::custom(format!(
+                                "{} has no row {}",
+                                table.name, row.0
+                            ))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, erased_serde::Error>>()?;
 
-                let mut hasher = DefaultHasher::new();
-                id.hash(&mut hasher);
-                (hasher.finish(), rows)
+                // keyed by name rather than `TypeId`, which changes from one build to the next
+                Ok((table.name, rows))
             })
-            .collect();
+            .collect::<Result<HashMap<&str, Vec<Box<dyn Serialize>>>, erased_serde::Error>>()?;
 
         (Box::new(scene) as Box<dyn erased_serde::Serialize>).erased_serialize(&mut serializer)?;
 
         Ok(())
     }
 
+    /// Spawns the entities of a saved scene alongside whatever is already in the world, which
+    /// can be emptied with [`World::clear`] first to replace it instead
     pub fn load<'a, E, D: serde::Deserializer<'a>>(
         world: &'a World<E>,
         deserializer: D,
     ) -> Result<Self, <D as Deserializer>::Error> {
         deserializer.deserialize_map(ArchetypesSeed { world })
     }
+
+    pub fn to_bytes<E>(&self, world: &World<E>, format: Format) -> Result<Vec<u8>, Error> {
+        let mut buffer = Vec::new();
+        match format {
+            Format::Json => self.save(world, &mut serde_json::Serializer::pretty(&mut buffer))?,
+            Format::Binary => self.save(
+                world,
+                &mut bincode::Serializer::new(&mut buffer, bincode::options()),
+            )?,
+            Format::Compressed => {
+                let mut encoder = GzEncoder::new(&mut buffer, Compression::default());
+                encoder.write_all(&self.to_bytes(world, Format::Binary)?)?;
+                encoder.finish()?;
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Loads a scene written by [`Scene::to_bytes`] in the same format
+    pub fn from_bytes<E>(world: &World<E>, bytes: &[u8], format: Format) -> Result<Self, Error> {
+        Ok(match format {
+            Format::Json => Self::load(world, &mut serde_json::Deserializer::from_slice(bytes))?,
+            Format::Binary => Self::load(
+                world,
+                &mut bincode::Deserializer::from_slice(bytes, bincode::options()),
+            )?,
+            Format::Compressed => {
+                let mut decompressed = Vec::new();
+                GzDecoder::new(bytes).read_to_end(&mut decompressed)?;
+                Self::from_bytes(world, &decompressed, Format::Binary)?
+            }
+        })
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -126,35 +202,122 @@ impl<'de, E> Visitor<'de> for ArchetypesSeed<'de, E> {
         A: serde::de::MapAccess<'de>,
     {
         let mut entities: Vec<EntityId> = Vec::new();
-        while let Some(hash) = map.next_key()? {
-            let (id, table) = self
+        while let Some(name) = map.next_key::<String>()? {
+            let Some((id, table)) = self
                 .world
                 .archetypes
                 .iter()
-                .find(|(id, _)| {
-                    let mut hasher = DefaultHasher::new();
-                    id.hash(&mut hasher);
-                    let id_hash = hasher.finish();
-                    id_hash == hash
-                })
-                .expect("Missing archetype needed to deserialize scene");
+                .find(|(_, table)| table.name == name)
+            else {
+                return Err(serde::de::Error::custom(format!(
+                    "{name} isn't registered, so can't be loaded"
+                )));
+            };
 
             let seed = EntitiesSeed { table };
             let mut world = self.world.entities.borrow_mut();
             let rows = map.next_value_seed(seed)?;
             rows.into_iter().for_each(|row| {
-                world.insert(EntityId(self.world.next_id.get()), (*id, row));
-                entities.push(EntityId(self.world.next_id.get()));
+                let entity = EntityId(self.world.next_id.get());
+                world.insert(entity, (*id, row));
+                self.world.index_name(entity, table, row);
+                entities.push(entity);
                 self.world.next_id.set(self.world.next_id.get() + 1)
             })
         }
 
-        Ok(Scene {
-            entities: Vec::new(),
-        })
+        Ok(Scene { entities })
     }
 
     fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "Map of archetype hashes to entities")
+        write!(f, "Map of archetype names to entities")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::prelude::*;
+
+    #[derive(Archetype, Clone, Debug, PartialEq, Serialize, Deserialize)]
+    struct Saved {
+        id: u32,
+        name: String,
+    }
+
+    fn empty() -> World<()> {
+        World::new().register::<Saved>()
+    }
+
+    #[test]
+    pub fn test_formats() {
+        let world = empty();
+        (0..100).for_each(|id| {
+            world.spawn(Saved {
+                id,
+                name: format!("saved entity number {id}"),
+            });
+        });
+        let mut scene = Scene::default();
+        scene.from_world(&world);
+
+        let saved = |world: &World<()>| {
+            let mut saved = world
+                .query::<EntityId>()
+                .into_iter()
+                .map(|id| (*world.get_component::<u32>(id).unwrap(), id))
+                .map(|(id, entity)| {
+                    let name = world.get_component::<String>(entity).unwrap().clone();
+                    Saved { id, name }
+                })
+                .collect::<Vec<Saved>>();
+            saved.sort_by_key(|saved| saved.id);
+            saved
+        };
+
+        let sizes = [Format::Json, Format::Binary, Format::Compressed].map(|format| {
+            let bytes = scene.to_bytes(&world, format).unwrap();
+            let loaded = empty();
+            let scene = Scene::from_bytes(&loaded, &bytes, format).unwrap();
+            assert_eq!(scene.entities.len(), 100);
+            assert_eq!(saved(&loaded), saved(&world));
+            bytes.len()
+        });
+        let [json, binary, compressed] = sizes;
+        assert!(binary < json);
+        assert!(compressed < binary);
+    }
+
+    #[test]
+    pub fn test_partial() {
+        let world = empty();
+        let ids = (0..3)
+            .map(|id| {
+                world.spawn(Saved {
+                    id,
+                    name: format!("{id}"),
+                })
+            })
+            .collect::<Vec<EntityId>>();
+        let mut scene = Scene::default();
+        scene.add(ids[0]);
+        scene.add(ids[2]);
+        scene.add(ids[2]);
+
+        let bytes = scene.to_bytes(&world, Format::Json).unwrap();
+        let loaded = empty();
+        let scene = Scene::from_bytes(&loaded, &bytes, Format::Json).unwrap();
+        assert_eq!(scene.entities().len(), 2);
+        let mut saved = loaded.query::<&u32>().iter().copied().collect::<Vec<u32>>();
+        saved.sort();
+        assert_eq!(saved, [0, 2]);
+
+        // the whole world on top of some of it still only has each entity once
+        let mut scene = Scene::default();
+        scene.add(ids[1]);
+        scene.from_world(&world);
+        assert_eq!(scene.entities().len(), 3);
     }
 }
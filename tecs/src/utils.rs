@@ -30,36 +30,188 @@ impl Timer {
     }
 }
 
+/// A countdown advanced by the world's [`Clock`] through [`Cooldown::tick`], rather than by
+/// comparing against wall clock time like [`Timer`].
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Cooldown {
+    pub duration: Duration,
+    #[serde(skip)]
+    remaining: Duration,
+}
+
+impl Cooldown {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            remaining: Duration::ZERO,
+        }
+    }
+
+    pub fn start(&mut self) {
+        self.remaining = self.duration
+    }
+
+    pub fn ready(&self) -> bool {
+        self.remaining.is_zero()
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.remaining
+    }
+
+    /// The fraction of the cooldown that has elapsed, in the range [0.0, 1.0]
+    pub fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        1.0 - self.remaining.as_secs_f32() / self.duration.as_secs_f32()
+    }
+
+    /// Advances the cooldown, returning true if it expired during this step
+    pub fn advance(&mut self, delta: Duration) -> bool {
+        if self.ready() {
+            return false;
+        }
+        self.remaining = self.remaining.saturating_sub(delta);
+        self.ready()
+    }
+
+    pub fn tick<E: 'static>(world: &World<E>) {
+        if State::paused(world) {
+            return;
+        }
+        let delta = world.get::<Clock>().unwrap().delta;
+        world.query::<&mut Cooldown>().for_each(|cooldown| {
+            cooldown.advance(delta);
+        });
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Clock {
     pub delta: Duration,
+    /// Total time since the clock started, as of the start of the current frame
+    pub elapsed: Duration,
     pub start: Instant,
     last: Instant,
+    fps: f32,
 }
 
 impl Clock {
+    // weight given to the latest frame when smoothing the fps
+    const FPS_SMOOTHING: f32 = 0.1;
+
     pub fn add<E: 'static>(world: World<E>) -> World<E> {
+        let now = Instant::now();
         world
             .with_resource(Self {
                 delta: Duration::ZERO,
-                start: Instant::now(),
-                last: Instant::now(),
+                elapsed: Duration::ZERO,
+                start: now,
+                last: now,
+                fps: 0.0,
             })
             .with_ticker(Self::tick)
+            .with_ticker(Cooldown::tick)
     }
 
     pub fn tick<E>(world: &World<E>) {
         let mut clock = world.get_mut::<Clock>().unwrap();
         let now = Instant::now();
         clock.delta = now - clock.last;
+        clock.elapsed = now - clock.start;
         clock.last = now;
+
+        let fps = 1.0 / clock.delta.as_secs_f32().max(f32::EPSILON);
+        clock.fps = if clock.fps == 0.0 {
+            fps
+        } else {
+            clock.fps + (fps - clock.fps) * Self::FPS_SMOOTHING
+        };
+    }
+
+    /// The time the current frame started, so everything in a frame shares a timestamp
+    pub fn now(&self) -> Instant {
+        self.last
+    }
+
+    /// Frames per second, smoothed over recent frames
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+}
+
+/// Accumulates frame time so the simulation can be stepped at a fixed rate independent of the
+/// framerate, see [`World::fixed_tick`].
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    pub delta: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    // limits how far the simulation can fall behind, so a long frame doesn't cause a spiral of
+    // ever longer catch ups
+    const MAX_ACCUMULATED: Duration = Duration::from_millis(250);
+
+    pub fn new(rate: f32) -> Self {
+        Self {
+            delta: Duration::from_secs_f32(1.0 / rate),
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    pub fn add<E: 'static>(rate: f32) -> impl FnOnce(World<E>) -> World<E> {
+        move |world| {
+            world
+                .with_resource(Self::new(rate))
+                .with_ticker(Self::tick)
+        }
+    }
+
+    /// Stops accumulating while paused, so no fixed ticks run until the world is running again
+    pub fn tick<E>(world: &World<E>) {
+        if State::paused(world) {
+            return;
+        }
+        let delta = world.get::<Clock>().unwrap().delta;
+        let mut timestep = world.get_mut::<FixedTimestep>().unwrap();
+        timestep.accumulator = (timestep.accumulator + delta).min(Self::MAX_ACCUMULATED);
+    }
+
+    /// Consumes a single step from the accumulated time, returning false once the simulation
+    /// has caught up
+    pub fn step(&mut self) -> bool {
+        if self.accumulator < self.delta {
+            return false;
+        }
+        self.accumulator -= self.delta;
+        true
+    }
+
+    /// How far between the previous and next fixed step the current frame is, for interpolating
+    /// rendered state
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.delta.as_secs_f32()
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum State {
     Stopped,
     Running,
+    /// Everything keeps being drawn and taking input, but nothing in the world moves on
+    Paused,
+}
+
+impl State {
+    /// Whether systems that move the simulation on should skip this tick. A world without a
+    /// [`State`] is never paused.
+    pub fn paused<E>(world: &World<E>) -> bool {
+        world
+            .get::<State>()
+            .is_some_and(|state| *state == State::Paused)
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -70,3 +222,62 @@ impl Display for Name {
         write!(f, "{}", self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    pub fn test_cooldown() {
+        let mut cooldown = Cooldown::new(Duration::from_secs(2));
+        assert!(cooldown.ready());
+
+        cooldown.start();
+        assert!(!cooldown.ready());
+        assert!(!cooldown.advance(Duration::from_secs(1)));
+        assert_eq!(cooldown.progress(), 0.5);
+        assert!(cooldown.advance(Duration::from_secs(3)));
+        assert!(cooldown.ready());
+        assert!(!cooldown.advance(Duration::from_secs(1)));
+    }
+
+    #[test]
+    pub fn test_fixed_timestep() {
+        let mut timestep = FixedTimestep::new(20.0);
+        timestep.accumulator = Duration::from_millis(120);
+        assert!(timestep.step());
+        assert!(timestep.step());
+        assert!(!timestep.step());
+        assert!((timestep.alpha() - 0.4).abs() < 1e-4);
+    }
+
+    #[test]
+    pub fn test_paused() {
+        #[derive(Archetype)]
+        struct Ability {
+            cooldown: Cooldown,
+        }
+
+        let world = World::<()>::new()
+            .register_unsaved::<Ability>()
+            .with_resource(State::Paused)
+            .with(Clock::add)
+            .with(FixedTimestep::add(1000.0));
+        let mut cooldown = Cooldown::new(Duration::from_secs(60));
+        cooldown.start();
+        world.spawn(Ability { cooldown });
+
+        let remaining = || world.query::<&Cooldown>().iter().next().unwrap().remaining();
+        std::thread::sleep(Duration::from_millis(5));
+        world.tick();
+        assert_eq!(remaining(), Duration::from_secs(60));
+        assert!(!world.get_mut::<FixedTimestep>().unwrap().step());
+
+        *world.get_mut::<State>().unwrap() = State::Running;
+        std::thread::sleep(Duration::from_millis(5));
+        world.tick();
+        assert!(remaining() < Duration::from_secs(60));
+        assert!(world.get_mut::<FixedTimestep>().unwrap().step());
+    }
+}
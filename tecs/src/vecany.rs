@@ -1,10 +1,37 @@
-use std::any::TypeId;
+use std::{any::TypeId, mem::ManuallyDrop};
 
 pub struct VecAny {
     ptr: Option<*mut ()>,
     len: usize,
     cap: usize,
     ty: TypeId,
+    // frees the items as the type they really are, which is only known once something has been
+    // put in
+    drop: Option<unsafe fn(*mut (), usize, usize)>,
+}
+
+/// Rebuilds the `Vec<T>` the raw parts came from, so dropping it drops every item
+unsafe fn drop_vec<T>(ptr: *mut (), len: usize, cap: usize) {
+    drop(Vec::<T>::from_raw_parts(ptr.cast(), len, cap))
+}
+
+/// A `Vec<T>` taken out of a [`VecAny`], which is put back when this is dropped. Putting it back
+/// on drop means the `VecAny` is left holding it even if whatever was using it panics, instead of
+/// both freeing the same buffer.
+struct Taken<'a, T> {
+    vec: &'a mut VecAny,
+    data: ManuallyDrop<Vec<T>>,
+}
+
+impl<T> Drop for Taken<'_, T> {
+    fn drop(&mut self) {
+        let data = unsafe { ManuallyDrop::take(&mut self.data) };
+        let (ptr, len, cap) = data.into_raw_parts();
+        self.vec.ptr = Some(ptr.cast());
+        self.vec.len = len;
+        self.vec.cap = cap;
+        self.vec.drop = Some(drop_vec::<T>);
+    }
 }
 
 impl VecAny {
@@ -18,6 +45,7 @@ impl VecAny {
             len: 0,
             cap: 0,
             ty,
+            drop: None,
         }
     }
 
@@ -28,6 +56,7 @@ impl VecAny {
             len,
             cap,
             ty: TypeId::of::<T>(),
+            drop: Some(drop_vec::<T>),
         }
     }
 
@@ -36,16 +65,19 @@ impl VecAny {
             return;
         }
 
-        let mut data: Vec<T> = match self.ptr {
+        // nothing owns the items while they are taken out
+        let data: Vec<T> = match self.ptr.take() {
             Some(ptr) => unsafe { Vec::<T>::from_raw_parts(ptr.cast(), self.len, self.cap) },
             None => Vec::<T>::new(),
         };
-        f(&mut data);
+        self.len = 0;
+        self.cap = 0;
 
-        let (ptr, len, cap) = data.into_raw_parts();
-        self.ptr = Some(ptr.cast());
-        self.len = len;
-        self.cap = cap;
+        let mut taken = Taken {
+            vec: self,
+            data: ManuallyDrop::new(data),
+        };
+        f(&mut taken.data);
     }
 
     pub fn downcast_ref<T: 'static>(&self) -> Option<&[T]> {
@@ -70,6 +102,20 @@ impl VecAny {
         })
     }
 
+    /// The item at `index` as a `T`, or `None` if it is out of bounds or the items aren't `T`
+    pub fn get<T: 'static>(&self, index: usize) -> Option<&T> {
+        self.downcast_ref::<T>()?.get(index)
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, index: usize) -> Option<&mut T> {
+        self.downcast_mut::<T>()?.get_mut(index)
+    }
+
+    /// Iterates over the items as `T`, which is empty if they aren't `T`
+    pub fn iter<T: 'static>(&self) -> impl Iterator<Item = &T> {
+        self.downcast_ref::<T>().unwrap_or_default().iter()
+    }
+
     pub fn push<T: 'static>(&mut self, item: T) {
         self.run(|data| data.push(item))
     }
@@ -87,8 +133,21 @@ impl VecAny {
     }
 }
 
+impl Drop for VecAny {
+    fn drop(&mut self) {
+        if let (Some(ptr), Some(drop)) = (self.ptr, self.drop) {
+            unsafe { drop(ptr, self.len, self.cap) }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        rc::Rc,
+    };
+
     use super::*;
 
     #[test]
@@ -102,6 +161,26 @@ mod tests {
         assert_eq!(Some(data.as_mut_slice()), vecany.downcast_mut::<usize>());
     }
 
+    #[test]
+    pub fn test_get() {
+        let mut vecany = VecAny::from_vec(vec![0_usize, 1]);
+        assert_eq!(vecany.get::<usize>(1), Some(&1));
+        assert_eq!(vecany.get::<usize>(2), None);
+        assert_eq!(vecany.get::<u8>(0), None);
+        *vecany.get_mut::<usize>(0).unwrap() = 2;
+        assert_eq!(vecany.get::<usize>(0), Some(&2));
+        assert!(vecany.get_mut::<usize>(5).is_none());
+    }
+
+    #[test]
+    pub fn test_iter() {
+        let mut vecany = VecAny::new::<usize>();
+        vecany.push(0_usize);
+        vecany.push(1_usize);
+        assert_eq!(vecany.iter::<usize>().copied().collect::<Vec<_>>(), [0, 1]);
+        assert_eq!(vecany.iter::<u8>().count(), 0);
+    }
+
     #[test]
     pub fn test_uninit() {
         let mut vecany = VecAny::new_uninit(TypeId::of::<usize>());
@@ -112,4 +191,41 @@ mod tests {
         assert_eq!(Some(data.as_slice()), vecany.downcast_ref::<usize>());
         assert_eq!(Some(data.as_mut_slice()), vecany.downcast_mut::<usize>());
     }
+
+    #[test]
+    pub fn test_drop() {
+        let shared = Rc::new(());
+        let mut vecany = VecAny::new_uninit(TypeId::of::<Rc<()>>());
+        (0..3).for_each(|_| vecany.push(shared.clone()));
+        assert_eq!(Rc::strong_count(&shared), 4);
+
+        vecany.run::<Rc<()>>(|data| {
+            data.swap_remove(0);
+        });
+        assert_eq!(Rc::strong_count(&shared), 3);
+
+        drop(vecany);
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    pub fn test_panic_in_run() {
+        let shared = Rc::new(());
+        let mut vecany = VecAny::new_uninit(TypeId::of::<Rc<()>>());
+        (0..2).for_each(|_| vecany.push(shared.clone()));
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            vecany.run::<Rc<()>>(|data| {
+                data.push(shared.clone());
+                panic!("panicked while running");
+            })
+        }));
+        assert!(result.is_err());
+        // the items are still there, including the one pushed before the panic
+        assert_eq!(vecany.len(), 3);
+        assert_eq!(Rc::strong_count(&shared), 4);
+
+        drop(vecany);
+        assert_eq!(Rc::strong_count(&shared), 1);
+    }
 }
@@ -40,11 +40,11 @@ pub fn derive_answer_fn(input: TokenStream) -> TokenStream {
                 )*
             }
 
-            fn get(table: &tecs::Table, row: tecs::RowIndex) -> Self {
+            fn get(table: &tecs::Table, row: tecs::RowIndex) -> Option<Self> {
                 let mut columns = table.columns();
-                Self {
-                    #(#fields: columns.next().unwrap().get::<#types>(row).unwrap().clone()),*
-                }
+                Some(Self {
+                    #(#fields: columns.next()?.get::<#types>(row)?.clone()),*
+                })
             }
         }
     };
@@ -1,11 +1,17 @@
-use std::{collections::HashMap, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver, TryRecvError},
+};
 
-use anyhow::Result;
-use glam::{Vec3, Vec4};
+use anyhow::{anyhow, bail, Result};
+use glam::{Vec2, Vec3, Vec4};
 use gltf::Glb;
+use log::{debug, warn};
 use serde::{Deserialize, Serialize};
+use styx::{Font, FontSettings};
 
-use crate::renderer::Vertex;
+use crate::{renderer::Vertex, World};
 
 pub struct Mesh {
     pub vertices: Vec<Vertex>,
@@ -14,78 +20,844 @@ pub struct Mesh {
 }
 
 impl Mesh {
+    /// Loads the first mesh in a `.glb` file, or the one a [`MeshId::in_scene`] points to
     pub fn load<T: AsRef<Path>>(path: T) -> Result<Self> {
-        let model = Glb::load(&std::fs::read(path).unwrap()).unwrap();
+        let path = path.as_ref();
+        let (file, mesh) = match path.to_str().and_then(|path| path.rsplit_once('#')) {
+            Some((file, mesh)) => (Path::new(file), mesh.parse()?),
+            None => (path, 0),
+        };
+        let model = Glb::load(&std::fs::read(file)?)?;
+        if mesh >= model.gltf.meshes.len() {
+            bail!("{} has no mesh {mesh}", file.display());
+        }
+        Self::from_glb(&model, mesh)
+    }
 
-        let positions: Vec<Vec3> = bytemuck::cast_slice::<u8, f32>(
-            &model.gltf.meshes[0].primitives[0]
-                .get_attribute_data(&model, "POSITION")
-                .unwrap(),
-        )
-        .chunks(3)
-        .map(Vec3::from_slice)
-        .collect();
-
-        let normals: Vec<Vec3> = bytemuck::cast_slice::<u8, f32>(
-            &model.gltf.meshes[0].primitives[0]
-                .get_attribute_data(&model, "NORMAL")
-                .unwrap(),
-        )
-        .chunks(3)
-        .map(Vec3::from_slice)
-        .collect();
+    /// Loads the first primitive of one of the meshes in a glTF file, filling in the normals and
+    /// tangents if it doesn't have them. Fails if the mesh has no primitives or no positions.
+    pub fn from_glb(model: &Glb, mesh: usize) -> Result<Self> {
+        let Some(primitive) = model.gltf.meshes[mesh].primitives.first() else {
+            bail!("mesh {mesh} has no primitives");
+        };
+        let floats = |attribute: &str| {
+            primitive
+                .get_attribute_data(model, attribute)
+                .map(|data| bytemuck::cast_slice::<u8, f32>(&data).to_vec())
+        };
+
+        let positions: Vec<Vec3> = floats("POSITION")
+            .ok_or_else(|| anyhow!("mesh {mesh} has no positions"))?
+            .chunks(3)
+            .map(Vec3::from_slice)
+            .collect();
+
+        // without indices every three vertices are a triangle
+        let indices: Vec<u32> = primitive
+            .get_indices_data(model)
+            .unwrap_or_else(|| (0..positions.len() as u32).collect());
+
+        let normals: Vec<Vec3> = match floats("NORMAL") {
+            Some(normals) => normals.chunks(3).map(Vec3::from_slice).collect(),
+            None => generate_normals(&positions, &indices),
+        };
+
+        let tangents: Vec<(Vec3, f32)> = match floats("TANGENT") {
+            Some(tangents) => tangents
+                .chunks(4)
+                .map(|tangent| (Vec3::from_slice(tangent), tangent[3]))
+                .collect(),
+            None => {
+                let uvs = floats("TEXCOORD_0")
+                    .map(|uvs| uvs.chunks(2).map(Vec2::from_slice).collect::<Vec<_>>());
+                generate_tangents(&positions, &normals, uvs.as_deref(), &indices)
+            }
+        };
 
         let vertices: Vec<Vertex> = positions
             .into_iter()
             .zip(normals)
-            .map(|(position, normal)| Vertex { position, normal })
+            .zip(tangents)
+            .map(|((position, normal), (tangent, handedness))| Vertex {
+                position,
+                normal,
+                tangent,
+                handedness,
+            })
             .collect();
 
-        let indices: Vec<u32> = model.gltf.meshes[0].primitives[0]
-            .get_indices_data(&model)
-            .unwrap();
+        let mut welded = Mesh {
+            vertices,
+            num_indices: indices.len() as u32,
+            indices,
+        };
+        let before = welded.vertices.len();
+        welded.weld();
+        debug!(
+            "Welded mesh {} from {} to {} vertices",
+            mesh,
+            before,
+            welded.vertices.len()
+        );
+        Ok(welded)
+    }
+
+    /// Merges vertices that are exactly the same, pointing the indices at the one that is kept.
+    /// Exporters often split vertices per face, which leaves copies wherever the faces agree.
+    pub fn weld(&mut self) {
+        let mut unique: HashMap<[u32; 10], u32> = HashMap::new();
+        let mut vertices = Vec::new();
+        let remap = self
+            .vertices
+            .iter()
+            .map(|vertex| {
+                *unique.entry(bytemuck::cast(*vertex)).or_insert_with(|| {
+                    vertices.push(*vertex);
+                    vertices.len() as u32 - 1
+                })
+            })
+            .collect::<Vec<u32>>();
+        self.indices
+            .iter_mut()
+            .for_each(|index| *index = remap[*index as usize]);
+        self.vertices = vertices;
+    }
+
+    /// The distance of the furthest vertex from the origin, so a sphere of this radius around
+    /// the origin holds the whole mesh
+    pub fn radius(&self) -> f32 {
+        self.vertices
+            .iter()
+            .map(|vertex| vertex.position.length())
+            .fold(f32::EPSILON, f32::max)
+    }
 
-        Ok(Mesh {
+    /// A flat circle of radius 1 lying in the XZ plane, facing up. Each triangle is wound both
+    /// ways so it can be seen from either side.
+    pub fn disc(segments: u32) -> Self {
+        let mut vertices = vec![Vertex {
+            position: Vec3::ZERO,
+            normal: Vec3::Y,
+            tangent: Vec3::X,
+            handedness: 1.0,
+        }];
+        vertices.extend((0..segments).map(|i| {
+            let angle = i as f32 / segments as f32 * std::f32::consts::TAU;
+            Vertex {
+                position: Vec3::new(angle.cos(), 0.0, angle.sin()),
+                normal: Vec3::Y,
+                tangent: Vec3::X,
+                handedness: 1.0,
+            }
+        }));
+
+        let indices: Vec<u32> = (0..segments)
+            .flat_map(|i| {
+                let a = 1 + i;
+                let b = 1 + (i + 1) % segments;
+                [0, a, b, 0, b, a]
+            })
+            .collect();
+
+        Mesh {
             vertices,
             num_indices: indices.len() as u32,
             indices,
-        })
+        }
     }
 }
 
+/// Smooth normals for a mesh that doesn't come with any. Each face is weighted by its area, so
+/// thin slivers don't pull the normals of their corners towards them.
+fn generate_normals(positions: &[Vec3], indices: &[u32]) -> Vec<Vec3> {
+    let mut normals = vec![Vec3::ZERO; positions.len()];
+    indices.chunks_exact(3).for_each(|triangle| {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        // the length of the cross product is twice the area of the triangle
+        let normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        [a, b, c].into_iter().for_each(|i| normals[i] += normal);
+    });
+    normals
+        .into_iter()
+        .map(|normal| normal.try_normalize().unwrap_or(Vec3::Y))
+        .collect()
+}
+
+/// Tangents along the direction U increases in, and which way V increases relative to them.
+/// Without texture coordinates there is no such direction, so any along the surface is used.
+fn generate_tangents(
+    positions: &[Vec3],
+    normals: &[Vec3],
+    uvs: Option<&[Vec2]>,
+    indices: &[u32],
+) -> Vec<(Vec3, f32)> {
+    let Some(uvs) = uvs else {
+        return normals
+            .iter()
+            .map(|normal| (normal.any_orthonormal_vector(), 1.0))
+            .collect();
+    };
+
+    let mut tangents = vec![Vec3::ZERO; positions.len()];
+    let mut bitangents = vec![Vec3::ZERO; positions.len()];
+    indices.chunks_exact(3).for_each(|triangle| {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        let (edge1, edge2) = (positions[b] - positions[a], positions[c] - positions[a]);
+        let (duv1, duv2) = (uvs[b] - uvs[a], uvs[c] - uvs[a]);
+        let determinant = duv1.perp_dot(duv2);
+        // the texture is squashed to a line over this face, so it says nothing about direction
+        if determinant.abs() < f32::EPSILON {
+            return;
+        }
+        let tangent = (edge1 * duv2.y - edge2 * duv1.y) / determinant;
+        let bitangent = (edge2 * duv1.x - edge1 * duv2.x) / determinant;
+        [a, b, c].into_iter().for_each(|i| {
+            tangents[i] += tangent;
+            bitangents[i] += bitangent;
+        });
+    });
+
+    normals
+        .iter()
+        .zip(tangents)
+        .zip(bitangents)
+        .map(|((normal, tangent), bitangent)| {
+            let tangent = (tangent - *normal * normal.dot(tangent))
+                .try_normalize()
+                .unwrap_or_else(|| normal.any_orthonormal_vector());
+            let handedness = if normal.cross(tangent).dot(bitangent) < 0.0 {
+                -1.0
+            } else {
+                1.0
+            };
+            (tangent, handedness)
+        })
+        .collect()
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable, Serialize, Deserialize)]
 pub struct Material {
+    /// In sRGB, like the ui colours, and converted to linear by the shader
     pub colour: Vec4,
 }
+
 impl Material {
-    pub const DEBUG_MATERIAL: Material = Material {
-        colour: Vec4::new(1.0, 0.0, 0.95, 1.0),
-    };
+    pub fn load<T: AsRef<Path>>(path: T) -> Result<Self> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
 
-    pub const RED: Material = Material {
-        colour: Vec4::new(1.0, 0.0, 0.0, 1.0),
-    };
+/// A reference to a material file, so shared materials are stored once and edits to the file
+/// apply everywhere it is used
+#[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaterialId(pub String);
+
+impl MaterialId {
+    pub fn debug() -> Self {
+        Self(String::from("assets/materials/debug.json"))
+    }
+
+    pub fn red() -> Self {
+        Self(String::from("assets/materials/red.json"))
+    }
+
+    pub fn white() -> Self {
+        Self(String::from("assets/materials/white.json"))
+    }
+}
+
+impl AsRef<Path> for MaterialId {
+    fn as_ref(&self) -> &Path {
+        self.0.as_ref()
+    }
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MeshId(pub String);
 
+impl MeshId {
+    /// The generated [`Mesh::disc`], which has no file and has to be inserted into the
+    /// [`MeshCache`] up front
+    pub fn disc() -> Self {
+        Self(String::from("disc"))
+    }
+
+    /// One of the meshes in a glTF scene, which is loaded from the scene's file like any other
+    /// mesh
+    pub fn in_scene<P: AsRef<Path>>(path: P, mesh: usize) -> Self {
+        Self(format!("{}#{}", path.as_ref().display(), mesh))
+    }
+}
+
 impl AsRef<Path> for MeshId {
     fn as_ref(&self) -> &Path {
         self.0.as_ref()
     }
 }
 
+/// Meshes by the file they came from, so each file is only parsed the first time it is used no
+/// matter how many objects share it
 #[derive(Default)]
-pub struct MeshCache(HashMap<MeshId, Mesh>);
+pub struct MeshCache {
+    meshes: HashMap<MeshId, Mesh>,
+    // being parsed by a [`Loader`], which shouldn't be drawn until they are done
+    loading: HashSet<MeshId>,
+    // failed to load in a [`Loader`], with why, so they are left out instead of loaded again
+    failed: HashMap<MeshId, String>,
+    // counted for the tests, to check files aren't parsed more than once
+    #[cfg(test)]
+    parsed: usize,
+}
 
 impl MeshCache {
     pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<&Mesh> {
         let id = MeshId(path.as_ref().to_str().unwrap().to_owned());
-        if self.0.get(&id).is_none() {
-            self.0.insert(id.clone(), Mesh::load(path)?);
+        if let Some(e) = self.failed.get(&id) {
+            bail!("Failed to load mesh {}: {e}", id.0);
+        }
+        if !self.meshes.contains_key(&id) {
+            let mesh = Mesh::load(path)?;
+            #[cfg(test)]
+            {
+                self.parsed += 1;
+            }
+            self.meshes.insert(id.clone(), mesh);
+        }
+        Ok(self.meshes.get(&id).unwrap())
+    }
+
+    /// Adds a mesh that isn't loaded from a file
+    pub fn insert(&mut self, id: MeshId, mesh: Mesh) {
+        self.meshes.insert(id, mesh);
+    }
+
+    /// How many files have been parsed so far
+    #[cfg(test)]
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// Whether the mesh can be drawn, rather than still loading or having failed to
+    pub fn available(&self, id: &MeshId) -> bool {
+        !self.loading.contains(id) && !self.failed.contains_key(id)
+    }
+}
+
+#[derive(Default)]
+pub struct MaterialCache {
+    materials: HashMap<MaterialId, Material>,
+    // failed to load in a [`Loader`], with why
+    failed: HashMap<MaterialId, String>,
+}
+
+impl MaterialCache {
+    pub fn load(&mut self, id: &MaterialId) -> Result<Material> {
+        if let Some(e) = self.failed.get(id) {
+            bail!("Failed to load material {}: {e}", id.0);
         }
-        Ok(self.0.get(&id).unwrap())
+        if let Some(material) = self.materials.get(id) {
+            return Ok(*material);
+        }
+        let material = Material::load(id)?;
+        self.materials.insert(id.clone(), material);
+        Ok(material)
+    }
+
+    pub fn insert(&mut self, id: MaterialId, material: Material) {
+        self.materials.insert(id, material);
+    }
+
+    /// Whether the material didn't fail to load in a [`Loader`]
+    pub fn available(&self, id: &MaterialId) -> bool {
+        !self.failed.contains_key(id)
+    }
+}
+
+enum Loaded {
+    Mesh(MeshId, Result<Mesh>),
+    Material(MaterialId, Result<Material>),
+}
+
+/// Loads meshes and materials on another thread, so the game keeps drawing while they load and
+/// doesn't stall the first time they are used. Shaders and fonts are needed to draw anything at
+/// all, so they are read by a [`Preload`] instead.
+pub struct Loader {
+    receiver: Receiver<Loaded>,
+    // what was sent to be loaded, so anything left over can be failed if the thread dies
+    meshes: Vec<MeshId>,
+    materials: Vec<MaterialId>,
+    total: usize,
+    loaded: usize,
+    errors: Vec<String>,
+}
+
+impl Loader {
+    /// Starts loading everything that isn't in the caches yet
+    pub fn spawn(
+        world: &World,
+        meshes: impl IntoIterator<Item = MeshId>,
+        materials: impl IntoIterator<Item = MaterialId>,
+    ) -> Self {
+        let mut mesh_cache = world.get_mut::<MeshCache>().unwrap();
+        let meshes = meshes
+            .into_iter()
+            .filter(|id| {
+                !mesh_cache.meshes.contains_key(id) && mesh_cache.loading.insert(id.clone())
+            })
+            .collect::<Vec<MeshId>>();
+        let material_cache = world.get::<MaterialCache>().unwrap();
+        let materials = materials
+            .into_iter()
+            .filter(|id| !material_cache.materials.contains_key(id))
+            .collect::<HashSet<MaterialId>>()
+            .into_iter()
+            .collect::<Vec<MaterialId>>();
+
+        let total = meshes.len() + materials.len();
+        let (sender, receiver) = mpsc::channel();
+        let (sent_meshes, sent_materials) = (meshes.clone(), materials.clone());
+        std::thread::spawn(move || {
+            // the receiver is dropped if the game closes while loading, which leaves nothing to
+            // load for
+            meshes.into_iter().try_for_each(|id| {
+                let mesh = Mesh::load(&id);
+                sender.send(Loaded::Mesh(id, mesh))
+            })?;
+            materials.into_iter().try_for_each(|id| {
+                let material = Material::load(&id);
+                sender.send(Loaded::Material(id, material))
+            })
+        });
+
+        Self {
+            receiver,
+            meshes: sent_meshes,
+            materials: sent_materials,
+            total,
+            loaded: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Moves whatever has finished loading into the caches. Anything that failed is marked as
+    /// such, so it is left out of drawing instead of erroring every frame.
+    pub fn receive(&mut self, meshes: &mut MeshCache, materials: &mut MaterialCache) {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(loaded) => self.store(loaded, meshes, materials),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.abandon(meshes, materials);
+                    return;
+                }
+            }
+        }
+    }
+
+    fn store(&mut self, loaded: Loaded, meshes: &mut MeshCache, materials: &mut MaterialCache) {
+        self.loaded += 1;
+        match loaded {
+            Loaded::Mesh(id, mesh) => {
+                meshes.loading.remove(&id);
+                match mesh {
+                    Ok(mesh) => {
+                        #[cfg(test)]
+                        {
+                            meshes.parsed += 1;
+                        }
+                        meshes.insert(id, mesh);
+                    }
+                    Err(e) => {
+                        warn!("Failed to load mesh {}: {e}", id.0);
+                        self.errors.push(format!("{}: {e}", id.0));
+                        meshes.failed.insert(id, e.to_string());
+                    }
+                }
+            }
+            Loaded::Material(id, material) => match material {
+                Ok(material) => materials.insert(id, material),
+                Err(e) => {
+                    warn!("Failed to load material {}: {e}", id.0);
+                    self.errors.push(format!("{}: {e}", id.0));
+                    materials.failed.insert(id, e.to_string());
+                }
+            },
+        }
+    }
+
+    /// Fails everything the loading thread didn't get to, such as when it panics, so loading
+    /// still finishes rather than waiting forever
+    fn abandon(&mut self, meshes: &mut MeshCache, materials: &mut MaterialCache) {
+        let stopped = || anyhow!("the loading thread stopped");
+        let unloaded = self
+            .meshes
+            .iter()
+            .filter(|id| meshes.loading.contains(id))
+            .map(|id| Loaded::Mesh(id.clone(), Err(stopped())))
+            .chain(
+                self.materials
+                    .iter()
+                    .filter(|id| !materials.materials.contains_key(id))
+                    .filter(|id| !materials.failed.contains_key(id))
+                    .map(|id| Loaded::Material(id.clone(), Err(stopped()))),
+            )
+            .collect::<Vec<_>>();
+        unloaded
+            .into_iter()
+            .for_each(|loaded| self.store(loaded, meshes, materials));
+        self.loaded = self.total;
+    }
+
+    /// Why each asset that failed to load so far did
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    /// How much has loaded, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        if self.total == 0 {
+            return 1.0;
+        }
+        self.loaded as f32 / self.total as f32
+    }
+
+    pub fn loaded(&self) -> usize {
+        self.loaded
+    }
+
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    pub fn done(&self) -> bool {
+        self.loaded == self.total
+    }
+
+    pub fn tick(world: &World) {
+        world.get_mut::<Loader>().unwrap().receive(
+            &mut world.get_mut::<MeshCache>().unwrap(),
+            &mut world.get_mut::<MaterialCache>().unwrap(),
+        );
+    }
+
+    pub fn add(self) -> impl FnOnce(World) -> World {
+        move |world| world.with_resource(self).with_ticker(Self::tick)
+    }
+}
+
+enum Preloaded {
+    Shader(&'static str, std::io::Result<Vec<u8>>),
+    // boxed, as a font is far bigger than anything else sent
+    Font(Box<Result<Font>>),
+}
+
+/// Reads the shaders and parses the font on another thread while the window and device are
+/// created. They can't wait behind the loading screen like a [`Loader`], as it needs them to be
+/// drawn at all.
+#[derive(Default)]
+pub struct Preload {
+    // what the paths are relative to, which is the working directory when empty
+    root: PathBuf,
+    // none when nothing was preloaded, so everything is read as it is asked for
+    receiver: Option<Receiver<Preloaded>>,
+    shaders: HashMap<&'static str, std::io::Result<Vec<u8>>>,
+    font: Option<Result<Font>>,
+}
+
+impl Preload {
+    pub const SHADERS: [&'static str; 10] = [
+        "assets/shaders/shader.vert.spv",
+        "assets/shaders/shader.frag.spv",
+        "assets/shaders/fullscreen.vert.spv",
+        "assets/shaders/sky.frag.spv",
+        "assets/shaders/line.vert.spv",
+        "assets/shaders/line.frag.spv",
+        "assets/shaders/upscale.frag.spv",
+        "assets/shaders/grayscale.frag.spv",
+        "assets/shaders/ui.vert.spv",
+        "assets/shaders/ui.frag.spv",
+    ];
+    pub const FONT: &'static str = "assets/fonts/JetBrainsMono-Medium.ttf";
+
+    pub fn spawn() -> Self {
+        Self::spawn_in("")
+    }
+
+    /// Preloads from paths relative to `root` rather than the working directory
+    pub fn spawn_in<P: Into<PathBuf>>(root: P) -> Self {
+        let root = root.into();
+        let (sender, receiver) = mpsc::channel();
+        let thread_root = root.clone();
+        std::thread::spawn(move || {
+            Self::SHADERS.into_iter().try_for_each(|path| {
+                sender.send(Preloaded::Shader(
+                    path,
+                    std::fs::read(thread_root.join(path)),
+                ))
+            })?;
+            sender.send(Preloaded::Font(Box::new(Self::load_font(&thread_root))))
+        });
+        Self {
+            root,
+            receiver: Some(receiver),
+            ..Default::default()
+        }
+    }
+
+    fn load_font(root: &Path) -> Result<Font> {
+        Font::from_bytes(
+            std::fs::read(root.join(Self::FONT))?,
+            FontSettings::default(),
+        )
+        .map_err(|e| anyhow!(e))
+    }
+
+    // takes what the thread has sent until `done`, or until it has nothing left to send
+    fn wait(&mut self, done: impl Fn(&Self) -> bool) {
+        while !done(self) {
+            match self
+                .receiver
+                .as_ref()
+                .and_then(|receiver| receiver.recv().ok())
+            {
+                Some(Preloaded::Shader(path, code)) => {
+                    self.shaders.insert(path, code);
+                }
+                Some(Preloaded::Font(font)) => self.font = Some(*font),
+                None => return,
+            }
+        }
+    }
+
+    /// The SPIR-V of a shader, waiting for it if it is still being read. Shaders that weren't
+    /// preloaded, or were already taken, are read now.
+    pub fn shader(&mut self, path: &str) -> Result<Vec<u8>> {
+        self.wait(|preload| preload.shaders.contains_key(path));
+        match self.shaders.remove(path) {
+            Some(code) => Ok(code?),
+            None => Ok(std::fs::read(self.root.join(path))?),
+        }
+    }
+
+    /// The ui's font, waiting for it if it is still being parsed
+    pub fn font(&mut self) -> Result<Font> {
+        self.wait(|preload| preload.font.is_some());
+        self.font
+            .take()
+            .unwrap_or_else(|| Self::load_font(&self.root))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_mesh_cache() {
+        let cube = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/meshes/cube.glb");
+        let mut meshes = MeshCache::default();
+        meshes.load(cube).unwrap();
+        assert_eq!(meshes.parsed(), 1);
+
+        let indices = meshes.load(cube).unwrap().num_indices;
+        assert_eq!(meshes.load(cube).unwrap().num_indices, indices);
+        assert_eq!(meshes.parsed(), 1);
+
+        assert!(meshes.load("missing.glb").is_err());
+    }
+
+    #[test]
+    pub fn test_loader() {
+        let cube = MeshId(String::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/meshes/cube.glb"
+        )));
+        let white = MaterialId(String::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/materials/white.json"
+        )));
+        let missing = MeshId(String::from("missing.glb"));
+        let world = World::new()
+            .with_resource(MeshCache::default())
+            .with_resource(MaterialCache::default());
+
+        // the same mesh twice is only loaded once
+        let mut loader = Loader::spawn(
+            &world,
+            [cube.clone(), cube.clone(), missing.clone()],
+            [white.clone()],
+        );
+        assert_eq!(loader.total(), 3);
+        assert!(world.get::<MeshCache>().unwrap().loading.contains(&cube));
+
+        while !loader.done() {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+            loader.receive(
+                &mut world.get_mut::<MeshCache>().unwrap(),
+                &mut world.get_mut::<MaterialCache>().unwrap(),
+            );
+        }
+        assert_eq!(loader.progress(), 1.0);
+
+        assert_eq!(loader.errors().len(), 1);
+
+        let mut meshes = world.get_mut::<MeshCache>().unwrap();
+        assert!(!meshes.loading.contains(&cube));
+        assert!(meshes.available(&cube));
+        assert_eq!(meshes.parsed(), 1);
+        meshes.load(&cube).unwrap();
+        assert_eq!(meshes.parsed(), 1);
+
+        // left out of drawing, and not read again
+        assert!(!meshes.available(&missing));
+        assert!(meshes.load(&missing).is_err());
+        assert!(world
+            .get::<MaterialCache>()
+            .unwrap()
+            .materials
+            .contains_key(&white));
+    }
+
+    #[test]
+    pub fn test_loader_stopped() {
+        let mesh = MeshId(String::from("mesh.glb"));
+        let material = MaterialId(String::from("material.json"));
+        let mut meshes = MeshCache::default();
+        meshes.loading.insert(mesh.clone());
+        let mut materials = MaterialCache::default();
+
+        // as if the loading thread panicked before sending anything
+        let (_, receiver) = mpsc::channel();
+        let mut loader = Loader {
+            receiver,
+            meshes: vec![mesh.clone()],
+            materials: vec![material.clone()],
+            total: 2,
+            loaded: 0,
+            errors: Vec::new(),
+        };
+        loader.receive(&mut meshes, &mut materials);
+
+        assert!(loader.done());
+        assert_eq!(loader.errors().len(), 2);
+        assert!(!meshes.loading.contains(&mesh));
+        assert!(!meshes.available(&mesh));
+        assert!(!materials.available(&material));
+    }
+
+    #[test]
+    pub fn test_missing_primitive() {
+        let model = |mesh: &str| Glb {
+            gltf: serde_json::from_str(&format!(
+                r#"{{ "asset": {{ "version": "2.0" }}, "meshes": [{mesh}] }}"#
+            ))
+            .unwrap(),
+            buffer: Vec::new(),
+        };
+        assert!(Mesh::from_glb(&model(r#"{ "primitives": [] }"#), 0).is_err());
+        let model = model(r#"{ "primitives": [{ "attributes": {} }] }"#);
+        assert!(Mesh::from_glb(&model, 0).is_err());
+    }
+
+    #[test]
+    pub fn test_material_round_trip() {
+        let load = |name: &str| {
+            Material::load(format!(
+                "{}/../assets/materials/{name}.json",
+                env!("CARGO_MANIFEST_DIR")
+            ))
+            .unwrap()
+        };
+        let round_trip = |material: Material| {
+            serde_json::from_str::<Material>(&serde_json::to_string(&material).unwrap()).unwrap()
+        };
+
+        let white = load("white");
+        assert_eq!(white.colour, Vec4::ONE);
+        assert_eq!(round_trip(white).colour, white.colour);
+
+        // the copper ore nodes' material
+        let copper = load("copper");
+        assert_eq!(copper.colour, Vec4::new(1.0, 0.5, 0.0, 1.0));
+        assert_eq!(round_trip(copper).colour, copper.colour);
+
+        let id = MaterialId(String::from("assets/materials/copper.json"));
+        let json = serde_json::to_string(&id).unwrap();
+        assert_eq!(serde_json::from_str::<MaterialId>(&json).unwrap(), id);
+    }
+
+    #[test]
+    pub fn test_preload() {
+        let mut preload = Preload::spawn_in(concat!(env!("CARGO_MANIFEST_DIR"), "/.."));
+        let code = preload.shader(Preload::SHADERS[0]).unwrap();
+        assert!(!code.is_empty());
+        // taken the first time, so read again after that
+        assert_eq!(preload.shader(Preload::SHADERS[0]).unwrap(), code);
+        assert!(preload.shader("assets/shaders/missing.spv").is_err());
+        preload.font().unwrap();
+    }
+
+    #[test]
+    pub fn test_generated_normals() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/meshes/cube_no_normals.glb"
+        );
+        let mesh = Mesh::load(path).unwrap();
+        assert_eq!(mesh.vertices.len(), 8);
+        mesh.vertices.iter().for_each(|vertex| {
+            assert!((vertex.normal.length() - 1.0).abs() < 1e-5);
+            // the cube is centred on the origin, so outwards is away from it
+            assert!(vertex.normal.dot(vertex.position) > 0.0);
+            assert!(vertex.tangent.dot(vertex.normal).abs() < 1e-5);
+        });
+    }
+
+    #[test]
+    pub fn test_weld() {
+        let vertex = |position: Vec3| Vertex {
+            position,
+            normal: Vec3::Y,
+            tangent: Vec3::X,
+            handedness: 1.0,
+        };
+        // two triangles making a square, with the corners they share duplicated
+        let corners = [
+            Vec3::ZERO,
+            Vec3::X,
+            Vec3::Z,
+            Vec3::X,
+            Vec3::Z,
+            Vec3::new(1.0, 0.0, 1.0),
+        ];
+        let mut mesh = Mesh {
+            vertices: corners.map(vertex).to_vec(),
+            indices: (0..6).collect(),
+            num_indices: 6,
+        };
+        mesh.weld();
+
+        assert_eq!(mesh.vertices.len(), 4);
+        let triangles = mesh
+            .indices
+            .iter()
+            .map(|index| mesh.vertices[*index as usize].position)
+            .collect::<Vec<Vec3>>();
+        assert_eq!(triangles, corners);
+    }
+
+    #[test]
+    pub fn test_generated_tangents() {
+        let positions = [Vec3::ZERO, Vec3::X, Vec3::Z];
+        let normals = [Vec3::Y; 3];
+        // U runs along X, and V along Z
+        let uvs = [Vec2::ZERO, Vec2::X, Vec2::Y];
+        let tangents = generate_tangents(&positions, &normals, Some(&uvs), &[0, 1, 2]);
+        tangents.iter().for_each(|(tangent, handedness)| {
+            assert!(tangent.abs_diff_eq(Vec3::X, 1e-5));
+            // Y cross X is -Z, the opposite way to V
+            assert_eq!(*handedness, -1.0);
+        });
     }
 }
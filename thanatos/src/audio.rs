@@ -0,0 +1,409 @@
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+use anyhow::Result;
+use glam::{Quat, Vec3};
+use log::{debug, warn};
+use tecs::EntityId;
+
+use crate::{camera::Camera, event::Event, transform, World};
+
+// sounds any further from the listener than this aren't played at all
+const MAX_DISTANCE: f32 = 40.0;
+// sounds closer than this are played at full volume, and only start panning beyond it
+const FULL_VOLUME_DISTANCE: f32 = 4.0;
+
+/// The bytes of an encoded sound file, shared with whatever is playing it
+pub type Sound = Arc<[u8]>;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq)]
+pub struct SoundId(pub String);
+
+impl SoundId {
+    pub fn hit() -> Self {
+        Self(String::from("assets/sounds/hit.wav"))
+    }
+
+    pub fn gather() -> Self {
+        Self(String::from("assets/sounds/gather.wav"))
+    }
+
+    pub fn click() -> Self {
+        Self(String::from("assets/sounds/click.wav"))
+    }
+
+    pub fn death() -> Self {
+        Self(String::from("assets/sounds/death.wav"))
+    }
+
+    pub fn music() -> Self {
+        Self(String::from("assets/sounds/music.wav"))
+    }
+
+    pub fn all() -> [Self; 5] {
+        [
+            Self::hit(),
+            Self::gather(),
+            Self::click(),
+            Self::death(),
+            Self::music(),
+        ]
+    }
+}
+
+impl AsRef<Path> for SoundId {
+    fn as_ref(&self) -> &Path {
+        self.0.as_ref()
+    }
+}
+
+/// How loud a sound is and which way it is heard from, for sounds played somewhere in the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    /// From 0 for silent to 1 for as loud as the volume allows
+    pub gain: f32,
+    /// From -1 for only the left ear to 1 for only the right
+    pub pan: f32,
+}
+
+impl Placement {
+    /// Heard evenly in both ears at full volume, like music and the ui
+    pub const CENTRE: Self = Self {
+        gain: 1.0,
+        pan: 0.0,
+    };
+}
+
+/// Where sounds are heard from, which follows what the camera is looking at, panned or not
+#[derive(Clone, Copy, Debug)]
+pub struct Listener {
+    pub position: Vec3,
+    /// The direction to the listener's right, which is the right of the screen
+    pub right: Vec3,
+}
+
+impl Listener {
+    pub fn new(camera: &Camera) -> Self {
+        Self {
+            position: camera.focus(),
+            right: Quat::from_rotation_y(camera.theta) * Vec3::NEG_X,
+        }
+    }
+
+    /// How a sound played at `position` is heard, or `None` if it is too far away to hear
+    pub fn placement(&self, position: Vec3) -> Option<Placement> {
+        let offset = position - self.position;
+        let distance = offset.length();
+        if distance > MAX_DISTANCE {
+            return None;
+        }
+        let falloff = (distance - FULL_VOLUME_DISTANCE) / (MAX_DISTANCE - FULL_VOLUME_DISTANCE);
+        // eased in, so a sound just beside the listener doesn't play in only one ear
+        let pan =
+            offset.normalize_or_zero().dot(self.right) * (distance / FULL_VOLUME_DISTANCE).min(1.0);
+        Some(Placement {
+            gain: 1.0 - falloff.clamp(0.0, 1.0),
+            pan,
+        })
+    }
+}
+
+/// Where sounds are played, which is an audio device outside of tests
+pub trait Sink {
+    fn play(&mut self, sound: &Sound, volume: f32, placement: Placement, looping: bool);
+    /// Changes the volume of everything that is still playing
+    fn set_volume(&mut self, volume: f32);
+}
+
+/// Plays nothing, for when there is no audio device or the game is built without audio
+pub struct Silent;
+
+impl Sink for Silent {
+    fn play(&mut self, _: &Sound, _: f32, _: Placement, _: bool) {}
+    fn set_volume(&mut self, _: f32) {}
+}
+
+#[cfg(feature = "audio")]
+mod device {
+    use std::io::Cursor;
+
+    use anyhow::Result;
+    use log::warn;
+    use rodio::{source::ChannelVolume, Decoder, OutputStream, OutputStreamHandle, Source};
+
+    use super::{Placement, Sink, Sound};
+
+    /// Plays sounds through the default output device
+    pub struct Device {
+        // the device stops playing when this is dropped
+        _stream: OutputStream,
+        handle: OutputStreamHandle,
+        playing: Vec<rodio::Sink>,
+    }
+
+    impl Device {
+        pub fn new() -> Result<Self> {
+            let (stream, handle) = OutputStream::try_default()?;
+            Ok(Self {
+                _stream: stream,
+                handle,
+                playing: Vec::new(),
+            })
+        }
+    }
+
+    /// The volumes of the left and right channels
+    fn channels(placement: Placement) -> Vec<f32> {
+        vec![
+            (1.0 - placement.pan).min(1.0) * placement.gain,
+            (1.0 + placement.pan).min(1.0) * placement.gain,
+        ]
+    }
+
+    fn append<S>(sink: &rodio::Sink, source: S, looping: bool)
+    where
+        S: Source<Item = i16> + Send + 'static,
+    {
+        if looping {
+            sink.append(source.repeat_infinite());
+        } else {
+            sink.append(source);
+        }
+    }
+
+    impl Sink for Device {
+        fn play(&mut self, sound: &Sound, volume: f32, placement: Placement, looping: bool) {
+            self.playing.retain(|sink| !sink.empty());
+
+            let sink = match rodio::Sink::try_new(&self.handle) {
+                Ok(sink) => sink,
+                Err(e) => {
+                    warn!("Failed to play sound: {e}");
+                    return;
+                }
+            };
+            let source = match Decoder::new(Cursor::new(sound.clone())) {
+                Ok(source) => source,
+                Err(e) => {
+                    warn!("Failed to decode sound: {e}");
+                    return;
+                }
+            };
+            sink.set_volume(volume);
+            // mixing down to the two panned channels would lose the stereo of anything centred
+            if placement == Placement::CENTRE {
+                append(&sink, source, looping);
+            } else {
+                let source = ChannelVolume::new(source, channels(placement));
+                append(&sink, source, looping);
+            }
+            self.playing.push(sink);
+        }
+
+        fn set_volume(&mut self, volume: f32) {
+            self.playing.iter().for_each(|sink| sink.set_volume(volume));
+        }
+    }
+}
+
+/// The default output device, or silence if the game is built without the `audio` feature or
+/// there isn't a device to play on
+pub fn output() -> Box<dyn Sink> {
+    #[cfg(feature = "audio")]
+    match device::Device::new() {
+        Ok(device) => return Box::new(device),
+        Err(e) => warn!("No audio device, playing without sound: {e}"),
+    }
+    Box::new(Silent)
+}
+
+/// Sounds loaded up front so they are ready the moment something happens
+pub struct Audio {
+    sounds: HashMap<SoundId, Sound>,
+    sink: Box<dyn Sink>,
+    volume: f32,
+}
+
+impl Audio {
+    pub fn new(sink: Box<dyn Sink>) -> Self {
+        Self {
+            sounds: HashMap::new(),
+            sink,
+            volume: 1.0,
+        }
+    }
+
+    pub fn load(&mut self, id: SoundId) -> Result<()> {
+        let sound = std::fs::read(&id)?;
+        self.insert(id, sound.into());
+        Ok(())
+    }
+
+    pub fn insert(&mut self, id: SoundId, sound: Sound) {
+        self.sounds.insert(id, sound);
+    }
+
+    /// Loads every sound the game plays, carrying on without any that fail to load
+    pub fn preload(&mut self) {
+        SoundId::all().into_iter().for_each(|id| {
+            let path = id.0.clone();
+            if let Err(e) = self.load(id) {
+                warn!("Failed to load sound {path}: {e}");
+            }
+        });
+    }
+
+    /// Sets the volume from 0 for silence to 1 for full, including for sounds already playing
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.sink.set_volume(self.volume);
+    }
+
+    fn start(&mut self, id: &SoundId, placement: Placement, looping: bool) {
+        let Some(sound) = self.sounds.get(id) else {
+            debug!("Sound {} isn't loaded", id.0);
+            return;
+        };
+        self.sink.play(sound, self.volume, placement, looping);
+    }
+
+    pub fn play_sound(&mut self, id: &SoundId) {
+        self.start(id, Placement::CENTRE, false);
+    }
+
+    /// Plays a sound from somewhere in the world, quieter the further it is from the listener
+    /// and not at all once it is out of earshot
+    pub fn play_sound_at(&mut self, id: &SoundId, position: Vec3, listener: &Listener) {
+        if let Some(placement) = listener.placement(position) {
+            self.start(id, placement, false);
+        }
+    }
+
+    /// Plays a sound over and over, for background music
+    pub fn play_music(&mut self, id: &SoundId) {
+        self.start(id, Placement::CENTRE, true);
+    }
+
+    /// The sound played when an event happens, if it makes one, along with the entity it comes
+    /// from for sounds that happen somewhere in the world
+    fn sound(event: &Event) -> Option<(SoundId, Option<EntityId>)> {
+        match event {
+            Event::AttackLanded(id) => Some((SoundId::hit(), Some(*id))),
+            Event::Gathered(_, id) => Some((SoundId::gather(), Some(*id))),
+            Event::UiClicked => Some((SoundId::click(), None)),
+            Event::EntityDied(_) => Some((SoundId::death(), None)),
+            _ => None,
+        }
+    }
+
+    pub fn event(world: &World, event: &Event) {
+        let Some((sound, entity)) = Self::sound(event) else {
+            return;
+        };
+        let position = entity.and_then(|id| transform::global(world, id));
+        let listener = world.get::<Camera>().map(|camera| Listener::new(&camera));
+
+        let mut audio = world.get_mut::<Audio>().unwrap();
+        match (position, listener) {
+            (Some(position), Some(listener)) => {
+                audio.play_sound_at(&sound, position.translation, &listener)
+            }
+            _ => audio.play_sound(&sound),
+        }
+    }
+
+    pub fn add(mut self) -> impl FnOnce(World) -> World {
+        self.preload();
+        self.play_music(&SoundId::music());
+        move |world| world.with_resource(self).with_handler(Self::event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use tecs::prelude::*;
+
+    use super::*;
+
+    #[derive(Archetype)]
+    struct Dummy {
+        id: u32,
+    }
+
+    type Played = Rc<RefCell<Vec<(Sound, f32, Placement, bool)>>>;
+
+    struct Mock(Played);
+
+    impl Sink for Mock {
+        fn play(&mut self, sound: &Sound, volume: f32, placement: Placement, looping: bool) {
+            self.0
+                .borrow_mut()
+                .push((sound.clone(), volume, placement, looping));
+        }
+
+        fn set_volume(&mut self, _: f32) {}
+    }
+
+    #[test]
+    pub fn test_attack_sound() {
+        let played = Played::default();
+        let mut audio = Audio::new(Box::new(Mock(played.clone())));
+        audio.insert(SoundId::hit(), Sound::from(*b"hit"));
+        audio.insert(SoundId::music(), Sound::from(*b"music"));
+        audio.set_volume(2.0);
+        audio.play_music(&SoundId::music());
+
+        let world = World::new()
+            .register_unsaved::<Dummy>()
+            .with_resource(audio)
+            .with_handler(Audio::event);
+        let dummy = world.spawn(Dummy { id: 0 });
+        world.submit(Event::AttackLanded(dummy));
+        // nothing is loaded for gathering, so it stays quiet
+        world.submit(Event::Gathered(
+            nyx::data::NodeId(String::from("copper_ore")),
+            dummy,
+        ));
+        world.submit(Event::ServerTick);
+
+        let played = played.borrow();
+        assert_eq!(played.len(), 2);
+        assert_eq!(&*played[0].0, b"music");
+        assert!(played[0].3);
+        assert_eq!(&*played[1].0, b"hit");
+        assert_eq!(played[1].1, 1.0);
+        // without a camera to listen from, it is heard as if it were right there
+        assert_eq!(played[1].2, Placement::CENTRE);
+        assert!(!played[1].3);
+    }
+
+    #[test]
+    pub fn test_spatial() {
+        let listener = Listener {
+            position: Vec3::ZERO,
+            right: Vec3::NEG_X,
+        };
+
+        let right = listener.placement(Vec3::new(-10.0, 0.0, 0.0)).unwrap();
+        assert!(right.pan > 0.99);
+        let left = listener.placement(Vec3::new(10.0, 0.0, 0.0)).unwrap();
+        assert!(left.pan < -0.99);
+
+        let near = listener.placement(Vec3::new(0.0, 0.0, 2.0)).unwrap();
+        let far = listener.placement(Vec3::new(0.0, 0.0, 30.0)).unwrap();
+        assert_eq!(near.gain, 1.0);
+        assert!(far.gain < near.gain);
+        assert!(near.pan.abs() < 1e-5);
+        assert!(listener.placement(Vec3::new(0.0, 0.0, 50.0)).is_none());
+
+        let played = Played::default();
+        let mut audio = Audio::new(Box::new(Mock(played.clone())));
+        audio.insert(SoundId::hit(), Sound::from(*b"hit"));
+        audio.play_sound_at(&SoundId::hit(), Vec3::new(-10.0, 0.0, 0.0), &listener);
+        audio.play_sound_at(&SoundId::hit(), Vec3::new(0.0, 0.0, 50.0), &listener);
+        let played = played.borrow();
+        assert_eq!(played.len(), 1);
+        assert_eq!(played[0].2, right);
+    }
+}
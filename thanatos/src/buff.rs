@@ -0,0 +1,263 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use tecs::utils::{FixedTimestep, State};
+
+use crate::{
+    combat::{CombatDefensive, CombatOffensive},
+    World,
+};
+
+/// A temporary change to an entity's combat stats, as a fraction of the stat
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub enum StatModifier {
+    // 0.2 is +20% damage
+    Damage(f32),
+    // -0.2 is -20% to every resistance
+    Resistance(f32),
+}
+
+impl StatModifier {
+    pub fn is_debuff(&self) -> bool {
+        match self {
+            StatModifier::Damage(amount) | StatModifier::Resistance(amount) => *amount < 0.0,
+        }
+    }
+}
+
+impl fmt::Display for StatModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StatModifier::Damage(amount) => write!(f, "{:+.0}% damage", amount * 100.0),
+            StatModifier::Resistance(amount) => write!(f, "{:+.0}% resistance", amount * 100.0),
+        }
+    }
+}
+
+/// What happens when a buff is applied to an entity that already has it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Stacking {
+    // the duration starts again
+    Refresh,
+    // another stack is added, up to the maximum, and the duration starts again
+    Stack(u32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+pub struct BuffEffect {
+    pub modifier: StatModifier,
+    pub duration: f32, // seconds
+    pub stacking: Stacking,
+}
+
+impl fmt::Display for BuffEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} for {:.1}s ({:?})",
+            self.modifier, self.duration, self.stacking
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ActiveBuff {
+    pub effect: BuffEffect,
+    pub remaining: f32,
+    pub stacks: u32,
+}
+
+impl fmt::Display for ActiveBuff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.effect.modifier)?;
+        if self.stacks > 1 {
+            write!(f, " x{}", self.stacks)?;
+        }
+        write!(f, " ({:.1}s)", self.remaining)
+    }
+}
+
+/// The buffs and debuffs currently affecting an entity
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Buffs(pub Vec<ActiveBuff>);
+
+impl Buffs {
+    /// Applies a buff, following its stacking rules if the same buff is already active
+    pub fn add(&mut self, effect: BuffEffect) {
+        let Some(active) = self.0.iter_mut().find(|active| active.effect == effect) else {
+            self.0.push(ActiveBuff {
+                effect,
+                remaining: effect.duration,
+                stacks: 1,
+            });
+            return;
+        };
+
+        active.remaining = effect.duration;
+        if let Stacking::Stack(max) = effect.stacking {
+            active.stacks = (active.stacks + 1).min(max);
+        }
+    }
+
+    /// Counts down the buffs by `delta` seconds, removing any that have run out
+    pub fn update(&mut self, delta: f32) {
+        self.0
+            .iter_mut()
+            .for_each(|active| active.remaining -= delta);
+        self.0.retain(|active| active.remaining > 0.0);
+    }
+
+    fn multiplier(&self, stat: fn(StatModifier) -> Option<f32>) -> f32 {
+        let total: f32 = self
+            .0
+            .iter()
+            .filter_map(|active| Some(stat(active.effect.modifier)? * active.stacks as f32))
+            .sum();
+        // debuffs can take a stat down to nothing but not below it
+        (1.0 + total).max(0.0)
+    }
+
+    pub fn damage_multiplier(&self) -> f32 {
+        self.multiplier(|modifier| match modifier {
+            StatModifier::Damage(amount) => Some(amount),
+            _ => None,
+        })
+    }
+
+    pub fn resistance_multiplier(&self) -> f32 {
+        self.multiplier(|modifier| match modifier {
+            StatModifier::Resistance(amount) => Some(amount),
+            _ => None,
+        })
+    }
+
+    /// Scales damage by the damage buffs
+    pub fn damage(&self, damage: u32) -> u32 {
+        (damage as f32 * self.damage_multiplier()).round() as u32
+    }
+
+    /// The offensive stats with the damage buffs applied
+    pub fn offensive(&self, base: &CombatOffensive) -> CombatOffensive {
+        let mut stats = base.clone();
+        [
+            &mut stats.fire,
+            &mut stats.earth,
+            &mut stats.lightning,
+            &mut stats.air,
+            &mut stats.nature,
+        ]
+        .into_iter()
+        .for_each(|attack| attack.damage = self.damage(attack.damage));
+        stats.true_damage = self.damage(stats.true_damage);
+        stats
+    }
+
+    /// The defensive stats with the resistance buffs applied
+    pub fn defensive(&self, base: &CombatDefensive) -> CombatDefensive {
+        let multiplier = self.resistance_multiplier();
+        let mut stats = *base;
+        [
+            &mut stats.fire_resistance,
+            &mut stats.earth_resistance,
+            &mut stats.lightning_resistance,
+            &mut stats.air_resistance,
+            &mut stats.nature_resistance,
+        ]
+        .into_iter()
+        .for_each(|resistance| *resistance = (*resistance as f32 * multiplier).round() as i32);
+        stats
+    }
+}
+
+pub fn add(world: World) -> World {
+    world.with_fixed_ticker(tick)
+}
+
+/// Counts down the buffs on the fixed step, so how long they last doesn't depend on the frame rate
+pub fn tick(world: &World) {
+    if State::paused(world) {
+        return;
+    }
+    let delta = world.get::<FixedTimestep>().unwrap().delta.as_secs_f32();
+    let mut buffs = world.query::<&mut Buffs>();
+    buffs.for_each(|buffs| buffs.update(delta));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::combat::{AttackType, Attackable};
+
+    const NONE: AttackType = AttackType {
+        damage: 0,
+        penetration: 0,
+    };
+
+    fn rage() -> BuffEffect {
+        BuffEffect {
+            modifier: StatModifier::Damage(0.5),
+            duration: 2.0,
+            stacking: Stacking::Refresh,
+        }
+    }
+
+    fn attack(buffs: &Buffs) -> u32 {
+        let base = CombatOffensive {
+            fire: AttackType {
+                damage: 10,
+                penetration: 0,
+            },
+            earth: NONE,
+            lightning: NONE,
+            air: NONE,
+            nature: NONE,
+            true_damage: 10,
+        };
+        let mut target = CombatDefensive {
+            health: 100,
+            max_health: 100,
+            fire_resistance: 0,
+            earth_resistance: 0,
+            lightning_resistance: 0,
+            air_resistance: 0,
+            nature_resistance: 0,
+        };
+        target.receive_attack(&buffs.offensive(&base)).sum_damage()
+    }
+
+    #[test]
+    pub fn test_damage_buff_expires() {
+        let mut buffs = Buffs::default();
+        assert_eq!(attack(&buffs), 20);
+
+        buffs.add(rage());
+        assert_eq!(attack(&buffs), 30);
+
+        buffs.update(1.5);
+        assert_eq!(attack(&buffs), 30);
+
+        buffs.update(0.5);
+        assert!(buffs.0.is_empty());
+        assert_eq!(attack(&buffs), 20);
+    }
+
+    #[test]
+    pub fn test_stacking() {
+        let mut buffs = Buffs::default();
+        buffs.add(rage());
+        buffs.update(1.5);
+        // refreshing resets the duration without adding a stack
+        buffs.add(rage());
+        assert_eq!(buffs.0[0].stacks, 1);
+        assert_eq!(buffs.0[0].remaining, 2.0);
+
+        let stacking = BuffEffect {
+            stacking: Stacking::Stack(2),
+            ..rage()
+        };
+        (0..3).for_each(|_| buffs.add(stacking));
+        assert_eq!(buffs.0.len(), 2);
+        assert_eq!(buffs.0[1].stacks, 2);
+        assert_eq!(attack(&buffs), 50);
+    }
+}
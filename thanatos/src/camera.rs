@@ -1,15 +1,54 @@
+use std::f32::consts::FRAC_PI_2;
+
 use glam::{Mat4, Quat, Vec2, Vec3, Vec4, Vec4Swizzles};
 use log::trace;
+use tecs::{utils::Clock, Is, SystemMut};
+use winit::event::MouseButton;
 
 use crate::{
     event::Event,
-    window::{Mouse, Window},
+    player::Player,
+    transform::Transform,
+    window::{Keybind, Keyboard, Mouse, Window},
     World,
 };
 
+const NEAR: f32 = 0.1;
+// kept short of straight down or level, where looking at the focus with Y as up breaks down
+const MIN_ELEVATION: f32 = 0.1;
+const MAX_ELEVATION: f32 = FRAC_PI_2 - 0.1;
+const MIN_DISTANCE: f32 = 2.0;
+const MAX_DISTANCE: f32 = 50.0;
+
+/// The space a camera can see, up to some distance as the projection itself never ends
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    /// The near plane's corners then the far plane's, each going bottom left, bottom right, top
+    /// right, top left
+    pub corners: [Vec3; 8],
+    /// The near, far, left, right, bottom and top planes, as a normal pointing inwards and the
+    /// negated distance along it from the origin
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Whether any part of a sphere is inside
+    pub fn contains_sphere(&self, centre: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.xyz().dot(centre) + plane.w >= -radius)
+    }
+}
+
 pub struct Camera {
+    /// What the camera is looking at, which trails behind the player while following them
     pub target: Vec3,
+    /// How far the camera has been panned away from its target, along the ground
+    pub pan: Vec3,
+    /// The angle around the focus, turning around Y
     pub theta: f32,
+    /// The angle above the ground the focus is looked at from
+    pub phi: f32,
     pub distance: f32,
     pub fov: f32,
     pub aspect: f32,
@@ -18,32 +57,107 @@ pub struct Camera {
 impl Camera {
     pub fn new(window: &Window) -> Self {
         let size = window.window.inner_size();
-        let aspect = size.width as f32 / size.height as f32;
+        Self::looking_at_origin(size.width as f32 / size.height as f32)
+    }
+
+    /// Looks down at the origin from 10 units away, at 45 degrees above the ground
+    pub fn looking_at_origin(aspect: f32) -> Self {
         Self {
             target: Vec3::ZERO,
+            pan: Vec3::ZERO,
             theta: 0.0,
+            phi: std::f32::consts::FRAC_PI_4,
             distance: 10.0,
             fov: std::f32::consts::PI / 2.0,
             aspect,
         }
     }
 
+    /// The point the camera orbits around and looks at
+    pub fn focus(&self) -> Vec3 {
+        self.target + self.pan
+    }
+
     pub fn eye(&self) -> Vec3 {
-        let eye = Vec3::new(0.0, -1.0, -1.0).normalize() * self.distance;
+        let eye = Vec3::new(0.0, -self.phi.sin(), -self.phi.cos()) * self.distance;
         let rotated = Quat::from_rotation_y(self.theta) * eye;
-        rotated + self.target
+        rotated + self.focus()
     }
 
     pub fn direction(&self) -> Vec3 {
-        (self.eye() - self.target).normalize()
+        (self.eye() - self.focus()).normalize()
+    }
+
+    /// Turns the camera around its focus, keeping it between looking straight down and level
+    /// with the ground
+    pub fn orbit(&mut self, theta: f32, phi: f32) {
+        self.theta += theta;
+        self.phi = (self.phi + phi).clamp(MIN_ELEVATION, MAX_ELEVATION);
+    }
+
+    /// Moves the camera closer to its focus for positive amounts, by a fraction of how far away
+    /// it is so zooming feels the same at any distance
+    pub fn zoom(&mut self, amount: f32) {
+        self.distance = (self.distance * (1.0 - amount)).clamp(MIN_DISTANCE, MAX_DISTANCE);
     }
 
     pub fn get_matrix(&self) -> Mat4 {
-        let view = Mat4::look_at_rh(self.eye(), self.target, Vec3::Y);
-        let projection = Mat4::perspective_infinite_rh(self.fov, self.aspect, 0.1);
+        let view = Mat4::look_at_rh(self.eye(), self.focus(), Vec3::Y);
+        let projection = Mat4::perspective_infinite_rh(self.fov, self.aspect, NEAR);
         projection * view
     }
 
+    /// What the camera can see up to `far` away
+    pub fn frustum(&self, far: f32) -> Frustum {
+        let eye = self.eye();
+        let forward = (self.focus() - eye).normalize();
+        let right = forward.cross(Vec3::Y).normalize();
+        let up = right.cross(forward);
+
+        let corners = [NEAR, far].map(|distance| {
+            let height = (self.fov / 2.0).tan() * distance;
+            let width = height * self.aspect;
+            let centre = eye + forward * distance;
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .map(|(x, y)| centre + right * width * x + up * height * y)
+        });
+        let corners: [Vec3; 8] = bytemuck::cast(corners);
+
+        let plane = |normal: Vec3, point: Vec3| normal.extend(-normal.dot(point));
+        // the sides all meet at the eye, and everything straight ahead is inside them
+        let side = |a: usize, b: usize| {
+            let normal = (corners[a] - eye).cross(corners[b] - eye).normalize();
+            let normal = if normal.dot(forward) < 0.0 {
+                -normal
+            } else {
+                normal
+            };
+            plane(normal, eye)
+        };
+
+        Frustum {
+            corners,
+            planes: [
+                plane(forward, corners[0]),
+                plane(-forward, corners[4]),
+                side(0, 3),
+                side(1, 2),
+                side(0, 1),
+                side(2, 3),
+            ],
+        }
+    }
+
+    /// Projects a world position into normalised device coordinates, returning `None` when the
+    /// position is behind the camera.
+    pub fn world_to_ndc(&self, pos: Vec3) -> Option<Vec2> {
+        let transformed = self.get_matrix() * pos.extend(1.0);
+        if transformed.w <= 0.0 {
+            return None;
+        }
+        Some(transformed.xy() / transformed.w)
+    }
+
     pub fn ndc_to_world(&self, pos: Vec2) -> Vec3 {
         let transform = self.get_matrix().inverse();
         let transformed = transform * Vec4::new(pos.x, pos.y, 0.0, 1.0);
@@ -52,7 +166,8 @@ impl Camera {
 
     pub fn handle_resize(world: &World, event: &Event) {
         match event {
-            Event::Resized(new_size) => {
+            // keep the previous aspect while minimized rather than dividing by zero
+            Event::Resized(new_size) if new_size.height != 0 => {
                 let mut camera = world.get_mut::<Camera>().unwrap();
                 camera.aspect = new_size.width as f32 / new_size.height as f32;
             }
@@ -60,21 +175,203 @@ impl Camera {
         }
     }
 
-    pub fn rotate_camera(world: &World) {
-        let mouse = world.get::<Mouse>().unwrap();
-        let mut camera = world.get_mut::<Camera>().unwrap();
-        if mouse.is_down(winit::event::MouseButton::Right) {
-            trace!("{:?}", mouse.delta.x);
-            camera.theta -= mouse.delta.x * 0.02;
-        }
-    }
-
     pub fn add(self) -> impl FnOnce(World) -> World {
         move |world| {
             world
                 .with_resource(self)
                 .with_handler(Self::handle_resize)
-                .with_ticker(Self::rotate_camera)
+                .with_system_mut(CameraController::default())
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Keeps up with the player, for playing
+    #[default]
+    Follow,
+    /// Stays put and can be panned around, for looking over the scene
+    Orbit,
+}
+
+/// Orbits the camera while the right mouse button is held and zooms with the wheel. While
+/// following the player its focus eases after them, otherwise it can be panned along the ground
+/// with the middle mouse button.
+pub struct CameraController {
+    pub mode: CameraMode,
+    /// Radians turned for each unit the mouse moves
+    pub sensitivity: f32,
+    /// The fraction of the distance zoomed in for each line scrolled
+    pub zoom_speed: f32,
+    /// Added to the player's position to get the point that is followed
+    pub offset: Vec3,
+    /// How quickly the camera catches up with the player, where higher is snappier
+    pub smoothing: f32,
+    /// How far the player can get from the focus before the camera moves, so it doesn't jitter
+    /// with every small step
+    pub dead_zone: f32,
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        Self {
+            mode: CameraMode::default(),
+            sensitivity: 0.02,
+            zoom_speed: 0.1,
+            offset: Vec3::ZERO,
+            smoothing: 8.0,
+            dead_zone: 0.05,
         }
     }
 }
+
+impl CameraController {
+    /// Eases the camera's target `delta` seconds closer to `position`
+    pub fn follow(&self, camera: &mut Camera, position: Vec3, delta: f32) {
+        let goal = position + self.offset;
+        if camera.target.distance(goal) <= self.dead_zone {
+            return;
+        }
+        // the same fraction of the way each second, whatever the frame rate
+        let t = 1.0 - (-self.smoothing * delta).exp();
+        camera.target = camera.target.lerp(goal, t);
+    }
+}
+
+impl SystemMut<Event> for CameraController {
+    fn tick(&mut self, world: &World) {
+        let keyboard = world.get::<Keyboard>().unwrap();
+        let mouse = world.get::<Mouse>().unwrap();
+        let mut window = world.get_mut::<Window>().unwrap();
+        let mut camera = world.get_mut::<Camera>().unwrap();
+
+        // grab the cursor while looking around so it can't hit the edge of the screen
+        if mouse.pressed(MouseButton::Right) {
+            window.set_cursor_grab(true);
+        } else if mouse.released(MouseButton::Right) {
+            window.set_cursor_grab(false);
+        }
+
+        if mouse.is_down(MouseButton::Right) {
+            trace!("{:?}", mouse.raw_delta);
+            camera.orbit(
+                -mouse.raw_delta.x * self.sensitivity,
+                mouse.raw_delta.y * self.sensitivity,
+            );
+        }
+
+        if keyboard.pressed(Keybind::CameraMode) {
+            self.mode = match self.mode {
+                CameraMode::Follow => CameraMode::Orbit,
+                CameraMode::Orbit => CameraMode::Follow,
+            };
+            camera.pan = Vec3::ZERO;
+        }
+
+        if self.mode == CameraMode::Follow {
+            let delta = world.get::<Clock>().unwrap().delta.as_secs_f32();
+            let (transforms, _) = world.query::<(&Transform, Is<Player>)>();
+            let position = transforms
+                .iter()
+                .next()
+                .map(|transform| transform.translation);
+            if let Some(position) = position {
+                self.follow(&mut camera, position, delta);
+            }
+        }
+
+        // drags the ground along with the cursor, further when it is further away
+        if self.mode == CameraMode::Orbit && mouse.is_down(MouseButton::Middle) {
+            let scale = camera.distance / window.window.inner_size().height.max(1) as f32;
+            let drag = Vec3::new(mouse.delta.x, 0.0, mouse.delta.y) * scale;
+            let drag = Quat::from_rotation_y(camera.theta) * drag;
+            camera.pan += drag;
+        }
+
+        if mouse.scroll != 0.0 {
+            camera.zoom(mouse.scroll * self.zoom_speed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_frustum() {
+        let camera = Camera::looking_at_origin(1.0);
+        let frustum = camera.frustum(50.0);
+
+        assert!(frustum.contains_sphere(camera.focus(), 1.0));
+        // behind the camera, and past the far plane
+        assert!(!frustum.contains_sphere(camera.eye() * 2.0, 1.0));
+        assert!(!frustum.contains_sphere(camera.eye() - camera.direction() * 60.0, 1.0));
+        // off to the side, but big enough to reach back in
+        let side = Vec3::X * 30.0;
+        assert!(!frustum.contains_sphere(side, 1.0));
+        assert!(frustum.contains_sphere(side, 30.0));
+
+        // every corner lies on the planes that meet there
+        frustum.corners.iter().for_each(|corner| {
+            let on = frustum
+                .planes
+                .iter()
+                .filter(|plane| (plane.xyz().dot(*corner) + plane.w).abs() < 1e-3)
+                .count();
+            assert_eq!(on, 3);
+        });
+    }
+
+    #[test]
+    pub fn test_orbit() {
+        let mut camera = Camera {
+            target: Vec3::new(1.0, 2.0, 3.0),
+            ..Camera::looking_at_origin(1.0)
+        };
+        // the default elevation is the same as the fixed camera it replaced
+        let fixed = Vec3::new(0.0, -1.0, -1.0).normalize() * 10.0 + camera.target;
+        assert!(camera.eye().abs_diff_eq(fixed, 1e-4));
+
+        camera.orbit(1.0, 10.0);
+        assert_eq!(camera.phi, MAX_ELEVATION);
+        camera.orbit(0.0, -10.0);
+        assert_eq!(camera.phi, MIN_ELEVATION);
+        // orbiting never moves the camera nearer or further
+        assert!((camera.eye().distance(camera.focus()) - 10.0).abs() < 1e-4);
+
+        camera.zoom(0.5);
+        assert_eq!(camera.distance, 5.0);
+        camera.zoom(-100.0);
+        assert_eq!(camera.distance, MAX_DISTANCE);
+    }
+
+    #[test]
+    pub fn test_follow() {
+        let mut camera = Camera::looking_at_origin(1.0);
+        let controller = CameraController {
+            offset: Vec3::Y,
+            ..Default::default()
+        };
+        let delta = 1.0 / 60.0;
+
+        // running along at a steady pace, the camera trails behind but keeps up
+        let mut player = Vec3::ZERO;
+        (0..120).for_each(|_| {
+            player += Vec3::X * 5.0 * delta;
+            controller.follow(&mut camera, player, delta);
+        });
+        let lag = camera.target.distance(player + Vec3::Y);
+        assert!(lag < 1.0);
+        assert!(camera.target.x < player.x);
+
+        // and settles on them once they stop
+        (0..120).for_each(|_| controller.follow(&mut camera, player, delta));
+        assert!(camera.target.distance(player + Vec3::Y) <= controller.dead_zone);
+
+        // small steps inside the dead zone don't move it at all
+        let settled = camera.target;
+        controller.follow(&mut camera, player + Vec3::X * 0.01, delta);
+        assert!(camera.target.distance(settled) <= controller.dead_zone);
+    }
+}
@@ -1,20 +1,34 @@
-use std::fmt;
+use std::{collections::HashMap, fmt, time::Duration};
 
+use glam::{Quat, Vec2, Vec3, Vec4};
 use gltf::Material;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use styx::{
-    components::{Clicked, HAlign, HGroup, Text, VAlign, VGroup},
+    components::{Clicked, HAlign, HGroup, ProgressBar, Text, VAlign, VGroup},
     Signal,
 };
-use tecs::{Is, SystemMut};
+use tecs::{prelude::*, utils::Clock, utils::Cooldown, utils::State};
+use winit::{
+    event::MouseButton,
+    keyboard::{Key, NamedKey},
+};
 
 use crate::{
-    combat::CombatOffensive,
+    assets::{MaterialId, Mesh, MeshCache, MeshId},
+    buff::{BuffEffect, Buffs},
+    collider::Collider,
+    combat::{attack, kill, AttackKind, CombatDefensive, Damage, Faction},
+    equipment::equipped_passives,
     event::Event,
+    ground,
     player::Player,
-    renderer::{Anchor, Ui},
-    targeting::SelectedEntity,
-    uiutils, World,
+    renderer::{Anchor, Decal, Ui},
+    targeting::{self, SelectedEntity},
+    transform::Transform,
+    uiutils,
+    window::{Keybind, Keyboard, Mouse},
+    TargetDummy, World,
 };
 
 // casting UI for the player
@@ -22,14 +36,109 @@ use crate::{
 pub struct CastingUI {
     pub open: bool,
     pub casts: Vec<Signal>,
+    // a point targeted skill waiting for the player to click where it should land
+    aiming: Option<Skill>,
+    indicator: Option<EntityId>,
+    charging: Option<Charge>,
+    // keyed by skill name
+    cooldowns: HashMap<String, Cooldown>,
 }
 
 // MAX EQUIPED SKILLS
 const MAX_EQUIPED: usize = 20;
 
+// the size of the indicator for point targeted skills with no area of effect
+const POINT_INDICATOR_RADIUS: f32 = 0.25;
+// lifts the indicator off the ground so it doesn't fight with it in the depth buffer
+const INDICATOR_OFFSET: f32 = 0.01;
+const INDICATOR_SEGMENTS: u32 = 32;
+
+// how far the player can drift during a stationary cast before it is interrupted
+const STATIONARY_THRESHOLD: f32 = 0.1;
+// the fraction of a skill's cooldown it is put on when its cast is interrupted
+const INTERRUPTED_COOLDOWN: f32 = 0.5;
+const BUFF_COLOUR: Vec4 = Vec4::new(0.4, 1.0, 0.4, 1.0);
+const DEBUFF_COLOUR: Vec4 = Vec4::new(1.0, 0.4, 0.4, 1.0);
+// how far above the player the charge bar is drawn
+const CHARGE_BAR_OFFSET: Vec3 = Vec3::new(0.0, 2.0, 0.0);
+const CHARGE_BAR_SIZE: Vec2 = Vec2::new(120.0, 10.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChargeState {
+    Charging,
+    Ready,
+    Interrupted,
+}
+
+/// A charge skill that has started casting, fired once it has charged for long enough
+#[derive(Debug, Clone)]
+pub struct Charge {
+    pub skill: Skill,
+    pub target: SelectedEntity,
+    elapsed: f32,
+    // where the caster was when the cast started
+    start: Vec3,
+}
+
+impl Charge {
+    pub fn new(skill: Skill, target: SelectedEntity, position: Vec3) -> Self {
+        Self {
+            skill,
+            target,
+            elapsed: 0.0,
+            start: position,
+        }
+    }
+
+    /// Advances the charge by `delta` seconds with the caster now at `position`
+    pub fn update(&mut self, delta: f32, position: Vec3) -> ChargeState {
+        let CastType::Charge {
+            charge_duration,
+            stationary_cast,
+        } = self.skill.cast_type
+        else {
+            return ChargeState::Ready;
+        };
+
+        if stationary_cast && position.distance(self.start) > STATIONARY_THRESHOLD {
+            return ChargeState::Interrupted;
+        }
+
+        self.elapsed += delta;
+        if self.elapsed >= charge_duration {
+            ChargeState::Ready
+        } else {
+            ChargeState::Charging
+        }
+    }
+
+    /// How far through charging the skill is, in the range [0.0, 1.0]
+    pub fn progress(&self) -> f32 {
+        match self.skill.cast_type {
+            CastType::Charge {
+                charge_duration, ..
+            } if charge_duration > 0.0 => (self.elapsed / charge_duration).min(1.0),
+            _ => 1.0,
+        }
+    }
+}
+
+/// Marks where a skill being aimed will land
+#[derive(Archetype)]
+struct TargetIndicator {
+    transform: Transform,
+    decal: Decal,
+}
+
 pub fn add(world: World) -> World {
+    world
+        .get_mut::<MeshCache>()
+        .unwrap()
+        .insert(MeshId::disc(), Mesh::disc(INDICATOR_SEGMENTS));
     let ui = CastingUI::new(&world);
-    world.with_system_mut(ui)
+    world
+        .register_unsaved::<TargetIndicator>()
+        .with_system_mut(ui)
 }
 
 impl CastingUI {
@@ -38,25 +147,219 @@ impl CastingUI {
 
         Self {
             open: true,
-            casts: vec![ui.signals.signal(); MAX_EQUIPED],
+            casts: (0..MAX_EQUIPED).map(|_| ui.signals.signal()).collect(),
+            aiming: None,
+            indicator: None,
+            charging: None,
+            cooldowns: HashMap::new(),
+        }
+    }
+
+    /// Starts casting a skill at a target, charging it up first if it is a charge skill
+    fn begin(&mut self, world: &World, skill: Skill, target: SelectedEntity) {
+        if self
+            .cooldowns
+            .get(&skill.name)
+            .is_some_and(|cooldown| !cooldown.ready())
+        {
+            warn!("{} is on cooldown", skill.name);
+            return;
+        }
+        if let Some(charge) = &self.charging {
+            warn!("already casting {}", charge.skill.name);
+            return;
+        }
+
+        match skill.cast_type {
+            CastType::Instant => {
+                if cast(world, &skill, &target) {
+                    self.start_cooldown(world, &skill, 1.0);
+                }
+            }
+            CastType::Charge { .. } => {
+                info!("charging {}", skill.name);
+                self.charging = Some(Charge::new(skill, target, player_position(world)));
+            }
+        }
+    }
+
+    /// Puts a skill on cooldown, shortened by the haste of the player's equipment
+    fn start_cooldown(&mut self, world: &World, skill: &Skill, fraction: f32) {
+        let seconds = equipped_passives(world).cooldown(skill.cooldown * fraction);
+        let mut cooldown = Cooldown::new(Duration::from_secs_f32(seconds));
+        cooldown.start();
+        self.cooldowns.insert(skill.name.clone(), cooldown);
+    }
+
+    /// Advances the cooldowns and the skill being charged, casting it once it is charged or
+    /// cancelling it if the player moved during a stationary cast
+    fn charge(&mut self, world: &World) {
+        let delta = world.get::<Clock>().unwrap().delta;
+        self.cooldowns.values_mut().for_each(|cooldown| {
+            cooldown.advance(delta);
+        });
+
+        let Some(charge) = &mut self.charging else {
+            return;
+        };
+
+        match charge.update(delta.as_secs_f32(), player_position(world)) {
+            ChargeState::Charging => (),
+            ChargeState::Ready => {
+                let charge = self.charging.take().unwrap();
+                if cast(world, &charge.skill, &charge.target) {
+                    self.start_cooldown(world, &charge.skill, 1.0);
+                }
+            }
+            ChargeState::Interrupted => {
+                let charge = self.charging.take().unwrap();
+                warn!("{} was interrupted", charge.skill.name);
+                self.start_cooldown(world, &charge.skill, INTERRUPTED_COOLDOWN);
+            }
+        }
+    }
+
+    fn draw_charge(&self, world: &World) {
+        let Some(charge) = &self.charging else {
+            return;
+        };
+
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        ui.add(
+            Anchor::World(player_position(world) + CHARGE_BAR_OFFSET),
+            ProgressBar {
+                progress: charge.progress(),
+                size: CHARGE_BAR_SIZE,
+                colour: Vec4::new(0.2, 0.6, 1.0, 1.0),
+                background: Vec4::new(0.1, 0.1, 0.1, 1.0),
+                radius: 2.0,
+            },
+        );
+    }
+
+    fn start_aiming(&mut self, world: &World, skill: Skill) {
+        self.stop_aiming(world);
+        info!("choose a target for {}", skill.name);
+
+        // entity targeted skills are aimed by clicking on the entity itself
+        if !matches!(skill.targeting_method, SkillTargeting::Point { .. }) {
+            self.aiming = Some(skill);
+            return;
+        }
+
+        // cover the largest area the skill affects
+        let radius = skill
+            .effects
+            .iter()
+            .filter_map(|effect| effect.area_of_effect)
+            .fold(POINT_INDICATOR_RADIUS, f32::max);
+
+        self.indicator = Some(world.spawn(TargetIndicator {
+            transform: Transform {
+                translation: Vec3::ZERO,
+                rotation: Quat::IDENTITY,
+                scale: Vec3::new(radius, 1.0, radius),
+            },
+            decal: Decal {
+                mesh: MeshId::disc(),
+                material: MaterialId(String::from("assets/materials/indicator.json")),
+            },
+        }));
+        self.aiming = Some(skill);
+    }
+
+    fn stop_aiming(&mut self, world: &World) {
+        self.aiming = None;
+        if let Some(indicator) = self.indicator.take() {
+            world.despawn::<TargetIndicator>(indicator);
+        }
+    }
+
+    /// Moves the indicator of the skill being aimed to the cursor, casting the skill at whatever
+    /// is clicked on or dropping it on escape. The escape is consumed, so it doesn't also pause
+    /// the game.
+    fn aim(&mut self, world: &World) {
+        let Some(skill) = self.aiming.clone() else {
+            return;
+        };
+
+        let mut keyboard = world.get_mut::<Keyboard>().unwrap();
+        let cancelled = keyboard.pressed(Key::Named(NamedKey::Escape));
+        keyboard.consume(Key::Named(NamedKey::Escape));
+        drop(keyboard);
+        if cancelled {
+            self.stop_aiming(world);
+            return;
+        }
+
+        let position = ground::ground_point(world, targeting::cursor_ray(world));
+        let confirmed = world.get::<Mouse>().unwrap().pressed(MouseButton::Left);
+        if confirmed {
+            self.stop_aiming(world);
+            let target = match skill.targeting_method {
+                SkillTargeting::Point { .. } => position.map(SelectedEntity::Position),
+                _ => {
+                    let caster = player(world);
+                    targeting::entity_under_cursor(world, |id| {
+                        skill.targeting_method.allows(world, caster, id)
+                    })
+                    .map(SelectedEntity::EntityId)
+                }
+            };
+
+            match target {
+                Some(target) => self.begin(world, skill, target),
+                None => warn!("{} can't be cast there", skill.name),
+            }
+            return;
+        }
+
+        if let (Some(indicator), Some(position)) = (self.indicator, position) {
+            let mut transform = world.get_component_mut::<Transform>(indicator).unwrap();
+            transform.translation = position + Vec3::Y * INDICATOR_OFFSET;
         }
     }
 }
 
 impl SystemMut<Event> for CastingUI {
     fn tick(&mut self, world: &World) {
+        let keyboard = world.get::<Keyboard>().unwrap();
+        if keyboard.pressed(Keybind::Casting) {
+            self.open = !self.open;
+        }
+        drop(keyboard);
+
+        if !State::paused(world) {
+            self.aim(world);
+            self.charge(world);
+        }
+        self.draw_charge(world);
+
         if !self.open {
             return;
         }
         let mut ui = world.get_mut::<Ui>().unwrap();
 
-        // Get the equiped skills for the player
-        let (mut selected, player_offensive, _) =
-            world.query_one::<(&mut SelectedEntity, &CombatOffensive, Is<Player>)>();
+        // Get the equiped skills and active buffs for the player
+        let (skills, buffs, _) = world.query_one::<(&EquipedSkills, &Buffs, Is<Player>)>();
 
-        let equiped = &player_offensive.equiped_skills;
+        let equiped = &skills.0;
 
         let mut skill_slot_ui = VGroup::new(VAlign::Top, 32.0);
+        for active in &buffs.0 {
+            skill_slot_ui = skill_slot_ui.add(Text {
+                text: active.to_string(),
+                font: ui.font.clone(),
+                font_size: 24.0,
+                colour: if active.effect.modifier.is_debuff() {
+                    DEBUFF_COLOUR
+                } else {
+                    BUFF_COLOUR
+                },
+            });
+        }
+        drop(buffs);
+
         // add the equiped skills to the view
         for (i, skill) in equiped.iter().enumerate() {
             let mut skill_ui = HGroup::new(HAlign::Left, 32.0);
@@ -68,7 +371,12 @@ impl SystemMut<Event> for CastingUI {
             });
 
             skill_ui = skill_ui.add(Text {
-                text: uiutils::progress_bar_string(15, 0.25),
+                text: uiutils::progress_bar_string(
+                    15,
+                    self.cooldowns
+                        .get(&skill.name)
+                        .map_or(1.0, Cooldown::progress),
+                ),
                 font: ui.font.clone(),
                 font_size: 16.0,
                 colour: crate::colours::rarity_colour(nyx::item::Rarity::Epic),
@@ -90,17 +398,185 @@ impl SystemMut<Event> for CastingUI {
 
         ui.add(Anchor::BottomCenter, skill_slot_ui);
 
-        // TODO! DEBUG REMOVE
-        for i in 0..equiped.len() {
-            if ui.signals.get(self.casts[i]) {
-                println!("cast skill {}", i);
+        let cast: Vec<Skill> = equiped
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| ui.signals.get(self.casts[*i]))
+            .map(|(_, skill)| skill.clone())
+            .collect();
+
+        // casting needs to look at (and damage) other entities so let go of everything first
+        drop(ui);
+        drop(skills);
+
+        for skill in cast {
+            let selected = {
+                let (selected, _) = world.query_one::<(&SelectedEntity, Is<Player>)>();
+                selected.clone()
+            };
+
+            match (skill.targeting_method, selected) {
+                (SkillTargeting::Point { .. }, _) => self.start_aiming(world, skill),
+                (SkillTargeting::Entity { .. }, SelectedEntity::EntityId(id))
+                    if skill.targeting_method.allows(world, player(world), id) =>
+                {
+                    self.begin(world, skill, SelectedEntity::EntityId(id))
+                }
+                (SkillTargeting::Entity { .. }, _) => self.start_aiming(world, skill),
+                (SkillTargeting::None, selected) => self.begin(world, skill, selected),
+            }
+        }
+    }
+}
+
+fn player(world: &World) -> EntityId {
+    let (ids, _) = world.query::<(EntityId, Is<Player>)>();
+    ids[0]
+}
+
+fn player_position(world: &World) -> Vec3 {
+    let (transform, _) = world.query_one::<(&Transform, Is<Player>)>();
+    transform.translation
+}
+
+/// Fires a skill's effects at the player's target, as long as the skill can target it and it is
+/// in range. Returns whether the skill was cast.
+pub fn cast(world: &World, skill: &Skill, target: &SelectedEntity) -> bool {
+    let caster = player(world);
+    let (player_transform, _) = world.query_one::<(&Transform, Is<Player>)>();
+    let origin = player_transform.translation;
+
+    // where area effects are centred and the entity single target effects apply to
+    let (centre, target) = match (skill.targeting_method, target) {
+        (SkillTargeting::Point { range }, SelectedEntity::Position(position)) => {
+            if origin.distance(*position) > range {
+                warn!("{} is out of range", skill.name);
+                return false;
             }
+            (*position, None)
+        }
+        (SkillTargeting::Entity { range, .. }, SelectedEntity::EntityId(id)) => {
+            if !skill.targeting_method.allows(world, caster, *id) {
+                warn!("{} can't be cast on the selected target", skill.name);
+                return false;
+            }
+            let Some(transform) = world.get_component::<Transform>(*id) else {
+                return false;
+            };
+            if origin.distance(transform.translation) > range {
+                warn!("{} is out of range", skill.name);
+                return false;
+            }
+            (transform.translation, Some(*id))
+        }
+        // skills without a target are cast on the caster
+        (SkillTargeting::None, _) => (origin, Some(caster)),
+        _ => {
+            warn!("{} can't be cast on the selected target", skill.name);
+            return false;
+        }
+    };
+    drop(player_transform);
+
+    info!("casting {}", skill.name);
+    let mut died = Vec::new();
+    for effect in &skill.effects {
+        let effect = &empowered(world, caster, effect);
+        match (effect.area_of_effect, target) {
+            (Some(radius), _) => died.extend(resolve_area(world, skill, effect, centre, radius)),
+            (None, Some(id)) => {
+                if apply(world, skill, effect, id) {
+                    died.push(id);
+                }
+            }
+            // a single target effect with nothing to target
+            (None, None) => (),
+        }
+    }
+
+    // don't leave a dead entity selected
+    let (mut targeted, _) = world.query_one::<(&mut SelectedEntity, Is<Player>)>();
+    if let SelectedEntity::EntityId(id) = *targeted {
+        if died.contains(&id) {
+            *targeted = SelectedEntity::None;
+        }
+    }
+    drop(targeted);
+
+    kill::<TargetDummy>(world, died);
+    true
+}
+
+/// The effect with the damage buffs of the caster applied
+fn empowered(world: &World, caster: EntityId, effect: &Effect) -> Effect {
+    let (Some(buffs), EffectType::Damage(mut damage)) =
+        (world.get_component::<Buffs>(caster), effect.variant)
+    else {
+        return *effect;
+    };
+
+    damage.true_damage = buffs.damage(damage.true_damage);
+    damage.melee_damage = buffs.damage(damage.melee_damage);
+    damage.ranged_damage = buffs.damage(damage.ranged_damage);
+    damage.magic_damage = buffs.damage(damage.magic_damage);
+    Effect {
+        variant: EffectType::Damage(damage),
+        ..*effect
+    }
+}
+
+/// Applies an effect of a skill to every entity with a collider within `radius` of `centre`,
+/// returning the ones it killed
+pub fn resolve_area(
+    world: &World,
+    skill: &Skill,
+    effect: &Effect,
+    centre: Vec3,
+    radius: f32,
+) -> Vec<EntityId> {
+    // TODO: this checks every collider, it will want a broadphase once there are a lot of them
+    let caught: Vec<EntityId> = {
+        let (ids, colliders, _) = world.query::<(EntityId, &Collider, &CombatDefensive)>();
+        ids.iter()
+            .zip(colliders.iter())
+            .filter(|(_, collider)| collider.within_radius(centre, radius, world))
+            .map(|(id, _)| *id)
+            .collect()
+    };
+
+    caught
+        .into_iter()
+        .filter(|id| apply(world, skill, effect, *id))
+        .collect()
+}
+
+/// Applies a single effect of a skill to an entity, returning whether it killed them
+fn apply(world: &World, skill: &Skill, effect: &Effect, id: EntityId) -> bool {
+    match effect.variant {
+        EffectType::Damage(damage) => {
+            let kind = AttackKind::Skill(skill.name.clone());
+            attack(world, id, Damage::Flat(damage.total()), kind)
+        }
+        EffectType::BufDebuf(buff) => {
+            if let Some(mut buffs) = world.get_component_mut::<Buffs>(id) {
+                info!("{:?} gained {}", id, buff.modifier);
+                buffs.add(buff);
+            }
+            false
+        }
+        variant => {
+            warn!("{} is not implemented", variant);
+            false
         }
     }
 }
 
 // skills are something that the user actively "casts" or activates
 
+/// The skills the player can cast from the casting UI
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct EquipedSkills(pub Vec<Skill>);
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Skill {
     pub name: String,
@@ -143,6 +619,36 @@ pub enum SkillTargeting {
     None, // press button to cast / targets self
 }
 
+impl SkillTargeting {
+    /// Whether a skill cast by `caster` can be cast on `target`. Only entity targeted skills are
+    /// cast on entities, and entities without a [`Faction`] are treated as neutral.
+    pub fn allows(self, world: &World, caster: EntityId, target: EntityId) -> bool {
+        let SkillTargeting::Entity {
+            can_target_friendly,
+            can_target_enemies,
+            can_target_self,
+            ..
+        } = self
+        else {
+            return false;
+        };
+
+        if caster == target {
+            return can_target_self;
+        }
+
+        let faction = |id| {
+            world
+                .get_component::<Faction>(id)
+                .map(|faction| *faction)
+                .unwrap_or(Faction::Neutral)
+        };
+        let (caster, target) = (faction(caster), faction(target));
+        (can_target_friendly && caster.is_friendly(target))
+            || (can_target_enemies && caster.is_enemy(target))
+    }
+}
+
 impl fmt::Display for SkillTargeting {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -192,7 +698,7 @@ impl fmt::Display for CastType {
 pub enum EffectType {
     Damage(DamageEffect), //damage types and stats
     DOT(DamageOverTimeEffect),
-    BufDebuf,                 //+20% haste, rage etc (temporary changes in power)
+    BufDebuf(BuffEffect),     //+20% haste, rage etc (temporary changes in power)
     Healing(HealEffect),      // instant/over time, amount, percent/aboslute
     Movement(MovementEffect), // teleports the player or dashes to a position etc
 }
@@ -202,7 +708,7 @@ impl fmt::Display for EffectType {
         match self {
             EffectType::Damage(damage_effect) => write!(f, "Damage: {}", damage_effect),
             EffectType::DOT(dot_effect) => write!(f, "DOT: {}", dot_effect),
-            EffectType::BufDebuf => write!(f, "Buff/Debuff"),
+            EffectType::BufDebuf(buff) => write!(f, "Buff/Debuff: {}", buff),
             EffectType::Healing(heal_effect) => write!(f, "Healing: {}", heal_effect),
             EffectType::Movement(movement_effect) => write!(f, "Movement: {}", movement_effect),
         }
@@ -232,6 +738,12 @@ pub struct DamageEffect {
     pub magic_damage: u32,
 }
 
+impl DamageEffect {
+    pub fn total(&self) -> u32 {
+        self.true_damage + self.melee_damage + self.ranged_damage + self.magic_damage
+    }
+}
+
 impl fmt::Display for DamageEffect {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -305,3 +817,150 @@ impl fmt::Display for DamageOverTimeType {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collider::{ColliderKind, ColliderPositionKind};
+
+    #[derive(Archetype)]
+    struct Dummy {
+        collider: Collider,
+        defensive: CombatDefensive,
+    }
+
+    #[derive(Archetype)]
+    struct Unit {
+        faction: Faction,
+    }
+
+    fn targeting(friendly: bool, enemies: bool) -> SkillTargeting {
+        SkillTargeting::Entity {
+            range: 10.0,
+            can_target_friendly: friendly,
+            can_target_enemies: enemies,
+            can_target_self: false,
+        }
+    }
+
+    fn dummy(position: Vec3) -> Dummy {
+        Dummy {
+            collider: Collider {
+                kind: ColliderKind::Sphere(0.5),
+                position: ColliderPositionKind::Absolute(position),
+            },
+            defensive: CombatDefensive {
+                health: 100,
+                max_health: 100,
+                fire_resistance: 0,
+                earth_resistance: 0,
+                lightning_resistance: 0,
+                air_resistance: 0,
+                nature_resistance: 0,
+            },
+        }
+    }
+
+    #[test]
+    pub fn test_area_of_effect() {
+        let world = World::new().register_unsaved::<Dummy>();
+        let inside = [
+            world.spawn(dummy(Vec3::new(1.0, 0.0, 0.0))),
+            world.spawn(dummy(Vec3::new(-1.0, 0.0, 1.0))),
+        ];
+        let outside = world.spawn(dummy(Vec3::new(5.0, 0.0, 0.0)));
+
+        let effect = Effect {
+            area_of_effect: Some(2.0),
+            variant: EffectType::Damage(DamageEffect {
+                true_damage: 0,
+                melee_damage: 0,
+                ranged_damage: 0,
+                magic_damage: 20,
+            }),
+        };
+        let died = resolve_area(&world, &charge_skill(false), &effect, Vec3::ZERO, 2.0);
+        assert!(died.is_empty());
+
+        for id in inside {
+            assert_eq!(
+                world.get_component::<CombatDefensive>(id).unwrap().health,
+                80
+            );
+        }
+        assert_eq!(
+            world
+                .get_component::<CombatDefensive>(outside)
+                .unwrap()
+                .health,
+            100
+        );
+    }
+
+    #[test]
+    pub fn test_heal_targets_friendly() {
+        let world = World::new().register_unsaved::<Unit>();
+        let caster = world.spawn(Unit {
+            faction: Faction::Player,
+        });
+        let ally = world.spawn(Unit {
+            faction: Faction::Player,
+        });
+        let enemy = world.spawn(Unit {
+            faction: Faction::Enemy,
+        });
+
+        let heal = targeting(true, false);
+        assert!(heal.allows(&world, caster, ally));
+        assert!(!heal.allows(&world, caster, enemy));
+        assert!(!heal.allows(&world, caster, caster));
+    }
+
+    #[test]
+    pub fn test_attack_targets_enemies() {
+        let world = World::new().register_unsaved::<Unit>();
+        let caster = world.spawn(Unit {
+            faction: Faction::Player,
+        });
+        let ally = world.spawn(Unit {
+            faction: Faction::Player,
+        });
+        let enemy = world.spawn(Unit {
+            faction: Faction::Enemy,
+        });
+        let neutral = world.spawn(Unit {
+            faction: Faction::Neutral,
+        });
+
+        let attack = targeting(false, true);
+        assert!(attack.allows(&world, caster, enemy));
+        assert!(!attack.allows(&world, caster, ally));
+        assert!(!attack.allows(&world, caster, neutral));
+    }
+
+    fn charge_skill(stationary_cast: bool) -> Skill {
+        Skill {
+            name: "charge".to_string(),
+            description: String::new(),
+            cooldown: 10.0,
+            targeting_method: SkillTargeting::None,
+            cast_type: CastType::Charge {
+                charge_duration: 1.0,
+                stationary_cast,
+            },
+            effects: Vec::new(),
+        }
+    }
+
+    #[test]
+    pub fn test_stationary_charge_interrupted() {
+        let mut charge = Charge::new(charge_skill(true), SelectedEntity::None, Vec3::ZERO);
+        assert_eq!(charge.update(0.5, Vec3::ZERO), ChargeState::Charging);
+        assert_eq!(charge.update(0.1, Vec3::X), ChargeState::Interrupted);
+
+        // moving doesn't matter if the cast isn't stationary
+        let mut charge = Charge::new(charge_skill(false), SelectedEntity::None, Vec3::ZERO);
+        assert_eq!(charge.update(0.5, Vec3::X), ChargeState::Charging);
+        assert_eq!(charge.update(0.5, Vec3::X * 2.0), ChargeState::Ready);
+    }
+}
@@ -1,10 +1,8 @@
-use std::default;
-
 use glam::Vec3;
 use serde::{Deserialize, Serialize};
 use tecs::EntityId;
 
-use crate::World;
+use crate::{transform, World};
 
 #[derive(Clone, Copy, Debug)]
 pub struct Ray {
@@ -31,7 +29,68 @@ pub enum ColliderKind {
     Aabb(Vec3),
 }
 
-#[derive(Clone, Copy, Debug)]
+impl ColliderKind {
+    /// The smallest translation that moves this collider at `position` out of `other` at
+    /// `other_position`, or `None` if they don't overlap.
+    pub fn penetration(
+        self,
+        position: Vec3,
+        other: ColliderKind,
+        other_position: Vec3,
+    ) -> Option<Vec3> {
+        let offset = position - other_position;
+
+        match (self, other) {
+            (ColliderKind::Sphere(radius), ColliderKind::Sphere(other_radius)) => {
+                let overlap = radius + other_radius - offset.length();
+                if overlap <= 0.0 {
+                    return None;
+                }
+                // push along an arbitrary axis if the centres are exactly on top of each other
+                Some(offset.try_normalize().unwrap_or(Vec3::X) * overlap)
+            }
+            (ColliderKind::Sphere(radius), ColliderKind::Aabb(size)) => {
+                let closest = offset.clamp(-size, size);
+                let outside = offset - closest;
+                if outside != Vec3::ZERO {
+                    let overlap = radius - outside.length();
+                    if overlap <= 0.0 {
+                        return None;
+                    }
+                    return Some(outside.normalize() * overlap);
+                }
+
+                // the centre is inside the box, so push out through the nearest face
+                let axis = Self::min_axis(size - offset.abs());
+                Some(axis * offset.signum() * ((size - offset.abs()) * axis + radius))
+            }
+            (ColliderKind::Aabb(_), ColliderKind::Sphere(_)) => other
+                .penetration(other_position, self, position)
+                .map(|translation| -translation),
+            (ColliderKind::Aabb(size), ColliderKind::Aabb(other_size)) => {
+                let overlap = size + other_size - offset.abs();
+                if overlap.cmple(Vec3::ZERO).any() {
+                    return None;
+                }
+                let axis = Self::min_axis(overlap);
+                Some(axis * offset.signum() * overlap)
+            }
+        }
+    }
+
+    /// A unit vector along the axis with the smallest component
+    fn min_axis(v: Vec3) -> Vec3 {
+        if v.x <= v.y && v.x <= v.z {
+            Vec3::X
+        } else if v.y <= v.z {
+            Vec3::Y
+        } else {
+            Vec3::Z
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum ColliderPositionKind {
     Absolute(Vec3),
     Relative(Vec3, EntityId), //offset, parent transform
@@ -50,14 +109,15 @@ impl Default for ColliderPositionKind {
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct Collider {
     pub kind: ColliderKind,
-    // pub position: ColliderPositionKind,
-    pub position: Vec3,
+    pub position: ColliderPositionKind,
 }
 
 impl Collider {
-    pub fn within(&self, point: Vec3) -> bool {
-        // let point = point - self.calculate_position(world);
-        let point = point - self.position;
+    pub fn within(&self, point: Vec3, world: &World) -> bool {
+        let Some(position) = self.calculate_position(world) else {
+            return false;
+        };
+        let point = point - position;
 
         match self.kind {
             ColliderKind::Sphere(radius) => point.length() < radius,
@@ -69,25 +129,29 @@ impl Collider {
         }
     }
 
-    fn calculate_position(&self, world: &World) -> Vec3 {
-        return self.position;
-        // relative positioning of colliders not finished and reverted to old implementation
-
-        // return match self.position {
-        //     ColliderPositionKind::Absolute(pos) => pos,
-        //     ColliderPositionKind::Relative(rel_pos, parent_id) => {
-        //         let x = world
-        //             .get_component::<crate::transform::Transform>(parent_id)
-        //             .map(|t| *t)
-        //             .unwrap_or_default();
-        //         x.translation + rel_pos
-        //     }
-        // };
+    /// Whether any part of the collider lies within `radius` of `point`
+    pub fn within_radius(&self, point: Vec3, radius: f32, world: &World) -> bool {
+        let Some(position) = self.calculate_position(world) else {
+            return false;
+        };
+        ColliderKind::Sphere(radius)
+            .penetration(point, self.kind, position)
+            .is_some()
+    }
+
+    /// Returns the world position of the collider, or `None` if it is relative to an entity
+    /// that no longer exists.
+    pub fn calculate_position(&self, world: &World) -> Option<Vec3> {
+        match self.position {
+            ColliderPositionKind::Absolute(position) => Some(position),
+            ColliderPositionKind::Relative(offset, parent_id) => {
+                transform::global(world, parent_id).map(|transform| transform.translation + offset)
+            }
+        }
     }
 
     fn quadratic(a: f32, b: f32, c: f32) -> Option<(f32, f32)> {
         let discriminant = b * b - 4.0 * a * c;
-        println!("{}", discriminant);
         if discriminant < 0.0 {
             return None;
         }
@@ -97,7 +161,7 @@ impl Collider {
     }
 
     pub fn intersects(&self, mut ray: Ray, world: &World) -> Option<Vec3> {
-        let calculated_position = self.calculate_position(world);
+        let calculated_position = self.calculate_position(world)?;
 
         ray.translate(-calculated_position);
 
@@ -149,3 +213,87 @@ impl Collider {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use glam::Quat;
+    use tecs::prelude::*;
+
+    use super::*;
+    use crate::transform::Transform;
+
+    #[derive(Archetype)]
+    struct Dummy {
+        transform: Transform,
+        collider: Collider,
+    }
+
+    #[test]
+    pub fn test_sphere_sphere() {
+        let sphere = ColliderKind::Sphere(1.0);
+        let position = Vec3::new(1.5, 0.0, 0.0);
+
+        let translation = sphere.penetration(position, sphere, Vec3::ZERO).unwrap();
+        let resolved = position + translation;
+        assert!(resolved.length() >= 2.0 - 1e-4);
+        assert!(sphere
+            .penetration(position + Vec3::X, sphere, Vec3::ZERO)
+            .is_none());
+    }
+
+    #[test]
+    pub fn test_sphere_aabb() {
+        let sphere = ColliderKind::Sphere(1.0);
+        let aabb = ColliderKind::Aabb(Vec3::ONE);
+
+        // overlapping a face from outside the box
+        let position = Vec3::new(1.5, 0.0, 0.0);
+        let resolved = position + sphere.penetration(position, aabb, Vec3::ZERO).unwrap();
+        assert!(resolved.x >= 2.0 - 1e-4);
+
+        // centre inside the box
+        let position = Vec3::new(0.0, 0.0, -0.8);
+        let resolved = position + sphere.penetration(position, aabb, Vec3::ZERO).unwrap();
+        assert!(resolved.z <= -2.0 + 1e-4);
+        assert!(sphere
+            .penetration(resolved - Vec3::Z * 1e-3, aabb, Vec3::ZERO)
+            .is_none());
+    }
+
+    #[test]
+    pub fn test_moving_raycast() {
+        let world = World::new().register_unsaved::<Dummy>();
+        let dummy = world.spawn(Dummy {
+            transform: Transform::new(Vec3::ZERO, Quat::IDENTITY, Vec3::ONE),
+            collider: Collider {
+                kind: ColliderKind::Sphere(1.0),
+                position: ColliderPositionKind::default(),
+            },
+        });
+        let collider = {
+            let mut collider = world.get_component_mut::<Collider>(dummy).unwrap();
+            collider.position = ColliderPositionKind::Relative(Vec3::Y, dummy);
+            *collider
+        };
+        let down = |x: f32| Ray {
+            origin: Vec3::new(x, 10.0, 0.0),
+            direction: -Vec3::Y,
+        };
+
+        let hit = collider.intersects(down(0.0), &world).unwrap();
+        assert!(hit.abs_diff_eq(Vec3::new(0.0, 2.0, 0.0), 1e-4));
+
+        // the collider follows the dummy, so it is missed where it used to be
+        world
+            .get_component_mut::<Transform>(dummy)
+            .unwrap()
+            .translation = Vec3::X * 5.0;
+        assert!(collider.intersects(down(0.0), &world).is_none());
+        let hit = collider.intersects(down(5.0), &world).unwrap();
+        assert!(hit.abs_diff_eq(Vec3::new(5.0, 2.0, 0.0), 1e-4));
+
+        // and nothing is hit once the dummy is gone
+        world.despawn::<Dummy>(dummy);
+        assert!(collider.intersects(down(5.0), &world).is_none());
+    }
+}
@@ -1,4 +1,4 @@
-use glam::Vec4;
+use glam::{Vec3, Vec4};
 use nyx::item::Rarity;
 
 pub const COMMON: Vec4 = Vec4::ONE;
@@ -16,3 +16,15 @@ pub fn rarity_colour(rarity: Rarity) -> Vec4 {
         Rarity::Legendary => LEGENDARY
     }
 }
+
+/// Converts an srgb colour to linear, for the places the gpu doesn't do it, such as clearing
+/// an srgb image
+pub fn srgb_to_linear(colour: Vec3) -> Vec3 {
+    Vec3::from_array(colour.to_array().map(|channel| {
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    }))
+}
@@ -1,225 +1,253 @@
 use glam::{Quat, Vec3, Vec4};
-use log::{error, info, trace, warn};
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use tecs::{EntityId, Is};
+use tecs::{utils::State, Archetype, EntityId, Is};
+
+use nyx::protocol::{Clientbound, Serverbound};
 
 use crate::{
+    buff::Buffs,
     camera::Camera,
-    casting::Skill,
+    equipment::{equipped_passives, EquipmentBonus},
+    event::Event,
+    net::Connection,
     player::Player,
     renderer::RenderObject,
     targeting::{Selectable, SelectedEntity},
     transform::Transform,
-    window::Keyboard,
+    window::{Keybind, Keyboard},
     TargetDummy, World,
 };
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct AttackType {
-    pub damage: u32,
-    pub penetration: u32,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CombatOffensive {
-    pub fire: AttackType,
-    pub earth: AttackType,
-    pub lightning: AttackType,
-    pub air: AttackType,
-    pub nature: AttackType,
+pub use nyx::combat::*;
 
-    pub true_damage: u32,
-    pub equiped_skills: Vec<Skill>,
+/// Which side an entity is on, deciding which skills can be cast on it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Faction {
+    Player,
+    Enemy,
+    Neutral,
 }
 
-impl std::fmt::Display for CombatOffensive {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "CombatOffensive: \nfire: {:?}\nearth: {:?}\nlightning: {:?}\nair: {:?}\nnature: {:?}\ntrue_damage: {:?}",
-            self.fire, self.earth, self.lightning, self.air, self.nature, self.true_damage
-        )
+impl Faction {
+    pub fn is_friendly(self, other: Faction) -> bool {
+        self == other && self != Faction::Neutral
     }
-}
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-pub struct CombatDefensive {
-    pub health: u32,
-    pub max_health: u32,
-    pub fire_resistance: u32,
-    pub earth_resistance: u32,
-    pub lightning_resistance: u32,
-    pub air_resistance: u32,
-    pub nature_resistance: u32,
-}
-
-impl std::fmt::Display for CombatDefensive {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "CombatDefensive: \nhealth: {}\nfire_resistance: {}\nearth_resistance: {}\nlightning_resistance: {}\nair_resistance: {}\nnature_resistance: {}\n",
-            self.health, self.fire_resistance, self.earth_resistance, self.lightning_resistance, self.air_resistance, self.nature_resistance
+    pub fn is_enemy(self, other: Faction) -> bool {
+        matches!(
+            (self, other),
+            (Faction::Player, Faction::Enemy) | (Faction::Enemy, Faction::Player)
         )
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub struct AttackOutcome {
-    pub fire_damage: u32,
-    pub earth_damage: u32,
-    pub lightning_damage: u32,
-    pub air_damage: u32,
-    pub nature_damage: u32,
-
-    pub true_damage: u32,
+/// Announces the deaths of entities killed this tick, leaving them to be despawned once every
+/// system has had a chance to react to the [`Event::EntityDied`]
+pub fn kill<T: Archetype>(world: &World, died: Vec<EntityId>) {
+    let mut announced = Vec::new();
+    for id in died {
+        // an entity can be hit by more than one attack in the same tick
+        if announced.contains(&id) {
+            continue;
+        }
+        announced.push(id);
 
-    pub post_attack_health: u32,
+        world.submit(Event::EntityDied(id));
+        world.defer(move |world| world.despawn::<T>(id));
+    }
 }
 
-impl AttackOutcome {
-    pub fn sum_damage(&self) -> u32 {
-        return self.fire_damage
-            + self.earth_damage
-            + self.lightning_damage
-            + self.air_damage
-            + self.nature_damage
-            + self.true_damage;
+/// Attacks an entity, returning whether it died. While connected, attacks on entities the
+/// server knows about are resolved by the server, which works out the damage of the `kind` of
+/// attack itself and sends back their new health instead.
+pub fn attack(world: &World, id: EntityId, damage: Damage, kind: AttackKind) -> bool {
+    let target = world.get_component::<TargetId>(id).map(|target| *target);
+    if let (Some(target), Some(mut conn)) = (target, world.get_mut::<Connection>()) {
+        if conn.id.is_some() {
+            conn.write(Serverbound::Attack {
+                target,
+                attack: kind,
+            })
+            .unwrap();
+            return false;
+        }
     }
-}
 
-pub trait Attackable {
-    /// returns the new health of the attacked entity along with how much damge was done.
-    /// If the damage would put the player bellow 0, the health will be set to 0.
-    fn receive_attack(&mut self, damage_source: &CombatOffensive) -> AttackOutcome;
+    let Some(mut defensive) = world.get_component_mut::<CombatDefensive>(id) else {
+        return false;
+    };
+    let mut buffed = world
+        .get_component::<Buffs>(id)
+        .map_or(*defensive, |buffs| buffs.defensive(&defensive));
+    // only the entity wearing the equipment gets its resistance
+    if world.get_component::<EquipmentBonus>(id).is_some() {
+        buffed = equipped_passives(world).defensive(&buffed);
+    }
+    let health = damage.apply(&mut buffed);
+    defensive.health = health;
+    info!("{:?} was attacked, {} health left", id, health);
+    // the attacker is usually still borrowing the components of whoever it is
+    world.defer(move |world| world.submit(Event::AttackLanded(id)));
+    health == 0
 }
 
-impl Attackable for CombatDefensive {
-    fn receive_attack(&mut self, source: &CombatOffensive) -> AttackOutcome {
-        let fire_damage: u32;
-        if source.fire.penetration > self.fire_resistance {
-            fire_damage = source.fire.damage;
-        } else {
-            fire_damage =
-                source.fire.damage * 100 / (100 + (self.fire_resistance - source.fire.penetration));
-        }
+/// Takes the health of targets from the server, killing them when it runs out
+fn set_health(world: &World, event: &Event) {
+    let Event::Recieved(Clientbound::SetHealth(target, health)) = event else {
+        return;
+    };
+
+    let id = {
+        let (ids, targets) = world.query::<(EntityId, &TargetId)>();
+        let id = ids
+            .iter()
+            .zip(targets.iter())
+            .find(|(_, other)| *other == target)
+            .map(|(id, _)| *id);
+        id
+    };
+    let Some(id) = id else {
+        return;
+    };
+
+    let previous = {
+        let mut defensive = world.get_component_mut::<CombatDefensive>(id).unwrap();
+        std::mem::replace(&mut defensive.health, *health)
+    };
+    if *health < previous {
+        world.submit(Event::AttackLanded(id));
+    }
+    if *health != 0 || previous == 0 {
+        return;
+    }
 
-        // repeat for each type of damage
+    let (mut targeted, _) = world.query_one::<(&mut SelectedEntity, Is<Player>)>();
+    if matches!(*targeted, SelectedEntity::EntityId(selected) if selected == id) {
+        *targeted = SelectedEntity::None;
+    }
+    drop(targeted);
 
-        let earth_damage: u32;
-        if source.earth.penetration > self.earth_resistance {
-            earth_damage = source.earth.damage;
-        } else {
-            earth_damage = source.earth.damage * 100
-                / (100 + (self.earth_resistance - source.earth.penetration));
-        }
+    kill::<TargetDummy>(world, vec![id]);
+}
 
-        let lightning_damage: u32;
-        if source.lightning.penetration > self.lightning_resistance {
-            lightning_damage = source.lightning.damage;
-        } else {
-            lightning_damage = source.lightning.damage * 100
-                / (100 + (self.lightning_resistance - source.lightning.penetration));
-        }
+pub fn add(world: World) -> World {
+    world.with_ticker(tick).with_handler(set_health)
+}
 
-        let air_damage: u32;
-        if source.air.penetration > self.air_resistance {
-            air_damage = source.air.damage;
-        } else {
-            air_damage =
-                source.air.damage * 100 / (100 + (self.air_resistance - source.air.penetration));
-        }
+/// Attacks are triggered by key presses, which only last the frame they happen in, so this stays
+/// on the per-frame tick rather than the fixed step
+pub fn tick(world: &World) {
+    if State::paused(world) {
+        return;
+    }
+    let mut died = Vec::new();
+    let passives = equipped_passives(world);
+    let mut dealt = 0;
+
+    {
+        let keyboard = world.get::<Keyboard>().unwrap();
+
+        let (player_offensive, buffs, mut targeted, _) =
+            world.query_one::<(&CombatOffensive, &Buffs, &mut SelectedEntity, Is<Player>)>();
+        let player_offensive = buffs.offensive(&player_offensive);
+
+        // attack every entity that is a target dummy
+        if keyboard.pressed(Keybind::AttackAll) {
+            let (dummy_ids, _) = world.query::<(EntityId, Is<crate::TargetDummy>)>();
+            for (index, id) in dummy_ids.iter().enumerate() {
+                let offensive = passives.roll_crit(&player_offensive);
+                dealt += offensive.total_damage();
+                if attack(world, *id, Damage::Attack(offensive), AttackKind::Basic) {
+                    // print to console including position
+                    let dummy_transfrom = world.get_component::<Transform>(*id).unwrap();
+                    info!(
+                        "Entity {} died at ({}, {}, {})",
+                        index,
+                        dummy_transfrom.translation.x,
+                        dummy_transfrom.translation.y,
+                        dummy_transfrom.translation.z
+                    );
 
-        let nature_damage: u32;
-        if source.nature.penetration > self.nature_resistance {
-            nature_damage = source.nature.damage;
-        } else {
-            nature_damage = source.nature.damage * 100
-                / (100 + (self.nature_resistance - source.nature.penetration));
+                    *targeted = SelectedEntity::None;
+                    died.push(*id);
+                }
+            }
         }
 
-        let total_damage = fire_damage
-            + earth_damage
-            + lightning_damage
-            + air_damage
-            + nature_damage
-            + source.true_damage;
-
-        // to prevent underflows check if the total damage is greater than health and if so set to 0
-        if total_damage > self.health {
-            self.health = 0;
-        } else {
-            self.health -= total_damage;
+        // attack the targeted entity
+        if keyboard.pressed(Keybind::AttackTarget) {
+            match *targeted {
+                SelectedEntity::None | SelectedEntity::Position(_) => warn!("No enemy Targeted"),
+                SelectedEntity::EntityId(targeted_id) => {
+                    let offensive = passives.roll_crit(&player_offensive);
+                    dealt += offensive.total_damage();
+                    let damage = Damage::Attack(offensive);
+                    if attack(world, targeted_id, damage, AttackKind::Basic) {
+                        died.push(targeted_id);
+                        *targeted = SelectedEntity::None;
+                    }
+                }
+            }
         }
+    }
 
-        return AttackOutcome {
-            fire_damage,
-            earth_damage,
-            lightning_damage,
-            air_damage,
-            nature_damage,
-
-            true_damage: source.true_damage,
-            post_attack_health: self.health,
-        };
+    // lifesteal is worked out from the damage before the target's resistances, as attacks on
+    // targets the server knows about are resolved there
+    let healed = passives.lifesteal(dealt);
+    if healed > 0 {
+        let (mut defensive, _) = world.query_one::<(&mut CombatDefensive, Is<Player>)>();
+        defensive.health = defensive
+            .health
+            .saturating_add(healed)
+            .min(defensive.max_health);
     }
+
+    // the requirement to pass in the type is a little annoying as this should work for any entity that implements Attackable
+    kill::<TargetDummy>(world, died);
 }
 
-pub fn tick(world: &World) {
-    let keyboard = world.get::<Keyboard>().unwrap();
-
-    let (player_offensive, mut targeted, _) =
-        world.query_one::<(&CombatOffensive, &mut SelectedEntity, Is<Player>)>();
-
-    // TODO! BROKEN WHEN PRESS Z AFTER DUMMY DIES
-    // attack every entity that is a target dummy
-    if keyboard.pressed("z") {
-        // let (player_offensive, _) = world.query_one::<(&CombatOffensive, Is<Player>)>();
-
-        let (dummy_ids, _) = world.query::<(EntityId, Is<crate::TargetDummy>)>();
-        for (index, id) in dummy_ids.iter().enumerate() {
-            let mut defense_struct = world
-                .get_component_mut::<crate::combat::CombatDefensive>(*id)
-                .unwrap();
-            let outcome = defense_struct.receive_attack(&player_offensive);
-            info!("Outcome from attack: {:?}", outcome);
-
-            if outcome.post_attack_health == 0 {
-                // print to console including position
-                let dummy_transfrom = world.get_component::<Transform>(*id).unwrap();
-                info!(
-                    "Entity {} died at ({}, {}, {})",
-                    index,
-                    dummy_transfrom.translation.x,
-                    dummy_transfrom.translation.y,
-                    dummy_transfrom.translation.z
-                );
-
-                *targeted = SelectedEntity::None;
-                world.despawn::<crate::TargetDummy>(*id);
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tecs::prelude::*;
+
+    #[derive(Archetype)]
+    struct Dummy {
+        defensive: CombatDefensive,
     }
 
-    // attack the targeted entity
-    if keyboard.pressed("x") {
-        match *targeted {
-            SelectedEntity::None => warn!("No enemy Targeted"),
-            SelectedEntity::EntityId(targeted_id) => {
-                let outcome = world
-                    .get_component_mut::<CombatDefensive>(targeted_id)
-                    .unwrap()
-                    .receive_attack(&player_offensive);
-                info!("Outcome from attack: {:?}", outcome);
-
-                if outcome.post_attack_health == 0 {
-                    // the requirement to pass in the type is a little annoying as this should work for any entity that implements Attackable
-                    world.despawn::<TargetDummy>(targeted_id);
-                    *targeted = SelectedEntity::None;
+    struct Deaths(usize);
+
+    #[test]
+    pub fn test_death_event_once() {
+        let world = World::new()
+            .register_unsaved::<Dummy>()
+            .with_resource(Deaths(0))
+            .with_handler(|world, event| {
+                if let Event::EntityDied(_) = event {
+                    world.get_mut::<Deaths>().unwrap().0 += 1;
                 }
-            }
-            SelectedEntity::Position(_) => {
-                error!("area of effect/non entity targeting not implemented")
-            }
-        }
+            });
+
+        let id = world.spawn(Dummy {
+            defensive: CombatDefensive {
+                health: 0,
+                max_health: 100,
+                fire_resistance: 0,
+                earth_resistance: 0,
+                lightning_resistance: 0,
+                air_resistance: 0,
+                nature_resistance: 0,
+            },
+        });
+
+        kill::<Dummy>(&world, vec![id, id]);
+        assert_eq!(world.get::<Deaths>().unwrap().0, 1);
+        // still around for anything reacting to the death until the end of the tick
+        assert!(world.get_component::<CombatDefensive>(id).is_some());
+
+        world.tick();
+        assert!(world.get_component::<CombatDefensive>(id).is_none());
     }
 }
@@ -1,8 +1,8 @@
 use glam::{Vec2, Vec4};
 use nyx::{
-    data,
+    data::Content,
     item::{Inventory, Item, Rarity, Recipe, RecipeOutput, RARITIES},
-    protocol::Serverbound, task::Proficiencies,
+    protocol::{Clientbound, Serverbound}, task::Proficiencies,
 };
 use styx::{
     components::{
@@ -17,13 +17,20 @@ use crate::{
     event::Event,
     net::Connection,
     renderer::{Anchor, Ui},
-    window::Keyboard,
+    window::{Keybind, Keyboard},
     World,
 };
 
+const LOCKED_COLOUR: Vec4 = Vec4::new(0.4, 0.4, 0.4, 1.0);
+
 pub struct CraftUi {
     open: bool,
     craft: Signal,
+    more: Signal,
+    less: Signal,
+    max: Signal,
+    // how many times the selected recipe is crafted at once
+    count: usize,
     recipe: Option<usize>,
     recipes: Vec<(Signal, Recipe)>,
     inputs: Vec<(Rarity, Vec<Signal>)>,
@@ -37,9 +44,16 @@ impl CraftUi {
             .map(|recipe| (ui.signals.signal(), recipe.clone()))
             .collect();
         let craft = ui.signals.signal();
+        let more = ui.signals.signal();
+        let less = ui.signals.signal();
+        let max = ui.signals.signal();
         Self {
             open: false,
             craft,
+            more,
+            less,
+            max,
+            count: 1,
             recipe: None,
             recipes,
             inputs: Vec::new(),
@@ -50,7 +64,7 @@ impl CraftUi {
 impl SystemMut<Event> for CraftUi {
     fn tick(&mut self, world: &World) {
         let keyboard = world.get::<Keyboard>().unwrap();
-        if keyboard.pressed("c") {
+        if keyboard.pressed(Keybind::Craft) {
             self.open = !self.open;
         }
 
@@ -60,14 +74,22 @@ impl SystemMut<Event> for CraftUi {
 
         let mut ui = world.get_mut::<Ui>().unwrap();
         let inventory = world.get_mut::<Inventory>().unwrap();
+        let proficiencies = world.get::<Proficiencies>().unwrap();
 
         let mut view = VGroup::new(VAlign::Top, 32.0);
+        let items = inventory.items().collect::<Vec<_>>();
 
+        // clicking a locked recipe asks the server to learn it
+        let mut learn = None;
         let recipes = self.recipes.iter().enumerate().fold(
             HGroup::new(HAlign::Left, 16.0),
             |component, (i, (signal, recipe))| {
-                if ui.signals.get(*signal) {
+                let unlocked = proficiencies.is_unlocked(&recipe.id);
+                if ui.signals.get(*signal) && !unlocked {
+                    learn = Some(recipe.id.clone());
+                } else if ui.signals.get(*signal) {
                     self.recipe = Some(i);
+                    self.count = 1;
                     self.inputs = (0..self.recipes[i].1.inputs.len())
                         .map(|_| {
                             (
@@ -84,6 +106,10 @@ impl SystemMut<Event> for CraftUi {
                     }
                     RecipeOutput::Equipment(equipment) => equipment.to_string(),
                 };
+                // how many can be made from common inputs, which recipes start with
+                let max =
+                    recipe.max_craftable(&items, &vec![Rarity::Common; recipe.inputs.len()]);
+                let text = format!("{text} ({max})");
 
                 component.add(Clicked {
                     signal: *signal,
@@ -91,7 +117,7 @@ impl SystemMut<Event> for CraftUi {
                         text,
                         font_size: 48.0,
                         font: ui.font.clone(),
-                        colour: Vec4::ONE,
+                        colour: if unlocked { Vec4::ONE } else { LOCKED_COLOUR },
                     },
                 })
             },
@@ -109,6 +135,26 @@ impl SystemMut<Event> for CraftUi {
         if let Some(index) = &self.recipe {
             let recipe = &self.recipes.get(*index).unwrap().1;
 
+            if ui.signals.get(self.more) && recipe.output.batchable() {
+                self.count += 1;
+            }
+            if ui.signals.get(self.less) {
+                self.count = self.count.saturating_sub(1).max(1);
+            }
+            let max = recipe.max_craftable(
+                &items,
+                &self
+                    .inputs
+                    .iter()
+                    .map(|(rarity, _)| *rarity)
+                    .collect::<Vec<_>>(),
+            );
+            if ui.signals.get(self.max) {
+                self.count = max.max(1);
+            }
+            let count = self.count;
+            let affordable = proficiencies.is_unlocked(&recipe.id) && count <= max;
+
             let inputs = recipe.inputs.iter().cloned().zip(&mut self.inputs).fold(
                 HGroup::new(HAlign::Left, 16.0).add(text("Inputs:", 48.0, ui.font.clone())),
                 |inputs, ((kind, quantity), (rarity, signals))| {
@@ -122,7 +168,7 @@ impl SystemMut<Event> for CraftUi {
                         });
 
                     let left = Text {
-                        text: format!("{} x {}", kind, quantity),
+                        text: format!("{} x {}", kind, quantity * count),
                         font_size: 48.0,
                         font: ui.font.clone(),
                         colour: rarity_colour(*rarity),
@@ -165,7 +211,7 @@ impl SystemMut<Event> for CraftUi {
                 })
                 .add({
                     let tags = recipe.output.tags();
-                    let rank_up = world.get::<Proficiencies>().unwrap().rank_up.get(&tags);
+                    let rank_up = proficiencies.rank_up_for(&tags);
                     let chances = recipe.rarity_chances(
                         &self
                             .inputs
@@ -188,20 +234,40 @@ impl SystemMut<Event> for CraftUi {
                     )
                 });
 
+            let quantity = HGroup::new(HAlign::Left, 16.0)
+                .add(Clicked {
+                    signal: self.less,
+                    child: text("-", 48.0, ui.font.clone()),
+                })
+                .add(text(count, 48.0, ui.font.clone()))
+                .add(Clicked {
+                    signal: self.more,
+                    child: text("+", 48.0, ui.font.clone()),
+                })
+                .add(Clicked {
+                    signal: self.max,
+                    child: text(format!("Max ({max})"), 48.0, ui.font.clone()),
+                });
+
             let button = Clicked {
                 signal: self.craft,
                 child: Container {
                     padding: 32.0,
                     colour: Vec4::new(0.2, 0.2, 0.2, 1.0),
                     radius: 8.0,
-                    child: text("Craft", 48.0, ui.font.clone()),
+                    child: Text {
+                        text: String::from("Craft"),
+                        font_size: 48.0,
+                        font: ui.font.clone(),
+                        colour: if affordable { Vec4::ONE } else { LOCKED_COLOUR },
+                    },
                 },
             };
 
             let recipe = HGroup::new(HAlign::Left, 96.0)
                 .add(inputs)
                 .add(output)
-                .add(button);
+                .add(VGroup::new(VAlign::Top, 16.0).add(quantity).add(button));
             let recipe = Container {
                 child: recipe,
                 padding: 32.0,
@@ -225,19 +291,52 @@ impl SystemMut<Event> for CraftUi {
                 .iter()
                 .map(|(rarity, _)| *rarity)
                 .collect::<Vec<_>>();
-            if recipe.craftable(&inventory.items().collect::<Vec<_>>(), &rarities)
+            if proficiencies.is_unlocked(&recipe.id)
+                && recipe.craftable(&inventory.items().collect::<Vec<_>>(), &rarities, self.count)
                 && ui.signals.get(self.craft)
             {
                 let mut conn = world.get_mut::<Connection>().unwrap();
-                conn.write(Serverbound::Craft(*index, rarities)).unwrap();
+                conn.write(Serverbound::Craft(recipe.id.clone(), rarities, self.count))
+                    .unwrap();
             }
         }
 
+        if let Some(id) = learn {
+            let mut conn = world.get_mut::<Connection>().unwrap();
+            conn.write(Serverbound::LearnRecipe(id)).unwrap();
+        }
+
         ui.add(Anchor::Center, view);
     }
 }
 
+fn unlock(world: &World, event: &Event) {
+    if let Event::Recieved(Clientbound::UnlockRecipe(id)) = event {
+        world
+            .get_mut::<Proficiencies>()
+            .unwrap()
+            .unlocked
+            .insert(id.clone());
+    }
+}
+
+/// Takes the ranks the server has worked out after a craft, which keeps the chances shown up to
+/// date
+fn set_rank(world: &World, event: &Event) {
+    if let Event::Recieved(Clientbound::SetRank(tag, rank)) = event {
+        world
+            .get_mut::<Proficiencies>()
+            .unwrap()
+            .ranks
+            .insert(*tag, *rank);
+    }
+}
+
 pub fn add(world: World) -> World {
-    let ui = CraftUi::new(&world, &data::recipes());
-    world.with_system_mut(ui)
+    let recipes = world.get::<Content>().unwrap().recipes.clone();
+    let ui = CraftUi::new(&world, &recipes);
+    world
+        .with_system_mut(ui)
+        .with_handler(unlock)
+        .with_handler(set_rank)
 }
@@ -0,0 +1,98 @@
+use glam::{Vec2, Vec4};
+use nyx::{equipment::EquipmentId, item::Item};
+use styx::components::{Container, Offset, Text};
+use winit::event::MouseButton;
+
+use crate::{
+    event::Event,
+    renderer::{Anchor, Ui},
+    World,
+};
+
+/// What is being dragged. Items are dragged a whole stack at a time, as the inventory only
+/// keeps one stack of each item, so there is nowhere to put half of one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Payload {
+    Equipment(EquipmentId),
+    Item(Item),
+}
+
+struct Dragged {
+    payload: Payload,
+    label: String,
+    colour: Vec4,
+}
+
+/// The drag in progress. UI elements are rebuilt every frame, so it is kept here rather than in
+/// any of them.
+#[derive(Default)]
+pub struct Drag {
+    dragging: Option<Dragged>,
+    // the mouse was let go this tick, so whatever is being dragged gets dropped
+    released: bool,
+}
+
+impl Drag {
+    /// Picks something up, showing `label` at the cursor until it is dropped
+    pub fn start(&mut self, payload: Payload, label: String, colour: Vec4) {
+        self.dragging = Some(Dragged {
+            payload,
+            label,
+            colour,
+        });
+    }
+
+    /// What is being dropped this tick, if anything. Drop targets are told by their signals.
+    pub fn dropped(&self) -> Option<Payload> {
+        self.dragging
+            .as_ref()
+            .filter(|_| self.released)
+            .map(|dragged| dragged.payload)
+    }
+}
+
+fn mouse(world: &World, event: &Event) {
+    let mut drag = world.get_mut::<Drag>().unwrap();
+    match event {
+        Event::MousePress(MouseButton::Left) => drag.released = false,
+        Event::MouseRelease(MouseButton::Left) => drag.released = true,
+        _ => (),
+    }
+}
+
+/// Draws what is being dragged under the cursor, and lets go of it once every system has had the
+/// chance to see it dropped
+fn tick(world: &World) {
+    let drag = world.get::<Drag>().unwrap();
+    if drag.released {
+        world.defer(|world| *world.get_mut::<Drag>().unwrap() = Drag::default());
+        return;
+    }
+    let Some(dragged) = &drag.dragging else {
+        return;
+    };
+
+    let mut ui = world.get_mut::<Ui>().unwrap();
+    let ghost = Offset {
+        offset: Vec2::new(8.0, 8.0),
+        child: Container {
+            padding: 8.0,
+            radius: 4.0,
+            colour: Vec4::new(0.1, 0.1, 0.1, 0.8),
+            child: Text {
+                text: dragged.label.clone(),
+                font_size: 32.0,
+                font: ui.font.clone(),
+                colour: dragged.colour,
+            },
+        },
+    };
+    ui.add(Anchor::Cursor, ghost);
+}
+
+pub fn add(world: World) -> World {
+    world
+        .with_resource(Drag::default())
+        .with_handler(mouse)
+        .with_ticker(tick)
+}
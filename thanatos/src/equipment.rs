@@ -1,33 +1,144 @@
-use glam::{Vec2, Vec4};
+use glam::{Quat, Vec2, Vec4};
+use log::warn;
 use nyx::{
-    equipment::{EquipmentId, EquipmentInventory, Equipped, Passive},
+    combat::CombatOffensive,
+    equipment::{
+        combat_bonus, Equipment, EquipmentId, EquipmentInventory, EquipmentKind, Equipped, Passive,
+        PassiveStats, Slot,
+    },
     item::{Inventory, Item, ItemStack},
     protocol::{Clientbound, Serverbound},
 };
 use styx::{
     components::{
-        text, Clicked, Constrain, Container, HAlign, HGroup, RightClicked, Text, VAlign, VGroup,
+        text, Clicked, Constrain, Container, Dropped, HAlign, HGroup, Hovered, Image, RightClicked,
+        Text, VAlign, VGroup,
     },
     Constraint, Signal,
 };
-use tecs::SystemMut;
+use serde::{Deserialize, Serialize};
+use tecs::{utils::Clock, SystemMut};
 
 use crate::{
+    assets::{MaterialId, MeshId},
     colours::rarity_colour,
+    drag::{Drag, Payload},
     event::Event,
     net::Connection,
-    renderer::{Anchor, Ui},
-    window::Keyboard,
+    renderer::{Anchor, RenderObject, Renderer, Ui},
+    tooltip::tooltip,
+    window::{Keybind, Keyboard},
     World,
 };
 
+/// The stats equipment has added to an entity's offensive stats, so they can be taken off again
+/// when the equipment changes
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct EquipmentBonus(pub CombatOffensive);
+
 pub struct EquipmentUi {
     open: bool,
     refine: Signal,
     reagent: Option<Item>,
     reagents: Vec<(Item, Signal)>,
     refining: Option<EquipmentId>,
-    signals: Vec<(EquipmentId, (Signal, Signal))>,
+    // the piece the cursor is over, compared against what is equipped if it isn't equipped itself
+    hovered: Option<EquipmentId>,
+    // drag, refine, hover and drop signals for each piece
+    signals: Vec<(EquipmentId, (Signal, Signal, Signal, Signal))>,
+    // drag and drop signals for each slot
+    slots: Vec<(Slot, (Signal, Signal))>,
+    // dropping an equipped piece on the list takes it off
+    list: Signal,
+    // how far the weapon preview has turned, in radians
+    spin: f32,
+    // the weapon last drawn into the preview, and how long ago
+    previewed: Option<EquipmentId>,
+    since_preview: f32,
+}
+
+// the width and height of the weapon preview in pixels
+const PREVIEW_SIZE: u32 = 256;
+// the least time between drawing the spinning weapon again, in seconds
+const PREVIEW_INTERVAL: f32 = 1.0 / 30.0;
+
+/// What a piece of equipment looks like. There are no models for equipment yet, so every piece
+/// is a plain cube.
+fn model(_kind: EquipmentKind) -> RenderObject {
+    RenderObject {
+        mesh: MeshId(String::from("assets/meshes/cube.glb")),
+        material: MaterialId::white(),
+    }
+}
+
+/// Where a dragged piece of equipment was dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DropTarget {
+    Slot(Slot),
+    Piece(EquipmentId),
+    List,
+}
+
+/// Equips, unequips or moves a piece of equipment dropped on a target, returning false if the
+/// piece can't go there
+fn drop_equipment(
+    equipped: &mut Equipped,
+    equipment: &mut EquipmentInventory,
+    id: EquipmentId,
+    target: DropTarget,
+) -> bool {
+    let Some(index) = equipment.0.iter().position(|piece| piece.id == id) else {
+        return false;
+    };
+
+    match target {
+        DropTarget::Slot(slot) => equipped.equip(slot, &equipment.0[index]),
+        // picking a piece up and putting it straight back down is a click
+        DropTarget::Piece(other) if other == id => {
+            equipped.toggle(&equipment.0[index]);
+            true
+        }
+        DropTarget::Piece(other) => {
+            let piece = equipment.0.remove(index);
+            let position = equipment
+                .0
+                .iter()
+                .position(|piece| piece.id == other)
+                .unwrap_or(equipment.0.len());
+            equipment.0.insert(position, piece);
+            true
+        }
+        DropTarget::List => {
+            let slot = equipment.0[index].kind.slot();
+            if equipped.get(slot) == Some(id) {
+                equipped.unequip(slot);
+            }
+            true
+        }
+    }
+}
+
+/// The piece to refine with an item dropped on a target, if it is a reagent dropped on a piece
+fn drop_item(item: Item, target: DropTarget) -> Option<EquipmentId> {
+    match target {
+        DropTarget::Piece(id) if item.passive().is_some() => Some(id),
+        _ => None,
+    }
+}
+
+const BETTER_COLOUR: Vec4 = Vec4::new(0.4, 1.0, 0.4, 1.0);
+const WORSE_COLOUR: Vec4 = Vec4::new(1.0, 0.4, 0.4, 1.0);
+
+/// The stats shown when comparing equipment
+fn stat_lines(stats: &CombatOffensive) -> [(&'static str, u32); 6] {
+    [
+        ("Damage", stats.true_damage),
+        ("Fire", stats.fire.damage),
+        ("Earth", stats.earth.damage),
+        ("Lightning", stats.lightning.damage),
+        ("Air", stats.air.damage),
+        ("Nature", stats.nature.damage),
+    ]
 }
 
 impl EquipmentUi {
@@ -39,7 +150,13 @@ impl EquipmentUi {
             reagent: None,
             reagents: Vec::new(),
             refining: None,
+            hovered: None,
             signals: Vec::new(),
+            slots: Vec::new(),
+            list: ui.signals.signal(),
+            spin: 0.0,
+            previewed: None,
+            since_preview: 0.0,
         }
     }
 }
@@ -47,7 +164,7 @@ impl EquipmentUi {
 impl SystemMut<Event> for EquipmentUi {
     fn tick(&mut self, world: &World) {
         let keyboard = world.get::<Keyboard>().unwrap();
-        if keyboard.pressed("e") {
+        if keyboard.pressed(Keybind::Equipment) {
             self.open = !self.open;
         }
 
@@ -57,67 +174,297 @@ impl SystemMut<Event> for EquipmentUi {
 
         let mut ui = world.get_mut::<Ui>().unwrap();
         let mut equipped = world.get_mut::<Equipped>().unwrap();
+        let mut equipment = world.get_mut::<EquipmentInventory>().unwrap();
+        let mut drag = world.get_mut::<Drag>().unwrap();
 
-        self.signals.drain(..).for_each(|(equipment, signal)| {
+        let mut picked = None;
+        // slots take priority over the pieces in the list, which take priority over the list
+        let mut target = ui.signals.get(self.list).then_some(DropTarget::List);
+
+        self.hovered = None;
+        self.signals.drain(..).for_each(|(id, signal)| {
             if ui.signals.get(signal.0) {
-                equipped.weapon = match equipped.weapon {
-                    Some(current) if current == equipment => None,
-                    _ => Some(equipment),
-                }
+                picked = Some(id);
             }
 
             if ui.signals.get(signal.1) {
-                self.refining = Some(equipment);
+                self.refining = Some(id);
+            }
+
+            if ui.signals.get(signal.2) {
+                self.hovered = Some(id);
+            }
+
+            if ui.signals.get(signal.3) {
+                target = Some(DropTarget::Piece(id));
             }
         });
+        self.slots.drain(..).for_each(|(slot, signal)| {
+            if ui.signals.get(signal.0) {
+                picked = equipped.get(slot);
+            }
 
-        let equipment = world.get::<EquipmentInventory>().unwrap();
+            if ui.signals.get(signal.1) {
+                target = Some(DropTarget::Slot(slot));
+            }
+        });
+
+        if let Some(piece) = picked.and_then(|id| equipment.0.iter().find(|piece| piece.id == id)) {
+            drag.start(
+                Payload::Equipment(piece.id),
+                piece.kind.to_string(),
+                rarity_colour(piece.rarity),
+            );
+        }
+
+        match (drag.dropped(), target) {
+            (Some(Payload::Equipment(id)), Some(target)) => {
+                let before = equipped.clone();
+                if drop_equipment(&mut equipped, &mut equipment, id, target) {
+                    sync_equipped(world, &before, &equipped);
+                } else {
+                    warn!("{:?} can't be dropped on {:?}", id, target);
+                }
+            }
+            // a reagent dropped on a piece is picked to refine it with
+            (Some(Payload::Item(item)), Some(target)) => match drop_item(item, target) {
+                Some(id) => {
+                    self.refining = Some(id);
+                    self.reagent = Some(item);
+                }
+                None => warn!("{:?} can't be dropped on {:?}", item, target),
+            },
+            _ => (),
+        }
+
+        let slots = equipped.clone();
         let equipped = equipped.equipment().collect::<Vec<_>>();
 
-        let list = equipment.0.iter().fold(
-            HGroup::new(styx::components::HAlign::Left, 16.0),
-            |list, equipable| {
-                let mut colour = rarity_colour(equipable.rarity);
-                if !equipped.iter().any(|id| equipable.id == *id) {
-                    colour *= Vec4::new(0.5, 0.5, 0.5, 1.0)
+        let delta = world.get::<Clock>().unwrap().delta.as_secs_f32();
+        self.spin += delta;
+        self.since_preview += delta;
+        let weapon = slots
+            .weapon
+            .and_then(|id| equipment.0.iter().find(|piece| piece.id == id));
+        // the preview is only drawn again when the weapon changes, or every so often as it spins
+        let due = self.previewed != slots.weapon || self.since_preview >= PREVIEW_INTERVAL;
+        // there is no renderer to draw the preview with when running headless
+        let preview = weapon
+            .zip(world.get_mut::<Renderer>())
+            .and_then(|(weapon, mut renderer)| {
+                if !due {
+                    return renderer.preview_image();
                 }
+                self.previewed = slots.weapon;
+                self.since_preview = 0.0;
+                renderer
+                    .preview(
+                        &model(weapon.kind),
+                        Quat::from_rotation_y(self.spin),
+                        PREVIEW_SIZE,
+                    )
+                    .inspect_err(|e| warn!("Failed to draw the weapon preview: {e}"))
+                    .ok()
+            });
+
+        let worn = Slot::ALL.into_iter().fold(HGroup::new(HAlign::Left, 8.0), |worn, slot| {
+            let signals = (ui.signals.signal(), ui.signals.signal());
+            self.slots.push((slot, signals));
+
+            let piece = slots
+                .get(slot)
+                .and_then(|id| equipment.0.iter().find(|piece| piece.id == id));
+            let (name, colour) = piece.map_or((String::from("Empty"), Vec4::splat(0.5)), |piece| {
+                (piece.kind.to_string(), rarity_colour(piece.rarity))
+            });
+            worn.add(Dropped {
+                signal: signals.1,
+                child: Clicked {
+                    signal: signals.0,
+                    child: Text {
+                        text: format!("{slot}: {name}"),
+                        font_size: 32.0,
+                        font: ui.font.clone(),
+                        colour,
+                    },
+                },
+            })
+        });
+        let worn = match preview {
+            Some(view) => worn.add(Image {
+                view,
+                size: Vec2::splat(PREVIEW_SIZE as f32),
+            }),
+            None => worn,
+        };
+        let worn = Container {
+            padding: 32.0,
+            colour: Vec4::new(0.1, 0.1, 0.1, 1.0),
+            radius: 8.0,
+            child: worn,
+        };
 
-                let signals = (ui.signals.signal(), ui.signals.signal());
-                self.signals.push((equipable.id, signals));
+        // the inventory is grouped under the slot each piece is worn in
+        let list = Slot::ALL.into_iter().fold(
+            HGroup::new(styx::components::HAlign::Left, 16.0),
+            |list, slot| {
+                let pieces = equipment
+                    .0
+                    .iter()
+                    .filter(|piece| piece.kind.slot() == slot)
+                    .collect::<Vec<_>>();
+                if pieces.is_empty() {
+                    return list;
+                }
 
-                let desc = HGroup::new(HAlign::Left, 8.0).add(Text {
-                    text: format!("{}", equipable.kind),
-                    font_size: 48.0,
+                let list = list.add(Text {
+                    text: slot.to_string(),
+                    font_size: 32.0,
                     font: ui.font.clone(),
-                    colour,
+                    colour: Vec4::new(0.7, 0.7, 0.7, 1.0),
                 });
-                let desc = equipable.passives.iter().fold(desc, |desc, passive| {
-                    desc.add(Text {
-                        text: passive.to_string(),
-                        font_size: 24.0,
+                pieces.into_iter().fold(list, |list, equipable| {
+                    let mut colour = rarity_colour(equipable.rarity);
+                    if !equipped.iter().any(|id| equipable.id == *id) {
+                        colour *= Vec4::new(0.5, 0.5, 0.5, 1.0)
+                    }
+
+                    let signals = (
+                        ui.signals.signal(),
+                        ui.signals.signal(),
+                        ui.signals.signal(),
+                        ui.signals.signal(),
+                    );
+                    self.signals.push((equipable.id, signals));
+
+                    let desc = HGroup::new(HAlign::Left, 8.0).add(Text {
+                        text: format!("{}", equipable.kind),
+                        font_size: 48.0,
                         font: ui.font.clone(),
                         colour,
-                    })
-                });
+                    });
+                    let desc = equipable.passives.iter().fold(desc, |desc, passive| {
+                        desc.add(Text {
+                            text: passive.to_string(),
+                            font_size: 24.0,
+                            font: ui.font.clone(),
+                            colour,
+                        })
+                    });
 
-                list.add(RightClicked {
-                    signal: signals.1,
-                    child: Clicked {
-                        signal: signals.0,
-                        child: desc,
-                    },
+                    list.add(Dropped {
+                        signal: signals.3,
+                        child: RightClicked {
+                            signal: signals.1,
+                            child: Clicked {
+                                signal: signals.0,
+                                child: Hovered {
+                                    signal: signals.2,
+                                    child: desc,
+                                },
+                            },
+                        },
+                    })
                 })
             },
         );
 
-        let list = Container {
-            padding: 32.0,
-            colour: Vec4::new(0.1, 0.1, 0.1, 1.0),
-            radius: 8.0,
-            child: list,
+        let list = Dropped {
+            signal: self.list,
+            child: Container {
+                padding: 32.0,
+                colour: Vec4::new(0.1, 0.1, 0.1, 1.0),
+                radius: 8.0,
+                child: list,
+            },
         };
 
-        let mut view = VGroup::new(VAlign::Top, 32.0).add(list);
+        let mut view = VGroup::new(VAlign::Top, 32.0).add(worn).add(list);
+
+        if let Some(piece) = self
+            .hovered
+            .and_then(|id| equipment.0.iter().find(|piece| piece.id == id))
+        {
+            tooltip(&mut ui, piece.kind.to_string(), piece.rarity, piece.describe());
+        }
+
+        let hovered = self
+            .hovered
+            .filter(|id| !equipped.contains(id))
+            .and_then(|id| equipment.0.iter().find(|piece| piece.id == id));
+        if let Some(hovered) = hovered {
+            // compare against whatever is worn in the same slot
+            let current = slots
+                .get(hovered.kind.slot())
+                .and_then(|id| equipment.0.iter().find(|piece| piece.id == id));
+            let current_stats = current.map(Equipment::combat_bonus).unwrap_or_default();
+            let hovered_stats = hovered.combat_bonus();
+
+            let passives = |group: HGroup, piece: Option<&Equipment>| {
+                piece
+                    .iter()
+                    .flat_map(|piece| piece.passives.iter())
+                    .fold(group, |group, passive| {
+                        group.add(Text {
+                            text: passive.to_string(),
+                            font_size: 24.0,
+                            colour: Vec4::ONE,
+                            font: ui.font.clone(),
+                        })
+                    })
+            };
+
+            let lines = stat_lines(&hovered_stats)
+                .into_iter()
+                .zip(stat_lines(&current_stats))
+                .filter(|((_, after), (_, before))| *after != 0 || *before != 0)
+                .collect::<Vec<_>>();
+
+            let inputs = lines.iter().fold(
+                HGroup::new(HAlign::Left, 16.0).add(text("Equipped:", 48.0, ui.font.clone())),
+                |inputs, (_, (name, before))| {
+                    inputs.add(Text {
+                        text: format!("{name}: {before}"),
+                        font_size: 48.0,
+                        colour: Vec4::ONE,
+                        font: ui.font.clone(),
+                    })
+                },
+            );
+            let inputs = passives(inputs, current);
+
+            let outputs = lines.iter().fold(
+                HGroup::new(HAlign::Left, 16.0).add(text("Hovered:", 48.0, ui.font.clone())),
+                |outputs, ((name, after), (_, before))| {
+                    let delta = *after as i64 - *before as i64;
+                    outputs.add(Text {
+                        text: format!("{name}: {after} ({delta:+})"),
+                        font_size: 48.0,
+                        colour: match delta.signum() {
+                            1 => BETTER_COLOUR,
+                            -1 => WORSE_COLOUR,
+                            _ => Vec4::ONE,
+                        },
+                        font: ui.font.clone(),
+                    })
+                },
+            );
+            let outputs = passives(outputs, Some(hovered));
+
+            let comparison = Container {
+                child: HGroup::new(HAlign::Left, 96.0).add(inputs).add(outputs),
+                padding: 32.0,
+                colour: Vec4::new(0.1, 0.1, 0.1, 1.0),
+                radius: 8.0,
+            };
+            view = view.add(Constrain {
+                child: comparison,
+                constraint: Constraint {
+                    min: Vec2::ZERO,
+                    max: Vec2::new(800.0, 600.0),
+                },
+            });
+        }
 
         if let Some(input) = self.refining {
             self.reagents.drain(..).for_each(|(reagent, signal)| {
@@ -226,6 +573,50 @@ impl SystemMut<Event> for EquipmentUi {
     }
 }
 
+/// Tells the server about every slot that changed, as it works out the damage of attacks from
+/// what it thinks is equipped
+fn sync_equipped(world: &World, before: &Equipped, after: &Equipped) {
+    let mut conn = world.get_mut::<Connection>().unwrap();
+    if conn.id.is_none() {
+        return;
+    }
+    Slot::ALL
+        .into_iter()
+        .filter(|slot| before.get(*slot) != after.get(*slot))
+        .for_each(|slot| conn.write(Serverbound::Equip(slot, after.get(slot))).unwrap());
+}
+
+/// The passives of everything equipped, combined
+pub fn equipped_passives(world: &World) -> PassiveStats {
+    let equipped = world.get::<Equipped>().unwrap();
+    let equipment = world.get::<EquipmentInventory>().unwrap();
+    PassiveStats::new(equipped.worn(&equipment).flat_map(|piece| piece.passives.iter()))
+}
+
+/// Keeps the offensive stats in line with the equipped pieces, swapping the old bonus for the new
+/// one whenever the equipment or its passives change
+fn apply_bonus(world: &World) {
+    let bonus = {
+        let equipped = world.get::<Equipped>().unwrap();
+        let equipment = world.get::<EquipmentInventory>().unwrap();
+        combat_bonus(equipped.worn(&equipment))
+    };
+
+    let (mut offensive, mut applied) =
+        world.query::<(&mut CombatOffensive, &mut EquipmentBonus)>();
+    let mut previous = applied
+        .map(|applied| {
+            (applied.0 != bonus).then(|| std::mem::replace(&mut applied.0, bonus.clone()))
+        })
+        .into_iter();
+    offensive.for_each(|stats| {
+        if let Some(previous) = previous.next().unwrap() {
+            *stats -= &previous;
+            *stats += &bonus;
+        }
+    });
+}
+
 fn net(world: &World, event: &Event) {
     match event {
         Event::Recieved(Clientbound::AddEquipment(piece)) => {
@@ -250,5 +641,115 @@ pub fn add(world: World) -> World {
         .with_resource(Equipped::default())
         .with_resource(EquipmentInventory(Vec::new()))
         .with_system_mut(ui)
+        .with_ticker(apply_bonus)
         .with_handler(net)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nyx::{
+        equipment::EquipmentKind,
+        item::{ItemKind, Rarity},
+    };
+    use tecs::prelude::*;
+
+    #[derive(Archetype)]
+    struct Fighter {
+        offensive: CombatOffensive,
+        bonus: EquipmentBonus,
+    }
+
+    fn piece(id: u64, kind: EquipmentKind) -> Equipment {
+        Equipment {
+            id: EquipmentId(id),
+            kind,
+            rarity: Rarity::Common,
+            durability: 10,
+            passives: Vec::new(),
+        }
+    }
+
+    #[test]
+    pub fn test_drag_weapon_into_slot() {
+        let mut equipped = Equipped::default();
+        let mut equipment = EquipmentInventory(vec![
+            piece(0, EquipmentKind::CopperHelmet),
+            piece(1, EquipmentKind::CopperSword),
+        ]);
+
+        // armour doesn't fit in the weapon slot
+        let helmet = EquipmentId(0);
+        let target = DropTarget::Slot(Slot::Weapon);
+        assert!(!drop_equipment(&mut equipped, &mut equipment, helmet, target));
+        assert_eq!(equipped.weapon, None);
+
+        let sword = EquipmentId(1);
+        assert!(drop_equipment(&mut equipped, &mut equipment, sword, target));
+        assert_eq!(equipped.weapon, Some(sword));
+
+        // dropping it back on the list takes it off
+        assert!(drop_equipment(&mut equipped, &mut equipment, sword, DropTarget::List));
+        assert_eq!(equipped.weapon, None);
+
+        // dropping it on another piece moves it there
+        let target = DropTarget::Piece(helmet);
+        assert!(drop_equipment(&mut equipped, &mut equipment, sword, target));
+        assert_eq!(equipment.0[0].id, sword);
+    }
+
+    #[test]
+    pub fn test_drag_reagent_onto_piece() {
+        let item = |kind| Item {
+            kind,
+            rarity: Rarity::Common,
+        };
+        let reagent = item(ItemKind::FireDamageReagent);
+        let sword = EquipmentId(1);
+
+        assert_eq!(drop_item(reagent, DropTarget::Piece(sword)), Some(sword));
+        // only reagents can refine, and only a piece can be refined
+        assert_eq!(
+            drop_item(item(ItemKind::CopperOre), DropTarget::Piece(sword)),
+            None
+        );
+        assert_eq!(drop_item(reagent, DropTarget::Slot(Slot::Weapon)), None);
+        assert_eq!(drop_item(reagent, DropTarget::List), None);
+    }
+
+    #[test]
+    pub fn test_fire_weapon_raises_fire_attack() {
+        let world = World::new()
+            .register_unsaved::<Fighter>()
+            .with_resource(Equipped::default())
+            .with_resource(EquipmentInventory(vec![Equipment {
+                id: EquipmentId(0),
+                kind: EquipmentKind::CopperSword,
+                rarity: Rarity::Common,
+                durability: 10,
+                passives: vec![Passive::FireDamage(0.5)],
+            }]))
+            .with_ticker(apply_bonus);
+
+        let mut base = CombatOffensive::default();
+        base.fire.damage = 2;
+        let fighter = world.spawn(Fighter {
+            offensive: base.clone(),
+            bonus: EquipmentBonus::default(),
+        });
+
+        world.tick();
+        assert_eq!(*world.get_component::<CombatOffensive>(fighter).unwrap(), base);
+
+        world.get_mut::<Equipped>().unwrap().weapon = Some(EquipmentId(0));
+        world.tick();
+        let offensive = world.get_component::<CombatOffensive>(fighter).unwrap().clone();
+        assert_eq!(offensive.fire.damage, 7);
+        assert_eq!(offensive.true_damage, 10);
+
+        // unequipping takes the bonus off again
+        world.get_mut::<Equipped>().unwrap().weapon = None;
+        world.tick();
+        assert_eq!(*world.get_component::<CombatOffensive>(fighter).unwrap(), base);
+    }
+}
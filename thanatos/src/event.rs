@@ -1,5 +1,6 @@
 use glam::Vec2;
-use nyx::protocol::Clientbound;
+use nyx::{data::NodeId, protocol::Clientbound};
+use tecs::EntityId;
 pub use winit::event::MouseButton;
 pub use winit::keyboard::Key;
 
@@ -13,5 +14,14 @@ pub enum Event {
     MouseRelease(MouseButton),
     MouseMove { position: Vec2, delta: Vec2 },
     Recieved(Clientbound),
+    /// Submitted when an entity dies, while it still exists. It is despawned at the end of the tick.
+    EntityDied(EntityId),
+    /// An attack hit an entity and did some damage, whether it was worked out here or by the
+    /// server
+    AttackLanded(EntityId),
+    /// The player finished gathering from the node entity, submitted at the end of the tick
+    Gathered(NodeId, EntityId),
+    /// Something in the ui was clicked on, submitted at the end of the tick
+    UiClicked,
     ServerTick
 }
@@ -0,0 +1,76 @@
+use glam::Vec4;
+use nyx::protocol::Clientbound;
+use styx::components::Text;
+use tecs::Is;
+
+use crate::{
+    combat::{CombatDefensive, CombatOffensive},
+    event::Event,
+    net::Connection,
+    player::Player,
+    renderer::{Anchor, Ui},
+    World,
+};
+
+pub use nyx::experience::*;
+
+fn handle_death(world: &World, event: &Event) {
+    let Event::EntityDied(id) = event else {
+        return;
+    };
+    // while connected the server hands out experience for the kills it resolves
+    if world
+        .get::<Connection>()
+        .is_some_and(|conn| conn.id.is_some())
+    {
+        return;
+    }
+    // only entities that could be attacked give experience
+    let Some(max_health) = world
+        .get_component::<CombatDefensive>(*id)
+        .map(|defensive| defensive.max_health)
+    else {
+        return;
+    };
+
+    let (mut experience, mut stats, _) =
+        world.query_one::<(&mut Experience, &mut CombatOffensive, Is<Player>)>();
+    let levels = experience.kill(max_health);
+    (0..levels).for_each(|_| stats.level_up());
+}
+
+/// Takes the experience the server has worked out, levelling up the player's stats to match
+fn set_experience(world: &World, event: &Event) {
+    let Event::Recieved(Clientbound::SetExperience(new)) = event else {
+        return;
+    };
+
+    let (mut experience, mut stats, _) =
+        world.query_one::<(&mut Experience, &mut CombatOffensive, Is<Player>)>();
+    (experience.level..new.level).for_each(|_| stats.level_up());
+    *experience = *new;
+}
+
+fn draw(world: &World) {
+    let mut ui = world.get_mut::<Ui>().unwrap();
+    let (experience, _) = world.query_one::<(&Experience, Is<Player>)>();
+    let text = Text {
+        text: format!(
+            "Level {} ({}/{} xp)",
+            experience.level,
+            experience.xp,
+            experience.required()
+        ),
+        font: ui.font.clone(),
+        font_size: 24.0,
+        colour: Vec4::ONE,
+    };
+    ui.add(Anchor::TopCenter, text);
+}
+
+pub fn add(world: World) -> World {
+    world
+        .with_handler(handle_death)
+        .with_handler(set_experience)
+        .with_ticker(draw)
+}
@@ -0,0 +1,245 @@
+use glam::{Vec2, Vec4};
+use log::{info, warn};
+use styx::{
+    components::{text, Clicked, Container, ProgressBar, VAlign, VGroup},
+    Signal,
+};
+use tecs::{
+    utils::{Clock, State},
+    SystemMut,
+};
+
+use crate::{
+    assets::Loader,
+    event::Event,
+    net::Connection,
+    notice::Notice,
+    renderer::{Anchor, Ui},
+    World,
+};
+
+// how many dots the connecting spinner moves through each second
+const SPINNER_RATE: f32 = 3.0;
+const LOADING_BAR_SIZE: Vec2 = Vec2::new(400.0, 16.0);
+
+/// Where the player is, from loading the game through to playing. The world is only simulated
+/// [`GameState::InGame`], in every other state it is frozen behind a menu.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameState {
+    Loading,
+    MainMenu,
+    Connecting,
+    InGame,
+    Paused,
+}
+
+impl GameState {
+    /// Moves to another state, asking to join the server on the way to
+    /// [`GameState::Connecting`]
+    pub fn transition(world: &World, to: GameState) {
+        let from = std::mem::replace(&mut *world.get_mut::<GameState>().unwrap(), to);
+        if from == to {
+            return;
+        }
+        info!("{from:?} -> {to:?}");
+
+        if to == GameState::Connecting {
+            if let Err(e) = world.get_mut::<Connection>().unwrap().connect() {
+                warn!("Failed to connect: {e}");
+            }
+        }
+
+        let mut state = world.get_mut::<State>().unwrap();
+        if *state != State::Stopped {
+            *state = match to {
+                GameState::InGame => State::Running,
+                _ => State::Paused,
+            };
+        }
+    }
+
+    /// Opens the main menu once the assets have loaded, saying if any of them failed to. Goes into
+    /// the game once the server lets us in, and back out to the main menu if it doesn't answer or
+    /// goes away.
+    pub fn tick(world: &World) {
+        let state = *world.get::<GameState>().unwrap();
+        let (joined, timed_out) = {
+            let conn = world.get::<Connection>().unwrap();
+            (conn.id.is_some(), conn.timed_out())
+        };
+
+        match state {
+            GameState::Loading if world.get::<Loader>().unwrap().done() => {
+                Self::transition(world, GameState::MainMenu);
+                let failed = world.get::<Loader>().unwrap().errors().len();
+                if failed > 0 {
+                    world
+                        .get_mut::<Notice>()
+                        .unwrap()
+                        .show(format!("{failed} assets failed to load, and are left out"));
+                }
+            }
+            GameState::Connecting if joined => Self::transition(world, GameState::InGame),
+            GameState::Connecting if timed_out => {
+                Self::transition(world, GameState::MainMenu);
+                world
+                    .get_mut::<Notice>()
+                    .unwrap()
+                    .show(String::from("The server didn't answer"));
+            }
+            GameState::InGame | GameState::Paused if !joined => {
+                Self::transition(world, GameState::MainMenu)
+            }
+            _ => (),
+        }
+    }
+}
+
+/// The menus shown before the player is in the game
+pub struct Menus {
+    play: Signal,
+    quit: Signal,
+}
+
+impl Menus {
+    pub fn new(world: &World) -> Self {
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        Self {
+            play: ui.signals.signal(),
+            quit: ui.signals.signal(),
+        }
+    }
+
+    fn main_menu(&self, world: &World) {
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        if ui.signals.get(self.play) {
+            world.defer(|world| GameState::transition(world, GameState::Connecting));
+        }
+        if ui.signals.get(self.quit) {
+            world.defer(|world| world.submit(Event::Stop));
+        }
+
+        let font = ui.font.clone();
+        let menu = VGroup::new(VAlign::Center, 24.0)
+            .add(text("Thanatos", 96.0, font.clone()))
+            .add(Clicked {
+                signal: self.play,
+                child: text("Play", 48.0, font.clone()),
+            })
+            .add(Clicked {
+                signal: self.quit,
+                child: text("Quit", 48.0, font),
+            });
+        ui.add(Anchor::Center, panel(menu));
+    }
+
+    fn loading(&self, world: &World) {
+        let loader = world.get::<Loader>().unwrap();
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        let font = ui.font.clone();
+        let screen = VGroup::new(VAlign::Center, 16.0)
+            .add(text(
+                format!("Loading {}/{}", loader.loaded(), loader.total()),
+                48.0,
+                font,
+            ))
+            .add(ProgressBar {
+                progress: loader.progress(),
+                size: LOADING_BAR_SIZE,
+                colour: Vec4::new(0.2, 0.6, 1.0, 1.0),
+                background: Vec4::new(0.05, 0.05, 0.05, 1.0),
+                radius: 4.0,
+            });
+        ui.add(Anchor::Center, panel(screen));
+    }
+
+    fn connecting(&self, world: &World) {
+        let elapsed = world.get::<Clock>().unwrap().elapsed.as_secs_f32();
+        let dots = (elapsed * SPINNER_RATE) as usize % 4;
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        // padded so the text doesn't shift as the dots come and go
+        let message = format!("Connecting{:<4}", ".".repeat(dots));
+        let font = ui.font.clone();
+        ui.add(Anchor::Center, panel(text(message, 48.0, font)));
+    }
+}
+
+impl SystemMut<Event> for Menus {
+    fn tick(&mut self, world: &World) {
+        let state = *world.get::<GameState>().unwrap();
+        match state {
+            GameState::Loading => self.loading(world),
+            GameState::MainMenu => self.main_menu(world),
+            GameState::Connecting => self.connecting(world),
+            GameState::InGame | GameState::Paused => (),
+        }
+    }
+}
+
+/// The background every menu is drawn on
+pub fn panel<T: styx::Element>(child: T) -> Container<T> {
+    Container {
+        padding: 32.0,
+        radius: 8.0,
+        colour: Vec4::new(0.1, 0.1, 0.1, 0.9),
+        child,
+    }
+}
+
+pub fn add(world: World) -> World {
+    let menus = Menus::new(&world);
+    world
+        .with_resource(GameState::Loading)
+        .with_ticker(GameState::tick)
+        .with_system_mut(menus)
+}
+
+#[cfg(test)]
+mod tests {
+    use nyx::{
+        protocol::{
+            self, ClientId, Clientbound, ClientboundBundle, Serverbound, Tick, MAX_PACKET_SIZE,
+        },
+        transport::{self, ServerTransport},
+    };
+
+    use super::*;
+
+    #[test]
+    pub fn test_transitions() {
+        let (server, client) = transport::loopback();
+        let world = World::new()
+            .with_resource(State::Paused)
+            .with_resource(GameState::MainMenu)
+            .with_resource(Connection::with_transport(Box::new(client), 7).unwrap());
+        let state = || *world.get::<GameState>().unwrap();
+
+        // nothing is sent to the server until the player chooses to play
+        GameState::tick(&world);
+        assert_eq!(state(), GameState::MainMenu);
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        assert!(server.recv_from(&mut buffer).is_err());
+
+        GameState::transition(&world, GameState::Connecting);
+        let (n, addr) = server.recv_from(&mut buffer).unwrap();
+        assert!(matches!(
+            protocol::deserialize(&buffer[..n]).unwrap(),
+            Serverbound::AuthRequest(7)
+        ));
+        GameState::tick(&world);
+        assert_eq!(state(), GameState::Connecting);
+        assert_eq!(*world.get::<State>().unwrap(), State::Paused);
+
+        let accepted = ClientboundBundle {
+            tick: Tick(1),
+            messages: vec![Clientbound::AuthSuccess(ClientId(0))],
+        };
+        server
+            .send_to(&protocol::serialize(&accepted).unwrap(), addr)
+            .unwrap();
+        Connection::tick(&world);
+        GameState::tick(&world);
+        assert_eq!(state(), GameState::InGame);
+        assert_eq!(*world.get::<State>().unwrap(), State::Running);
+    }
+}
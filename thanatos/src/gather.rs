@@ -1,46 +1,171 @@
-use glam::Vec3;
-use nyx::protocol::Serverbound;
+use std::time::Duration;
+
+use glam::{Vec2, Vec3, Vec4};
+use log::{info, warn};
+use nyx::{data::NodeId, protocol::Serverbound};
 use serde::{Deserialize, Serialize};
-use tecs::{EntityId, Is};
+use styx::components::ProgressBar;
+use tecs::{
+    utils::{Cooldown, FixedTimestep, State},
+    EntityId, Is,
+};
 
 use crate::{
-    collider::Collider, interact::Interactable, net::Connection, player::Player, renderer::Ui,
-    transform::Transform, Timer, World,
+    collider::Collider,
+    event::Event,
+    interact::Interactable,
+    net::Connection,
+    player::Player,
+    renderer::{Anchor, Ui},
+    transform::Transform,
+    World,
 };
 
+const GATHER_BAR_OFFSET: Vec3 = Vec3::new(0.0, 2.0, 0.0);
+const GATHER_BAR_SIZE: Vec2 = Vec2::new(120.0, 10.0);
+
+fn default_channel() -> Duration {
+    Duration::from_secs(2)
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Gatherable {
     pub collider: Collider,
-    pub loot: usize,
-    pub timer: Timer,
+    pub loot: NodeId,
+    /// How long the player has to stay by the node to gather from it
+    #[serde(default = "default_channel")]
+    pub channel: Duration,
 }
 
 impl Gatherable {
-    pub fn gatherable(&self, position: Vec3) -> bool {
-        self.collider.within(position)
+    pub fn gatherable(&self, position: Vec3, world: &World) -> bool {
+        self.collider.within(position, world)
+    }
+
+    pub fn gather(&self, cooldown: &mut Cooldown) -> NodeId {
+        cooldown.start();
+        self.loot.clone()
+    }
+}
+
+/// A gather the player has started, which only completes if they stay in range of the node
+/// until the timer runs out
+#[derive(Clone, Copy, Debug)]
+pub struct Channel {
+    pub node: EntityId,
+    pub timer: Cooldown,
+}
+
+/// The gather in progress, if there is one
+#[derive(Default)]
+pub struct Gathering(pub Option<Channel>);
+
+impl Gathering {
+    /// Starts gathering from a node, unless something is already being gathered
+    pub fn start(world: &World, node: EntityId) {
+        let mut gathering = world.get_mut::<Gathering>().unwrap();
+        if gathering.0.is_some() {
+            return;
+        }
+        let Some(gatherable) = world.get_component::<Gatherable>(node) else {
+            return;
+        };
+        let mut timer = Cooldown::new(gatherable.channel);
+        timer.start();
+        gathering.0 = Some(Channel { node, timer });
+    }
+
+    /// Moves the gather on by `delta` with the player at `position`. It is cancelled if they
+    /// have left the node's range, and once the time is up the node is gathered from.
+    pub fn advance(world: &World, position: Vec3, delta: Duration) {
+        let mut gathering = world.get_mut::<Gathering>().unwrap();
+        let Some(channel) = &mut gathering.0 else {
+            return;
+        };
+        let node = channel.node;
+        let Some(gatherable) = world.get_component::<Gatherable>(node) else {
+            // the node was despawned out from under the gather
+            gathering.0 = None;
+            return;
+        };
+
+        if !gatherable.gatherable(position, world) {
+            info!("Stopped gathering {}, out of range", gatherable.loot.0);
+            gathering.0 = None;
+            return;
+        }
+        channel.timer.advance(delta);
+        if !channel.timer.ready() {
+            return;
+        }
+        gathering.0 = None;
+
+        let mut cooldown = world.get_component_mut::<Cooldown>(node).unwrap();
+        let loot = gatherable.gather(&mut cooldown);
+        if let Err(e) = world
+            .get_mut::<Connection>()
+            .unwrap()
+            .write(Serverbound::Gather(loot.clone()))
+        {
+            warn!("Failed to send gather: {e}");
+        }
+        world.defer(move |world| world.submit(Event::Gathered(loot, node)));
+    }
+
+    fn draw(&self, world: &World, position: Vec3) {
+        let Some(channel) = &self.0 else {
+            return;
+        };
+        world.get_mut::<Ui>().unwrap().add(
+            Anchor::World(position + GATHER_BAR_OFFSET),
+            ProgressBar {
+                progress: channel.timer.progress(),
+                size: GATHER_BAR_SIZE,
+                colour: Vec4::new(0.8, 0.5, 0.2, 1.0),
+                background: Vec4::new(0.1, 0.1, 0.1, 1.0),
+                radius: 2.0,
+            },
+        );
     }
+}
+
+fn player_position(world: &World) -> Vec3 {
+    let (transforms, _) = world.query::<(&Transform, Is<Player>)>();
+    let position = transforms.iter().next().unwrap().translation;
+    position
+}
 
-    pub fn gather(&mut self) -> usize {
-        self.timer.start();
-        self.loot
+/// Runs the gather in progress on the fixed step, alongside the player's movement that can take
+/// them out of range of the node
+pub fn step(world: &World) {
+    if State::paused(world) {
+        return;
     }
+    let delta = world.get::<FixedTimestep>().unwrap().delta;
+    Gathering::advance(world, player_position(world), delta);
 }
 
+/// Draws the gather in progress and starts new ones. This reads the interact signals from the
+/// UI, which are only painted once a frame, so it stays on the per-frame tick.
 pub fn tick(world: &World) {
+    if State::paused(world) {
+        return;
+    }
+    let position = player_position(world);
+    world.get::<Gathering>().unwrap().draw(world, position);
+
     let entity = {
-        let (gatherables, mut interactables, entities) =
-            world.query::<(&Gatherable, &mut Interactable, EntityId)>();
+        let (gatherables, cooldowns, mut interactables, entities) =
+            world.query::<(&Gatherable, &Cooldown, &mut Interactable, EntityId)>();
 
         interactables.for_each(|interactable| interactable.priority = f32::MAX);
 
-        let (transforms, _) = world.query::<(&Transform, Is<Player>)>();
-        let transform = transforms.iter().next().unwrap();
         let Some((_, entity)) = gatherables
             .iter()
+            .zip(cooldowns.iter())
             .zip(entities)
-            .filter(|(gatherable, _)| gatherable.timer.done())
-            .filter(|(gatherable, _)| gatherable.gatherable(transform.translation))
-            .next()
+            .filter(|((_, cooldown), _)| cooldown.ready())
+            .find(|((gatherable, _), _)| gatherable.gatherable(position, world))
         else {
             return;
         };
@@ -56,9 +181,79 @@ pub fn tick(world: &World) {
         .map(|signal| ui.signals.get(signal))
         .unwrap_or_default()
     {
-        let mut gatherable = world.get_component_mut::<Gatherable>(entity).unwrap();
-        let mut conn = world.get_mut::<Connection>().unwrap();
-        conn.write(Serverbound::Gather(gatherable.gather()))
-            .unwrap();
+        Gathering::start(world, entity);
+    }
+}
+
+pub fn add(world: World) -> World {
+    world
+        .with_resource(Gathering::default())
+        .with_fixed_ticker(step)
+        .with_ticker(tick)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use nyx::transport;
+    use tecs::prelude::*;
+
+    use super::*;
+    use crate::collider::{ColliderKind, ColliderPositionKind};
+
+    #[derive(Archetype)]
+    struct Node {
+        gatherable: Gatherable,
+        cooldown: Cooldown,
+    }
+
+    #[test]
+    pub fn test_channel() {
+        let (_server, client) = transport::loopback();
+        let gathered = Rc::new(RefCell::new(Vec::new()));
+        let world = {
+            let gathered = gathered.clone();
+            World::new()
+                .register_unsaved::<Node>()
+                .with_resource(Connection::with_transport(Box::new(client), 0).unwrap())
+                .with_resource(Gathering::default())
+                .with_handler(move |_, event| {
+                    if let Event::Gathered(_, node) = event {
+                        gathered.borrow_mut().push(*node);
+                    }
+                })
+        };
+        let node = world.spawn(Node {
+            gatherable: Gatherable {
+                collider: Collider {
+                    kind: ColliderKind::Sphere(5.0),
+                    position: ColliderPositionKind::Absolute(Vec3::ZERO),
+                },
+                loot: NodeId(String::from("copper_ore")),
+                channel: Duration::from_secs(2),
+            },
+            cooldown: Cooldown::new(Duration::from_secs(1)),
+        });
+        let channelling = || world.get::<Gathering>().unwrap().0.is_some();
+
+        // walking away part way through cancels it
+        Gathering::start(&world, node);
+        Gathering::advance(&world, Vec3::ZERO, Duration::from_secs(1));
+        assert!(channelling());
+        Gathering::advance(&world, Vec3::X * 10.0, Duration::from_secs(1));
+        assert!(!channelling());
+        world.flush();
+        assert!(gathered.borrow().is_empty());
+
+        Gathering::start(&world, node);
+        Gathering::advance(&world, Vec3::ZERO, Duration::from_secs(1));
+        world.flush();
+        assert!(gathered.borrow().is_empty());
+        Gathering::advance(&world, Vec3::X, Duration::from_secs(1));
+        assert!(!channelling());
+        world.flush();
+        assert_eq!(*gathered.borrow(), [node]);
+        assert!(!world.get_component::<Cooldown>(node).unwrap().ready());
     }
 }
@@ -0,0 +1,228 @@
+use std::{f32::consts::TAU, mem::size_of};
+
+use bytemuck::offset_of;
+use glam::{Vec3, Vec3Swizzles, Vec4Swizzles};
+use hephaestus::vertex::{self, AttributeType};
+use tecs::{EntityId, SystemMut};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::{
+    assets::MeshCache,
+    camera::{Camera, Frustum},
+    collider::{Collider, ColliderKind},
+    colours,
+    event::Event,
+    gather::Gatherable,
+    ground,
+    renderer::RenderObject,
+    targeting, transform,
+    window::Keyboard,
+    World,
+};
+
+// enough for a sphere to look round at the sizes colliders are
+const SEGMENTS: usize = 24;
+// how far the targeting ray is drawn when it doesn't hit the ground
+const RAY_LENGTH: f32 = 100.0;
+// the camera sees forever, so its frustum is cut off where it stops being useful to look at
+const FRUSTUM_LENGTH: f32 = 50.0;
+const CULLED: Vec3 = Vec3::new(0.9, 0.1, 0.1);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Default, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LineVertex {
+    pub position: Vec3,
+    pub colour: Vec3,
+}
+
+impl LineVertex {
+    pub fn info() -> vertex::Info {
+        vertex::Info::new(size_of::<Self>())
+            .attribute(AttributeType::Vec3, 0)
+            .attribute(AttributeType::Vec3, offset_of!(LineVertex, colour))
+    }
+}
+
+/// Lines drawn over the world for debugging, which only last for the frame they are added in
+#[derive(Default)]
+pub struct DebugLines {
+    /// Whether the targeting ray and the bounds of every collider are drawn
+    pub enabled: bool,
+    vertices: Vec<LineVertex>,
+}
+
+impl DebugLines {
+    /// Adds a line from `from` to `to` in an srgb colour
+    pub fn line(&mut self, from: Vec3, to: Vec3, colour: Vec3) {
+        self.vertices.push(LineVertex {
+            position: from,
+            colour,
+        });
+        self.vertices.push(LineVertex {
+            position: to,
+            colour,
+        });
+    }
+
+    /// Outlines a sphere with a circle around each axis
+    pub fn sphere(&mut self, centre: Vec3, radius: f32, colour: Vec3) {
+        let point = |angle: f32| Vec3::new(angle.cos(), angle.sin(), 0.0) * radius;
+        (0..SEGMENTS).for_each(|i| {
+            let from = point(i as f32 / SEGMENTS as f32 * TAU);
+            let to = point((i + 1) as f32 / SEGMENTS as f32 * TAU);
+            self.line(centre + from, centre + to, colour);
+            self.line(centre + from.yzx(), centre + to.yzx(), colour);
+            self.line(centre + from.zxy(), centre + to.zxy(), colour);
+        });
+    }
+
+    /// Outlines a box reaching `size` out from `centre` along each axis, like an
+    /// [`ColliderKind::Aabb`]
+    pub fn aabb(&mut self, centre: Vec3, size: Vec3, colour: Vec3) {
+        let corner = |i: usize| {
+            centre
+                + size
+                    * Vec3::new(
+                        if i & 1 == 0 { -1.0 } else { 1.0 },
+                        if i & 2 == 0 { -1.0 } else { 1.0 },
+                        if i & 4 == 0 { -1.0 } else { 1.0 },
+                    )
+        };
+        // corners that differ along a single axis share an edge
+        (0..8).for_each(|i| {
+            [1, 2, 4]
+                .into_iter()
+                .filter(|axis| i & axis == 0)
+                .for_each(|axis| self.line(corner(i), corner(i | axis), colour))
+        });
+    }
+
+    /// Outlines a frustum, with lines from its near corners out to its far ones
+    pub fn frustum(&mut self, frustum: &Frustum, colour: Vec3) {
+        let corners = frustum.corners;
+        (0..4).for_each(|i| {
+            self.line(corners[i], corners[(i + 1) % 4], colour);
+            self.line(corners[4 + i], corners[4 + (i + 1) % 4], colour);
+            self.line(corners[i], corners[4 + i], colour);
+        });
+    }
+
+    /// Outlines a collider, if it still has a position
+    pub fn collider(&mut self, collider: &Collider, world: &World, colour: Vec3) {
+        let Some(position) = collider.calculate_position(world) else {
+            return;
+        };
+        match collider.kind {
+            ColliderKind::Sphere(radius) => self.sphere(position, radius, colour),
+            ColliderKind::Aabb(size) => self.aabb(position, size, colour),
+        }
+    }
+
+    /// Takes the lines added since the last time, leaving none to be drawn next frame
+    pub fn take(&mut self) -> Vec<LineVertex> {
+        std::mem::take(&mut self.vertices)
+    }
+}
+
+pub fn tick(world: &World) {
+    let keyboard = world.get::<Keyboard>().unwrap();
+    let mut lines = world.get_mut::<DebugLines>().unwrap();
+    if keyboard.pressed(Key::Named(NamedKey::F4)) {
+        lines.enabled = !lines.enabled;
+    }
+
+    if !lines.enabled {
+        return;
+    }
+
+    let ray = targeting::cursor_ray(world);
+    let end = ground::ground_point(world, ray).unwrap_or(ray.origin + ray.direction * RAY_LENGTH);
+    lines.line(ray.origin, end, colours::LEGENDARY.xyz());
+    lines.sphere(end, 0.1, colours::LEGENDARY.xyz());
+
+    world
+        .query::<&Collider>()
+        .iter()
+        .for_each(|collider| lines.collider(collider, world, colours::UNCOMMON.xyz()));
+    world
+        .query::<&Gatherable>()
+        .iter()
+        .for_each(|gatherable| lines.collider(&gatherable.collider, world, colours::RARE.xyz()));
+}
+
+/// Draws the camera's frustum and the bounding sphere of every object, green if it is inside
+/// the frustum and red if it could be culled. Freezing the frustum keeps it where it was, so
+/// the camera can be moved away to look at it from outside.
+#[derive(Default)]
+pub struct FrustumView {
+    enabled: bool,
+    frozen: Option<Frustum>,
+}
+
+impl SystemMut<Event> for FrustumView {
+    fn tick(&mut self, world: &World) {
+        let keyboard = world.get::<Keyboard>().unwrap();
+        if keyboard.pressed(Key::Named(NamedKey::F5)) {
+            self.enabled = !self.enabled;
+        }
+        if !self.enabled {
+            return;
+        }
+
+        let camera = world.get::<Camera>().unwrap();
+        if keyboard.pressed(Key::Named(NamedKey::F6)) {
+            self.frozen = match self.frozen {
+                Some(_) => None,
+                None => Some(camera.frustum(FRUSTUM_LENGTH)),
+            };
+        }
+        let frustum = self
+            .frozen
+            .unwrap_or_else(|| camera.frustum(FRUSTUM_LENGTH));
+
+        let mut lines = world.get_mut::<DebugLines>().unwrap();
+        lines.frustum(&frustum, colours::COMMON.xyz());
+
+        let mut meshes = world.get_mut::<MeshCache>().unwrap();
+        let (ids, objects) = world.query::<(EntityId, &RenderObject)>();
+        ids.iter().zip(objects.iter()).for_each(|(id, object)| {
+            let Ok(mesh) = meshes.load(&object.mesh) else {
+                return;
+            };
+            let transform = transform::global(world, *id).unwrap_or_default();
+            let radius = mesh.radius() * transform.scale.max_element();
+            let colour = if frustum.contains_sphere(transform.translation, radius) {
+                colours::UNCOMMON.xyz()
+            } else {
+                CULLED
+            };
+            lines.sphere(transform.translation, radius, colour);
+        });
+    }
+}
+
+pub fn add(world: World) -> World {
+    world
+        .with_resource(DebugLines::default())
+        .with_ticker(tick)
+        .with_system_mut(FrustumView::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_aabb_edges() {
+        let mut lines = DebugLines::default();
+        lines.aabb(Vec3::ZERO, Vec3::new(1.0, 2.0, 3.0), Vec3::ONE);
+        let vertices = lines.take();
+        assert_eq!(vertices.len(), 24);
+        // every edge runs along a single axis, the full length of the box
+        vertices.chunks(2).for_each(|edge| {
+            let length = (edge[1].position - edge[0].position).abs();
+            assert!([Vec3::X * 2.0, Vec3::Y * 4.0, Vec3::Z * 6.0].contains(&length));
+        });
+        assert!(lines.take().is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use glam::Vec3;
+use tecs::{Archetype, Is};
+
+use crate::{
+    collider::{Collider, ColliderKind, ColliderPositionKind, Ray},
+    transform::Transform,
+    World,
+};
+
+// rays are cast down from this height, so anything placed above it won't find the ground
+const RAY_HEIGHT: f32 = 1000.0;
+
+/// The surface entities rest on. Until there is real terrain this is a large flat box whose top
+/// is at y = 0.
+pub struct Ground {
+    pub collider: Collider,
+}
+
+impl Default for Ground {
+    fn default() -> Self {
+        Self {
+            collider: Collider {
+                kind: ColliderKind::Aabb(Vec3::new(RAY_HEIGHT, 1.0, RAY_HEIGHT)),
+                position: ColliderPositionKind::Absolute(Vec3::new(0.0, -1.0, 0.0)),
+            },
+        }
+    }
+}
+
+/// Where a ray first hits the ground, if it does at all
+pub fn ground_point(world: &World, ray: Ray) -> Option<Vec3> {
+    let ground = world.get::<Ground>().unwrap();
+    ground.collider.intersects(ray, world)
+}
+
+/// The height of the ground at the given position, found by casting a ray straight down
+pub fn ground_height(world: &World, x: f32, z: f32) -> Option<f32> {
+    let ray = Ray {
+        origin: Vec3::new(x, RAY_HEIGHT, z),
+        direction: Vec3::NEG_Y,
+    };
+    ground_point(world, ray).map(|position| position.y)
+}
+
+/// Moves a transform onto the ground, leaving it where it is if there's no ground below
+pub fn snap_to_ground(world: &World, transform: &mut Transform) {
+    if let Some(height) = ground_height(world, transform.translation.x, transform.translation.z) {
+        transform.translation.y = height;
+    }
+}
+
+/// Snaps every entity of an archetype onto the ground, for placing entities after spawning
+pub fn settle<T: Archetype>(world: &World) {
+    let (mut transforms, _) = world.query::<(&mut Transform, Is<T>)>();
+    transforms.for_each(|transform| snap_to_ground(world, transform));
+}
+
+pub fn add(world: World) -> World {
+    world.with_resource(Ground::default())
+}
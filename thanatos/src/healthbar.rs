@@ -0,0 +1,62 @@
+use glam::{Vec2, Vec3, Vec4};
+use styx::components::ProgressBar;
+use tecs::{Is, SystemMut};
+
+use crate::{
+    camera::Camera,
+    combat::CombatDefensive,
+    event::Event,
+    renderer::{Anchor, Ui},
+    transform::Transform,
+    TargetDummy, World,
+};
+
+// how far above the dummies origin the bar is drawn
+const OFFSET: Vec3 = Vec3::new(0.0, 3.0, 0.0);
+const SIZE: Vec2 = Vec2::new(80.0, 8.0);
+
+pub struct HealthBars {
+    // hide the bar of any dummy that hasn't taken damage yet
+    pub hide_full: bool,
+}
+
+impl SystemMut<Event> for HealthBars {
+    fn tick(&mut self, world: &World) {
+        let (transforms, defensive, _) =
+            world.query::<(&Transform, &CombatDefensive, Is<TargetDummy>)>();
+
+        let eye = world.get::<Camera>().unwrap().eye();
+        let mut bars = transforms
+            .iter()
+            .zip(defensive.iter())
+            .filter(|(_, stats)| !(self.hide_full && stats.health >= stats.max_health))
+            .map(|(transform, stats)| {
+                (
+                    transform.translation + OFFSET,
+                    stats.health as f32 / stats.max_health as f32,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // elements are painted in order, so draw the furthest bars first to keep nearer ones on top
+        bars.sort_by(|(a, _), (b, _)| b.distance(eye).total_cmp(&a.distance(eye)));
+
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        bars.into_iter().for_each(|(position, progress)| {
+            ui.add(
+                Anchor::World(position),
+                ProgressBar {
+                    progress,
+                    size: SIZE,
+                    colour: Vec4::new(0.8, 0.1, 0.1, 1.0),
+                    background: Vec4::new(0.1, 0.1, 0.1, 1.0),
+                    radius: 2.0,
+                },
+            )
+        });
+    }
+}
+
+pub fn add(world: World) -> World {
+    world.with_system_mut(HealthBars { hide_full: true })
+}
@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use glam::{Mat4, Quat, Vec3};
+use gltf::{Glb, Gltf, Node};
+use serde::{Deserialize, Serialize};
+use tecs::prelude::*;
+
+use crate::{
+    assets::{MaterialId, MeshId},
+    renderer::RenderObject,
+    transform::Transform,
+    World,
+};
+
+/// Something placed in the world by an imported glTF scene
+#[derive(Archetype, Clone, Serialize, Deserialize)]
+pub struct SceneObject {
+    pub render: RenderObject,
+    pub transform: Transform,
+}
+
+fn local(node: &Node) -> Mat4 {
+    match node.matrix {
+        // glTF matrices are column major, like glam's
+        Some(matrix) => Mat4::from_cols_array(&matrix),
+        None => Mat4::from_scale_rotation_translation(
+            node.scale.map(Vec3::from).unwrap_or(Vec3::ONE),
+            node.rotation
+                .map(Quat::from_array)
+                .unwrap_or(Quat::IDENTITY),
+            node.translation.map(Vec3::from).unwrap_or(Vec3::ZERO),
+        ),
+    }
+}
+
+fn walk(gltf: &Gltf, node: usize, parent: Mat4, objects: &mut Vec<(usize, Transform)>) {
+    let node = &gltf.nodes[node];
+    let matrix = parent * local(node);
+    if let Some(mesh) = node.mesh {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        objects.push((mesh, Transform::new(translation, rotation, scale)));
+    }
+    node.children
+        .iter()
+        .for_each(|child| walk(gltf, *child, matrix, objects));
+}
+
+/// Every node with a mesh in the default scene, along with its transform in world space, as
+/// entities don't have parents to be relative to
+pub fn flatten(gltf: &Gltf) -> Result<Vec<(usize, Transform)>> {
+    let scene = gltf
+        .scenes
+        .get(gltf.scene)
+        .ok_or_else(|| anyhow!("glTF file has no scene {}", gltf.scene))?;
+    let mut objects = Vec::new();
+    scene
+        .nodes
+        .iter()
+        .for_each(|node| walk(gltf, *node, Mat4::IDENTITY, &mut objects));
+    Ok(objects)
+}
+
+/// Spawns a [`SceneObject`] for every mesh node in a `.glb` scene, all drawn with the same
+/// material. The meshes are left to be loaded like any others, so a [`Loader`] can parse them
+/// in the background.
+///
+/// [`Loader`]: crate::assets::Loader
+pub fn import<P: AsRef<Path>>(
+    world: &World,
+    path: P,
+    material: MaterialId,
+) -> Result<Vec<EntityId>> {
+    let glb = Glb::load(&std::fs::read(&path)?)?;
+    let objects = flatten(&glb.gltf)?;
+
+    Ok(objects
+        .into_iter()
+        .map(|(mesh, transform)| {
+            world.spawn(SceneObject {
+                render: RenderObject {
+                    mesh: MeshId::in_scene(&path, mesh),
+                    material: material.clone(),
+                },
+                transform,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assets::{Mesh, MeshCache};
+
+    const SCENE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/scenes/test.glb");
+
+    #[test]
+    pub fn test_import() {
+        let world = World::new()
+            .register::<SceneObject>()
+            .with_resource(MeshCache::default());
+        let entities = import(&world, SCENE, MaterialId::white()).unwrap();
+        assert_eq!(entities.len(), 3);
+
+        let transform = |i: usize| *world.get_component::<Transform>(entities[i]).unwrap();
+        let close = |a: Vec3, b: Vec3| a.distance(b) < 1e-5;
+
+        // the root is scaled up, which its children inherit
+        assert!(close(transform(0).translation, Vec3::new(10.0, 0.0, 0.0)));
+        assert!(close(transform(0).scale, Vec3::splat(2.0)));
+        assert!(close(transform(1).translation, Vec3::new(10.0, 2.0, 0.0)));
+        // the last is one unit along a parent turned a quarter around Y
+        let last = transform(2);
+        assert!(close(last.translation, Vec3::new(10.0, 0.0, -2.0)));
+        assert!(last
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_y(std::f32::consts::FRAC_PI_2), 1e-5));
+
+        // each mesh is loaded out of the scene's file
+        assert!(world
+            .get_mut::<MeshCache>()
+            .unwrap()
+            .load(MeshId::in_scene(SCENE, 0))
+            .is_ok());
+        assert!(Mesh::load(MeshId::in_scene(SCENE, 99)).is_err());
+    }
+}
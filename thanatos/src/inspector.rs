@@ -0,0 +1,138 @@
+use glam::Vec4;
+use styx::{
+    components::{text, Clicked, Container, HAlign, HGroup, Text, VAlign, VGroup},
+    Signal,
+};
+use tecs::{EntityId, SystemMut};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::{
+    event::Event,
+    renderer::{Anchor, Ui},
+    window::Keyboard,
+    World,
+};
+
+// beyond this the list runs off the screen
+const MAX_LISTED: usize = 40;
+
+/// A debug overlay listing every entity, which shows the components of whichever is clicked
+#[derive(Default)]
+pub struct Inspector {
+    open: bool,
+    selected: Option<EntityId>,
+    signals: Vec<(EntityId, Signal)>,
+}
+
+/// An entity's components as pretty printed json, which only archetypes registered to be saved
+/// can be turned into
+fn describe(world: &World, id: EntityId) -> String {
+    match world.serialize_entity(id) {
+        Some(entity) => serde_json::to_string_pretty(&entity)
+            .unwrap_or_else(|e| format!("Failed to serialize: {e}")),
+        None => String::from("Not saved, so it can't be shown"),
+    }
+}
+
+impl SystemMut<Event> for Inspector {
+    fn tick(&mut self, world: &World) {
+        let keyboard = world.get::<Keyboard>().unwrap();
+        if keyboard.pressed(Key::Named(NamedKey::F3)) {
+            self.open = !self.open;
+        }
+
+        if !self.open {
+            return;
+        }
+
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        self.signals.drain(..).for_each(|(id, signal)| {
+            if ui.signals.get(signal) {
+                self.selected = Some(id);
+            }
+        });
+        self.selected = self.selected.filter(|id| world.contains(*id));
+
+        let ids = world.entity_ids();
+        let heading = format!("{} entities", ids.len());
+        let list = ids.into_iter().take(MAX_LISTED).fold(
+            HGroup::new(HAlign::Left, 4.0).add(text(heading, 24.0, ui.font.clone())),
+            |list, id| {
+                let signal = ui.signals.signal();
+                self.signals.push((id, signal));
+
+                // the module path makes the names too long to read at a glance
+                let archetype = world.archetype_name(id).unwrap_or_default();
+                let archetype = archetype.rsplit("::").next().unwrap_or(archetype);
+                let colour = if self.selected == Some(id) {
+                    Vec4::ONE
+                } else {
+                    Vec4::new(0.7, 0.7, 0.7, 1.0)
+                };
+                list.add(Clicked {
+                    signal,
+                    child: Text {
+                        text: format!("{id:?} {archetype}"),
+                        font_size: 20.0,
+                        font: ui.font.clone(),
+                        colour,
+                    },
+                })
+            },
+        );
+
+        let mut view = VGroup::new(VAlign::Top, 16.0).add(Container {
+            padding: 16.0,
+            colour: Vec4::new(0.1, 0.1, 0.1, 0.9),
+            radius: 8.0,
+            child: list,
+        });
+
+        if let Some(id) = self.selected {
+            let components = describe(world, id)
+                .lines()
+                .fold(HGroup::new(HAlign::Left, 2.0), |components, line| {
+                    components.add(text(line, 20.0, ui.font.clone()))
+                });
+            view = view.add(Container {
+                padding: 16.0,
+                colour: Vec4::new(0.1, 0.1, 0.1, 0.9),
+                radius: 8.0,
+                child: components,
+            });
+        }
+
+        ui.add(Anchor::TopLeft, view);
+    }
+}
+
+pub fn add(world: World) -> World {
+    world.with_system_mut(Inspector::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use tecs::prelude::*;
+
+    #[derive(Archetype, Clone, Serialize, Deserialize)]
+    struct Ore {
+        health: u32,
+    }
+
+    #[derive(Archetype)]
+    struct Marker {
+        id: u32,
+    }
+
+    #[test]
+    pub fn test_describe() {
+        let world = World::new().register::<Ore>().register_unsaved::<Marker>();
+        let ore = world.spawn(Ore { health: 5 });
+        let marker = world.spawn(Marker { id: 0 });
+
+        assert!(describe(&world, ore).contains("\"health\": 5"));
+        assert!(describe(&world, marker).contains("Not saved"));
+    }
+}
@@ -4,6 +4,7 @@ use styx::{
     components::{Container, Offset, Text, VAlign, VGroup},
     Signal,
 };
+use tecs::EntityId;
 
 use crate::{
     renderer::{Anchor, Ui},
@@ -88,6 +89,14 @@ fn interact_ui(world: &World) {
     }
 }
 
+/// Gives an interactable's signal back to the ui once it is despawned. Deferred, as entities
+/// can be despawned while the ui is in use.
+fn release(world: &World, _: EntityId, interactable: &Interactable) {
+    if let Some(signal) = interactable.signal {
+        world.defer(move |world| world.get_mut::<Ui>().unwrap().signals.release(signal));
+    }
+}
+
 pub fn add(world: World) -> World {
-    world.with_ticker(interact_ui)
+    world.with_ticker(interact_ui).with_remove_hook(release)
 }
@@ -3,31 +3,41 @@ use nyx::{
     item::{Inventory, Item, ItemStack},
     protocol::Clientbound,
 };
-use styx::components::{Container, HAlign, HGroup, Text};
+use styx::{
+    components::{Container, Dragged, HAlign, HGroup, Hovered, Text},
+    Signal,
+};
 use tecs::SystemMut;
 
 use crate::{
     colours::rarity_colour,
+    drag::{Drag, Payload},
     event::Event,
     renderer::{Anchor, Ui},
-    window::Keyboard,
+    tooltip::tooltip,
+    window::{Keybind, Keyboard},
     World,
 };
 
 pub struct InventoryUi {
     open: bool,
+    // the hover and drag signals of each stack shown
+    signals: Vec<(Item, (Signal, Signal))>,
 }
 
 impl InventoryUi {
     pub fn new() -> Self {
-        Self { open: false }
+        Self {
+            open: false,
+            signals: Vec::new(),
+        }
     }
 }
 
 impl SystemMut<Event> for InventoryUi {
     fn tick(&mut self, world: &World) {
         let keyboard = world.get::<Keyboard>().unwrap();
-        if keyboard.pressed("i") {
+        if keyboard.pressed(Keybind::Inventory) {
             self.open = !self.open;
         }
 
@@ -37,18 +47,40 @@ impl SystemMut<Event> for InventoryUi {
         let mut ui = world.get_mut::<Ui>().unwrap();
         let inventory = world.get::<Inventory>().unwrap();
 
+        let mut hovered = None;
+        self.signals.drain(..).for_each(|(item, signals)| {
+            if ui.signals.get(signals.0) {
+                hovered = Some(item);
+            }
+            if ui.signals.get(signals.1) {
+                world.get_mut::<Drag>().unwrap().start(
+                    Payload::Item(item),
+                    item.kind.to_string(),
+                    rarity_colour(item.rarity),
+                );
+            }
+        });
+        if let Some(item) = hovered {
+            tooltip(&mut ui, item.kind.to_string(), item.rarity, item.describe());
+        }
+
         let stacks = inventory.items().fold(
             HGroup::new(HAlign::Left, 4.0),
-            |stacks,
-             ItemStack {
-                 item: Item { kind, rarity },
-                 quantity,
-             }| {
-                stacks.add(Text {
-                    text: format!("{kind} x {quantity}"),
-                    font_size: 24.0,
-                    font: ui.font.clone(),
-                    colour: rarity_colour(rarity),
+            |stacks, ItemStack { item, quantity }| {
+                let signals = (ui.signals.signal(), ui.signals.signal());
+                self.signals.push((item, signals));
+                let Item { kind, rarity } = item;
+                stacks.add(Dragged {
+                    signal: signals.1,
+                    child: Hovered {
+                        signal: signals.0,
+                        child: Text {
+                            text: format!("{kind} x {quantity}"),
+                            font_size: 24.0,
+                            font: ui.font.clone(),
+                            colour: rarity_colour(rarity),
+                        },
+                    },
                 })
             },
         );
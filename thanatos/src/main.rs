@@ -1,49 +1,81 @@
 mod assets;
+mod audio;
+mod buff;
 mod camera;
 mod casting;
 mod collider;
 mod colours;
 mod combat;
 mod craft;
+mod drag;
 mod equipment;
 mod event;
+mod experience;
+mod game;
 mod gather;
+mod gizmos;
+mod ground;
+mod healthbar;
+mod import;
+#[cfg(feature = "inspector")]
+mod inspector;
 mod interact;
 mod inventory;
+mod minimap;
 mod net;
+mod notice;
+mod pause;
 mod player;
+mod prefab;
 mod renderer;
+mod screenshot;
+mod settings;
+mod sky;
 mod targeting;
+mod tooltip;
 mod transform;
 mod uiutils;
 mod window;
 
 use crate::{camera::Camera, window::Window};
 use anyhow::Result;
-use assets::{Material, MeshCache, MeshId};
+use assets::{Loader, MaterialCache, MaterialId, MeshCache, MeshId, Preload};
+use audio::Audio;
 use casting::Skill;
-use collider::{Collider, ColliderKind};
+use collider::{Collider, ColliderKind, ColliderPositionKind};
 use event::Event;
 use gather::Gatherable;
-use glam::{Quat, Vec3, Vec4};
+use glam::{Quat, Vec3};
 use interact::Interactable;
 use net::Connection;
+use nyx::data::{
+    skills,
+    targets::{self, DUMMY_1, DUMMY_2},
+    Content,
+};
 use nyx::task::Proficiencies;
 use player::Player;
 use renderer::{RenderObject, Renderer};
 use serde::{Deserialize, Serialize};
+use settings::Settings;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 use targeting::{Selectable, SelectedEntity};
 use tecs::prelude::*;
 use tecs::scene::Scene;
-use tecs::utils::{Clock, Name, State, Timer};
-use transform::Transform;
+use tecs::utils::{Clock, Cooldown, FixedTimestep, Name, State};
+use transform::{PreviousTransform, Transform};
+
+// how many times a second movement and other simulation is stepped, independent of the framerate
+const SIMULATION_RATE: f32 = 60.0;
+const MINIMIZED_SLEEP: Duration = Duration::from_millis(50);
 
 #[derive(Archetype, Clone, Serialize, Deserialize)]
 struct CopperOre {
     pub render: RenderObject,
     pub transform: Transform,
     pub gatherable: Gatherable,
+    pub cooldown: Cooldown,
     pub interactable: Interactable,
     pub name: Name,
 }
@@ -60,6 +92,10 @@ struct TargetDummy {
     pub defensive_stats: combat::CombatDefensive,
     pub collider: Collider,
     pub selectable: Selectable,
+    pub faction: combat::Faction,
+    pub target: combat::TargetId,
+    #[serde(default)]
+    pub buffs: buff::Buffs,
 }
 
 #[derive(Archetype, Clone, Serialize, Deserialize)]
@@ -68,32 +104,11 @@ struct DebugSphere {
     pub render: RenderObject,
 }
 
-impl CopperOre {
-    pub fn new(world: &World) -> Result<Self> {
-        Ok(CopperOre {
-            render: RenderObject {
-                mesh: MeshId(String::from("assets/meshes/copper_ore.glb")),
-                material: Material {
-                    colour: Vec4::new(1.0, 0.5, 0.0, 1.0),
-                },
-            },
-            transform: Transform::IDENTITY,
-            gatherable: Gatherable {
-                collider: Collider {
-                    kind: ColliderKind::Sphere(5.0),
-                    position: Vec3::ZERO,
-                },
-                loot: 0,
-                timer: Timer::new(Duration::from_secs(1)),
-            },
-            interactable: Interactable::new(&world, "Gather Copper Ore"),
-            name: Name(String::from("Copper Ore")),
-        })
-    }
-
-    pub fn with_transform(mut self, transform: Transform) -> Self {
-        self.transform = transform;
-        self
+impl TargetDummy {
+    /// Makes the dummies collider follow its transform, as its id isn't known until it is spawned
+    pub fn attach_collider(world: &World, id: EntityId) {
+        let mut collider = world.get_component_mut::<Collider>(id).unwrap();
+        collider.position = ColliderPositionKind::Relative(Vec3::ZERO, id);
     }
 }
 
@@ -120,62 +135,124 @@ impl CopperOre {
 
 pub type World = tecs::World<Event>;
 
+const FRAMES_IN_FLIGHT_ARG: &str = "--frames-in-flight";
+const RENDER_SCALE_ARG: &str = "--render-scale";
+const VOLUME_ARG: &str = "--volume";
+
+/// Parses the value following a command line flag, such as the `2` in `--frames-in-flight 2`
+fn arg<T: FromStr>(flag: &str) -> Result<Option<T>>
+where
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(std::env::args()
+        .skip_while(|arg| arg != flag)
+        .nth(1)
+        .map(|value| value.parse())
+        .transpose()?)
+}
+
 fn main() -> Result<()> {
     println!(
         "Logging Level: {}",
         std::env::var("RUST_LOG").unwrap_or_else(|_| "info".to_string())
     );
     pretty_env_logger::init();
+    // read while the window and device are being created
+    let mut preload = Preload::spawn();
     let window = Window::new();
 
-    let renderer = Renderer::new(&window)?;
+    // the arguments override the saved settings for this run only, without changing them
+    let settings = Settings::load(Settings::PATH);
+
+    // `--frames-in-flight 2` trades some throughput for less latency
+    let frames_in_flight = arg(FRAMES_IN_FLIGHT_ARG)?.unwrap_or(settings.frames_in_flight());
+    let mut renderer = Renderer::new(
+        &window,
+        frames_in_flight,
+        settings.samples(),
+        &mut preload,
+    )?;
+    // `--render-scale 0.5` draws the world at half resolution
+    renderer.set_render_scale(arg(RENDER_SCALE_ARG)?.unwrap_or(settings.render_scale()))?;
+    if settings.vsync() {
+        renderer.set_vsync(true)?;
+    }
     let camera = Camera::new(&window);
+    let mut audio = Audio::new(audio::output());
+    // `--volume 0.5` plays everything at half volume
+    audio.set_volume(arg(VOLUME_ARG)?.unwrap_or(settings.volume()));
 
     let world = World::new()
         .register::<Player>()
         .register::<CopperOre>()
         .register::<TargetDummy>()
         .register::<DebugSphere>()
-        .with_resource(State::Running)
+        .register::<import::SceneObject>()
+        // frozen behind the main menu until the server lets us in
+        .with_resource(State::Paused)
         .with_resource(Proficiencies::default())
+        .with_resource(Content::load()?)
         .with_resource(MeshCache::default())
+        .with_resource(MaterialCache::default())
         .with(Connection::add)
         .with(window.add())
-        .with(renderer.add())
+        // the previous transforms have to be captured before anything moves in a fixed tick
+        .with(transform::add)
+        .with(renderer.add(preload.font()?))
         .with(camera.add())
+        .with(audio.add())
+        .with(settings.add())
         .with(Clock::add)
+        .with(FixedTimestep::add(SIMULATION_RATE))
         .with(inventory::add)
         .with(craft::add)
+        .with(drag::add)
         .with(equipment::add)
         .with(interact::add)
         .with(targeting::add)
+        // ahead of the pause menu, so escape drops a skill being aimed before it pauses
         .with(casting::add)
+        .with(healthbar::add)
+        .with(minimap::add)
+        .with(ground::add)
+        .with(sky::add)
+        .with(experience::add)
+        .with(notice::add)
+        .with(game::add)
+        .with(pause::add)
+        .with(gizmos::add)
+        .with(screenshot::add)
         .with_handler(|world, event| match event {
             Event::Stop => {
                 *world.get_mut::<State>().unwrap() = State::Stopped;
             }
+            Event::Resized(size) => {
+                let mut renderer = world.get_mut::<Renderer>().unwrap();
+                renderer.minimized = size.width == 0 || size.height == 0;
+                renderer.resize((size.width, size.height));
+            }
             _ => (),
         })
-        .with_ticker(|world| {
-            let clock = world.get::<Clock>().unwrap();
-            // println!("FPS: {}", 1.0 / clock.delta.as_secs_f32());
-        })
-        .with_ticker(Player::tick)
-        .with_ticker(gather::tick)
-        .with_ticker(combat::tick)
+        // gameplay that only advances with time runs on the fixed step. Anything reading key
+        // presses or UI signals, which only last a frame, stays per-frame along with the purely
+        // visual tickers, as do skill cooldowns and charges since they are started by input.
+        .with_fixed_ticker(Player::tick)
+        .with(gather::add)
+        .with(combat::add)
+        .with(buff::add)
         .with_ticker(targeting::tick)
         .with(net::add);
+    #[cfg(feature = "inspector")]
+    let world = world.with(inspector::add);
 
     let mut transform = Transform::IDENTITY;
     transform.translation += Vec3::ZERO;
 
-    world.spawn(TargetDummy {
+    // the server keeps the health of the dummies, so their stats come from the shared data
+    let targets = targets::get();
+    let dummy = world.spawn(TargetDummy {
         transform: Transform::new(
-            Vec3 {
-                x: 5.,
-                y: 0.,
-                z: 0.,
-            },
+            targets::POSITIONS[DUMMY_1],
             glam::Quat::default(),
             Vec3 {
                 x: 1.,
@@ -187,40 +264,29 @@ fn main() -> Result<()> {
             mesh: MeshId(String::from(
                 "assets/meshes/mannequin_armor_dummy_medieval_game_prop_Resized.glb",
             )),
-            material: Material::DEBUG_MATERIAL,
-        },
-        defensive_stats: combat::CombatDefensive {
-            health: 100,
-            max_health: 100,
-            fire_resistance: 0,
-            earth_resistance: 0,
-            lightning_resistance: 0,
-            air_resistance: 0,
-            nature_resistance: 0,
+            material: MaterialId::debug(),
         },
+        defensive_stats: targets[DUMMY_1],
         collider: Collider {
             // kind: ColliderKind::Aabb(Vec3 { x: 1., y: 1., z: 1. }),
             kind: ColliderKind::Sphere(3.),
-            position: Vec3 {
-                x: 5.0,
-                y: 0.0,
-                z: 0.0,
-            },
+            // replaced with a collider relative to the dummy once it has been spawned
+            position: ColliderPositionKind::default(),
         },
         selectable: Selectable {
-            selected_material: Material::RED,
-            unselected_material: Material::DEBUG_MATERIAL,
+            selected_material: MaterialId::red(),
+            unselected_material: MaterialId::debug(),
             selected_name: "Target1".to_string(),
         },
+        faction: combat::Faction::Enemy,
+        target: combat::TargetId(DUMMY_1),
+        buffs: buff::Buffs::default(),
     });
+    TargetDummy::attach_collider(&world, dummy);
 
-    world.spawn(TargetDummy {
+    let dummy = world.spawn(TargetDummy {
         transform: Transform::new(
-            Vec3 {
-                x: -5.,
-                y: 0.,
-                z: 0.,
-            },
+            targets::POSITIONS[DUMMY_2],
             glam::Quat::default(),
             Vec3 {
                 x: 1.,
@@ -232,144 +298,192 @@ fn main() -> Result<()> {
             mesh: MeshId(String::from(
                 "assets/meshes/mannequin_armor_dummy_medieval_game_prop_Resized.glb",
             )),
-            material: Material::DEBUG_MATERIAL,
-        },
-        defensive_stats: combat::CombatDefensive {
-            health: 200,
-            max_health: 200,
-            fire_resistance: 0,
-            earth_resistance: 0,
-            lightning_resistance: 0,
-            air_resistance: 0,
-            nature_resistance: 0,
+            material: MaterialId::debug(),
         },
+        defensive_stats: targets[DUMMY_2],
         collider: Collider {
             // kind: ColliderKind::Aabb(Vec3 { x: 1., y: 1., z: 1. }),
             kind: ColliderKind::Sphere(3.),
-            position: Vec3 {
-                x: -5.0,
-                y: 0.0,
-                z: 0.0,
-            },
+            // replaced with a collider relative to the dummy once it has been spawned
+            position: ColliderPositionKind::default(),
         },
         selectable: Selectable {
-            selected_material: Material::RED,
-            unselected_material: Material::DEBUG_MATERIAL,
+            selected_material: MaterialId::red(),
+            unselected_material: MaterialId::debug(),
             selected_name: "Target2".to_string(),
         },
+        faction: combat::Faction::Enemy,
+        target: combat::TargetId(DUMMY_2),
+        buffs: buff::Buffs::default(),
     });
+    TargetDummy::attach_collider(&world, dummy);
 
     world.spawn(Player {
         render: RenderObject {
             mesh: MeshId(String::from("assets/meshes/cube.glb")),
-            material: Material { colour: Vec4::ONE },
+            material: MaterialId::white(),
         },
         transform,
         health: player::Health(100.0),
-        offensive_stats: combat::CombatOffensive {
-            fire: combat::AttackType {
-                damage: 0,
-                penetration: 0,
-            },
-            earth: combat::AttackType {
-                damage: 0,
-                penetration: 0,
-            },
-            lightning: combat::AttackType {
-                damage: 0,
-                penetration: 0,
-            },
-            air: combat::AttackType {
-                damage: 0,
-                penetration: 0,
-            },
-            nature: combat::AttackType {
-                damage: 0,
-                penetration: 0,
-            },
-            true_damage: 32,
-            equiped_skills: vec![
-                Skill {
-                    name: "fireball".to_string(),
-                    description: "strike an area with a fireball doing {DAMAGE} damage."
-                        .to_string(),
-                    cooldown: 12.0,
-                    targeting_method: casting::SkillTargeting::Point { range: 10.0 },
-                    cast_type: casting::CastType::Charge {
-                        charge_duration: 4.0,
-                        stationary_cast: true,
-                    },
-                    effects: [
-                        casting::Effect {
-                            area_of_effect: Some(2.0),
-                            variant: casting::EffectType::Damage(casting::DamageEffect {
-                                true_damage: 0,
-                                melee_damage: 0,
-                                ranged_damage: 0,
-                                magic_damage: 20,
-                            }),
-                        },
-                        casting::Effect {
-                            area_of_effect: None,
-                            variant: casting::EffectType::BufDebuf,
-                        },
-                    ]
-                    .to_vec(),
+        experience: experience::Experience::default(),
+        faction: combat::Faction::Player,
+        offensive_stats: combat::CombatOffensive::player(1),
+        skills: casting::EquipedSkills(vec![
+            Skill {
+                name: skills::FIREBALL.to_string(),
+                description: "strike an area with a fireball doing {DAMAGE} damage.".to_string(),
+                cooldown: 12.0,
+                targeting_method: casting::SkillTargeting::Point { range: 10.0 },
+                cast_type: casting::CastType::Charge {
+                    charge_duration: 4.0,
+                    stationary_cast: true,
                 },
-                Skill {
-                    name: "IceDart".to_string(),
-                    description: "strike an area with a Shard of ICE doing {DAMAGE} damage."
-                        .to_string(),
-                    cooldown: 12.0,
-                    targeting_method: casting::SkillTargeting::Point { range: 10.0 },
-                    cast_type: casting::CastType::Charge {
-                        charge_duration: 4.0,
-                        stationary_cast: true,
+                effects: [
+                    casting::Effect {
+                        area_of_effect: Some(2.0),
+                        variant: casting::EffectType::Damage(casting::DamageEffect {
+                            true_damage: 0,
+                            melee_damage: 0,
+                            ranged_damage: 0,
+                            magic_damage: 20,
+                        }),
                     },
-                    effects: [
-                        casting::Effect {
-                            area_of_effect: Some(2.0),
-                            variant: casting::EffectType::Damage(casting::DamageEffect {
-                                true_damage: 0,
-                                melee_damage: 0,
-                                ranged_damage: 0,
-                                magic_damage: 20,
-                            }),
-                        },
-                        casting::Effect {
-                            area_of_effect: None,
-                            variant: casting::EffectType::BufDebuf,
-                        },
-                    ]
-                    .to_vec(),
+                    casting::Effect {
+                        area_of_effect: Some(2.0),
+                        variant: casting::EffectType::BufDebuf(buff::BuffEffect {
+                            modifier: buff::StatModifier::Resistance(-0.2),
+                            duration: 5.0,
+                            stacking: buff::Stacking::Refresh,
+                        }),
+                    },
+                ]
+                .to_vec(),
+            },
+            Skill {
+                name: skills::ICE_DART.to_string(),
+                description: "strike an area with a Shard of ICE doing {DAMAGE} damage.".to_string(),
+                cooldown: 12.0,
+                targeting_method: casting::SkillTargeting::Point { range: 10.0 },
+                cast_type: casting::CastType::Charge {
+                    charge_duration: 4.0,
+                    stationary_cast: true,
                 },
-            ],
-        },
+                effects: [
+                    casting::Effect {
+                        area_of_effect: Some(2.0),
+                        variant: casting::EffectType::Damage(casting::DamageEffect {
+                            true_damage: 0,
+                            melee_damage: 0,
+                            ranged_damage: 0,
+                            magic_damage: 20,
+                        }),
+                    },
+                    casting::Effect {
+                        area_of_effect: Some(2.0),
+                        variant: casting::EffectType::BufDebuf(buff::BuffEffect {
+                            modifier: buff::StatModifier::Resistance(-0.1),
+                            duration: 3.0,
+                            stacking: buff::Stacking::Stack(5),
+                        }),
+                    },
+                ]
+                .to_vec(),
+            },
+            Skill {
+                name: "Empower".to_string(),
+                description: "increase your damage by 20% for 10 seconds.".to_string(),
+                cooldown: 20.0,
+                targeting_method: casting::SkillTargeting::None,
+                cast_type: casting::CastType::Instant,
+                effects: [casting::Effect {
+                    area_of_effect: None,
+                    variant: casting::EffectType::BufDebuf(buff::BuffEffect {
+                        modifier: buff::StatModifier::Damage(0.2),
+                        duration: 10.0,
+                        stacking: buff::Stacking::Refresh,
+                    }),
+                }]
+                .to_vec(),
+            },
+        ]),
+        buffs: buff::Buffs::default(),
+        equipment_bonus: equipment::EquipmentBonus::default(),
         targeted_entity: SelectedEntity::None,
+        previous: PreviousTransform(transform),
     });
 
-    world.spawn(CopperOre::new(&world)?.with_transform(Transform {
-        translation: Vec3::ONE,
-        rotation: Quat::IDENTITY,
-        scale: Vec3::new(3.0, 1.0, 2.0),
-    }));
+    prefab::spawn(
+        &world,
+        prefab::COPPER_ORE,
+        Some(Transform {
+            translation: Vec3::ONE,
+            rotation: Quat::IDENTITY,
+            scale: Vec3::new(3.0, 1.0, 2.0),
+        }),
+    )?;
+    ground::settle::<CopperOre>(&world);
+    ground::settle::<TargetDummy>(&world);
+    // laid out in Blender, and loaded behind the loading screen along with everything else
+    import::import(&world, "assets/scenes/test.glb", MaterialId::white())?;
+
+    let (meshes, materials): (Vec<MeshId>, Vec<MaterialId>) = world
+        .query::<&RenderObject>()
+        .iter()
+        .map(|object| (object.mesh.clone(), object.material.clone()))
+        .unzip();
+    let loader = Loader::spawn(&world, meshes, materials);
+    let world = world.with(loader.add());
     // let mut scene = Scene::default();
     // scene.from_world(&world);
 
-    // let mut buffer: Vec<u8> = Vec::new();
-    // let mut serializer = serde_json::Serializer::pretty(&mut buffer);
-    // scene.save(&world, &mut serializer).unwrap();
+    // let buffer = scene.to_bytes(&world, Format::Compressed).unwrap();
     // std::fs::write("assets/scenes/test.scene", buffer).unwrap();
 
     // let buffer = std::fs::read("assets/scenes/test.scene").unwrap();
-    // Scene::load(&world, &mut serde_json::Deserializer::from_slice(&buffer)).unwrap();
+    // Scene::from_bytes(&world, &buffer, Format::Compressed).unwrap();
 
     loop {
         if let State::Stopped = *world.get::<State>().unwrap() {
             break;
         }
+        // nothing is drawn while minimized, so avoid spinning the loop as fast as possible
+        if world.get::<Renderer>().unwrap().minimized {
+            std::thread::sleep(MINIMIZED_SLEEP);
+        }
         world.tick();
+        while world.get_mut::<FixedTimestep>().unwrap().step() {
+            world.fixed_tick();
+        }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use nyx::data::{nodes, NodeId};
+    use tecs::scene::Format;
+
+    use super::*;
+
+    #[test]
+    pub fn test_scene() {
+        let world = World::new()
+            .register::<CopperOre>()
+            .register::<TargetDummy>();
+        let bytes = std::fs::read(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../assets/scenes/test.scene"
+        ))
+        .unwrap();
+        let scene = Scene::from_bytes(&world, &bytes, Format::Json).unwrap();
+        assert_eq!(scene.entities().len(), 3);
+
+        // the ore names its node rather than where it is in the data files
+        let content = Content::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../data")).unwrap();
+        let gatherables = world.query::<&Gatherable>();
+        let loot = &gatherables.iter().next().unwrap().loot;
+        assert_eq!(*loot, NodeId(String::from(nodes::COPPER_ORE)));
+        assert!(content.node(loot).is_some());
+    }
+}
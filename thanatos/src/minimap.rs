@@ -0,0 +1,164 @@
+use glam::{Quat, Vec2, Vec3, Vec4};
+use styx::components::{Marker, Markers};
+use tecs::{Is, SystemMut};
+
+use crate::{
+    camera::Camera,
+    colours,
+    event::Event,
+    game::GameState,
+    gather::Gatherable,
+    net::OtherPlayer,
+    player::Player,
+    renderer::{Anchor, Ui},
+    transform::Transform,
+    window::{Keybind, Keyboard},
+    TargetDummy, World,
+};
+
+const SIZE: Vec2 = Vec2::splat(160.0);
+const MARKER_SIZE: f32 = 6.0;
+const PLAYER_MARKER_SIZE: f32 = 8.0;
+const MIN_RANGE: f32 = 10.0;
+const MAX_RANGE: f32 = 200.0;
+// how much each press of a zoom key changes the range by
+const ZOOM_STEP: f32 = 1.25;
+
+/// A map in the corner of the screen showing what is around the player from above, turned so
+/// that up on the map is the way the camera is facing
+pub struct Minimap {
+    /// How far from the player, in world units, the edge of the map is
+    pub range: f32,
+}
+
+impl Default for Minimap {
+    fn default() -> Self {
+        Self { range: 40.0 }
+    }
+}
+
+impl Minimap {
+    /// Where `position` is on the map around `centre` with the camera turned `theta` around Y,
+    /// from -1 to 1 across it, or `None` if it is off the edge
+    pub fn project(&self, centre: Vec3, theta: f32, position: Vec3) -> Option<Vec2> {
+        let rotation = Quat::from_rotation_y(theta);
+        let offset = position - centre;
+        // the camera looks along +Z with its right to -X before it is turned
+        let right = offset.dot(rotation * Vec3::NEG_X);
+        let forward = offset.dot(rotation * Vec3::Z);
+        // forwards is up the screen, which is towards -Y in the ui
+        let projected = Vec2::new(right, -forward) / self.range;
+        (projected.length() <= 1.0).then_some(projected)
+    }
+
+    pub fn zoom(&mut self, factor: f32) {
+        self.range = (self.range * factor).clamp(MIN_RANGE, MAX_RANGE);
+    }
+
+    fn markers(&self, world: &World, centre: Vec3, theta: f32) -> Vec<Marker> {
+        let mut markers = Vec::new();
+        let mut mark = |positions: Vec<Vec3>, size: f32, colour: Vec4| {
+            markers.extend(positions.into_iter().filter_map(|position| {
+                Some(Marker {
+                    position: self.project(centre, theta, position)?,
+                    size,
+                    colour,
+                })
+            }))
+        };
+
+        let (transforms, _) = world.query::<(&Transform, &Gatherable)>();
+        mark(
+            positions(transforms.iter()),
+            MARKER_SIZE,
+            colours::LEGENDARY,
+        );
+        let (transforms, _) = world.query::<(&Transform, Is<TargetDummy>)>();
+        mark(
+            positions(transforms.iter()),
+            MARKER_SIZE,
+            Vec4::new(0.8, 0.1, 0.1, 1.0),
+        );
+        let (transforms, _) = world.query::<(&Transform, Is<OtherPlayer>)>();
+        mark(positions(transforms.iter()), MARKER_SIZE, colours::RARE);
+        // drawn last so the player is on top of anything they are standing by
+        mark(vec![centre], PLAYER_MARKER_SIZE, colours::COMMON);
+        markers
+    }
+}
+
+fn positions<'a>(transforms: impl Iterator<Item = &'a Transform>) -> Vec<Vec3> {
+    transforms.map(|transform| transform.translation).collect()
+}
+
+impl SystemMut<Event> for Minimap {
+    fn tick(&mut self, world: &World) {
+        let state = *world.get::<GameState>().unwrap();
+        if !matches!(state, GameState::InGame | GameState::Paused) {
+            return;
+        }
+
+        let keyboard = world.get::<Keyboard>().unwrap();
+        if keyboard.pressed(Keybind::MapZoomIn) {
+            self.zoom(1.0 / ZOOM_STEP);
+        }
+        if keyboard.pressed(Keybind::MapZoomOut) {
+            self.zoom(ZOOM_STEP);
+        }
+
+        let centre = {
+            let (transforms, _) = world.query::<(&Transform, Is<Player>)>();
+            let centre = transforms.iter().next().unwrap().translation;
+            centre
+        };
+        let theta = world.get::<Camera>().unwrap().theta;
+        let markers = self.markers(world, centre, theta);
+
+        world.get_mut::<Ui>().unwrap().add(
+            Anchor::TopRight,
+            Markers {
+                size: SIZE,
+                background: Vec4::new(0.1, 0.1, 0.1, 0.8),
+                radius: 8.0,
+                markers,
+            },
+        );
+    }
+}
+
+pub fn add(world: World) -> World {
+    world.with_system_mut(Minimap::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+
+    #[test]
+    pub fn test_project() {
+        let minimap = Minimap { range: 10.0 };
+        let centre = Vec3::new(5.0, 0.0, 5.0);
+        let project = |theta, position| minimap.project(centre, theta, position).unwrap();
+
+        // with the camera unturned, +Z is up the map and -X is to the right
+        assert!(project(0.0, centre).abs_diff_eq(Vec2::ZERO, 1e-5));
+        assert!(project(0.0, centre + Vec3::Z * 5.0).abs_diff_eq(Vec2::new(0.0, -0.5), 1e-5));
+        assert!(project(0.0, centre - Vec3::X * 10.0).abs_diff_eq(Vec2::new(1.0, 0.0), 1e-5));
+        // height doesn't matter from above
+        assert!(project(0.0, centre + Vec3::Y * 50.0).abs_diff_eq(Vec2::ZERO, 1e-5));
+
+        // turning the camera turns the map with it, so what it now faces is up
+        let facing = Quat::from_rotation_y(FRAC_PI_2) * Vec3::Z * 5.0;
+        assert!(project(FRAC_PI_2, centre + facing).abs_diff_eq(Vec2::new(0.0, -0.5), 1e-5));
+
+        assert!(minimap
+            .project(centre, 0.0, centre + Vec3::new(8.0, 0.0, 8.0))
+            .is_none());
+
+        let mut minimap = minimap;
+        minimap.zoom(100.0);
+        assert_eq!(minimap.range, MAX_RANGE);
+    }
+}
@@ -1,18 +1,31 @@
 use anyhow::Result;
-use glam::{Vec3, Vec4};
-use log::trace;
-use nyx::protocol::{ClientId, Clientbound, ClientboundBundle, Serverbound, Tick, TPS};
+use glam::{Quat, Vec3};
+use log::{error, trace, warn};
+use nyx::{
+    data::Content,
+    protocol::{
+        self, ClientId, Clientbound, ClientboundBundle, Quantized, Sequence, Serverbound, Tick,
+        MAX_PACKET_SIZE, TPS,
+    },
+    transport::{self, ClientTransport},
+};
 use std::{
-    cell::RefCell,
+    cell::{Cell, RefCell},
     collections::{HashMap, VecDeque},
     io::ErrorKind,
     net::UdpSocket,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
     time::{Duration, Instant},
 };
-use tecs::prelude::*;
+use tecs::{prelude::*, utils::Timer};
 
 use crate::{
-    assets::{Material, MeshId},
+    assets::{MaterialId, MeshId},
+    combat::Faction,
     event::Event,
     player::Player,
     renderer::RenderObject,
@@ -20,77 +33,185 @@ use crate::{
     World,
 };
 
+// runs the server in the same process instead of connecting to one
+const LOCAL_ARG: &str = "--local";
+// how long to wait for the server to let us in before giving up
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct Connection {
-    socket: UdpSocket,
+    socket: Box<dyn ClientTransport>,
+    // reused for every packet, so past the end of the latest one it still holds older ones
+    buffer: Vec<u8>,
     pub id: Option<ClientId>,
     pub tick: Tick,
+    checksum: u64,
+    auth: Timer,
+    // the server started by `Connection::local`, which is stopped once we are done with it
+    server: Option<(Arc<AtomicBool>, JoinHandle<Result<()>>)>,
 }
 
 impl Connection {
-    pub fn new() -> Result<Self> {
+    /// Opens a socket to the server, which isn't joined until [`Connection::connect`] is called
+    pub fn new(checksum: u64) -> Result<Self> {
         let socket = UdpSocket::bind("127.0.0.1:0")?;
         socket.connect("127.0.0.1:8080")?;
         socket.set_nonblocking(true)?;
-        let mut conn = Self {
+        Self::with_transport(Box::new(socket), checksum)
+    }
+
+    /// Starts a server on another thread and connects to it without any sockets
+    pub fn local(content: Content) -> Result<Self> {
+        let checksum = content.checksum();
+        let (server, client) = transport::loopback();
+        let running = Arc::new(AtomicBool::new(true));
+        let handle = {
+            let running = running.clone();
+            std::thread::spawn(move || hypnos::run(server, content, running))
+        };
+        let mut conn = Self::with_transport(Box::new(client), checksum)?;
+        conn.server = Some((running, handle));
+        Ok(conn)
+    }
+
+    pub fn with_transport(socket: Box<dyn ClientTransport>, checksum: u64) -> Result<Self> {
+        Ok(Self {
             socket,
+            buffer: vec![0; MAX_PACKET_SIZE],
             id: None,
             tick: Tick(0),
-        };
-        conn.write(Serverbound::AuthRequest).unwrap();
-        Ok(conn)
+            checksum,
+            auth: Timer::new(AUTH_TIMEOUT),
+            server: None,
+        })
+    }
+
+    /// Asks to join the server, sending the checksum of the content so it can check it has the
+    /// same data files
+    pub fn connect(&mut self) -> Result<()> {
+        self.auth.start();
+        self.write(Serverbound::AuthRequest(self.checksum))
+    }
+
+    /// Whether the server has taken too long to answer since [`Connection::connect`]
+    pub fn timed_out(&self) -> bool {
+        self.id.is_none() && self.auth.done()
     }
 
     pub fn write(&mut self, message: Serverbound) -> Result<()> {
-        let data = bincode::serialize(&message)?;
+        let data = protocol::serialize(&message)?;
         self.socket.send(&data)?;
         Ok(())
     }
 
     fn get(&mut self) -> Option<ClientboundBundle> {
-        let mut buffer = [0; 4096];
-        match self.socket.recv(&mut buffer) {
-            Ok(_) => Some(bincode::deserialize(&buffer).unwrap()),
-            Err(e) if e.kind() == ErrorKind::WouldBlock => None,
-            Err(e) => panic!("{e}"),
-        }
+        receive(self.socket.as_ref(), &mut self.buffer)
     }
 
     pub fn tick(world: &World) {
-        let messages: Vec<Clientbound> = {
+        let events = {
             let mut conn = world.get_mut::<Connection>().unwrap();
 
-            let Some(bundle) = conn.get() else { return };
-            conn.tick = bundle.tick;
-            trace!("Received: {:?}", bundle.tick);
-
-            bundle
-                .messages
-                .into_iter()
-                .filter(|message| match message {
-                    Clientbound::AuthSuccess(id) => {
-                        conn.id = Some(*id);
-                        false
-                    }
-                    _ => true,
-                })
-                .collect()
+            let bundles = std::iter::from_fn(|| conn.get()).collect::<Vec<_>>();
+            let mut events = Vec::new();
+            for (tick, messages) in by_tick(bundles) {
+                trace!("Received: {:?}", tick);
+                let ticked = tick != conn.tick;
+                conn.tick = tick;
+                events.extend(conn.handle(messages).into_iter().map(Event::Recieved));
+                // each tick's messages are followed by its own tick event
+                if ticked {
+                    events.push(Event::ServerTick);
+                }
+            }
+            events
         };
+        events.into_iter().for_each(|event| world.submit(event));
+    }
+
+    /// Handles the messages about the connection itself, passing on the rest
+    fn handle(&mut self, messages: Vec<Clientbound>) -> Vec<Clientbound> {
         messages
             .into_iter()
-            .for_each(|message| world.submit(Event::Recieved(message)));
-        world.submit(Event::ServerTick);
+            .filter(|message| match message {
+                Clientbound::AuthSuccess(id) => {
+                    self.id = Some(*id);
+                    self.write(Serverbound::RequestSnapshot).unwrap();
+                    false
+                }
+                Clientbound::AuthFailure(reason) => {
+                    error!("the server turned us away: {reason}");
+                    false
+                }
+                Clientbound::ServerShutdown => {
+                    // nothing more is sent once we stop thinking we are connected
+                    warn!("the server is shutting down");
+                    self.id = None;
+                    true
+                }
+                _ => true,
+            })
+            .collect()
     }
 
     pub fn add(world: World) -> World {
-        world
-            .with_resource(Self::new().unwrap())
-            .with_ticker(Self::tick)
+        let conn = {
+            let content = world.get::<Content>().unwrap();
+            if std::env::args().any(|arg| arg == LOCAL_ARG) {
+                Self::local(content.clone())
+            } else {
+                Self::new(content.checksum())
+            }
+        };
+        world.with_resource(conn.unwrap()).with_ticker(Self::tick)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let Some((running, handle)) = self.server.take() else {
+            return;
+        };
+        running.store(false, Ordering::Relaxed);
+        match handle.join() {
+            Ok(Err(e)) => error!("the local server failed: {e}"),
+            Err(_) => error!("the local server panicked"),
+            Ok(Ok(())) => (),
+        }
+    }
+}
+
+/// Groups bundles by the tick they were sent for, in the order they arrived, as a tick can be split
+/// over several bundles and several ticks can arrive in one frame
+fn by_tick(bundles: Vec<ClientboundBundle>) -> Vec<(Tick, Vec<Clientbound>)> {
+    bundles.into_iter().fold(Vec::new(), |mut ticks, bundle| {
+        match ticks.last_mut() {
+            Some((tick, messages)) if *tick == bundle.tick => messages.extend(bundle.messages),
+            _ => ticks.push((bundle.tick, bundle.messages)),
+        }
+        ticks
+    })
+}
+
+/// The next bundle sent to a socket, skipping over any packets that can't be read. Only the bytes
+/// received are decoded, as the rest of the buffer still holds whatever was received before.
+fn receive(socket: &dyn ClientTransport, buffer: &mut [u8]) -> Option<ClientboundBundle> {
+    loop {
+        match socket.recv(buffer) {
+            Ok(n) => match protocol::deserialize(&buffer[..n]) {
+                Ok(bundle) => return Some(bundle),
+                Err(e) => warn!("Dropping a malformed packet of {n} bytes: {e}"),
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return None,
+            Err(e) => panic!("{e}"),
+        }
     }
 }
 
+/// The positions and rotations received for another player, played back a couple of ticks late
+/// so there is always something to interpolate towards
 #[derive(Clone, Debug)]
 pub struct Positions {
-    queue: VecDeque<(Instant, Vec3)>,
+    queue: VecDeque<(Instant, Vec3, Quat)>,
 }
 
 impl Positions {
@@ -100,27 +221,39 @@ impl Positions {
         }
     }
 
-    pub fn push(&mut self, position: Vec3) {
+    pub fn push(&mut self, position: Vec3, rotation: Quat) {
         self.queue.push_back((
             Instant::now() + Duration::from_secs_f32(2.0 / TPS),
             position,
+            rotation,
         ))
     }
 
-    pub fn get(&mut self) -> Option<Vec3> {
-        let now = Instant::now();
+    /// Drops everything buffered, jumping straight to a position
+    pub fn reset(&mut self, position: Vec3, rotation: Quat) {
+        self.queue.clear();
+        self.queue.push_back((Instant::now(), position, rotation));
+    }
+
+    pub fn get(&mut self) -> Option<(Vec3, Quat)> {
+        self.sample(Instant::now())
+    }
+
+    fn sample(&mut self, now: Instant) -> Option<(Vec3, Quat)> {
         match self.queue.len() {
             0 => None,
-            1 => self.queue.get(1).map(|x| x.1),
+            1 => self.queue.front().map(|x| (x.1, x.2)),
             _ => {
                 let first = self.queue.front().unwrap();
                 let second = self.queue.get(1).unwrap();
                 if second.0 < now {
                     self.queue.pop_front();
-                    self.get()
+                    self.sample(now)
                 } else {
-                    let t = (now - first.0).as_secs_f32() / (second.0 - first.0).as_secs_f32();
-                    Some(second.1 * t + first.1 * (1.0 - t))
+                    // before the first entry is due it is held rather than extrapolated backwards
+                    let t = now.saturating_duration_since(first.0).as_secs_f32()
+                        / (second.0 - first.0).as_secs_f32();
+                    Some((first.1.lerp(second.1, t), first.2.slerp(second.2, t)))
                 }
             }
         }
@@ -133,66 +266,121 @@ pub struct OtherPlayer {
     pub render: RenderObject,
     pub transform: Transform,
     pub positions: Positions,
+    pub faction: Faction,
 }
 
 pub struct MovementSystem {
-    positions: RefCell<HashMap<Tick, Vec3>>,
+    // where the player was when each move was sent, until the server has echoed it back
+    positions: RefCell<HashMap<Sequence, Vec3>>,
+    sequence: Cell<Sequence>,
 }
 
 impl MovementSystem {
-    fn spawn(&self, world: &World, client_id: ClientId, position: Vec3) {
+    fn spawn(&self, world: &World, client_id: ClientId, position: Vec3, rotation: Quat) {
         let render = RenderObject {
             mesh: MeshId(String::from("assets/meshes/cube.glb")),
-            material: Material { colour: Vec4::ONE },
+            material: MaterialId::white(),
         };
         let mut transform = Transform::IDENTITY;
         transform.translation = position;
+        transform.rotation = rotation;
         world.spawn(OtherPlayer {
             client_id,
             render,
             transform,
             positions: Positions::new(),
+            faction: Faction::Player,
         });
     }
 
-    fn move_player(&self, world: &World, position: Vec3, tick: Tick) {
+    fn move_player(&self, world: &World, position: Quantized, sequence: Sequence) {
         let (mut transform, _) = world.query_one::<(&mut Transform, Is<Player>)>();
 
-        if let Some(actual) = self.positions.borrow().get(&tick) {
-            if position == *actual {
+        // anything older has either been applied or dropped by the server, so won't be echoed
+        let mut positions = self.positions.borrow_mut();
+        positions.retain(|other, _| *other >= sequence);
+        // the server only sends positions to the nearest step, so compare at the same precision
+        if let Some(actual) = positions.get(&sequence) {
+            if position == Quantized::from(*actual) {
                 return;
             }
         }
 
-        transform.translation = position;
+        transform.translation = position.into();
     }
 
-    fn move_other_player(&self, world: &World, client_id: ClientId, position: Vec3) {
+    /// Runs `f` on the buffered positions of another player, returning false if they aren't known
+    fn with_positions(world: &World, client_id: ClientId, f: impl FnOnce(&mut Positions)) -> bool {
         let (mut positions, client_ids, _) =
             world.query::<(&mut Positions, &ClientId, Is<OtherPlayer>)>();
-        let mut n = client_ids
-            .iter()
-            .position(|other| client_id == *other)
-            .unwrap() as i64;
+        let Some(n) = client_ids.iter().position(|other| client_id == *other) else {
+            return false;
+        };
+        let mut n = n as i64;
+        let mut f = Some(f);
 
         positions.for_each(|positions| {
             if n == 0 {
-                positions.push(position);
+                f.take().unwrap()(positions);
             };
             n -= 1
-        })
+        });
+        true
     }
 
-    fn update_buffered_positions(world: &World) {
-        let (mut transforms, mut positions) = world.query::<(&mut Transform, &mut Positions)>();
-        let mut positions = positions.map(|position| position.get()).into_iter();
-        transforms.for_each(|transform| {
-            if let Some(position) = positions.next().unwrap() {
-                transform.translation = position
+    fn move_other_player(
+        &self,
+        world: &World,
+        client_id: ClientId,
+        position: Vec3,
+        rotation: Quat,
+    ) {
+        let known = Self::with_positions(world, client_id, |positions| {
+            positions.push(position, rotation)
+        });
+        if !known {
+            // we were never told they spawned, so something was missed
+            warn!("{client_id:?} moved without being spawned, requesting a snapshot");
+            let mut conn = world.get_mut::<Connection>().unwrap();
+            conn.write(Serverbound::RequestSnapshot).unwrap();
+        }
+    }
+
+    /// Replaces the other players with the ones in a snapshot
+    fn apply_snapshot(&self, world: &World, entities: &[(ClientId, Vec3, Quat)]) {
+        let known = world
+            .query::<(&ClientId, Is<OtherPlayer>)>()
+            .0
+            .iter()
+            .copied()
+            .collect::<Vec<_>>();
+        let (spawned, despawned) = reconcile(&known, entities);
+        despawned
+            .into_iter()
+            .for_each(|client_id| self.despawn(world, client_id));
+        entities.iter().for_each(|(client_id, position, rotation)| {
+            if spawned.contains(client_id) {
+                self.spawn(world, *client_id, *position, *rotation);
+            } else {
+                Self::with_positions(world, *client_id, |positions| {
+                    positions.reset(*position, *rotation)
+                });
             }
         });
     }
 
+    fn update_buffered_positions(world: &World) {
+        let (mut transforms, mut positions) = world.query::<(&mut Transform, &mut Positions)>();
+        transforms
+            .zip(&mut positions)
+            .for_each(|(transform, positions)| {
+                if let Some((position, rotation)) = positions.get() {
+                    transform.translation = position;
+                    transform.rotation = rotation;
+                }
+            });
+    }
+
     fn despawn(&self, world: &World, client_id: ClientId) {
         let id = {
             let (entities, client_ids, _) = world.query::<(EntityId, &ClientId, Is<OtherPlayer>)>();
@@ -215,13 +403,20 @@ impl MovementSystem {
         if option_position.is_none() {
             return;
         }
-        let position = option_position.unwrap().translation;
+        let Transform {
+            translation: position,
+            rotation,
+            ..
+        } = *option_position.unwrap();
         if conn.id.is_none() {
             return;
         }
         let tick = conn.tick;
-        conn.write(Serverbound::Move(position, tick)).unwrap();
-        self.positions.borrow_mut().insert(tick, position);
+        let sequence = self.sequence.get();
+        conn.write(Serverbound::Move(position, rotation, tick, sequence))
+            .unwrap();
+        self.positions.borrow_mut().insert(sequence, position);
+        self.sequence.set(Sequence(sequence.0 + 1));
     }
 }
 
@@ -229,18 +424,26 @@ impl System<Event> for MovementSystem {
     fn event(&self, world: &World, event: &Event) {
         match event {
             Event::Recieved(message) => match message {
-                Clientbound::Spawn(client_id, position) => self.spawn(world, *client_id, *position),
-                Clientbound::Move(client_id, position, tick) => {
-                    trace!("Moving {client_id:?} from {tick:?}");
+                Clientbound::Spawn(client_id, position, rotation) => {
+                    self.spawn(world, *client_id, *position, *rotation)
+                }
+                Clientbound::Move(client_id, position, rotation, sequence) => {
+                    trace!("Moving {client_id:?} from {sequence:?}");
 
-                    let conn = world.get::<Connection>().unwrap();
-                    if *client_id == conn.id.unwrap() {
-                        self.move_player(world, *position, *tick);
+                    let id = world.get::<Connection>().unwrap().id;
+                    if Some(*client_id) == id {
+                        self.move_player(world, *position, *sequence);
                     } else {
-                        self.move_other_player(world, *client_id, *position);
+                        self.move_other_player(world, *client_id, (*position).into(), *rotation);
                     }
                 }
                 Clientbound::Despawn(client_id) => self.despawn(world, *client_id),
+                // nobody else is around once the server has gone
+                Clientbound::ServerShutdown => world.clear_archetype::<OtherPlayer>(),
+                Clientbound::Snapshot { tick, entities } => {
+                    trace!("Applying snapshot from {tick:?}");
+                    self.apply_snapshot(world, entities)
+                }
                 _ => (),
             },
             Event::ServerTick => self.send_player_position(world),
@@ -253,10 +456,131 @@ impl System<Event> for MovementSystem {
     }
 }
 
+/// Which players need spawning and which need despawning to go from the known players to the ones
+/// in a snapshot
+fn reconcile(
+    known: &[ClientId],
+    entities: &[(ClientId, Vec3, Quat)],
+) -> (Vec<ClientId>, Vec<ClientId>) {
+    let spawned = entities
+        .iter()
+        .map(|(client_id, ..)| *client_id)
+        .filter(|client_id| !known.contains(client_id))
+        .collect();
+    let despawned = known
+        .iter()
+        .copied()
+        .filter(|client_id| !entities.iter().any(|(other, ..)| other == client_id))
+        .collect();
+    (spawned, despawned)
+}
+
 pub fn add(world: World) -> World {
     world
         .register_unsaved::<OtherPlayer>()
         .with_system(MovementSystem {
             positions: RefCell::new(HashMap::new()),
+            sequence: Cell::new(Sequence::default()),
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    #[test]
+    pub fn test_buffered_rotation() {
+        let start = Instant::now();
+        let step = Duration::from_millis(100);
+        let mut positions = Positions::new();
+        positions
+            .queue
+            .push_back((start, Vec3::ZERO, Quat::IDENTITY));
+        positions
+            .queue
+            .push_back((start + step, Vec3::X, Quat::from_rotation_y(FRAC_PI_2)));
+
+        let (position, rotation) = positions.sample(start + step / 2).unwrap();
+        assert!(position.abs_diff_eq(Vec3::X / 2.0, 1e-5));
+        assert!(rotation.abs_diff_eq(Quat::from_rotation_y(FRAC_PI_2 / 2.0), 1e-5));
+
+        // once the second entry is due the first is dropped and it is held
+        let (position, rotation) = positions.sample(start + step * 2).unwrap();
+        assert_eq!(positions.queue.len(), 1);
+        assert_eq!(position, Vec3::X);
+        assert!(rotation.abs_diff_eq(Quat::from_rotation_y(FRAC_PI_2), 1e-5));
+    }
+
+    #[test]
+    pub fn test_reconcile_snapshot() {
+        let known = [ClientId(0), ClientId(1)];
+        let entities = [
+            (ClientId(1), Vec3::ONE, Quat::IDENTITY),
+            (ClientId(2), Vec3::ZERO, Quat::IDENTITY),
+        ];
+        let (spawned, despawned) = reconcile(&known, &entities);
+        assert_eq!(spawned, vec![ClientId(2)]);
+        assert_eq!(despawned, vec![ClientId(0)]);
+
+        // an empty snapshot clears everyone
+        let (spawned, despawned) = reconcile(&known, &[]);
+        assert!(spawned.is_empty());
+        assert_eq!(despawned, known);
+    }
+
+    #[test]
+    pub fn test_by_tick() {
+        let bundle = |tick, client_id| ClientboundBundle {
+            tick: Tick(tick),
+            messages: vec![Clientbound::Despawn(ClientId(client_id))],
+        };
+        let ticks = by_tick(vec![bundle(1, 0), bundle(1, 1), bundle(2, 2), bundle(3, 3)]);
+        // each tick is kept apart, with the bundles it was split over put back together
+        assert_eq!(
+            ticks.iter().map(|(tick, _)| *tick).collect::<Vec<_>>(),
+            vec![Tick(1), Tick(2), Tick(3)]
+        );
+        assert_eq!(ticks[0].1.len(), 2);
+        assert_eq!(ticks[2].1.len(), 1);
+    }
+
+    #[test]
+    pub fn test_local_server_stops() {
+        let content = Content::load_from(concat!(env!("CARGO_MANIFEST_DIR"), "/../data")).unwrap();
+        let conn = Connection::local(content).unwrap();
+        let (running, _) = conn.server.as_ref().unwrap();
+        let running = running.clone();
+        // dropping the connection waits for the server to shut down
+        drop(conn);
+        assert!(!running.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    pub fn test_receive_stale_buffer() {
+        let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.connect(client.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+
+        let long = ClientboundBundle {
+            tick: Tick(1),
+            messages: (0..10).map(|i| Clientbound::Despawn(ClientId(i))).collect(),
+        };
+        let short = ClientboundBundle {
+            tick: Tick(2),
+            messages: vec![Clientbound::Despawn(ClientId(0))],
+        };
+        server.send(&protocol::serialize(&long).unwrap()).unwrap();
+        server.send(&protocol::serialize(&short).unwrap()).unwrap();
+
+        let mut buffer = vec![0; MAX_PACKET_SIZE];
+        assert_eq!(receive(&client, &mut buffer).unwrap().messages.len(), 10);
+        // the end of the long bundle is still in the buffer, but isn't read as part of this one
+        let received = receive(&client, &mut buffer).unwrap();
+        assert_eq!(received.tick, Tick(2));
+        assert_eq!(received.messages.len(), 1);
+    }
+}
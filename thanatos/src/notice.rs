@@ -0,0 +1,83 @@
+use std::time::Duration;
+
+use glam::Vec4;
+use nyx::protocol::Clientbound;
+use styx::components::{Container, Text};
+use tecs::utils::Timer;
+
+use crate::{
+    event::Event,
+    renderer::{Anchor, Ui},
+    World,
+};
+
+// how long a notice stays on screen
+const DURATION: Duration = Duration::from_secs(3);
+const COLOUR: Vec4 = Vec4::new(0.9, 0.2, 0.2, 1.0);
+// pushes the notice down below the level shown at the top of the screen
+const PADDING: f32 = 40.0;
+
+/// The last thing the server had to tell the player, like why it refused an action, shown until
+/// its timer runs out
+pub struct Notice {
+    message: Option<String>,
+    timer: Timer,
+}
+
+impl Default for Notice {
+    fn default() -> Self {
+        Self {
+            message: None,
+            timer: Timer::new(DURATION),
+        }
+    }
+}
+
+impl Notice {
+    pub fn show(&mut self, message: String) {
+        self.message = Some(message);
+        self.timer.start();
+    }
+}
+
+fn handle_net(world: &World, event: &Event) {
+    let mut notice = world.get_mut::<Notice>().unwrap();
+    match event {
+        Event::Recieved(Clientbound::ActionFailed { reason }) => {
+            log::warn!("Server refused action: {reason:?}");
+            notice.show(reason.to_string());
+        }
+        Event::Recieved(Clientbound::ServerShutdown) => {
+            notice.show(String::from("The server is closing"));
+        }
+        _ => (),
+    }
+}
+
+fn draw(world: &World) {
+    let notice = world.get::<Notice>().unwrap();
+    let Some(message) = notice.message.clone().filter(|_| !notice.timer.done()) else {
+        return;
+    };
+    let mut ui = world.get_mut::<Ui>().unwrap();
+    let text = Text {
+        text: message,
+        font: ui.font.clone(),
+        font_size: 24.0,
+        colour: COLOUR,
+    };
+    let padded = Container {
+        padding: PADDING,
+        radius: 0.0,
+        colour: Vec4::ZERO,
+        child: text,
+    };
+    ui.add(Anchor::TopCenter, padded);
+}
+
+pub fn add(world: World) -> World {
+    world
+        .with_resource(Notice::default())
+        .with_handler(handle_net)
+        .with_ticker(draw)
+}
@@ -0,0 +1,175 @@
+use glam::{Vec2, Vec4};
+use styx::{
+    components::{
+        text, Checkbox, CheckboxHandle, Clicked, Dropdown, DropdownHandle, HAlign, HGroup, Slider,
+        SliderHandle, VAlign, VGroup,
+    },
+    Signal,
+};
+use tecs::SystemMut;
+use winit::keyboard::{Key, NamedKey};
+
+use crate::{
+    event::Event,
+    game::{self, GameState},
+    renderer::{Anchor, Renderer, Ui},
+    settings::Settings,
+    window::Keyboard,
+    World,
+};
+
+/// Pauses the game on escape, showing a menu to carry on playing, change settings or quit
+pub struct PauseMenu {
+    resume: Signal,
+    quit: Signal,
+    volume: SliderHandle,
+    render_scale: SliderHandle,
+    vsync: CheckboxHandle,
+    samples: DropdownHandle<u32>,
+    samples_picked: Signal,
+    // every number of samples the device supports, from none up
+    sample_options: Vec<u32>,
+}
+
+impl PauseMenu {
+    pub fn new(world: &World) -> Self {
+        let mut ui = world.get_mut::<Ui>().unwrap();
+        let settings = world.get::<Settings>().unwrap();
+        let renderer = world.get::<Renderer>().unwrap();
+        let max = renderer.ctx.device.physical.max_samples().as_raw();
+        let samples = Renderer::supported_samples(&renderer.ctx, settings.samples()).as_raw();
+        Self {
+            resume: ui.signals.signal(),
+            quit: ui.signals.signal(),
+            volume: SliderHandle::new(settings.volume()),
+            render_scale: SliderHandle::new(settings.render_scale()),
+            vsync: CheckboxHandle::new(settings.vsync()),
+            samples: DropdownHandle::new(samples),
+            samples_picked: ui.signals.signal(),
+            sample_options: (0..)
+                .map(|power| 1 << power)
+                .take_while(|samples| *samples <= max)
+                .collect(),
+        }
+    }
+
+    /// Applies the sliders once they are let go, as changing the render scale or vsync rebuilds
+    /// the swapchain and every change is saved
+    fn apply(&self, world: &World, ui: &Ui) {
+        let mut settings = world.get_mut::<Settings>().unwrap();
+        if !self.volume.grabbed() && self.volume.value() != settings.volume() {
+            settings.set_volume(self.volume.value());
+        }
+        if !self.render_scale.grabbed() && self.render_scale.value() != settings.render_scale() {
+            settings.set_render_scale(self.render_scale.value());
+        }
+        if self.vsync.checked() != settings.vsync() {
+            settings.set_vsync(self.vsync.checked());
+        }
+        if ui.signals.get(self.samples_picked) {
+            settings.set_samples(self.samples.selected());
+        }
+    }
+}
+
+fn setting<T: ToString>(
+    label: T,
+    handle: &SliderHandle,
+    min: f32,
+    max: f32,
+    step: Option<f32>,
+    ui: &Ui,
+) -> VGroup {
+    VGroup::new(VAlign::Center, 16.0)
+        .add(text(label, 24.0, ui.font.clone()))
+        .add(Slider {
+            handle: handle.clone(),
+            min,
+            max,
+            step,
+            size: Vec2::new(200.0, 20.0),
+            colour: Vec4::new(0.8, 0.5, 0.2, 1.0),
+            background: Vec4::new(0.3, 0.3, 0.3, 1.0),
+        })
+}
+
+impl SystemMut<Event> for PauseMenu {
+    fn tick(&mut self, world: &World) {
+        let state = *world.get::<GameState>().unwrap();
+        let mut ui = world.get_mut::<Ui>().unwrap();
+
+        let toggled = world
+            .get::<Keyboard>()
+            .unwrap()
+            .pressed(Key::Named(NamedKey::Escape));
+        if toggled || ui.signals.get(self.resume) {
+            match state {
+                GameState::InGame => GameState::transition(world, GameState::Paused),
+                GameState::Paused => GameState::transition(world, GameState::InGame),
+                _ => (),
+            }
+        }
+        if ui.signals.get(self.quit) {
+            world.defer(|world| world.submit(Event::Stop));
+        }
+
+        if state != GameState::Paused {
+            return;
+        }
+        self.apply(world, &ui);
+
+        let font = ui.font.clone();
+        let menu = VGroup::new(VAlign::Center, 24.0)
+            .add(text("Paused", 64.0, font.clone()))
+            .add(Clicked {
+                signal: self.resume,
+                child: text("Resume", 32.0, font.clone()),
+            })
+            .add(
+                HGroup::new(HAlign::Left, 16.0)
+                    .add(setting("Volume", &self.volume, 0.0, 1.0, None, &ui))
+                    .add(setting(
+                        "Render scale",
+                        &self.render_scale,
+                        Renderer::MIN_RENDER_SCALE,
+                        1.0,
+                        Some(0.05),
+                        &ui,
+                    ))
+                    .add(Checkbox::new(
+                        self.vsync.clone(),
+                        text("Vsync", 24.0, font.clone()),
+                    ))
+                    // the render passes are built for a number of samples, so it can't change
+                    // while running
+                    .add(
+                        VGroup::new(VAlign::Center, 16.0)
+                            .add(text("Antialiasing (on restart)", 24.0, font.clone()))
+                            .add(Dropdown::new(
+                                self.samples.clone(),
+                                self.samples_picked,
+                                self.sample_options
+                                    .iter()
+                                    .map(|&samples| {
+                                        let label = match samples {
+                                            1 => String::from("Off"),
+                                            samples => format!("{samples}x"),
+                                        };
+                                        (samples, text(label, 24.0, font.clone()))
+                                    })
+                                    .collect(),
+                            )),
+                    ),
+            )
+            .add(Clicked {
+                signal: self.quit,
+                child: text("Quit", 32.0, font),
+            });
+        ui.add(Anchor::Center, game::panel(menu));
+    }
+}
+
+pub fn add(world: World) -> World {
+    let menu = PauseMenu::new(&world);
+    world.with_system_mut(menu)
+}
@@ -1,14 +1,27 @@
 use std::default;
 
 use crate::{
-    camera::Camera, combat::CombatOffensive, renderer::RenderObject, targeting::SelectedEntity,
-    transform::Transform, window::Keyboard, Clock, World,
+    buff::Buffs,
+    camera::Camera,
+    casting::EquipedSkills,
+    collider::{Collider, ColliderKind},
+    combat::{CombatOffensive, Faction},
+    equipment::EquipmentBonus,
+    experience::Experience,
+    ground,
+    renderer::RenderObject,
+    targeting::SelectedEntity,
+    transform::{PreviousTransform, Transform},
+    window::{Keybind, Keyboard},
+    World,
 };
 use glam::{Quat, Vec3};
 use serde::{Deserialize, Serialize};
-use tecs::prelude::*;
+use tecs::{prelude::*, utils::FixedTimestep};
 
 const SPEED: f32 = 5.0;
+// the shape pushed out of other colliders when moving
+const COLLIDER: ColliderKind = ColliderKind::Sphere(0.5);
 
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Health(pub f32);
@@ -25,8 +38,19 @@ pub struct Player {
     pub transform: Transform,
     pub health: Health,
     pub offensive_stats: CombatOffensive,
+    #[serde(default)]
+    pub experience: Experience,
+    pub faction: Faction,
+    #[serde(default)]
+    pub skills: EquipedSkills,
+    #[serde(default)]
+    pub buffs: Buffs,
+    #[serde(default)]
+    pub equipment_bonus: EquipmentBonus,
     #[serde(skip)]
     pub targeted_entity: SelectedEntity,
+    #[serde(skip)]
+    pub previous: PreviousTransform,
 }
 
 impl Player {
@@ -40,35 +64,63 @@ impl Player {
         }
     }
 
-    pub fn tick(world: &World) {
-        let keyboard = world.get::<Keyboard>().unwrap();
-        let mut camera = world.get_mut::<Camera>().unwrap();
-        let clock = world.get::<Clock>().unwrap();
-
-        let (mut transform, _) = world.query_one::<(&mut Transform, Is<Player>)>();
+    /// The velocity the player wants to move at from the movement keys, relative to the
+    /// direction the camera is facing
+    fn input_velocity(keyboard: &Keyboard, theta: f32) -> Vec3 {
+        let mut direction = Vec3::ZERO;
 
-        let rotation = Quat::from_rotation_y(camera.theta);
+        if keyboard.is_down(Keybind::Forward) {
+            direction += Vec3::Z;
+        }
 
-        if keyboard.is_down("w") {
-            transform.translation += rotation * Vec3::Z * SPEED * clock.delta.as_secs_f32();
+        if keyboard.is_down(Keybind::Back) {
+            direction -= Vec3::Z;
         }
 
-        if keyboard.is_down("s") {
-            transform.translation -= rotation * Vec3::Z * SPEED * clock.delta.as_secs_f32();
+        if keyboard.is_down(Keybind::Left) {
+            direction += Vec3::X;
         }
 
-        if keyboard.is_down("a") {
-            transform.translation += rotation * Vec3::X * SPEED * clock.delta.as_secs_f32();
+        if keyboard.is_down(Keybind::Right) {
+            direction -= Vec3::X;
         }
 
-        if keyboard.is_down("d") {
-            transform.translation -= rotation * Vec3::X * SPEED * clock.delta.as_secs_f32();
+        // normalised so moving diagonally isn't faster than moving in a straight line
+        Quat::from_rotation_y(theta) * direction.normalize_or_zero() * SPEED
+    }
+
+    pub fn tick(world: &World) {
+        let keyboard = world.get::<Keyboard>().unwrap();
+        let camera = world.get::<Camera>().unwrap();
+        let delta = world.get::<FixedTimestep>().unwrap().delta.as_secs_f32();
+
+        let obstacles = world
+            .query::<&Collider>()
+            .iter()
+            .filter_map(|collider| Some((collider.kind, collider.calculate_position(world)?)))
+            .collect::<Vec<_>>();
+
+        let (mut transform, _) = world.query_one::<(&mut Transform, Is<Player>)>();
+
+        let velocity = Self::input_velocity(&keyboard, camera.theta);
+        transform.translation += velocity * delta;
+        // face the way they are walking, which is synced to the other players
+        if velocity != Vec3::ZERO {
+            transform.rotation = Quat::from_rotation_y(velocity.x.atan2(velocity.z));
         }
 
-        camera.target = transform.translation;
+        // push the player back out of anything they walked into
+        for (kind, position) in obstacles {
+            if let Some(translation) = COLLIDER.penetration(transform.translation, kind, position) {
+                transform.translation += translation;
+            }
+        }
+        ground::snap_to_ground(world, &mut transform);
     }
 }
 
 pub fn add(world: World) -> World {
-    world.with_ticker(Player::tick).with_ticker(Player::death)
+    world
+        .with_fixed_ticker(Player::tick)
+        .with_ticker(Player::death)
 }
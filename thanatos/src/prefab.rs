@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use tecs::{
+    scene::{Format, Scene},
+    EntityId,
+};
+
+use crate::{transform::Transform, World};
+
+pub const COPPER_ORE: &str = "assets/prefabs/copper_ore.json";
+
+/// Saves an entity as it is now as a prefab, a scene of just that entity which any number of
+/// copies can be spawned from. Its archetype has to be registered to be saved. Only the tests
+/// write prefabs so far.
+#[cfg(test)]
+pub fn save<P: AsRef<Path>>(world: &World, id: EntityId, path: P) -> Result<()> {
+    let mut scene = Scene::default();
+    scene.add(id);
+    let bytes = scene
+        .to_bytes(world, Format::Json)
+        .map_err(|e| anyhow!(e))?;
+    std::fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Spawns a copy of the entity saved at `path`, moved to `transform` if there is one
+pub fn spawn<P: AsRef<Path>>(
+    world: &World,
+    path: P,
+    transform: Option<Transform>,
+) -> Result<EntityId> {
+    let path = path.as_ref();
+    let bytes = std::fs::read(path)?;
+    let scene = Scene::from_bytes(world, &bytes, Format::Json).map_err(|e| anyhow!(e))?;
+    let &[id] = scene.entities() else {
+        bail!(
+            "{} holds {} entities rather than one",
+            path.display(),
+            scene.entities().len()
+        );
+    };
+
+    if let Some(transform) = transform {
+        match world.get_component_mut::<Transform>(id) {
+            Some(mut current) => *current = transform,
+            None => bail!("{} doesn't have a transform to move", path.display()),
+        }
+    }
+    Ok(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{Quat, Vec3};
+    use serde::{Deserialize, Serialize};
+    use tecs::prelude::*;
+
+    use super::*;
+
+    #[derive(Archetype, Clone, Serialize, Deserialize)]
+    struct Rock {
+        transform: Transform,
+        weight: u32,
+    }
+
+    #[test]
+    pub fn test_prefab() {
+        let path =
+            std::env::temp_dir().join(format!("thanatos-prefab-{}.json", std::process::id()));
+        let world = World::new().register::<Rock>();
+        let rock = world.spawn(Rock {
+            transform: Transform::IDENTITY,
+            weight: 7,
+        });
+        save(&world, rock, &path).unwrap();
+
+        let at = |x: f32| Transform::new(Vec3::X * x, Quat::IDENTITY, Vec3::ONE);
+        let copies = [1.0, 2.0].map(|x| spawn(&world, &path, Some(at(x))).unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_ne!(copies[0], copies[1]);
+        assert_eq!(world.query::<&u32>().iter().count(), 3);
+        copies.iter().zip([1.0, 2.0]).for_each(|(id, x)| {
+            let transform = world.get_component::<Transform>(*id).unwrap();
+            assert_eq!(transform.translation, Vec3::X * x);
+            assert_eq!(*world.get_component::<u32>(*id).unwrap(), 7);
+        });
+        // the original is left where it was
+        let original = world.get_component::<Transform>(rock).unwrap();
+        assert_eq!(original.translation, Vec3::ZERO);
+    }
+}
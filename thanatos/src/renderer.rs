@@ -1,40 +1,53 @@
 use std::{collections::VecDeque, mem::size_of, rc::Rc};
 
 use crate::{
-    assets::{Material, MeshCache, MeshId},
+    assets::{Material, MaterialCache, MaterialId, MeshCache, MeshId, Preload},
     camera::Camera,
+    colours,
     event::Event,
-    transform::Transform,
-    window::{Mouse, Window},
+    gizmos::{DebugLines, LineVertex},
+    screenshot,
+    transform::{self, Transform},
+    window::{self, Keyboard, Mouse, Window},
     World,
 };
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use bytemuck::offset_of;
-use glam::{Vec2, Vec3};
+use glam::{Mat4, Quat, Vec2, Vec3, Vec4};
 use hephaestus::{
-    buffer::Static,
-    descriptor,
-    image::{Image, ImageInfo, ImageView},
+    buffer::{Dynamic, Static},
+    command::{ImageToBufferRegion, Recorder, TransitionLayout},
+    debug, descriptor,
+    image::{Image, ImageHandle, ImageInfo, ImageView, Sampler},
     pipeline::{
         self, clear_colour, clear_depth, AttachmentInfo, Framebuffer, ImageLayout,
-        PipelineBindPoint, RenderPass, ShaderModule, Subpass, Viewport,
+        PipelineBindPoint, PrimitiveTopology, RenderPass, ShaderModule, Subpass, Viewport,
     },
+    post,
     task::{Fence, Semaphore, SubmitInfo, Task},
     vertex::{self, AttributeType},
-    AttachmentLoadOp, AttachmentStoreOp, BufferUsageFlags, Context, DescriptorType, Extent2D,
-    Format, ImageAspectFlags, ImageUsageFlags, PipelineStageFlags, SampleCountFlags, VkResult,
+    AccessFlags, AttachmentLoadOp, AttachmentStoreOp, BufferUsageFlags, ClearValue, Context,
+    DescriptorType, Extent2D, Extent3D, Format, ImageAspectFlags, ImageUsageFlags, Offset3D,
+    PipelineStageFlags, SampleCountFlags, VkResult,
 };
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
-use styx::{Element, Font, FontSettings, Signals};
-use tecs::EntityId;
-use winit::event::MouseButton;
+use styx::{Element, Font, Signals};
+use tecs::{utils::FixedTimestep, EntityId};
+use winit::{
+    event::MouseButton,
+    keyboard::{Key, NamedKey},
+};
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: Vec3,
     pub normal: Vec3,
+    /// Points along increasing U, for normal mapping
+    pub tangent: Vec3,
+    /// Which way the bitangent points, as `cross(normal, tangent) * handedness`
+    pub handedness: f32,
 }
 
 impl Vertex {
@@ -42,6 +55,56 @@ impl Vertex {
         vertex::Info::new(size_of::<Self>())
             .attribute(AttributeType::Vec3, 0)
             .attribute(AttributeType::Vec3, offset_of!(Vertex, normal))
+            // the tangent and its handedness are read together, like glTF stores them
+            .attribute(AttributeType::Vec4, offset_of!(Vertex, tangent))
+    }
+}
+
+/// The sun lighting the scene and the sky behind it
+#[derive(Clone, Copy, Debug)]
+pub struct Lighting {
+    /// The direction sunlight travels in
+    pub direction: Vec3,
+    /// In srgb, like material colours
+    pub colour: Vec3,
+    pub intensity: f32,
+    /// How much light reaches the faces turned away from the sun
+    pub ambient: f32,
+    /// The srgb colour behind everything, which the scene is cleared to
+    pub sky: Vec3,
+}
+
+impl Default for Lighting {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::NEG_ONE.normalize(),
+            colour: Vec3::ONE,
+            intensity: 0.5,
+            ambient: 0.5,
+            sky: Vec3::ZERO,
+        }
+    }
+}
+
+/// The camera's uniform buffer, laid out like the shaders' `Camera` block
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CameraData {
+    view_proj: Mat4,
+    sun_direction: Vec4,
+    sun_colour: Vec4,
+    sky: Vec4,
+}
+
+impl CameraData {
+    fn new(view_proj: Mat4, lighting: &Lighting) -> Self {
+        Self {
+            view_proj,
+            sun_direction: lighting.direction.normalize().extend(lighting.ambient),
+            sun_colour: lighting.colour.extend(lighting.intensity),
+            // already linear, like the clear colour
+            sky: colours::srgb_to_linear(lighting.sky).extend(1.0),
+        }
     }
 }
 
@@ -56,15 +119,118 @@ impl Drop for Frame {
     }
 }
 
+/// The descriptor sets a frame allocates, which are all freed at once when its slot is reused
+struct FramePools {
+    camera: descriptor::Pool,
+    objects: descriptor::Pool,
+    // one for the ui and one for each picture in it
+    ui: descriptor::Pool,
+}
+
+impl FramePools {
+    // the ui and a few pictures, which the pool grows past when there are more on screen
+    const UI_SETS: usize = 16;
+
+    fn new(
+        camera_layout: &Rc<descriptor::Layout>,
+        object_layout: &Rc<descriptor::Layout>,
+        ui: &styx::Renderer,
+    ) -> Result<Self> {
+        Ok(Self {
+            // one for the scene and one for the preview
+            camera: camera_layout.pool(2)?,
+            // the objects and decals, and the preview
+            objects: object_layout.pool(3)?,
+            ui: ui.pool(Self::UI_SETS)?,
+        })
+    }
+
+    /// Only once the frame that last used them has finished
+    fn reset(&mut self) -> Result<()> {
+        self.camera.reset()?;
+        self.objects.reset()?;
+        self.ui.reset()?;
+        Ok(())
+    }
+}
+
+/// The frames submitted to the gpu that haven't been waited on yet, oldest first. Each frame
+/// takes a slot of the per frame resources, which can only be reused once it has finished.
+struct InFlight<T> {
+    frames: VecDeque<(usize, T)>,
+    count: usize,
+    next: usize,
+}
+
+impl<T> InFlight<T> {
+    fn new(count: usize) -> Result<Self> {
+        if count == 0 {
+            return Err(anyhow!("At least one frame has to be in flight"));
+        }
+        Ok(Self {
+            frames: VecDeque::with_capacity(count),
+            count,
+            next: 0,
+        })
+    }
+
+    /// Waits for the oldest frames until there is room for another, returning the slot it can
+    /// use
+    fn next_slot(&mut self) -> usize {
+        while self.frames.len() >= self.count {
+            drop(self.frames.pop_front());
+        }
+        let slot = self.next % self.count;
+        assert!(
+            self.frames.iter().all(|(used, _)| *used != slot),
+            "Slot {slot} is still in flight"
+        );
+        slot
+    }
+
+    fn push(&mut self, slot: usize, frame: T) {
+        self.frames.push_back((slot, frame));
+        self.next += 1;
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct RenderObject {
     pub mesh: MeshId,
-    pub material: Material,
+    pub material: MaterialId,
+}
+
+/// Drawn like a [`RenderObject`] but after everything else and without writing depth, so it
+/// lies on top of whatever surface it is placed on
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Decal {
+    pub mesh: MeshId,
+    pub material: MaterialId,
+}
+
+/// The buffers for a group of objects drawn with a single indirect draw
+struct Batch {
+    set: Rc<descriptor::Set>,
+    vertices: Rc<Static>,
+    indices: Rc<Static>,
+    draws: Rc<Static>,
+    count: u32,
+}
+
+impl Batch {
+    fn draw<'a>(&self, cmd: Recorder<'a>) -> Recorder<'a> {
+        cmd.bind_descriptor_set(&self.set, 1)
+            .bind_vertex_buffer(&self.vertices, 0)
+            .bind_index_buffer(&self.indices)
+            .draw_indexed_indirect(&self.draws, 0, self.count, 20)
+    }
 }
 
 #[derive(Clone, Copy)]
 pub enum Anchor {
     Cursor,
+    /// Centred above a position in the world, hidden when it is off-screen.
+    World(Vec3),
     TopLeft,
     TopCenter,
     TopRight,
@@ -81,23 +247,23 @@ pub struct Ui {
     pub signals: Signals,
     elements: Vec<(Anchor, Box<dyn Element>)>,
     events: Vec<styx::Event>,
+    // where the left button was pressed while it is held, and whether it has been dragged
+    pressed: Option<Vec2>,
+    dragging: bool,
 }
 
 impl Ui {
-    pub fn new() -> Self {
-        let font = Rc::new(
-            Font::from_bytes(
-                std::fs::read("assets/fonts/JetBrainsMono-Medium.ttf").unwrap(),
-                FontSettings::default(),
-            )
-            .unwrap(),
-        );
+    /// How far in pixels the cursor has to move with the button held for a press to become a drag
+    const DRAG_THRESHOLD: f32 = 4.0;
 
+    pub fn new(font: Font) -> Self {
         Self {
-            font,
+            font: Rc::new(font),
             signals: Signals::default(),
             events: Vec::new(),
             elements: Vec::new(),
+            pressed: None,
+            dragging: false,
         }
     }
 
@@ -110,11 +276,34 @@ impl Ui {
             Event::MousePress(button) => {
                 let mouse = world.get::<Mouse>().unwrap();
                 match button {
-                    MouseButton::Left => styx::Event::Click(mouse.position),
+                    MouseButton::Left => {
+                        world.get_mut::<Ui>().unwrap().pressed = Some(mouse.position);
+                        styx::Event::Click(mouse.position)
+                    }
                     MouseButton::Right => styx::Event::RightClick(mouse.position),
                     _ => return,
                 }
             }
+            Event::MouseRelease(MouseButton::Left) => {
+                let mouse = world.get::<Mouse>().unwrap();
+                let mut ui = world.get_mut::<Ui>().unwrap();
+                ui.pressed = None;
+                ui.dragging = false;
+                styx::Event::Release(mouse.position)
+            }
+            Event::KeyPress(Key::Character(text)) => {
+                let mut ui = world.get_mut::<Ui>().unwrap();
+                ui.events.extend(text.chars().map(styx::Event::Character));
+                return;
+            }
+            Event::KeyPress(Key::Named(key)) => match key {
+                NamedKey::Space => styx::Event::Character(' '),
+                NamedKey::Backspace => styx::Event::Key(styx::Key::Backspace),
+                NamedKey::Enter => styx::Event::Key(styx::Key::Enter),
+                NamedKey::Tab => styx::Event::Key(styx::Key::Tab),
+                NamedKey::Escape => styx::Event::Key(styx::Key::Escape),
+                _ => return,
+            },
             _ => return,
         };
 
@@ -122,23 +311,49 @@ impl Ui {
         ui.events.push(event)
     }
 
-    pub fn paint(&mut self, world: &World) -> styx::Scene {
-        let window = world.get::<Window>().unwrap();
-        let mouse = world.get::<Mouse>().unwrap();
-        let window_size = window.window.inner_size();
-        let window_size = Vec2::new(window_size.width as f32, window_size.height as f32);
+    pub fn paint(&mut self, world: &World, window_size: Vec2) -> styx::Scene {
+        // there is no mouse when rendering headless
+        let cursor = world
+            .get::<Mouse>()
+            .map(|mouse| mouse.position)
+            .unwrap_or_default();
+        let camera = world.get::<Camera>().unwrap();
 
         let constraint = styx::Constraint {
             min: Vec2::ZERO,
             max: window_size,
         };
 
+        // escape drops focus, and shouldn't then go on to the game in the same frame
+        let focused = self.signals.has_focus();
         self.signals.clear();
+        let mut clicked = false;
+        if world.get::<Mouse>().is_some() {
+            self.events.push(styx::Event::Hover(cursor));
+        }
+        if let Some(pressed) = self.pressed {
+            if !self.dragging && pressed.distance(cursor) > Self::DRAG_THRESHOLD {
+                self.dragging = true;
+                self.events.push(styx::Event::DragStart(pressed));
+            }
+            if self.dragging {
+                self.events.push(styx::Event::Drag(cursor));
+            }
+        }
         let mut scene = styx::Scene::new();
         self.elements.iter_mut().for_each(|(anchor, element)| {
             let size = element.layout(constraint);
             let origin = match anchor {
-                Anchor::Cursor => mouse.position,
+                Anchor::Cursor => cursor,
+                Anchor::World(position) => {
+                    let Some(ndc) = camera.world_to_ndc(*position) else {
+                        return;
+                    };
+                    if ndc.abs().cmpgt(Vec2::ONE).any() {
+                        return;
+                    }
+                    window::ndc_to_screen(ndc, window_size) - Vec2::new(size.x / 2.0, size.y)
+                }
                 Anchor::TopLeft => Vec2::ZERO,
                 Anchor::TopCenter => Vec2::new((window_size.x - size.x) / 2.0, 0.0),
                 Anchor::TopRight => Vec2::new(window_size.x - size.x, 0.0),
@@ -155,59 +370,227 @@ impl Ui {
                 Anchor::BottomRight => window_size - size,
             };
 
-            element.paint(
-                styx::Area { origin, size },
+            let area = styx::Area { origin, size };
+            clicked |= styx::clicked(&self.events, area);
+            element.draw(
+                area,
                 &mut scene,
                 &self.events,
                 &mut self.signals,
             );
         });
 
+        // only once every element has seen the events, so tab goes to the next one to draw
+        self.signals.navigate(&self.events);
         self.events.clear();
         self.elements.clear();
 
+        // keys go to whatever has focus rather than the game
+        if let Some(mut keyboard) = world.get_mut::<Keyboard>() {
+            keyboard.capture(focused || self.signals.has_focus());
+        }
+
+        if clicked {
+            world.defer(|world| world.submit(Event::UiClicked));
+        }
+
         scene
     }
 }
 
+/// Where the renderer draws each frame to
+enum Target {
+    Swapchain,
+    /// An image owned by the renderer, read back to the host by [`Renderer::render_to_image`].
+    /// Only the tests render headless so far.
+    #[cfg(test)]
+    Offscreen {
+        image: Rc<Image>,
+        view: Rc<ImageView>,
+    },
+}
+
+/// The pipelines the 3d scene is drawn with, which are built for a single render pass
+struct ScenePipelines {
+    objects: pipeline::Graphics,
+    sky: pipeline::Graphics,
+    decals: pipeline::Graphics,
+    lines: pipeline::Graphics,
+}
+
+/// The 3d scene drawn at the render scale, before it is upscaled into a frame
+struct SceneTarget {
+    framebuffer: Framebuffer,
+    image: Rc<Image>,
+    // samples the image to upscale it
+    set: Rc<descriptor::Set>,
+    // the scene after post-processing, upscaled instead when there is any
+    post: post::Target,
+}
+
+/// The image objects are drawn into on their own for the ui to show. It is kept between frames,
+/// so it only has to be drawn again when what it shows changes.
+struct PreviewTarget {
+    framebuffer: Framebuffer,
+    image: Rc<Image>,
+    view: Rc<ImageView>,
+    // the framebuffer doesn't keep its attachments alive
+    _depth: Rc<ImageView>,
+}
+
 pub struct Renderer {
+    target: Target,
     render_pass: RenderPass,
-    pipeline: pipeline::Graphics,
+    // draws the 3d scene, which the render pass then upscales and draws the ui over
+    scene_pass: RenderPass,
+    upscale_pipeline: pipeline::Graphics,
+    upscale_layout: Rc<descriptor::Layout>,
+    // post-processing effects are drawn between the scene and upscaling it
+    post_pass: RenderPass,
+    grayscale_pipeline: pipeline::Graphics,
+    /// Drains the colour out of the scene, leaving the ui as it is
+    pub grayscale: bool,
+    sampler: Rc<Sampler>,
+    scenes: Vec<SceneTarget>,
+    render_scale: f32,
+    scene_pipelines: ScenePipelines,
+    // for drawing the scene straight into the frame when there is nothing to upscale or
+    // post-process
+    direct_pipelines: ScenePipelines,
+    // a single sampled pass for drawing one object into an image the ui can show
+    preview_pass: RenderPass,
+    preview_pipeline: pipeline::Graphics,
+    preview: Option<PreviewTarget>,
+    // drawn into the preview image at the start of the next frame
+    queued_preview: Option<(RenderObject, Quat)>,
     ui: styx::Renderer,
     framebuffers: Vec<Framebuffer>,
+    // signalled when a frame has been drawn, for presenting to wait on. There is one for each
+    // swapchain image rather than each slot, as a frame's fence only covers drawing it and not
+    // the present waiting on the semaphore afterwards, while an image can't be acquired again
+    // until its last present is done.
     semaphores: Vec<Rc<Semaphore>>,
-    frame_index: usize,
-    tasks: VecDeque<Frame>,
-    camera_layout: Rc<descriptor::Layout>,
-    object_layout: Rc<descriptor::Layout>,
-    images: Vec<(Rc<Image>, Rc<Image>)>,
-    views: Vec<(Rc<ImageView>, Rc<ImageView>)>,
+    tasks: InFlight<Frame>,
+    // one for each slot of `tasks`, which also keep the descriptor layouts alive
+    pools: Vec<FramePools>,
+    images: Vec<Rc<Image>>,
+    // the framebuffers don't keep their attachments alive
+    views: Vec<Rc<ImageView>>,
+    format: Format,
+    samples: SampleCountFlags,
+    /// Set while the window has no area to draw to, during which frames are skipped
+    pub minimized: bool,
+    // the latest size the window was resized to, applied at the start of the next frame so a
+    // burst of resize events only recreates the swapchain once
+    pending_resize: Option<(u32, u32)>,
+    // set to save the next frame once it has been drawn
+    screenshot: bool,
+    leaks: debug::Tracker,
     pub ctx: Context,
 }
 
 impl Renderer {
-    pub const FRAMES_IN_FLIGHT: usize = 3;
-
-    pub fn new(window: &Window) -> Result<Self> {
+    /// Triple buffered, so the cpu can get ahead of the gpu by a couple of frames
+    pub const DEFAULT_FRAMES_IN_FLIGHT: usize = 3;
+    /// Lowered to the most the device supports
+    pub const DEFAULT_SAMPLES: u32 = 8;
+    const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_SRGB;
+    /// Below this the scene is too blurry to make out
+    pub const MIN_RENDER_SCALE: f32 = 0.25;
+
+    /// Creates a renderer that lets up to `frames_in_flight` frames be drawn at once. Fewer
+    /// frames lowers the latency, more lets the cpu and gpu overlap more of their work. The scene
+    /// is multisampled with as close to `samples` samples per pixel as the device supports.
+    pub fn new(
+        window: &Window,
+        frames_in_flight: usize,
+        samples: u32,
+        shaders: &mut Preload,
+    ) -> Result<Self> {
         let size = window.window.inner_size();
         let ctx = Context::new("thanatos", &window.window, (size.width, size.height))?;
+        let format = ctx.swapchain.as_ref().unwrap().format;
+        let samples = Self::supported_samples(&ctx, samples);
+        Self::with_target(
+            ctx,
+            Target::Swapchain,
+            format,
+            ImageLayout::PRESENT_SRC_KHR,
+            frames_in_flight,
+            samples,
+            shaders,
+        )
+    }
+
+    /// Creates a renderer without a window that draws into an image of the given size, for
+    /// automated visual tests
+    #[cfg(test)]
+    pub fn headless(size: (u32, u32), shaders: &mut Preload) -> Result<Self> {
+        let ctx = Context::headless("thanatos")?;
+        let extent = Extent2D {
+            width: size.0,
+            height: size.1,
+        };
+        let image = Image::new(
+            &ctx,
+            ImageInfo {
+                format: Self::OFFSCREEN_FORMAT,
+                extent,
+                usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::TRANSFER_SRC,
+                samples: SampleCountFlags::TYPE_1,
+            },
+        )?;
+        let view = ImageView::new(
+            &ctx.device,
+            &image,
+            Self::OFFSCREEN_FORMAT,
+            ImageAspectFlags::COLOR,
+            extent,
+        )?;
+        let samples = ctx.device.physical.max_samples();
+        Self::with_target(
+            ctx,
+            Target::Offscreen { image, view },
+            Self::OFFSCREEN_FORMAT,
+            ImageLayout::TRANSFER_SRC_OPTIMAL,
+            // headless frames are waited on as soon as they are submitted
+            1,
+            samples,
+            shaders,
+        )
+    }
+
+    /// The highest power of two samples per pixel up to `requested` that the device supports
+    pub fn supported_samples(ctx: &Context, requested: u32) -> SampleCountFlags {
+        let samples = requested.clamp(1, ctx.device.physical.max_samples().as_raw());
+        SampleCountFlags::from_raw(1 << (31 - samples.leading_zeros()))
+    }
+
+    fn with_target(
+        ctx: Context,
+        target: Target,
+        format: Format,
+        final_layout: ImageLayout,
+        frames_in_flight: usize,
+        samples: SampleCountFlags,
+        shaders: &mut Preload,
+    ) -> Result<Self> {
+        let tasks = InFlight::new(frames_in_flight)?;
 
         let vertex = ShaderModule::new(
             &ctx.device,
-            &std::fs::read("assets/shaders/shader.vert.spv").unwrap(),
+            &shaders.shader("assets/shaders/shader.vert.spv")?,
         )?;
 
         let fragment = ShaderModule::new(
             &ctx.device,
-            &std::fs::read("assets/shaders/shader.frag.spv").unwrap(),
+            &shaders.shader("assets/shaders/shader.frag.spv")?,
         )?;
 
-        let samples = ctx.device.physical.get_samples();
-
         let render_pass = {
             let mut builder = RenderPass::builder();
             let colour = builder.attachment(
-                ctx.swapchain.as_ref().unwrap().format,
+                format,
                 AttachmentInfo {
                     initial_layout: ImageLayout::UNDEFINED,
                     final_layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
@@ -217,6 +600,7 @@ impl Renderer {
                 },
             );
 
+            // only used when the scene is drawn straight into the frame
             let depth = builder.attachment(
                 Format::D32_SFLOAT,
                 AttachmentInfo {
@@ -229,16 +613,17 @@ impl Renderer {
             );
 
             let resolve = builder.attachment(
-                ctx.swapchain.as_ref().unwrap().format,
+                format,
                 AttachmentInfo {
                     initial_layout: ImageLayout::UNDEFINED,
-                    final_layout: ImageLayout::PRESENT_SRC_KHR,
+                    final_layout,
                     load_op: AttachmentLoadOp::DONT_CARE,
                     store_op: AttachmentStoreOp::STORE,
                     samples: SampleCountFlags::TYPE_1,
                 },
             );
 
+            // the scene, upscaled or drawn directly, then the ui over it
             builder.subpass(
                 Subpass::new(PipelineBindPoint::GRAPHICS)
                     .colour(colour, ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
@@ -253,267 +638,547 @@ impl Renderer {
             builder.build(&ctx.device)?
         };
 
+        let scene_pass = {
+            let mut builder = RenderPass::builder();
+            let colour = builder.attachment(
+                Self::OFFSCREEN_FORMAT,
+                AttachmentInfo {
+                    initial_layout: ImageLayout::UNDEFINED,
+                    final_layout: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    load_op: AttachmentLoadOp::CLEAR,
+                    store_op: AttachmentStoreOp::DONT_CARE,
+                    samples,
+                },
+            );
+
+            let depth = builder.attachment(
+                Format::D32_SFLOAT,
+                AttachmentInfo {
+                    initial_layout: ImageLayout::UNDEFINED,
+                    final_layout: ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                    load_op: AttachmentLoadOp::CLEAR,
+                    store_op: AttachmentStoreOp::DONT_CARE,
+                    samples,
+                },
+            );
+
+            let resolve = builder.attachment(
+                Self::OFFSCREEN_FORMAT,
+                AttachmentInfo {
+                    initial_layout: ImageLayout::UNDEFINED,
+                    final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    load_op: AttachmentLoadOp::DONT_CARE,
+                    store_op: AttachmentStoreOp::STORE,
+                    samples: SampleCountFlags::TYPE_1,
+                },
+            );
+
+            builder.subpass(
+                Subpass::new(PipelineBindPoint::GRAPHICS)
+                    .colour(colour, ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .depth(depth, ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL)
+                    .resolve(resolve, ImageLayout::COLOR_ATTACHMENT_OPTIMAL),
+            );
+            builder.build(&ctx.device)?
+        };
+
         let camera_layout = descriptor::Layout::new(&ctx, &[DescriptorType::UNIFORM_BUFFER], 1000)?;
         let object_layout =
             descriptor::Layout::new(&ctx, &[DescriptorType::STORAGE_BUFFER; 2], 1000)?;
 
-        let pipeline = pipeline::Graphics::builder()
+        let fullscreen_vertex = ShaderModule::new(
+            &ctx.device,
+            &shaders.shader("assets/shaders/fullscreen.vert.spv")?,
+        )?;
+        let sky_fragment =
+            ShaderModule::new(&ctx.device, &shaders.shader("assets/shaders/sky.frag.spv")?)?;
+        let line_vertex = ShaderModule::new(
+            &ctx.device,
+            &shaders.shader("assets/shaders/line.vert.spv")?,
+        )?;
+        let line_fragment = ShaderModule::new(
+            &ctx.device,
+            &shaders.shader("assets/shaders/line.frag.spv")?,
+        )?;
+
+        let scene_pipelines = |render_pass: &RenderPass| -> VkResult<ScenePipelines> {
+            let objects = pipeline::Graphics::builder()
+                .vertex(&vertex)
+                .vertex_info(Vertex::info())
+                .fragment(&fragment)
+                .render_pass(render_pass)
+                .subpass(0)
+                .viewport(Viewport::Dynamic)
+                .layouts(vec![&camera_layout, &object_layout])
+                .depth()
+                .multisampled(samples)
+                .build(&ctx.device)?;
+
+            let decals = pipeline::Graphics::builder()
+                .vertex(&vertex)
+                .vertex_info(Vertex::info())
+                .fragment(&fragment)
+                .render_pass(render_pass)
+                .subpass(0)
+                .viewport(Viewport::Dynamic)
+                .layouts(vec![&camera_layout, &object_layout])
+                .depth_read_only()
+                .multisampled(samples)
+                .build(&ctx.device)?;
+
+            // drawn first, behind everything else, so without writing to the depth buffer
+            let sky = pipeline::Graphics::fullscreen(&fullscreen_vertex, &sky_fragment)
+                .render_pass(render_pass)
+                .subpass(0)
+                .layouts(vec![&camera_layout])
+                .multisampled(samples)
+                .build(&ctx.device)?;
+
+            // without a depth test, so the bounds of colliders show through the meshes inside
+            // them
+            let lines = pipeline::Graphics::builder()
+                .vertex(&line_vertex)
+                .vertex_info(LineVertex::info())
+                .fragment(&line_fragment)
+                .render_pass(render_pass)
+                .subpass(0)
+                .viewport(Viewport::Dynamic)
+                .layouts(vec![&camera_layout])
+                .topology(PrimitiveTopology::LINE_LIST)
+                .multisampled(samples)
+                .build(&ctx.device)?;
+
+            Ok(ScenePipelines {
+                objects,
+                sky,
+                decals,
+                lines,
+            })
+        };
+        let direct_pipelines = scene_pipelines(&render_pass)?;
+        let scene_pipelines = scene_pipelines(&scene_pass)?;
+
+        let preview_pass = {
+            let mut builder = RenderPass::builder();
+            let colour = builder.attachment(
+                Self::OFFSCREEN_FORMAT,
+                AttachmentInfo {
+                    initial_layout: ImageLayout::UNDEFINED,
+                    final_layout: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    load_op: AttachmentLoadOp::CLEAR,
+                    store_op: AttachmentStoreOp::STORE,
+                    samples: SampleCountFlags::TYPE_1,
+                },
+            );
+
+            let depth = builder.attachment(
+                Format::D32_SFLOAT,
+                AttachmentInfo {
+                    initial_layout: ImageLayout::UNDEFINED,
+                    final_layout: ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                    load_op: AttachmentLoadOp::CLEAR,
+                    store_op: AttachmentStoreOp::DONT_CARE,
+                    samples: SampleCountFlags::TYPE_1,
+                },
+            );
+
+            builder.subpass(
+                Subpass::new(PipelineBindPoint::GRAPHICS)
+                    .colour(colour, ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+                    .depth(depth, ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL),
+            );
+            builder.build(&ctx.device)?
+        };
+
+        let preview_pipeline = pipeline::Graphics::builder()
             .vertex(&vertex)
             .vertex_info(Vertex::info())
             .fragment(&fragment)
-            .render_pass(&render_pass)
+            .render_pass(&preview_pass)
             .subpass(0)
             .viewport(Viewport::Dynamic)
             .layouts(vec![&camera_layout, &object_layout])
             .depth()
-            .multisampled(samples)
+            .multisampled(SampleCountFlags::TYPE_1)
             .build(&ctx.device)?;
 
-        let ui = styx::Renderer::new(&ctx, &render_pass, 1)?;
-
-        let (images, views) = Self::create_images(&ctx)?;
-
-        let framebuffers = ctx
-            .swapchain
-            .as_ref()
-            .unwrap()
-            .views
-            .iter()
-            .zip(&views)
-            .map(|(resolve, (colour, depth))| {
-                render_pass.get_framebuffer(&ctx.device, &[colour, depth, resolve])
-            })
-            .collect::<VkResult<Vec<Framebuffer>>>()?;
-
-        let semaphores = (0..Self::FRAMES_IN_FLIGHT)
-            .map(|_| Semaphore::new(&ctx.device))
-            .collect::<VkResult<Vec<Rc<Semaphore>>>>()?;
+        let upscale_fragment = ShaderModule::new(
+            &ctx.device,
+            &shaders.shader("assets/shaders/upscale.frag.spv")?,
+        )?;
+        let upscale_layout =
+            descriptor::Layout::new(&ctx, &[DescriptorType::COMBINED_IMAGE_SAMPLER], 1000)?;
+        let upscale_pipeline =
+            pipeline::Graphics::fullscreen(&fullscreen_vertex, &upscale_fragment)
+                .render_pass(&render_pass)
+                .subpass(0)
+                .layouts(vec![&upscale_layout])
+                .multisampled(samples)
+                .build(&ctx.device)?;
+
+        let post_pass = post::render_pass(&ctx.device, Self::OFFSCREEN_FORMAT)?;
+        let grayscale_fragment = ShaderModule::new(
+            &ctx.device,
+            &shaders.shader("assets/shaders/grayscale.frag.spv")?,
+        )?;
+        let grayscale_pipeline =
+            pipeline::Graphics::fullscreen(&fullscreen_vertex, &grayscale_fragment)
+                .render_pass(&post_pass)
+                .subpass(0)
+                .layouts(vec![&upscale_layout])
+                .build(&ctx.device)?;
+        let sampler = Sampler::new(&ctx.device)?;
+
+        let ui = styx::Renderer::with_shaders(
+            &ctx,
+            &render_pass,
+            1,
+            samples,
+            &shaders.shader("assets/shaders/ui.vert.spv")?,
+            &shaders.shader("assets/shaders/ui.frag.spv")?,
+        )?;
+        let pools = (0..frames_in_flight)
+            .map(|_| FramePools::new(&camera_layout, &object_layout, &ui))
+            .collect::<Result<Vec<_>>>()?;
 
-        Ok(Self {
+        let mut renderer = Self {
             ctx,
+            target,
+            format,
+            samples,
             render_pass,
-            pipeline,
+            scene_pass,
+            upscale_pipeline,
+            upscale_layout,
+            post_pass,
+            grayscale_pipeline,
+            grayscale: false,
+            sampler,
+            scenes: Vec::new(),
+            render_scale: 1.0,
+            scene_pipelines,
+            direct_pipelines,
+            preview_pass,
+            preview_pipeline,
+            preview: None,
+            queued_preview: None,
             ui,
-            framebuffers,
-            semaphores,
-            frame_index: 0,
-            tasks: VecDeque::new(),
-            camera_layout,
-            object_layout,
-            images,
-            views,
-        })
+            framebuffers: Vec::new(),
+            semaphores: Vec::new(),
+            tasks,
+            pools,
+            images: Vec::new(),
+            views: Vec::new(),
+            minimized: false,
+            pending_resize: None,
+            screenshot: false,
+            leaks: debug::Tracker::default(),
+        };
+        renderer.create_framebuffers()?;
+        Ok(renderer)
     }
 
-    pub fn add(self) -> impl FnOnce(World) -> World {
+    pub fn add(self, font: Font) -> impl FnOnce(World) -> World {
         move |world| {
             world
                 .with_resource(self)
-                .with_resource(Ui::new())
+                .with_resource(Ui::new(font))
+                .with_resource(Lighting::default())
+                .with_ticker(Self::toggle_grayscale)
                 .with_ticker(Self::draw)
                 .with_handler(Ui::event)
         }
     }
 
-    fn create_images(
-        ctx: &Context,
-    ) -> VkResult<(
-        Vec<(Rc<Image>, Rc<Image>)>,
-        Vec<(Rc<ImageView>, Rc<ImageView>)>,
-    )> {
-        let swapchain = ctx.swapchain.as_ref().unwrap();
-        let samples = ctx.device.physical.get_samples();
-        let images = ctx
-            .swapchain
-            .as_ref()
-            .unwrap()
-            .views
-            .iter()
-            .map(|_| {
-                Ok((
-                    Image::new(
-                        ctx,
-                        ImageInfo {
-                            format: swapchain.format,
-                            extent: swapchain.extent,
-                            usage: ImageUsageFlags::COLOR_ATTACHMENT,
-                            samples,
-                        },
-                    )?,
-                    Image::new(
-                        ctx,
-                        ImageInfo {
-                            format: Format::D32_SFLOAT,
-                            extent: swapchain.extent,
-                            usage: ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
-                            samples,
-                        },
-                    )?,
-                ))
-            })
-            .collect::<VkResult<Vec<_>>>()?;
+    fn toggle_grayscale(world: &World) {
+        if world
+            .get::<Keyboard>()
+            .map(|keyboard| keyboard.pressed(Key::Named(NamedKey::F7)))
+            .unwrap_or_default()
+        {
+            let mut renderer = world.get_mut::<Renderer>().unwrap();
+            renderer.grayscale = !renderer.grayscale;
+        }
+    }
 
-        let views = images
-            .iter()
-            .map(|(colour, depth)| {
-                Ok((
-                    ImageView::new(
-                        &ctx.device,
-                        &colour,
-                        swapchain.format,
-                        ImageAspectFlags::COLOR,
-                        swapchain.extent,
-                    )?,
-                    ImageView::new(
-                        &ctx.device,
-                        &depth,
-                        Format::D32_SFLOAT,
-                        ImageAspectFlags::DEPTH,
-                        ctx.swapchain.as_ref().unwrap().extent,
-                    )?,
-                ))
-            })
-            .collect::<VkResult<Vec<_>>>()?;
+    /// The size and format of the images frames are drawn into
+    #[cfg(test)]
+    pub fn target(&self) -> (Extent2D, Format) {
+        match &self.target {
+            Target::Swapchain => {
+                let swapchain = self.ctx.swapchain.as_ref().unwrap();
+                (swapchain.extent, swapchain.format)
+            }
+            Target::Offscreen { view, .. } => (view.extent, self.format),
+        }
+    }
 
-        Ok((images, views))
+    /// The views each frame is resolved into, one per swapchain image when presenting
+    fn target_views(&self) -> Vec<Rc<ImageView>> {
+        match &self.target {
+            Target::Swapchain => self.ctx.swapchain.as_ref().unwrap().views.clone(),
+            #[cfg(test)]
+            Target::Offscreen { view, .. } => vec![view.clone()],
+        }
     }
 
-    pub fn recreate_swapchain(&mut self, size: (u32, u32)) -> VkResult<()> {
-        unsafe { self.ctx.device.device_wait_idle()? }
-        self.ctx.surface.extent = Extent2D {
-            width: size.0,
-            height: size.1,
-        };
-        self.ctx.recreate_swapchain().unwrap();
+    fn create_framebuffers(&mut self) -> VkResult<()> {
+        let samples = self.samples;
+        let targets = self.target_views();
 
         self.framebuffers.clear();
         self.views.clear();
         self.images.clear();
+        self.scenes.clear();
 
-        let (images, views) = Self::create_images(&self.ctx)?;
-        self.images = images;
-        self.views = views;
-
-        self.framebuffers = self
-            .ctx
-            .swapchain
-            .as_ref()
-            .unwrap()
-            .views
+        // recreated along with the swapchain, which is only done once the device is idle
+        self.semaphores = targets
             .iter()
-            .zip(&self.views)
-            .map(|(resolve, (colour, depth))| {
+            .map(|_| Semaphore::new(&self.ctx.device))
+            .collect::<VkResult<Vec<Rc<Semaphore>>>>()?;
+
+        for resolve in targets {
+            let colour = Image::new(
+                &self.ctx,
+                ImageInfo {
+                    format: self.format,
+                    extent: resolve.extent,
+                    usage: ImageUsageFlags::COLOR_ATTACHMENT,
+                    samples,
+                },
+            )?;
+            let colour_view = ImageView::new(
+                &self.ctx.device,
+                &colour,
+                self.format,
+                ImageAspectFlags::COLOR,
+                resolve.extent,
+            )?;
+            let depth = Image::new(
+                &self.ctx,
+                ImageInfo {
+                    format: Format::D32_SFLOAT,
+                    extent: resolve.extent,
+                    usage: ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                    samples,
+                },
+            )?;
+            let depth_view = ImageView::new(
+                &self.ctx.device,
+                &depth,
+                Format::D32_SFLOAT,
+                ImageAspectFlags::DEPTH,
+                resolve.extent,
+            )?;
+
+            self.framebuffers.push(
                 self.render_pass
-                    .get_framebuffer(&self.ctx.device, &[colour, depth, resolve])
-            })
-            .collect::<VkResult<Vec<Framebuffer>>>()?;
+                    .get_framebuffer(&self.ctx.device, &[&colour_view, &depth_view, &resolve])?,
+            );
+            self.images.push(colour);
+            self.images.push(depth);
+            self.views.push(colour_view);
+            self.views.push(depth_view);
+
+            let extent = Extent2D {
+                width: ((resolve.extent.width as f32 * self.render_scale).round() as u32).max(1),
+                height: ((resolve.extent.height as f32 * self.render_scale).round() as u32).max(1),
+            };
+            let scene = self.create_scene(extent)?;
+            self.scenes.push(scene);
+        }
 
         Ok(())
     }
 
-    pub fn draw(world: &World) {
-        let mut renderer = world.get_mut::<Renderer>().unwrap();
-        if renderer.tasks.len() > Renderer::FRAMES_IN_FLIGHT {
-            let frame = renderer.tasks.pop_front().unwrap();
-            drop(frame);
-        }
+    fn create_scene(&mut self, extent: Extent2D) -> VkResult<SceneTarget> {
+        let samples = self.samples;
+        let image = |format, usage, samples| {
+            Image::new(
+                &self.ctx,
+                ImageInfo {
+                    format,
+                    extent,
+                    usage,
+                    samples,
+                },
+            )
+        };
+        let colour = image(
+            Self::OFFSCREEN_FORMAT,
+            ImageUsageFlags::COLOR_ATTACHMENT,
+            samples,
+        )?;
+        let depth = image(
+            Format::D32_SFLOAT,
+            ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            samples,
+        )?;
+        let resolve = image(
+            Self::OFFSCREEN_FORMAT,
+            ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED,
+            SampleCountFlags::TYPE_1,
+        )?;
 
-        let mut task = Task::new();
-        let image_available = Semaphore::new(&renderer.ctx.device).unwrap();
-        let render_finished =
-            renderer.semaphores[renderer.frame_index % Renderer::FRAMES_IN_FLIGHT].clone();
-        let in_flight = Fence::new(&renderer.ctx.device).unwrap();
-        let (image_index, suboptimal) = task
-            .acquire_next_image(
-                &renderer.ctx.device,
-                renderer.ctx.swapchain.as_ref().unwrap(),
-                image_available.clone(),
+        let views = vec![
+            ImageView::new(
+                &self.ctx.device,
+                &colour,
+                Self::OFFSCREEN_FORMAT,
+                ImageAspectFlags::COLOR,
+                extent,
+            )?,
+            ImageView::new(
+                &self.ctx.device,
+                &depth,
+                Format::D32_SFLOAT,
+                ImageAspectFlags::DEPTH,
+                extent,
+            )?,
+            ImageView::new(
+                &self.ctx.device,
+                &resolve,
+                Self::OFFSCREEN_FORMAT,
+                ImageAspectFlags::COLOR,
+                extent,
+            )?,
+        ];
+        let framebuffer = self
+            .scene_pass
+            .get_framebuffer(&self.ctx.device, &[&views[0], &views[1], &views[2]])?;
+        let set = self
+            .upscale_layout
+            .alloc()?
+            .write_image(
+                0,
+                &views[2],
+                &self.sampler,
+                ImageLayout::SHADER_READ_ONLY_OPTIMAL,
             )
-            .unwrap();
+            .finish();
 
-        let window = world.get::<Window>().unwrap();
-        let size = window.window.inner_size();
+        self.views.extend(views);
 
-        if suboptimal {
-            info!("Recreating swapchain");
-            renderer
-                .recreate_swapchain((size.width, size.height))
-                .unwrap();
-            return;
-        }
+        let post = post::Target::new(
+            &self.ctx,
+            &self.post_pass,
+            Self::OFFSCREEN_FORMAT,
+            extent,
+            &self.upscale_layout,
+            &self.sampler,
+        )?;
 
-        let camera = world.get::<Camera>().unwrap();
-        let camera_buffer = Static::new(
-            &renderer.ctx,
-            bytemuck::cast_slice::<f32, u8>(&camera.get_matrix().to_cols_array()),
-            BufferUsageFlags::UNIFORM_BUFFER,
-        )
-        .unwrap();
-        let camera_set = renderer
-            .camera_layout
-            .alloc()
-            .unwrap()
-            .write_buffer(0, &camera_buffer)
-            .finish();
+        Ok(SceneTarget {
+            framebuffer,
+            image: resolve,
+            set,
+            post,
+        })
+    }
 
-        let clear_values = [clear_colour([0.0, 0.0, 0.0, 1.0]), clear_depth(1.0)];
+    /// Draws the 3d scene at `scale` times the resolution of the window and upscales it, trading
+    /// sharpness for speed. The ui is always drawn at full resolution so text stays crisp. At full
+    /// scale the scene is drawn straight into the frame, unless it is being post-processed.
+    pub fn set_render_scale(&mut self, scale: f32) -> VkResult<()> {
+        self.render_scale = scale.clamp(Self::MIN_RENDER_SCALE, 1.0);
+        // the framebuffers can't be replaced while a frame is still using them
+        unsafe { self.ctx.device.device_wait_idle()? }
+        self.create_framebuffers()
+    }
 
+    /// Switches between waiting for vsync and presenting as soon as a frame is ready. Does
+    /// nothing without a window.
+    pub fn set_vsync(&mut self, vsync: bool) -> VkResult<()> {
+        let Some(surface) = self.ctx.surface.as_mut() else {
+            return Ok(());
+        };
+        surface.vsync = vsync;
+        let size = (surface.extent.width, surface.extent.height);
+        self.recreate_swapchain(size)
+    }
+
+    /// Queues the swapchain to be recreated at the given size before the next frame is drawn
+    pub fn resize(&mut self, size: (u32, u32)) {
+        self.pending_resize = Some(size);
+    }
+
+    /// Saves the next frame that is drawn into [`screenshot::DIRECTORY`]
+    pub fn screenshot(&mut self) {
+        self.screenshot = true;
+    }
+
+    /// Keeps the current swapchain if the new size is zero, as one can't be created without any
+    /// area
+    pub fn recreate_swapchain(&mut self, size: (u32, u32)) -> VkResult<()> {
+        if size.0 == 0 || size.1 == 0 {
+            return Ok(());
+        }
+        let Some(surface) = self.ctx.surface.as_mut() else {
+            return Ok(());
+        };
+        unsafe { self.ctx.device.device_wait_idle()? }
+        surface.extent = Extent2D {
+            width: size.0,
+            height: size.1,
+        };
+        self.ctx.recreate_swapchain().unwrap();
+        self.create_framebuffers()
+    }
+
+    /// Uploads the transforms, materials and meshes of a group of objects
+    fn batch(
+        &self,
+        world: &World,
+        objects: &[(Transform, &MeshId, &MaterialId)],
+        set: descriptor::Set,
+    ) -> Result<Batch> {
         let mut meshes = world.get_mut::<MeshCache>().unwrap();
-        let (entities, render_objects) = world.query::<(EntityId, &RenderObject)>();
 
-        let transforms = entities
+        let transforms = objects
             .iter()
-            .map(|id| {
-                world
-                    .get_component::<Transform>(*id)
-                    .map(|x| *x)
-                    .unwrap_or_default()
-            })
-            .flat_map(|transform| transform.matrix().to_cols_array())
+            .flat_map(|(transform, _, _)| transform.matrix().to_cols_array())
             .collect::<Vec<f32>>();
         let transform_buffer = Static::new(
-            &renderer.ctx,
+            &self.ctx,
             bytemuck::cast_slice::<f32, u8>(&transforms),
             BufferUsageFlags::STORAGE_BUFFER,
-        )
-        .unwrap();
+        )?;
+        transform_buffer.set_name("transforms");
 
-        let materials = render_objects
+        let mut material_cache = world.get_mut::<MaterialCache>().unwrap();
+        let materials = objects
             .iter()
-            .map(|object| object.material)
-            .collect::<Vec<Material>>();
+            .map(|(_, _, material)| material_cache.load(material))
+            .collect::<Result<Vec<Material>>>()?;
         let material_buffer = Static::new(
-            &renderer.ctx,
+            &self.ctx,
             bytemuck::cast_slice::<Material, u8>(&materials),
             BufferUsageFlags::STORAGE_BUFFER,
-        )
-        .unwrap();
+        )?;
+        material_buffer.set_name("materials");
 
-        let set = renderer
-            .object_layout
-            .alloc()
-            .unwrap()
+        let set = set
             .write_buffer(0, &transform_buffer)
             .write_buffer(1, &material_buffer)
             .finish();
 
-        let (vertices, indices) = render_objects.iter().fold(
+        let (vertices, indices) = objects.iter().try_fold(
             (Vec::new(), Vec::new()),
-            |(mut vertices, mut indices), object| {
-                let mesh = meshes.load(&object.mesh).unwrap();
+            |(mut vertices, mut indices), (_, mesh, _)| {
+                let mesh = meshes.load(mesh)?;
                 vertices.extend_from_slice(&mesh.vertices);
                 indices.extend_from_slice(&mesh.indices);
-                (vertices, indices)
+                Ok::<_, anyhow::Error>((vertices, indices))
             },
-        );
+        )?;
 
         let mut index_offset = 0;
         let mut vertex_offset = 0;
 
-        let draws = render_objects
+        let draws = objects
             .iter()
-            .flat_map(|object| {
-                let mesh = meshes.load(&object.mesh).unwrap();
+            .flat_map(|(_, mesh, _)| {
+                let mesh = meshes.load(mesh).unwrap();
                 let draw = [mesh.indices.len() as u32, 1, index_offset, vertex_offset, 0];
                 index_offset += mesh.indices.len() as u32;
                 vertex_offset += mesh.vertices.len() as u32;
@@ -521,68 +1186,596 @@ impl Renderer {
             })
             .collect::<Vec<u32>>();
         let draw_buffer = Static::new(
-            &renderer.ctx,
+            &self.ctx,
             bytemuck::cast_slice::<u32, u8>(&draws),
             BufferUsageFlags::INDIRECT_BUFFER,
-        )
-        .unwrap();
+        )?;
+        draw_buffer.set_name("draws");
 
         let vertex_buffer = Static::new(
-            &renderer.ctx,
+            &self.ctx,
             bytemuck::cast_slice::<Vertex, u8>(&vertices),
             BufferUsageFlags::VERTEX_BUFFER,
-        )
-        .unwrap();
+        )?;
+        vertex_buffer.set_name("vertices");
         let index_buffer = Static::new(
-            &renderer.ctx,
+            &self.ctx,
             bytemuck::cast_slice::<u32, u8>(&indices),
             BufferUsageFlags::INDEX_BUFFER,
-        )
-        .unwrap();
+        )?;
+        index_buffer.set_name("indices");
+
+        Ok(Batch {
+            set,
+            vertices: vertex_buffer,
+            indices: index_buffer,
+            draws: draw_buffer,
+            count: draws.len() as u32 / 5,
+        })
+    }
 
-        let scene = world.get_mut::<Ui>().unwrap().paint(&world);
+    /// Records the render pass for a frame into the framebuffer at `index`, with `lines` drawn
+    /// over the scene
+    fn record<'a>(
+        &'a self,
+        world: &World,
+        slot: usize,
+        index: usize,
+        size: Extent2D,
+        lines: &[LineVertex],
+        preview: Option<(RenderObject, Quat)>,
+    ) -> Result<Recorder<'a>> {
+        let pools = &self.pools[slot];
+        let camera = world.get::<Camera>().unwrap();
+        let lighting = world
+            .get::<Lighting>()
+            .map(|lighting| *lighting)
+            .unwrap_or_default();
+        let camera_buffer = Static::new_host_visible(
+            &self.ctx,
+            bytemuck::bytes_of(&CameraData::new(camera.get_matrix(), &lighting)),
+            BufferUsageFlags::UNIFORM_BUFFER,
+        )?;
+        camera_buffer.set_name("camera");
+        let camera_set = pools
+            .camera
+            .alloc()?
+            .write_buffer(0, &camera_buffer)
+            .finish();
+
+        // the clear colour is written as is, without the conversion shader outputs get
+        let sky = colours::srgb_to_linear(lighting.sky).extend(1.0);
+        let clear_values = [clear_colour(sky.to_array()), clear_depth(1.0)];
+
+        // only now that everything has moved, so children are drawn where their parents are this
+        // frame rather than the last
+        transform::propagate(world);
+
+        // the fixed ticks due this frame haven't run yet, so anything past the latest one is held
+        // there rather than guessed at
+        let alpha = world
+            .get::<FixedTimestep>()
+            .map(|timestep| timestep.alpha().min(1.0))
+            .unwrap_or(1.0);
+        let transform =
+            |id: &EntityId| transform::interpolated(world, *id, alpha).unwrap_or_default();
+
+        // anything still being loaded in the background is left out, rather than stalling here to
+        // load it, as is anything that failed to load
+        let meshes = world.get::<MeshCache>().unwrap();
+        let materials = world.get::<MaterialCache>().unwrap();
+        let (entities, render_objects) = world.query::<(EntityId, &RenderObject)>();
+        let objects = entities
+            .iter()
+            .zip(render_objects.iter())
+            .filter(|(_, object)| {
+                meshes.available(&object.mesh) && materials.available(&object.material)
+            })
+            .map(|(id, object)| (transform(id), &object.mesh, &object.material))
+            .collect::<Vec<_>>();
+        let (entities, decals) = world.query::<(EntityId, &Decal)>();
+        let decals = entities
+            .iter()
+            .zip(decals.iter())
+            .filter(|(_, decal)| {
+                meshes.available(&decal.mesh) && materials.available(&decal.material)
+            })
+            .map(|(id, decal)| (transform(id), &decal.mesh, &decal.material))
+            .collect::<Vec<_>>();
+        drop(meshes);
+        drop(materials);
+
+        let batch = self.batch(world, &objects, pools.objects.alloc()?)?;
+        let decals = if !decals.is_empty() {
+            Some(self.batch(world, &decals, pools.objects.alloc()?)?)
+        } else {
+            None
+        };
+
+        let viewport = Vec2::new(size.width as f32, size.height as f32);
+        let scene = world.get_mut::<Ui>().unwrap().paint(world, viewport);
         let frame = if !scene.is_empty() {
-            Some(
-                renderer
-                    .ui
-                    .prepare(
-                        &renderer.ctx,
-                        &scene,
-                        Vec2::new(size.width as f32, size.height as f32),
-                    )
-                    .unwrap(),
-            )
+            Some(self.ui.prepare(&self.ctx, &pools.ui, &scene, viewport)?)
         } else {
             None
         };
 
-        let cmd = renderer
-            .ctx
-            .command_pool
-            .alloc()
-            .unwrap()
-            .begin()
-            .unwrap()
-            .begin_render_pass(
-                &renderer.render_pass,
-                renderer.framebuffers.get(image_index as usize).unwrap(),
+        let cmd = self.ctx.command_pool.alloc()?.begin()?;
+        let cmd = match (preview, &self.preview) {
+            (Some(queued), Some(preview)) => {
+                self.record_preview(world, pools, preview, queued, cmd)?
+            }
+            _ => cmd,
+        };
+
+        let lines = if !lines.is_empty() {
+            let vertices = Static::new(
+                &self.ctx,
+                bytemuck::cast_slice::<LineVertex, u8>(lines),
+                BufferUsageFlags::VERTEX_BUFFER,
+            )?;
+            Some((vertices, lines.len() as u32))
+        } else {
+            None
+        };
+        let draw_scene = |cmd: Recorder<'a>, pipelines: &'a ScenePipelines| {
+            let cmd = cmd
+                .bind_graphics_pipeline(&pipelines.sky)
+                .bind_descriptor_set(&camera_set, 0)
+                .draw_fullscreen()
+                .bind_graphics_pipeline(&pipelines.objects)
+                .bind_descriptor_set(&camera_set, 0);
+            let cmd = batch.draw(cmd);
+            let cmd = match &decals {
+                Some(decals) => decals.draw(
+                    cmd.bind_graphics_pipeline(&pipelines.decals)
+                        .bind_descriptor_set(&camera_set, 0),
+                ),
+                None => cmd,
+            };
+            match &lines {
+                Some((vertices, count)) => cmd
+                    .bind_graphics_pipeline(&pipelines.lines)
+                    .bind_descriptor_set(&camera_set, 0)
+                    .bind_vertex_buffer(vertices, 0)
+                    .draw(*count, 1, 0, 0),
+                None => cmd,
+            }
+        };
+
+        // at full scale without any post-processing there is nothing to gain from drawing the
+        // scene into its own image first, so it goes straight into the frame
+        let cmd = if self.render_scale == 1.0 && !self.grayscale {
+            let cmd = cmd
+                .begin_render_pass(
+                    &self.render_pass,
+                    self.framebuffers.get(index).unwrap(),
+                    &clear_values,
+                )
+                .set_viewport(size.width, size.height)
+                .set_scissor(size.width, size.height);
+            draw_scene(cmd, &self.direct_pipelines)
+        } else {
+            self.upscale(
+                draw_scene(
+                    self.begin_scene(cmd, index, &clear_values),
+                    &self.scene_pipelines,
+                ),
+                index,
+                size,
                 &clear_values,
             )
-            .bind_graphics_pipeline(&renderer.pipeline)
-            .set_viewport(size.width, size.height)
-            .set_scissor(size.width, size.height)
-            .bind_descriptor_set(&camera_set, 0)
-            .bind_descriptor_set(&set, 1)
-            .bind_vertex_buffer(&vertex_buffer, 0)
-            .bind_index_buffer(&index_buffer)
-            .draw_indexed_indirect(&draw_buffer, 0, draws.len() as u32 / 5, 20);
+        };
 
         let cmd = match frame {
-            Some(frame) => renderer.ui.draw(frame, cmd),
+            Some(frame) => self.ui.draw(frame, cmd),
             None => cmd.next_subpass(),
         };
 
-        let cmd = cmd.end_render_pass().end().unwrap();
+        Ok(cmd.end_render_pass())
+    }
+
+    /// Starts drawing the scene into its own image, at the render scale
+    fn begin_scene<'a>(
+        &'a self,
+        cmd: Recorder<'a>,
+        index: usize,
+        clear_values: &[ClearValue],
+    ) -> Recorder<'a> {
+        let scene = self.scenes.get(index).unwrap();
+        let scene_size = scene.framebuffer.extent;
+        cmd.begin_render_pass(&self.scene_pass, &scene.framebuffer, clear_values)
+            .set_viewport(scene_size.width, scene_size.height)
+            .set_scissor(scene_size.width, scene_size.height)
+    }
+
+    /// Finishes drawing the scene into its own image, post-processes it, and starts the frame's
+    /// render pass by upscaling it
+    fn upscale<'a>(
+        &'a self,
+        cmd: Recorder<'a>,
+        index: usize,
+        size: Extent2D,
+        clear_values: &[ClearValue],
+    ) -> Recorder<'a> {
+        let scene = self.scenes.get(index).unwrap();
+        let scene_size = scene.framebuffer.extent;
+
+        // waits for a pass to finish drawing into an image before the next one samples it
+        fn written<'a>(cmd: Recorder<'a>, image: &Rc<Image>) -> Recorder<'a> {
+            cmd.transition_layout(
+                image,
+                TransitionLayout {
+                    from: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    to: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    before: (
+                        AccessFlags::COLOR_ATTACHMENT_WRITE,
+                        PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    ),
+                    after: (
+                        AccessFlags::SHADER_READ,
+                        PipelineStageFlags::FRAGMENT_SHADER,
+                    ),
+                },
+            )
+        }
+        let cmd = written(cmd.end_render_pass(), &scene.image);
+
+        let (cmd, upscaled) = if self.grayscale {
+            let cmd = cmd
+                .begin_render_pass(&self.post_pass, &scene.post.framebuffer, &clear_values[..1])
+                .bind_graphics_pipeline(&self.grayscale_pipeline)
+                .set_viewport(scene_size.width, scene_size.height)
+                .set_scissor(scene_size.width, scene_size.height)
+                .bind_descriptor_set(&scene.set, 0)
+                .draw_fullscreen()
+                .end_render_pass();
+            (written(cmd, &scene.post.image), &scene.post.set)
+        } else {
+            (cmd, &scene.set)
+        };
+
+        cmd.begin_render_pass(
+            &self.render_pass,
+            self.framebuffers.get(index).unwrap(),
+            clear_values,
+        )
+        .bind_graphics_pipeline(&self.upscale_pipeline)
+        .set_viewport(size.width, size.height)
+        .set_scissor(size.width, size.height)
+        .bind_descriptor_set(upscaled, 0)
+        .draw_fullscreen()
+    }
+
+    /// Queues an object to be drawn on its own, turned by `rotation`, into the square preview
+    /// image at the start of the next frame. Returns the preview image, which is drawn before the
+    /// ui so it can be shown straight away, and keeps what was last drawn into it until another
+    /// object is queued.
+    pub fn preview(
+        &mut self,
+        object: &RenderObject,
+        rotation: Quat,
+        size: u32,
+    ) -> Result<Rc<ImageView>> {
+        let view = match &self.preview {
+            Some(preview) if preview.view.extent.width == size => preview.view.clone(),
+            _ => {
+                // the frames in flight may still be drawing into the old image
+                unsafe { self.ctx.device.device_wait_idle()? };
+                let preview = self.create_preview(size)?;
+                let view = preview.view.clone();
+                self.preview = Some(preview);
+                view
+            }
+        };
+        self.queued_preview = Some((object.clone(), rotation));
+        Ok(view)
+    }
+
+    /// The preview image, as it was last drawn
+    pub fn preview_image(&self) -> Option<Rc<ImageView>> {
+        self.preview.as_ref().map(|preview| preview.view.clone())
+    }
+
+    fn create_preview(&self, size: u32) -> Result<PreviewTarget> {
+        let extent = Extent2D {
+            width: size,
+            height: size,
+        };
+        let colour = Image::new(
+            &self.ctx,
+            ImageInfo {
+                format: Self::OFFSCREEN_FORMAT,
+                extent,
+                usage: ImageUsageFlags::COLOR_ATTACHMENT | ImageUsageFlags::SAMPLED,
+                samples: SampleCountFlags::TYPE_1,
+            },
+        )?;
+        let depth = Image::new(
+            &self.ctx,
+            ImageInfo {
+                format: Format::D32_SFLOAT,
+                extent,
+                usage: ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+                samples: SampleCountFlags::TYPE_1,
+            },
+        )?;
+        let view = ImageView::new(
+            &self.ctx.device,
+            &colour,
+            Self::OFFSCREEN_FORMAT,
+            ImageAspectFlags::COLOR,
+            extent,
+        )?;
+        let depth = ImageView::new(
+            &self.ctx.device,
+            &depth,
+            Format::D32_SFLOAT,
+            ImageAspectFlags::DEPTH,
+            extent,
+        )?;
+        let framebuffer = self
+            .preview_pass
+            .get_framebuffer(&self.ctx.device, &[&view, &depth])?;
+        Ok(PreviewTarget {
+            framebuffer,
+            image: colour,
+            view,
+            _depth: depth,
+        })
+    }
+
+    /// Records drawing the queued preview into the preview image. The camera is pulled back far
+    /// enough to fit the whole mesh in.
+    fn record_preview<'a>(
+        &'a self,
+        world: &World,
+        pools: &FramePools,
+        preview: &'a PreviewTarget,
+        (object, rotation): (RenderObject, Quat),
+        cmd: Recorder<'a>,
+    ) -> Result<Recorder<'a>> {
+        let radius = match world.get_mut::<MeshCache>().unwrap().load(&object.mesh) {
+            Ok(mesh) => mesh.radius(),
+            Err(e) => {
+                warn!("Failed to draw the preview: {e}");
+                return Ok(cmd);
+            }
+        };
+        let fov = std::f32::consts::FRAC_PI_4;
+        // looking down at it from the same angle as the game camera
+        let eye = Vec3::new(0.0, -1.0, -1.0).normalize() * radius / (fov / 2.0).sin();
+        let matrix = Mat4::perspective_infinite_rh(fov, 1.0, 0.1)
+            * Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y);
+        let camera_buffer = Static::new_host_visible(
+            &self.ctx,
+            bytemuck::bytes_of(&CameraData::new(matrix, &Lighting::default())),
+            BufferUsageFlags::UNIFORM_BUFFER,
+        )?;
+        let camera_set = pools
+            .camera
+            .alloc()?
+            .write_buffer(0, &camera_buffer)
+            .finish();
+
+        let transform = Transform::new(Vec3::ZERO, rotation, Vec3::ONE);
+        let batch = self.batch(
+            world,
+            &[(transform, &object.mesh, &object.material)],
+            pools.objects.alloc()?,
+        )?;
+
+        let size = preview.view.extent.width;
+        // cleared to transparent so the panel behind shows around the object
+        let clear_values = [clear_colour([0.0, 0.0, 0.0, 0.0]), clear_depth(1.0)];
+        // the ui of an earlier frame may still be showing the image
+        let cmd = cmd
+            .transition_layout(
+                &*preview.image,
+                TransitionLayout {
+                    from: ImageLayout::UNDEFINED,
+                    to: ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    before: (
+                        AccessFlags::SHADER_READ,
+                        PipelineStageFlags::FRAGMENT_SHADER
+                            | PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                    ),
+                    after: (
+                        AccessFlags::COLOR_ATTACHMENT_WRITE,
+                        PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    ),
+                },
+            )
+            .begin_render_pass(&self.preview_pass, &preview.framebuffer, &clear_values)
+            .bind_graphics_pipeline(&self.preview_pipeline)
+            .set_viewport(size, size)
+            .set_scissor(size, size)
+            .bind_descriptor_set(&camera_set, 0);
+        let cmd = batch.draw(cmd).end_render_pass();
+
+        // drawn before this frame's ui samples it
+        Ok(cmd.transition_layout(
+            &*preview.image,
+            TransitionLayout {
+                from: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                to: ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                before: (
+                    AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                ),
+                after: (
+                    AccessFlags::SHADER_READ,
+                    PipelineStageFlags::FRAGMENT_SHADER,
+                ),
+            },
+        ))
+    }
+
+    /// Copies a 4 byte per pixel image that has just been drawn into a buffer the host can read
+    /// once the commands have run, leaving the image in the layout it was in
+    fn copy_to_host<'a, I: ImageHandle>(
+        &self,
+        cmd: Recorder<'a>,
+        image: &I,
+        layout: ImageLayout,
+        extent: Extent2D,
+    ) -> Result<(Recorder<'a>, Rc<Dynamic>)> {
+        let staging = Dynamic::new(
+            &self.ctx,
+            (extent.width * extent.height * 4) as usize,
+            BufferUsageFlags::TRANSFER_DST,
+        )?;
+
+        let cmd = cmd
+            .transition_layout(
+                image,
+                TransitionLayout {
+                    from: layout,
+                    to: ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    before: (
+                        AccessFlags::COLOR_ATTACHMENT_WRITE,
+                        PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                    ),
+                    after: (AccessFlags::TRANSFER_READ, PipelineStageFlags::TRANSFER),
+                },
+            )
+            .copy_image_to_buffer(
+                image,
+                ImageLayout::TRANSFER_SRC_OPTIMAL,
+                &staging,
+                ImageToBufferRegion {
+                    from_offset: Offset3D::default(),
+                    from_extent: Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    },
+                    to_offset: 0,
+                },
+            )
+            .transition_layout(
+                image,
+                TransitionLayout {
+                    from: ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    to: layout,
+                    before: (AccessFlags::TRANSFER_READ, PipelineStageFlags::TRANSFER),
+                    after: (AccessFlags::NONE, PipelineStageFlags::BOTTOM_OF_PIPE),
+                },
+            );
+        Ok((cmd, staging))
+    }
+
+    /// Renders a single frame with a headless renderer, returning the pixels as tightly packed
+    /// RGBA rows
+    #[cfg(test)]
+    pub fn render_to_image(world: &World) -> Result<Vec<u8>> {
+        let mut renderer = world.get_mut::<Renderer>().unwrap();
+        // the last image's commands have finished, as they are waited on below
+        renderer.pools[0].reset()?;
+        let preview = renderer.queued_preview.take();
+        let renderer = &*renderer;
+        let Target::Offscreen { image, view } = &renderer.target else {
+            return Err(anyhow!("Only a headless renderer can render to an image"));
+        };
+        let extent = view.extent;
+
+        let lines = world
+            .get_mut::<DebugLines>()
+            .map(|mut lines| lines.take())
+            .unwrap_or_default();
+        let cmd = renderer.record(world, 0, 0, extent, &lines, preview)?;
+        let (cmd, staging) =
+            renderer.copy_to_host(cmd, image, ImageLayout::TRANSFER_SRC_OPTIMAL, extent)?;
+        let cmd = cmd.end()?;
+
+        Task::run(
+            &renderer.ctx.device,
+            &renderer.ctx.device.queues.graphics,
+            &cmd,
+        )?;
+
+        Ok(staging.read()?)
+    }
+
+    pub fn draw(world: &World) {
+        // cleared even when the frame is skipped, so they don't build up while minimized
+        let lines = world
+            .get_mut::<DebugLines>()
+            .map(|mut lines| lines.take())
+            .unwrap_or_default();
+        let mut renderer = world.get_mut::<Renderer>().unwrap();
+        let window = world.get::<Window>().unwrap();
+        let size = window.window.inner_size();
+        if renderer.minimized || size.width == 0 || size.height == 0 {
+            return;
+        }
+
+        if let Some(size) = renderer.pending_resize.take() {
+            info!("Recreating swapchain");
+            renderer.recreate_swapchain(size).unwrap();
+        }
+
+        // the frame that last used this slot has to finish before it is used again
+        let slot = renderer.tasks.next_slot();
+        renderer.pools[slot].reset().unwrap();
+
+        let mut task = Task::new();
+        let image_available = Semaphore::new(&renderer.ctx.device).unwrap();
+        let in_flight = Fence::new(&renderer.ctx.device).unwrap();
+        let (image_index, suboptimal) = task
+            .acquire_next_image(
+                &renderer.ctx.device,
+                renderer.ctx.swapchain.as_ref().unwrap(),
+                image_available.clone(),
+            )
+            .unwrap();
+
+        if suboptimal {
+            info!("Recreating swapchain");
+            renderer
+                .recreate_swapchain((size.width, size.height))
+                .unwrap();
+            return;
+        }
+
+        // acquiring the image means its last present has finished waiting on this
+        let render_finished = renderer.semaphores[image_index as usize].clone();
+
+        let screenshot = std::mem::take(&mut renderer.screenshot);
+        let preview = renderer.queued_preview.take();
+        let cmd = renderer
+            .record(
+                world,
+                slot,
+                image_index as usize,
+                Extent2D {
+                    width: size.width,
+                    height: size.height,
+                },
+                &lines,
+                preview,
+            )
+            .unwrap();
+        let swapchain = renderer.ctx.swapchain.as_ref().unwrap();
+        let (cmd, capture) = match screenshot {
+            true if swapchain.usage.contains(ImageUsageFlags::TRANSFER_SRC) => {
+                let (cmd, staging) = renderer
+                    .copy_to_host(
+                        cmd,
+                        &swapchain.images[image_index as usize],
+                        ImageLayout::PRESENT_SRC_KHR,
+                        swapchain.extent,
+                    )
+                    .unwrap();
+                (cmd, Some((staging, swapchain.extent, swapchain.format)))
+            }
+            true => {
+                warn!("The swapchain can't be copied from, so no screenshot was taken");
+                (cmd, None)
+            }
+            false => (cmd, None),
+        };
+        let cmd = cmd.end().unwrap();
 
         task.submit(SubmitInfo {
             device: &renderer.ctx.device,
@@ -603,6 +1796,15 @@ impl Renderer {
             )
             .unwrap();
 
+        if let Some((staging, extent, format)) = capture {
+            // the frame has to be drawn before it can be read back
+            in_flight.wait().unwrap();
+            match staging.read() {
+                Ok(pixels) => screenshot::save(extent, format, pixels),
+                Err(e) => warn!("Failed to read back the screenshot: {e}"),
+            }
+        }
+
         if suboptimal {
             info!("Recreating swapchain");
             renderer
@@ -610,12 +1812,14 @@ impl Renderer {
                 .unwrap();
         }
 
-        renderer.tasks.push_back(Frame {
-            task,
-            fence: in_flight,
-        });
-
-        renderer.frame_index += 1;
+        renderer.tasks.push(
+            slot,
+            Frame {
+                task,
+                fence: in_flight,
+            },
+        );
+        renderer.leaks.frame();
     }
 }
 
@@ -624,3 +1828,141 @@ impl Drop for Renderer {
         unsafe { self.ctx.device.device_wait_idle().unwrap() }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec4;
+    use styx::components::{text, ProgressBar, TextHandle, TextInput};
+    use tecs::prelude::*;
+
+    use crate::window::Keybind;
+
+    #[derive(Archetype)]
+    struct Object {
+        render: RenderObject,
+    }
+
+    #[test]
+    pub fn test_in_flight_slots() {
+        assert!(InFlight::<()>::new(0).is_err());
+
+        for count in 1..=3 {
+            let mut in_flight = InFlight::new(count).unwrap();
+            for frame in 0..10 {
+                let slot = in_flight.next_slot();
+                assert!(in_flight.frames.len() < count);
+                assert!(in_flight.frames.iter().all(|(used, _)| *used != slot));
+                in_flight.push(slot, frame);
+            }
+            // the oldest frames were waited on to make room for the newest
+            let frames = in_flight.frames.iter().map(|(_, frame)| *frame);
+            assert!(frames.eq(10 - count..10));
+        }
+    }
+
+    #[test]
+    pub fn test_focus_captures_keybinds() {
+        let font = Preload::spawn_in(concat!(env!("CARGO_MANIFEST_DIR"), "/.."))
+            .font()
+            .unwrap();
+        let world = World::new()
+            .with_resource(Ui::new(font))
+            .with_resource(Keyboard::default())
+            .with_resource(Mouse::default())
+            .with_resource(Camera::looking_at_origin(1.0));
+        let handle = TextHandle::default();
+        let signal = world.get_mut::<Ui>().unwrap().signals.signal();
+        let frame = |events: &[Event]| {
+            events.iter().for_each(|event| {
+                if let Event::KeyPress(key) = event {
+                    world.get_mut::<Keyboard>().unwrap().press(key.clone());
+                }
+                Ui::event(&world, event);
+            });
+            let mut ui = world.get_mut::<Ui>().unwrap();
+            let font = ui.font.clone();
+            ui.add(
+                Anchor::TopLeft,
+                TextInput::new(handle.clone(), signal, 200.0, text("", 20.0, font)),
+            );
+            ui.paint(&world, Vec2::new(400.0, 400.0));
+        };
+        let key = |key: &str| Key::Character(key.into());
+
+        // clicking on the field at the top left
+        world.get_mut::<Mouse>().unwrap().position = Vec2::new(10.0, 10.0);
+        frame(&[Event::MousePress(MouseButton::Left)]);
+        frame(&[Event::KeyPress(key("i"))]);
+        assert_eq!(handle.text(), "i");
+        assert!(!world.get::<Keyboard>().unwrap().pressed(Keybind::Inventory));
+
+        // escape leaves the field, without pausing the game
+        frame(&[Event::KeyPress(Key::Named(NamedKey::Escape))]);
+        assert!(!world
+            .get::<Keyboard>()
+            .unwrap()
+            .pressed(Key::Named(NamedKey::Escape)));
+
+        world.get_mut::<Keyboard>().unwrap().release(&key("i"));
+        frame(&[Event::KeyPress(key("i"))]);
+        assert_eq!(handle.text(), "i");
+        assert!(world.get::<Keyboard>().unwrap().pressed(Keybind::Inventory));
+    }
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    pub fn test_ui_snapshot() {
+        let mut preload = Preload::spawn_in(concat!(env!("CARGO_MANIFEST_DIR"), "/.."));
+        let world = World::new()
+            .register_unsaved::<Object>()
+            .with_resource(Renderer::headless((64, 64), &mut preload).unwrap())
+            .with_resource(Ui::new(preload.font().unwrap()))
+            .with_resource(MeshCache::default())
+            .with_resource(MaterialCache::default())
+            .with_resource(Camera::looking_at_origin(1.0));
+        world.spawn(Object {
+            render: RenderObject {
+                mesh: MeshId(String::from(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../assets/meshes/cube.glb"
+                ))),
+                material: MaterialId(String::from(concat!(
+                    env!("CARGO_MANIFEST_DIR"),
+                    "/../assets/materials/white.json"
+                ))),
+            },
+        });
+
+        world.get_mut::<Ui>().unwrap().add(
+            Anchor::TopLeft,
+            ProgressBar {
+                progress: 1.0,
+                size: Vec2::new(16.0, 16.0),
+                colour: Vec4::new(1.0, 0.0, 0.0, 1.0),
+                background: Vec4::new(0.0, 0.0, 0.0, 1.0),
+                radius: 0.0,
+            },
+        );
+
+        // a mid grey swatch should come back out as the same srgb value it went in as
+        world.get_mut::<Ui>().unwrap().add(
+            Anchor::TopRight,
+            ProgressBar {
+                progress: 1.0,
+                size: Vec2::new(16.0, 16.0),
+                colour: Vec4::new(0.5, 0.5, 0.5, 1.0),
+                background: Vec4::new(0.0, 0.0, 0.0, 1.0),
+                radius: 0.0,
+            },
+        );
+
+        let pixels = Renderer::render_to_image(&world).unwrap();
+        let pixel = |x: usize, y: usize| &pixels[(y * 64 + x) * 4..(y * 64 + x) * 4 + 4];
+        assert_eq!(pixel(8, 8), [255, 0, 0, 255]);
+        assert_eq!(pixel(63, 63), [0, 0, 0, 255]);
+        let grey = pixel(56, 8);
+        assert!(grey[..3].iter().all(|channel| channel.abs_diff(128) <= 1));
+        assert_eq!(grey[3], 255);
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use hephaestus::{Extent2D, Format};
+use image::RgbaImage;
+use log::{info, warn};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::{renderer::Renderer, window::Keyboard, World};
+
+pub const DIRECTORY: &str = "screenshots";
+
+/// Turns pixels read back from an image of `format` into an RGBA image. The `_SRGB` formats are
+/// already encoded as sRGB in memory, which is what png expects, so only the channels of the
+/// BGRA formats need to be swapped around.
+pub fn to_image(extent: Extent2D, format: Format, mut pixels: Vec<u8>) -> Result<RgbaImage> {
+    if matches!(format, Format::B8G8R8A8_SRGB | Format::B8G8R8A8_UNORM) {
+        pixels
+            .chunks_exact_mut(4)
+            .for_each(|pixel| pixel.swap(0, 2));
+    }
+    RgbaImage::from_raw(extent.width, extent.height, pixels).ok_or_else(|| {
+        anyhow!(
+            "The pixels don't fill a {}x{} image",
+            extent.width,
+            extent.height
+        )
+    })
+}
+
+/// Writes a frame out to a png named after the time it was taken, on another thread so the game
+/// doesn't stall on encoding it
+pub fn save(extent: Extent2D, format: Format, pixels: Vec<u8>) {
+    std::thread::spawn(move || {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        let path = PathBuf::from(DIRECTORY).join(format!("{millis}.png"));
+        let saved = to_image(extent, format, pixels).and_then(|image| {
+            std::fs::create_dir_all(DIRECTORY)?;
+            image.save(&path)?;
+            Ok(())
+        });
+        match saved {
+            Ok(()) => info!("Saved a screenshot to {}", path.display()),
+            Err(e) => warn!("Failed to save a screenshot: {e}"),
+        }
+    });
+}
+
+/// Renders a frame with a headless renderer into an image
+#[cfg(test)]
+pub fn capture(world: &World) -> Result<RgbaImage> {
+    let pixels = Renderer::render_to_image(world)?;
+    let (extent, format) = world.get::<Renderer>().unwrap().target();
+    to_image(extent, format, pixels)
+}
+
+pub fn tick(world: &World) {
+    if world
+        .get::<Keyboard>()
+        .unwrap()
+        .pressed(Key::Named(NamedKey::F12))
+    {
+        world.get_mut::<Renderer>().unwrap().screenshot();
+    }
+}
+
+pub fn add(world: World) -> World {
+    world.with_ticker(tick)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assets::{MaterialCache, MeshCache, Preload},
+        camera::Camera,
+        renderer::Ui,
+    };
+
+    #[test]
+    pub fn test_bgra_to_rgba() {
+        let extent = Extent2D {
+            width: 2,
+            height: 1,
+        };
+        let pixels = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        let image = to_image(extent, Format::B8G8R8A8_SRGB, pixels.clone()).unwrap();
+        assert_eq!(image.as_raw(), &[3, 2, 1, 4, 7, 6, 5, 8]);
+        let image = to_image(extent, Format::R8G8B8A8_SRGB, pixels.clone()).unwrap();
+        assert_eq!(image.as_raw(), &pixels);
+
+        assert!(to_image(extent, Format::R8G8B8A8_SRGB, vec![0; 4]).is_err());
+    }
+
+    #[test]
+    #[ignore = "requires a vulkan capable device"]
+    pub fn test_capture() {
+        let mut preload = Preload::spawn_in(concat!(env!("CARGO_MANIFEST_DIR"), "/.."));
+        let world = World::new()
+            .with_resource(Renderer::headless((64, 48), &mut preload).unwrap())
+            .with_resource(Ui::new(preload.font().unwrap()))
+            .with_resource(MeshCache::default())
+            .with_resource(MaterialCache::default())
+            .with_resource(Camera::looking_at_origin(64.0 / 48.0));
+
+        let image = capture(&world).unwrap();
+        assert_eq!(image.dimensions(), (64, 48));
+        assert_eq!(image.as_raw().len(), 64 * 48 * 4);
+    }
+}
@@ -0,0 +1,234 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::{audio::Audio, renderer::Renderer, World};
+
+/// The player's choices that last between sessions. Changing one saves it, and applies it to
+/// the game at the end of the tick.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    frames_in_flight: usize,
+    samples: u32,
+    render_scale: f32,
+    vsync: bool,
+    volume: f32,
+    #[serde(skip)]
+    path: PathBuf,
+    #[serde(skip)]
+    changed: bool,
+    // what the game was last given, so only what has changed since is applied again
+    #[serde(skip)]
+    applied: Applied,
+}
+
+/// The settings that take effect while the game is running
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Applied {
+    render_scale: f32,
+    vsync: bool,
+    volume: f32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            frames_in_flight: Renderer::DEFAULT_FRAMES_IN_FLIGHT,
+            samples: Renderer::DEFAULT_SAMPLES,
+            render_scale: 1.0,
+            vsync: false,
+            volume: 1.0,
+            path: PathBuf::from(Self::PATH),
+            changed: false,
+            applied: Applied {
+                render_scale: 1.0,
+                vsync: false,
+                volume: 1.0,
+            },
+        }
+    }
+}
+
+impl Settings {
+    pub const PATH: &'static str = "settings.json";
+
+    /// Loads the settings saved at `path`, which are saved back there when they change. Like the
+    /// keybinds, the defaults are written out if there is no file yet. Values out of range in the
+    /// file are clamped the same as when they are set.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let loaded = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("Failed to load settings: {e}");
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        };
+
+        let mut settings = Self {
+            path,
+            ..Self::default()
+        };
+        settings.set_frames_in_flight(loaded.frames_in_flight);
+        settings.set_samples(loaded.samples);
+        settings.set_render_scale(loaded.render_scale);
+        settings.set_vsync(loaded.vsync);
+        settings.set_volume(loaded.volume);
+        // the game starts out with these, so there is nothing to apply yet
+        settings.changed = false;
+        settings.applied = settings.current();
+
+        if !settings.path.exists() {
+            if let Err(e) = settings.save() {
+                warn!("Failed to save default settings: {e}");
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> Result<()> {
+        std::fs::write(&self.path, serde_json::to_vec_pretty(self)?)?;
+        Ok(())
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.frames_in_flight
+    }
+
+    /// Only takes effect the next time the game is started
+    pub fn set_frames_in_flight(&mut self, frames: usize) {
+        self.frames_in_flight = frames.max(1);
+        self.changed = true;
+    }
+
+    /// How many samples per pixel to antialias the scene with, which is lowered to the most the
+    /// device supports
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    /// Only takes effect the next time the game is started
+    pub fn set_samples(&mut self, samples: u32) {
+        self.samples = samples.max(1);
+        self.changed = true;
+    }
+
+    pub fn render_scale(&self) -> f32 {
+        self.render_scale
+    }
+
+    pub fn set_render_scale(&mut self, scale: f32) {
+        self.render_scale = scale.clamp(Renderer::MIN_RENDER_SCALE, 1.0);
+        self.changed = true;
+    }
+
+    pub fn vsync(&self) -> bool {
+        self.vsync
+    }
+
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+        self.changed = true;
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume.clamp(0.0, 1.0);
+        self.changed = true;
+    }
+
+    fn current(&self) -> Applied {
+        Applied {
+            render_scale: self.render_scale,
+            vsync: self.vsync,
+            volume: self.volume,
+        }
+    }
+
+    /// Applies whatever changed this tick and saves it. Changing the render scale waits for the
+    /// gpu and vsync recreates the swapchain, so each is only done when it has changed.
+    pub fn tick(world: &World) {
+        let mut settings = world.get_mut::<Settings>().unwrap();
+        if !settings.changed {
+            return;
+        }
+        settings.changed = false;
+
+        let (before, after) = (settings.applied, settings.current());
+        settings.applied = after;
+        if after.render_scale != before.render_scale {
+            let mut renderer = world.get_mut::<Renderer>().unwrap();
+            if let Err(e) = renderer.set_render_scale(after.render_scale) {
+                warn!("Failed to change the render scale: {e}");
+            }
+        }
+        if after.vsync != before.vsync {
+            let mut renderer = world.get_mut::<Renderer>().unwrap();
+            if let Err(e) = renderer.set_vsync(after.vsync) {
+                warn!("Failed to change vsync: {e}");
+            }
+        }
+        if after.volume != before.volume {
+            world.get_mut::<Audio>().unwrap().set_volume(after.volume);
+        }
+
+        if let Err(e) = settings.save() {
+            warn!("Failed to save settings: {e}");
+        }
+    }
+
+    pub fn add(self) -> impl FnOnce(World) -> World {
+        move |world| world.with_resource(self).with_ticker(Self::tick)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_settings_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("thanatos-settings-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // the defaults are written out the first time
+        let mut settings = Settings::load(&path);
+        assert!(path.exists());
+        assert_eq!(settings.volume(), 1.0);
+
+        settings.set_frames_in_flight(2);
+        settings.set_samples(0);
+        settings.set_render_scale(0.1);
+        settings.set_vsync(true);
+        settings.set_volume(0.5);
+        settings.save().unwrap();
+
+        let loaded = Settings::load(&path);
+        assert_eq!(loaded.frames_in_flight(), 2);
+        assert_eq!(loaded.samples(), 1);
+        assert_eq!(loaded.render_scale(), Renderer::MIN_RENDER_SCALE);
+        assert!(loaded.vsync());
+        assert_eq!(loaded.volume(), 0.5);
+        assert!(!loaded.changed);
+
+        // edited by hand out of range
+        std::fs::write(
+            &path,
+            r#"{ "frames_in_flight": 0, "render_scale": 4.0, "volume": -1.0 }"#,
+        )
+        .unwrap();
+        let loaded = Settings::load(&path);
+        assert_eq!(loaded.frames_in_flight(), 1);
+        assert_eq!(loaded.render_scale(), 1.0);
+        assert_eq!(loaded.volume(), 0.0);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,120 @@
+use std::{f32::consts::TAU, time::Duration};
+
+use glam::Vec3;
+use tecs::utils::{Clock, State};
+
+use crate::{renderer::Lighting, World};
+
+// how long a whole day lasts at normal speed
+const DAY_LENGTH: Duration = Duration::from_secs(20 * 60);
+// tipped over a little so the sun isn't ever straight overhead
+const TILT: f32 = 0.3;
+
+const NIGHT_SKY: Vec3 = Vec3::new(0.02, 0.02, 0.08);
+const DUSK_SKY: Vec3 = Vec3::new(0.9, 0.45, 0.25);
+const DAY_SKY: Vec3 = Vec3::new(0.45, 0.7, 1.0);
+const LOW_SUN: Vec3 = Vec3::new(1.0, 0.6, 0.3);
+const HIGH_SUN: Vec3 = Vec3::new(1.0, 0.95, 0.9);
+
+/// How far through the day it is, which moves the sun and changes the colour of the sky
+pub struct TimeOfDay {
+    /// From 0 at midnight, through 0.5 at noon, back round to 1
+    pub time: f32,
+    /// How many times faster than normal the day goes by, or 0 to stop it
+    pub speed: f32,
+}
+
+impl Default for TimeOfDay {
+    fn default() -> Self {
+        Self {
+            // starting in the morning, so there's some light to see by
+            time: 0.3,
+            speed: 1.0,
+        }
+    }
+}
+
+fn smoothstep(from: f32, to: f32, x: f32) -> f32 {
+    let t = ((x - from) / (to - from)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+impl TimeOfDay {
+    /// Moves the time on by `delta`, wrapping around into the next day
+    pub fn advance(&mut self, delta: Duration) {
+        let days = delta.as_secs_f32() / DAY_LENGTH.as_secs_f32() * self.speed;
+        self.time = (self.time + days).rem_euclid(1.0);
+    }
+
+    /// The direction sunlight travels in, straight down at noon and straight up at midnight
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = self.time * TAU;
+        -Vec3::new(angle.sin(), -angle.cos(), TILT).normalize()
+    }
+
+    pub fn lighting(&self) -> Lighting {
+        // how high the sun is, from -1 when it is furthest below the horizon to 1 at its highest
+        let height = -self.sun_direction().y;
+        let day = smoothstep(-0.1, 0.3, height);
+        // the sky goes orange around the horizon, on the way between night and day
+        let dusk = 1.0 - (height.abs() * 4.0).min(1.0);
+        let sky = NIGHT_SKY.lerp(DAY_SKY, day).lerp(DUSK_SKY, dusk * 0.6);
+
+        Lighting {
+            direction: self.sun_direction(),
+            colour: LOW_SUN.lerp(HIGH_SUN, smoothstep(0.0, 0.5, height)),
+            intensity: 0.6 * smoothstep(-0.05, 0.2, height),
+            ambient: 0.15 + 0.35 * day,
+            sky,
+        }
+    }
+
+    pub fn tick(world: &World) {
+        let delta = world.get::<Clock>().unwrap().delta;
+        let mut time = world.get_mut::<TimeOfDay>().unwrap();
+        if !State::paused(world) {
+            time.advance(delta);
+        }
+        *world.get_mut::<Lighting>().unwrap() = time.lighting();
+    }
+}
+
+pub fn add(world: World) -> World {
+    world
+        .with_resource(TimeOfDay::default())
+        .with_ticker(TimeOfDay::tick)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    pub fn test_time_of_day() {
+        let mut time = TimeOfDay {
+            time: 0.9,
+            speed: 2.0,
+        };
+        // a fifth of a day at double speed goes past midnight
+        time.advance(DAY_LENGTH / 10);
+        assert!((time.time - 0.1).abs() < 1e-4);
+        time.advance(DAY_LENGTH * 3);
+        assert!((time.time - 0.1).abs() < 1e-3);
+        assert!((0.0..1.0).contains(&time.time));
+
+        let noon = TimeOfDay {
+            time: 0.5,
+            speed: 1.0,
+        };
+        let midnight = TimeOfDay {
+            time: 0.0,
+            speed: 1.0,
+        };
+        // the sun shines down from above at noon, and is below the ground at midnight
+        assert!(noon.sun_direction().y < -0.9);
+        assert!(midnight.sun_direction().y > 0.9);
+        assert!(noon.lighting().intensity > 0.5);
+        assert_eq!(midnight.lighting().intensity, 0.0);
+        assert!(noon.lighting().sky.length() > midnight.lighting().sky.length());
+    }
+}
@@ -1,12 +1,13 @@
 use glam::{Vec3, Vec4};
-use log::{error, info, trace};
+use log::{info, trace};
 use serde::{Deserialize, Serialize};
 use styx::components::{HAlign, HGroup, Text, VAlign, VGroup};
 use tecs::{EntityId, Is, SystemMut};
 
 use crate::{
-    assets::Material,
+    assets::MaterialId,
     camera::Camera,
+    collider::Ray,
     combat::{CombatDefensive, CombatOffensive},
     event::Event,
     player::Player,
@@ -15,7 +16,7 @@ use crate::{
     TargetDummy, World,
 };
 
-#[derive(Clone, Default)]
+#[derive(Clone, Debug, Default)]
 pub enum SelectedEntity {
     #[default]
     None,
@@ -26,9 +27,9 @@ pub enum SelectedEntity {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Selectable {
     // the material that will be used when the entity is selected
-    pub selected_material: Material,
+    pub selected_material: MaterialId,
     // the default material
-    pub unselected_material: Material,
+    pub unselected_material: MaterialId,
 
     pub selected_name: String,
 }
@@ -151,32 +152,55 @@ impl SystemMut<Event> for SelectedUI {
     }
 }
 
+/// A ray from the camera through the mouse cursor
+pub fn cursor_ray(world: &World) -> Ray {
+    let camera = world.get::<Camera>().unwrap();
+    let mouse = world.get::<crate::window::Mouse>().unwrap();
+    let window = world.get::<crate::window::Window>().unwrap();
+
+    let world_pos = camera.ndc_to_world(window.screen_to_ndc(mouse.position));
+    Ray::from_points(camera.eye(), world_pos)
+}
+
+/// The closest selectable entity under the cursor that passes `filter`
+pub fn entity_under_cursor(world: &World, filter: impl Fn(EntityId) -> bool) -> Option<EntityId> {
+    let ray = cursor_ray(world);
+    let (ids, colliders, _) = world.query::<(EntityId, &crate::collider::Collider, &Selectable)>();
+
+    ids.iter()
+        .zip(colliders.iter())
+        .filter(|(id, _)| filter(**id))
+        .filter_map(|(id, collider)| {
+            collider
+                .intersects(ray, world)
+                .map(|hit| (*id, hit.distance(ray.origin)))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id)
+}
+
 pub fn tick(world: &World) {
     // try and select via clicking on entity
-    let camera = world.get_mut::<Camera>().unwrap();
-    let mouse = world.get_mut::<crate::window::Mouse>().unwrap();
-    let window = world.get::<crate::window::Window>().unwrap();
+    let mouse = world.get::<crate::window::Mouse>().unwrap();
 
     let (mut targeted, _) = world.query_one::<(&mut SelectedEntity, Is<Player>)>();
 
     if mouse.pressed(winit::event::MouseButton::Left) {
-        let world_pos = camera.ndc_to_world(window.screen_to_ndc(mouse.position));
-        let ray = crate::collider::Ray::from_points(camera.eye(), world_pos);
+        let ray = cursor_ray(world);
 
         // clear the previous target and reset its material
         match *targeted {
             SelectedEntity::None => (),
-            SelectedEntity::EntityId(targeted_id) => {
+            // it may have died and been despawned since it was selected
+            SelectedEntity::EntityId(targeted_id) if world.contains(targeted_id) => {
                 let mut render_object = world
                     .get_component_mut::<RenderObject>(targeted_id)
                     .unwrap();
                 let selectable = world.get_component::<Selectable>(targeted_id).unwrap();
-                *render_object.material.colour = *selectable.unselected_material.colour;
+                render_object.material = selectable.unselected_material.clone();
                 trace!("target: {:?} cleared", targeted_id);
             }
-            SelectedEntity::Position(_) => {
-                error!("area of effect/non entity targeting not implemented");
-            }
+            SelectedEntity::EntityId(_) | SelectedEntity::Position(_) => (),
         }
 
         // get all the possible targets that can be selected
@@ -188,9 +212,6 @@ pub fn tick(world: &World) {
         )>();
 
         trace!("targeting: {:?}", ids.len());
-        if ids.len() == 0 {
-            return;
-        }
 
         let mut new_target_found = false;
 
@@ -200,7 +221,7 @@ pub fn tick(world: &World) {
             if collider.intersects(ray, world).is_some() {
                 let mut render_object = world.get_component_mut::<RenderObject>(ids[ind]).unwrap();
                 // set the rendered material of that entity to it's selected material
-                *render_object.material.colour = *selectable.selected_material.colour;
+                render_object.material = selectable.selected_material.clone();
                 info!("target: {:?} selected", ids[ind]);
                 // set as the targeted entity
                 *targeted = SelectedEntity::EntityId(ids[ind]);
@@ -208,9 +229,19 @@ pub fn tick(world: &World) {
             }
         }
 
+        // nothing was clicked on, so target the point on the ground under the cursor for
+        // point targeted skills
         if !new_target_found {
-            trace!("no target found inside raycast, deselecting previous target");
-            *targeted = SelectedEntity::None;
+            *targeted = match crate::ground::ground_point(world, ray) {
+                Some(position) => {
+                    trace!("no target found inside raycast, targeting {:?}", position);
+                    SelectedEntity::Position(position)
+                }
+                None => {
+                    trace!("no target found inside raycast, deselecting previous target");
+                    SelectedEntity::None
+                }
+            };
         }
     }
 }
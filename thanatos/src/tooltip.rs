@@ -0,0 +1,47 @@
+use glam::{Vec2, Vec4};
+use nyx::item::Rarity;
+use styx::components::{Container, HAlign, HGroup, Offset, Text};
+
+use crate::{
+    colours::rarity_colour,
+    renderer::{Anchor, Ui},
+};
+
+/// Shows details about something next to the cursor, with a border in the colour of its rarity
+pub fn tooltip(ui: &mut Ui, title: String, rarity: Rarity, lines: Vec<String>) {
+    let colour = rarity_colour(rarity);
+    let body = lines.into_iter().fold(
+        HGroup::new(HAlign::Left, 4.0).add(Text {
+            text: title,
+            font_size: 32.0,
+            font: ui.font.clone(),
+            colour,
+        }),
+        |body, line| {
+            body.add(Text {
+                text: line,
+                font_size: 20.0,
+                font: ui.font.clone(),
+                colour: Vec4::ONE,
+            })
+        },
+    );
+
+    ui.add(
+        Anchor::Cursor,
+        Offset {
+            offset: Vec2::new(16.0, 16.0),
+            child: Container {
+                padding: 2.0,
+                radius: 6.0,
+                colour,
+                child: Container {
+                    padding: 12.0,
+                    radius: 4.0,
+                    colour: Vec4::new(0.05, 0.05, 0.05, 1.0),
+                    child: body,
+                },
+            },
+        },
+    );
+}
@@ -1,5 +1,14 @@
+use std::collections::HashMap;
+
 use glam::{Mat4, Quat, Vec3};
+use log::warn;
 use serde::{Deserialize, Serialize};
+use tecs::EntityId;
+
+use crate::World;
+
+// deeper than any real hierarchy, so an entity that ends up its own ancestor can't loop forever
+const MAX_DEPTH: usize = 32;
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub struct Transform {
@@ -22,6 +31,25 @@ impl Transform {
     pub fn matrix(&self) -> Mat4 {
         Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
     }
+
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self::new(translation, rotation, scale)
+    }
+
+    /// Where a transform relative to this one ends up, such as a child relative to its parent
+    pub fn compose(&self, child: &Transform) -> Transform {
+        Self::from_matrix(self.matrix() * child.matrix())
+    }
+
+    /// Blends from this transform at `t = 0` to `other` at `t = 1`
+    pub fn lerp(&self, other: &Transform, t: f32) -> Transform {
+        Self::new(
+            self.translation.lerp(other.translation, t),
+            self.rotation.slerp(other.rotation, t),
+            self.scale.lerp(other.scale, t),
+        )
+    }
 }
 
 impl Default for Transform {
@@ -29,3 +57,236 @@ impl Default for Transform {
         Self::IDENTITY
     }
 }
+
+/// Makes an entity's [`Transform`] relative to another entity's, so it moves, turns and scales
+/// along with it
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Parent(pub EntityId);
+
+/// Where an entity was before the latest fixed tick, so it can be drawn smoothly moving between
+/// ticks rather than jumping at the simulation rate
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+pub struct PreviousTransform(pub Transform);
+
+/// The transforms of every entity with a [`Parent`] in world space, worked out right before
+/// drawing so entities sharing a parent don't each walk up the same chain
+#[derive(Default)]
+pub struct WorldTransforms(HashMap<EntityId, Transform>);
+
+fn resolve(
+    world: &World,
+    id: EntityId,
+    resolved: &mut HashMap<EntityId, Transform>,
+    depth: usize,
+) -> Option<Transform> {
+    if let Some(transform) = resolved.get(&id) {
+        return Some(*transform);
+    }
+    let local = *world.get_component::<Transform>(id)?;
+    let Some(parent) = world.get_component::<Parent>(id).map(|parent| parent.0) else {
+        return Some(local);
+    };
+    if depth >= MAX_DEPTH {
+        warn!("Giving up on the parents of {id:?}, which go on for too long");
+        return Some(local);
+    }
+
+    // an entity whose parent has been despawned is left relative to the origin instead
+    let transform = match resolve(world, parent, resolved, depth + 1) {
+        Some(parent) => parent.compose(&local),
+        None => local,
+    };
+    resolved.insert(id, transform);
+    Some(transform)
+}
+
+/// Caches the world transforms to draw with. Anything else asks [`global`], as they may have
+/// moved since.
+pub fn propagate(world: &World) {
+    if world.get::<WorldTransforms>().is_none() {
+        return;
+    }
+    let children = {
+        let (ids, _) = world.query::<(EntityId, &Parent)>();
+        ids.to_vec()
+    };
+    let mut resolved = HashMap::new();
+    children.into_iter().for_each(|id| {
+        resolve(world, id, &mut resolved, 0);
+    });
+    world.get_mut::<WorldTransforms>().unwrap().0 = resolved;
+}
+
+/// Where an entity is in the world once its parents are taken into account, or `None` if it
+/// doesn't have a transform
+pub fn global(world: &World, id: EntityId) -> Option<Transform> {
+    resolve(world, id, &mut HashMap::new(), 0)
+}
+
+// what was worked out for drawing this frame, for entities that haven't been spawned since
+fn propagated(world: &World, id: EntityId) -> Option<Transform> {
+    let cached = world
+        .get::<WorldTransforms>()
+        .and_then(|transforms| transforms.0.get(&id).copied());
+    cached.or_else(|| global(world, id))
+}
+
+/// Where an entity is drawn, `alpha` of the way from its [`PreviousTransform`] to where it is
+/// now. Entities without one aren't moved by fixed ticks, so are drawn where they are.
+pub fn interpolated(world: &World, id: EntityId, alpha: f32) -> Option<Transform> {
+    let Some(previous) = world.get_component::<PreviousTransform>(id).map(|x| x.0) else {
+        return propagated(world, id);
+    };
+    let local = previous.lerp(&*world.get_component::<Transform>(id)?, alpha);
+    let parent = world
+        .get_component::<Parent>(id)
+        .and_then(|parent| propagated(world, parent.0));
+    Some(match parent {
+        Some(parent) => parent.compose(&local),
+        None => local,
+    })
+}
+
+/// Remembers where everything was before a fixed tick moves it
+pub fn capture(world: &World) {
+    // both taken mutably, as only mutable columns can be zipped together
+    let (mut previous, mut transforms) = world.query::<(&mut PreviousTransform, &mut Transform)>();
+    previous
+        .zip(&mut transforms)
+        .for_each(|(previous, transform)| previous.0 = *transform);
+}
+
+/// Has to be added before anything that moves entities in fixed ticks, so the previous
+/// transforms are captured first. The world transforms are propagated by the renderer.
+pub fn add(world: World) -> World {
+    world
+        .with_resource(WorldTransforms::default())
+        .with_fixed_ticker(capture)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use tecs::prelude::*;
+
+    use super::*;
+
+    #[derive(Archetype)]
+    struct Base {
+        transform: Transform,
+    }
+
+    #[derive(Archetype)]
+    struct Attachment {
+        transform: Transform,
+        parent: Parent,
+    }
+
+    #[test]
+    pub fn test_hierarchy() {
+        let world = World::new()
+            .register_unsaved::<Base>()
+            .register_unsaved::<Attachment>()
+            .with(add);
+        let base = world.spawn(Base {
+            transform: Transform::new(Vec3::X, Quat::IDENTITY, Vec3::ONE),
+        });
+        let arm = world.spawn(Attachment {
+            transform: Transform::new(Vec3::Z, Quat::IDENTITY, Vec3::ONE),
+            parent: Parent(base),
+        });
+        let hand = world.spawn(Attachment {
+            transform: Transform::new(Vec3::Z, Quat::IDENTITY, Vec3::splat(0.5)),
+            parent: Parent(arm),
+        });
+
+        propagate(&world);
+        let position = |id| global(&world, id).unwrap().translation;
+        assert!(position(arm).abs_diff_eq(Vec3::new(1.0, 0.0, 1.0), 1e-5));
+        assert!(position(hand).abs_diff_eq(Vec3::new(1.0, 0.0, 2.0), 1e-5));
+
+        // turning the base a quarter turn swings everything attached to it round from +Z to +X
+        *world.get_component_mut::<Transform>(base).unwrap() = Transform::new(
+            Vec3::new(2.0, 1.0, 0.0),
+            Quat::from_rotation_y(FRAC_PI_2),
+            Vec3::ONE,
+        );
+        propagate(&world);
+        assert!(position(arm).abs_diff_eq(Vec3::new(3.0, 1.0, 0.0), 1e-5));
+        assert!(position(hand).abs_diff_eq(Vec3::new(4.0, 1.0, 0.0), 1e-5));
+        let hand = global(&world, hand).unwrap();
+        assert!(hand
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_y(FRAC_PI_2), 1e-5));
+        assert!(hand.scale.abs_diff_eq(Vec3::splat(0.5), 1e-5));
+
+        // without its parent the arm falls back to where it is relative to the origin
+        world.despawn::<Base>(base);
+        propagate(&world);
+        assert!(position(arm).abs_diff_eq(Vec3::Z, 1e-5));
+    }
+
+    #[test]
+    pub fn test_global_after_move() {
+        let world = World::new()
+            .register_unsaved::<Base>()
+            .register_unsaved::<Attachment>()
+            .with(add);
+        let base = world.spawn(Base {
+            transform: Transform::IDENTITY,
+        });
+        let arm = world.spawn(Attachment {
+            transform: Transform::new(Vec3::Z, Quat::IDENTITY, Vec3::ONE),
+            parent: Parent(base),
+        });
+        propagate(&world);
+
+        // moved after the last propagation, which doesn't leave the arm behind
+        world
+            .get_component_mut::<Transform>(base)
+            .unwrap()
+            .translation = Vec3::X;
+        let arm = global(&world, arm).unwrap();
+        assert!(arm.translation.abs_diff_eq(Vec3::new(1.0, 0.0, 1.0), 1e-5));
+    }
+
+    #[derive(Archetype)]
+    struct Moving {
+        transform: Transform,
+        previous: PreviousTransform,
+    }
+
+    #[test]
+    pub fn test_interpolated() {
+        let world = World::new().register_unsaved::<Moving>().with(add);
+        let start = Transform::new(Vec3::ZERO, Quat::IDENTITY, Vec3::ONE);
+        let id = world.spawn(Moving {
+            transform: start,
+            previous: PreviousTransform(start),
+        });
+
+        world.fixed_tick();
+        *world.get_component_mut::<Transform>(id).unwrap() = Transform::new(
+            Vec3::new(2.0, 0.0, 4.0),
+            Quat::from_rotation_y(FRAC_PI_2),
+            Vec3::splat(3.0),
+        );
+
+        let halfway = interpolated(&world, id, 0.5).unwrap();
+        assert!(halfway
+            .translation
+            .abs_diff_eq(Vec3::new(1.0, 0.0, 2.0), 1e-5));
+        assert!(halfway
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_y(FRAC_PI_2 / 2.0), 1e-5));
+        assert!(halfway.scale.abs_diff_eq(Vec3::splat(2.0), 1e-5));
+
+        // the next fixed tick starts from where the last one ended
+        world.fixed_tick();
+        let current = interpolated(&world, id, 0.0).unwrap();
+        assert!(current
+            .translation
+            .abs_diff_eq(Vec3::new(2.0, 0.0, 4.0), 1e-5));
+    }
+}
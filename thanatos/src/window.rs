@@ -1,24 +1,35 @@
 use std::{
     collections::{HashMap, HashSet},
+    path::Path,
     sync::Arc,
     time::Duration,
 };
 
+use anyhow::Result;
 use glam::Vec2;
+use log::warn;
+use serde::{Deserialize, Serialize};
 use winit::{
-    event::{ElementState, MouseButton, WindowEvent},
+    event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     keyboard::{Key, SmolStr},
     platform::pump_events::EventLoopExtPumpEvents,
-    window::WindowBuilder,
+    window::{CursorGrabMode, WindowBuilder},
 };
 
 use crate::{event::Event, World};
 
+const PIXELS_PER_LINE: f32 = 20.0;
+
 #[derive(Clone, Default)]
 pub struct Mouse {
     pub position: Vec2,
     pub delta: Vec2,
+    /// Unaccelerated motion from the device, which keeps updating while the cursor is grabbed
+    /// or at the edge of the window
+    pub raw_delta: Vec2,
+    /// How many lines the wheel has scrolled this frame, positive when scrolling up
+    pub scroll: f32,
     down: HashSet<MouseButton>,
     previous: HashSet<MouseButton>,
 }
@@ -39,46 +50,162 @@ impl Mouse {
     pub fn tick(world: &World) {
         let mut mouse = world.get_mut::<Mouse>().unwrap();
         mouse.delta = Vec2::ZERO;
+        mouse.raw_delta = Vec2::ZERO;
+        mouse.scroll = 0.0;
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Keybind {
     Interact,
+    Inventory,
+    Craft,
+    Equipment,
+    Casting,
+    AttackAll,
+    AttackTarget,
+    Forward,
+    Back,
+    Left,
+    Right,
+    CameraMode,
+    MapZoomIn,
+    MapZoomOut,
+}
+
+impl Keybind {
+    pub const ALL: [Keybind; 14] = [
+        Keybind::Interact,
+        Keybind::Inventory,
+        Keybind::Craft,
+        Keybind::Equipment,
+        Keybind::Casting,
+        Keybind::AttackAll,
+        Keybind::AttackTarget,
+        Keybind::Forward,
+        Keybind::Back,
+        Keybind::Left,
+        Keybind::Right,
+        Keybind::CameraMode,
+        Keybind::MapZoomIn,
+        Keybind::MapZoomOut,
+    ];
+
+    pub fn default_key(self) -> Key {
+        let key = match self {
+            Keybind::Interact => "f",
+            Keybind::Inventory => "i",
+            Keybind::Craft => "c",
+            Keybind::Equipment => "e",
+            Keybind::Casting => "k",
+            Keybind::AttackAll => "z",
+            Keybind::AttackTarget => "x",
+            Keybind::Forward => "w",
+            Keybind::Back => "s",
+            Keybind::Left => "a",
+            Keybind::Right => "d",
+            Keybind::CameraMode => "v",
+            Keybind::MapZoomIn => "=",
+            Keybind::MapZoomOut => "-",
+        };
+        Key::Character(SmolStr::new_inline(key))
+    }
 }
 
 #[derive(Clone)]
 pub struct Keyboard {
     previous: HashSet<Key>,
     down: HashSet<Key>,
+    // set while the ui has focus, so typing into it doesn't trigger keybinds too
+    captured: bool,
     pub keybinds: HashMap<Keybind, Key>,
 }
 
 impl Default for Keyboard {
     fn default() -> Self {
-        let mut keyboard = Self {
+        Self {
             previous: HashSet::new(),
             down: HashSet::new(),
-            keybinds: HashMap::new(),
-        };
-        keyboard.keybinds = HashMap::from([(Keybind::Interact, "f".into_key(&keyboard))]);
-        keyboard
+            captured: false,
+            keybinds: Keybind::ALL
+                .into_iter()
+                .map(|keybind| (keybind, keybind.default_key()))
+                .collect(),
+        }
     }
 }
 
 impl Keyboard {
+    pub const KEYBINDS_PATH: &'static str = "keybinds.json";
+
+    /// Loads any remapped keybinds over the defaults. If there is no file yet the defaults are
+    /// written to it, so there is something to edit to remap the controls.
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let mut keyboard = Self::default();
+        let Ok(data) = std::fs::read(&path) else {
+            if let Err(e) = keyboard.save(path) {
+                warn!("Failed to save default keybinds: {e}");
+            }
+            return keyboard;
+        };
+
+        match serde_json::from_slice::<HashMap<Keybind, String>>(&data) {
+            Ok(keybinds) => keybinds.into_iter().for_each(|(keybind, key)| {
+                keyboard.keybinds.insert(keybind, Key::Character(key.into()));
+            }),
+            Err(e) => warn!("Failed to load keybinds: {e}"),
+        }
+
+        keyboard
+    }
+
+    /// Only character keys are saved, any other binding falls back to its default when loaded
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let keybinds = self
+            .keybinds
+            .iter()
+            .filter_map(|(keybind, key)| match key {
+                Key::Character(key) => Some((*keybind, key.to_string())),
+                _ => None,
+            })
+            .collect::<HashMap<Keybind, String>>();
+        std::fs::write(path, serde_json::to_vec_pretty(&keybinds)?)?;
+        Ok(())
+    }
+
     pub fn pressed<T: IntoKey>(&self, key: T) -> bool {
         let key = key.into_key(self);
-        self.down.contains(&key) && !self.previous.contains(&key)
+        !self.captured && self.down.contains(&key) && !self.previous.contains(&key)
     }
 
     pub fn released<T: IntoKey>(&self, key: T) -> bool {
         let key = key.into_key(self);
-        !self.down.contains(&key) && self.previous.contains(&key)
+        !self.captured && !self.down.contains(&key) && self.previous.contains(&key)
     }
 
     pub fn is_down<T: IntoKey>(&self, key: T) -> bool {
-        self.down.contains(&key.into_key(self))
+        !self.captured && self.down.contains(&key.into_key(self))
+    }
+
+    pub fn press(&mut self, key: Key) {
+        self.down.insert(key);
+    }
+
+    pub fn release(&mut self, key: &Key) {
+        self.down.remove(key);
+    }
+
+    /// Hides every key from the game while `captured`, for when the ui is being typed into
+    pub fn capture(&mut self, captured: bool) {
+        self.captured = captured;
+    }
+
+    /// Hides a press from anything checking for it later in the tick, so it only does one thing
+    pub fn consume<T: IntoKey>(&mut self, key: T) {
+        let key = key.into_key(self);
+        if self.down.contains(&key) {
+            self.previous.insert(key);
+        }
     }
 }
 
@@ -107,6 +234,7 @@ impl IntoKey for Keybind {
 pub struct Window {
     event_loop: EventLoop<()>,
     pub window: Arc<winit::window::Window>,
+    grabbed: bool,
 }
 
 impl Window {
@@ -118,11 +246,41 @@ impl Window {
             .build(&event_loop)
             .unwrap();
         let window = Arc::new(window);
-        Self { event_loop, window }
+        Self {
+            event_loop,
+            window,
+            grabbed: false,
+        }
+    }
+
+    /// Locks and hides the cursor so mouse movement can be read from [`Mouse::raw_delta`]
+    /// without it leaving the window
+    pub fn set_cursor_grab(&mut self, grab: bool) {
+        if grab == self.grabbed {
+            return;
+        }
+
+        let result = if grab {
+            // not every platform supports locking the cursor in place
+            self.window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined))
+        } else {
+            self.window.set_cursor_grab(CursorGrabMode::None)
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to set cursor grab: {e}");
+            return;
+        }
+
+        self.window.set_cursor_visible(!grab);
+        self.grabbed = grab;
     }
 
     pub fn tick(world: &World) {
         let mut events = Vec::new();
+        let mut focus_lost = false;
 
         {
             let mut window = world.get_mut::<Window>().unwrap();
@@ -141,13 +299,14 @@ impl Window {
                         WindowEvent::CloseRequested => {
                             events.push(Event::Stop);
                         }
+                        WindowEvent::Focused(false) => focus_lost = true,
                         WindowEvent::KeyboardInput { event, .. } => match event.state {
                             ElementState::Pressed => {
-                                keyboard.down.insert(event.logical_key.clone());
+                                keyboard.press(event.logical_key.clone());
                                 events.push(Event::KeyPress(event.logical_key));
                             }
                             ElementState::Released => {
-                                keyboard.down.remove(&event.logical_key);
+                                keyboard.release(&event.logical_key);
                                 events.push(Event::KeyRelease(event.logical_key));
                             }
                         },
@@ -170,10 +329,30 @@ impl Window {
                                 delta: mouse.delta,
                             })
                         }
+                        WindowEvent::MouseWheel { delta, .. } => {
+                            mouse.scroll += match delta {
+                                MouseScrollDelta::LineDelta(_, lines) => lines,
+                                // touchpads scroll in pixels, roughly a line at a time
+                                MouseScrollDelta::PixelDelta(position) => {
+                                    position.y as f32 / PIXELS_PER_LINE
+                                }
+                            };
+                        }
                         _ => (),
                     },
+                    winit::event::Event::DeviceEvent {
+                        event: DeviceEvent::MouseMotion { delta },
+                        ..
+                    } => {
+                        mouse.raw_delta += Vec2::new(delta.0 as f32, delta.1 as f32);
+                    }
                     _ => (),
                 });
+
+            // give the cursor back when switching away, otherwise it stays hidden
+            if focus_lost {
+                window.set_cursor_grab(false);
+            }
         }
 
         events.into_iter().for_each(|event| world.submit(event));
@@ -184,7 +363,7 @@ impl Window {
             world
                 .with_resource(self)
                 .with_resource(Mouse::default())
-                .with_resource(Keyboard::default())
+                .with_resource(Keyboard::load(Keyboard::KEYBINDS_PATH))
                 .with_ticker(Mouse::tick)
                 .with_ticker(Self::tick)
         }
@@ -195,3 +374,9 @@ impl Window {
         pos * 2.0 / Vec2::new(size.width as f32, size.height as f32) - 1.0
     }
 }
+
+/// Where a point in normalized device coordinates lands on a screen of `size`. This takes the
+/// size rather than asking the window, as there is no window when rendering headless.
+pub fn ndc_to_screen(pos: Vec2, size: Vec2) -> Vec2 {
+    (pos + 1.0) / 2.0 * size
+}